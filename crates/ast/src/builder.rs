@@ -0,0 +1,337 @@
+//! Fluent construction of [`Module`]s, for tests and tools that need to put together a shader by
+//! hand.
+//!
+//! ```
+//! use ast::builder::{FnBuilder, ModuleBuilder};
+//! use ast::{DataType, Lit, ReturnStatement, ScalarType};
+//!
+//! let module = ModuleBuilder::new()
+//!     .add_function(
+//!         FnBuilder::new("one")
+//!             .returns(ScalarType::U32)
+//!             .stmt(ReturnStatement::new(Lit::U32(1)))
+//!             .build(),
+//!     )
+//!     .add_function(FnBuilder::new("main").entrypoint(1).build())
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(module.functions.len(), 2);
+//! ```
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use derive_more::Display;
+
+use crate::types::DataType;
+use crate::{
+    ExprNode, Extension, FnAttr, FnDecl, FnInput, FnInputAttr, FnOutput, GlobalConstDecl,
+    GlobalVarAttr, GlobalVarDecl, Module, ShaderStage, Statement, StructDecl, VarQualifier,
+};
+
+/// An invariant that was violated by the module passed to [`ModuleBuilder::build`].
+#[derive(Debug, Display, PartialEq, Eq)]
+pub enum BuildError {
+    #[display(fmt = "module has no entrypoint")]
+    MissingEntrypoint,
+    #[display(fmt = "`{_0}` is declared more than once")]
+    DuplicateName(String),
+    #[display(fmt = "`{_0}` and `{_1}` have the same group and binding")]
+    DuplicateBinding(String, String),
+}
+
+impl std::error::Error for BuildError {}
+
+#[derive(Default)]
+pub struct ModuleBuilder {
+    enables: Vec<Extension>,
+    structs: Vec<Rc<StructDecl>>,
+    consts: Vec<GlobalConstDecl>,
+    vars: Vec<GlobalVarDecl>,
+    functions: Vec<FnDecl>,
+}
+
+impl ModuleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an `enable` directive for `extension`, e.g. to allow using `f16`.
+    pub fn enable(mut self, extension: Extension) -> Self {
+        self.enables.push(extension);
+        self
+    }
+
+    pub fn add_struct(mut self, decl: Rc<StructDecl>) -> Self {
+        self.structs.push(decl);
+        self
+    }
+
+    pub fn add_const(
+        mut self,
+        name: impl Into<String>,
+        data_type: impl Into<DataType>,
+        initializer: impl Into<ExprNode>,
+    ) -> Self {
+        self.consts.push(GlobalConstDecl {
+            name: name.into(),
+            data_type: data_type.into(),
+            initializer: initializer.into(),
+        });
+        self
+    }
+
+    /// Adds a module-scope variable with no attributes, e.g. `var<private> x: i32;`.
+    pub fn add_var(
+        mut self,
+        qualifier: Option<VarQualifier>,
+        name: impl Into<String>,
+        data_type: impl Into<DataType>,
+        initializer: Option<ExprNode>,
+    ) -> Self {
+        self.vars.push(GlobalVarDecl {
+            attrs: vec![],
+            qualifier,
+            name: name.into(),
+            data_type: data_type.into(),
+            initializer,
+        });
+        self
+    }
+
+    /// Adds a resource variable bound to `@group(group) @binding(binding)`.
+    pub fn add_binding(
+        mut self,
+        group: i32,
+        binding: i32,
+        qualifier: VarQualifier,
+        name: impl Into<String>,
+        data_type: impl Into<DataType>,
+    ) -> Self {
+        self.vars.push(GlobalVarDecl {
+            attrs: vec![GlobalVarAttr::Group(group), GlobalVarAttr::Binding(binding)],
+            qualifier: Some(qualifier),
+            name: name.into(),
+            data_type: data_type.into(),
+            initializer: None,
+        });
+        self
+    }
+
+    pub fn add_function(mut self, decl: FnDecl) -> Self {
+        self.functions.push(decl);
+        self
+    }
+
+    /// Returns the module, checking that it has an entrypoint, that no name is declared twice at
+    /// module scope and that no two resources share a binding.
+    ///
+    /// The bodies of functions aren't checked - use [`typecheck`](crate::typecheck::typecheck)
+    /// for that.
+    pub fn build(self) -> Result<Module, BuildError> {
+        let mut names = HashSet::new();
+
+        let all_names = self
+            .structs
+            .iter()
+            .map(|it| &it.name)
+            .chain(self.consts.iter().map(|it| &it.name))
+            .chain(self.vars.iter().map(|it| &it.name))
+            .chain(self.functions.iter().map(|it| &it.name));
+
+        for name in all_names {
+            if !names.insert(name) {
+                return Err(BuildError::DuplicateName(name.clone()));
+            }
+        }
+
+        for (i, a) in self.vars.iter().enumerate() {
+            let binding = match (a.group_index(), a.binding_index()) {
+                (Some(group), Some(binding)) => (group, binding),
+                _ => continue,
+            };
+
+            for b in &self.vars[i + 1..] {
+                if b.group_index() == Some(binding.0) && b.binding_index() == Some(binding.1) {
+                    return Err(BuildError::DuplicateBinding(a.name.clone(), b.name.clone()));
+                }
+            }
+        }
+
+        let has_entrypoint = self
+            .functions
+            .iter()
+            .any(|it| it.attrs.iter().any(|attr| matches!(attr, FnAttr::Stage(_))));
+
+        if !has_entrypoint {
+            return Err(BuildError::MissingEntrypoint);
+        }
+
+        Ok(Module {
+            enables: self.enables,
+            structs: self.structs,
+            consts: self.consts,
+            vars: self.vars,
+            functions: self.functions,
+        })
+    }
+}
+
+pub struct FnBuilder {
+    decl: FnDecl,
+}
+
+impl FnBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        FnBuilder {
+            decl: FnDecl {
+                attrs: vec![],
+                name: name.into(),
+                inputs: vec![],
+                output: None,
+                body: vec![],
+            },
+        }
+    }
+
+    /// Makes this a compute shader entrypoint with the given workgroup size.
+    pub fn entrypoint(mut self, workgroup_size: u32) -> Self {
+        self.decl.attrs.push(FnAttr::Stage(ShaderStage::Compute));
+        self.decl
+            .attrs
+            .push(FnAttr::LitWorkgroupSize(workgroup_size));
+        self
+    }
+
+    pub fn param(mut self, name: impl Into<String>, data_type: impl Into<DataType>) -> Self {
+        self.decl.inputs.push(FnInput::new(name, data_type));
+        self
+    }
+
+    /// Adds a parameter with a `@builtin(builtin)` attribute, e.g. `global_invocation_id`.
+    pub fn builtin_param(
+        mut self,
+        builtin: impl Into<String>,
+        name: impl Into<String>,
+        data_type: impl Into<DataType>,
+    ) -> Self {
+        let mut input = FnInput::new(name, data_type);
+        input.attrs.push(FnInputAttr::Builtin(builtin.into()));
+        self.decl.inputs.push(input);
+        self
+    }
+
+    pub fn returns(mut self, data_type: impl Into<DataType>) -> Self {
+        self.decl.output = Some(FnOutput::new(data_type));
+        self
+    }
+
+    pub fn stmt(mut self, stmt: impl Into<Statement>) -> Self {
+        self.decl.body.push(stmt.into());
+        self
+    }
+
+    pub fn stmts(mut self, stmts: impl IntoIterator<Item = Statement>) -> Self {
+        self.decl.body.extend(stmts);
+        self
+    }
+
+    pub fn build(self) -> FnDecl {
+        self.decl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ScalarType;
+    use crate::writer::Writer;
+    use crate::{
+        AssignmentLhs, AssignmentOp, AssignmentStatement, BinOp, BinOpExpr, Lit, ReturnStatement,
+        StorageClass, VarExpr,
+    };
+
+    fn private() -> Option<VarQualifier> {
+        Some(VarQualifier {
+            storage_class: StorageClass::Private,
+            access_mode: None,
+        })
+    }
+
+    #[test]
+    fn builds_valid_module() {
+        let u32 = DataType::from(ScalarType::U32);
+
+        let add = BinOpExpr::new(
+            BinOp::Plus,
+            VarExpr::new("x").into_node(u32.clone()),
+            Lit::U32(1),
+        );
+
+        let module = ModuleBuilder::new()
+            .add_var(private(), "counter", u32.clone(), None)
+            .add_function(
+                FnBuilder::new("inc")
+                    .param("x", u32.clone())
+                    .returns(u32.clone())
+                    .stmt(ReturnStatement::new(add))
+                    .build(),
+            )
+            .add_function(
+                FnBuilder::new("main")
+                    .entrypoint(1)
+                    .stmt(AssignmentStatement::new(
+                        AssignmentLhs::name("counter", u32.clone()),
+                        AssignmentOp::Simple,
+                        Lit::U32(2),
+                    ))
+                    .build(),
+            )
+            .build()
+            .unwrap();
+
+        crate::typecheck::typecheck(&module).unwrap();
+
+        let mut out = String::new();
+        Writer::default().write_module(&mut out, &module).unwrap();
+
+        assert!(out.contains("var<private> counter: u32;"), "{out}");
+        assert!(out.contains("fn inc(x: u32) -> u32 {"), "{out}");
+        assert!(out.contains("@compute"), "{out}");
+    }
+
+    #[test]
+    fn rejects_invalid_modules() {
+        let main = || FnBuilder::new("main").entrypoint(1).build();
+
+        assert_eq!(
+            ModuleBuilder::new()
+                .add_function(FnBuilder::new("f").build())
+                .build(),
+            Err(BuildError::MissingEntrypoint)
+        );
+
+        assert_eq!(
+            ModuleBuilder::new()
+                .add_var(private(), "main", ScalarType::I32, None)
+                .add_function(main())
+                .build(),
+            Err(BuildError::DuplicateName("main".to_owned()))
+        );
+
+        let storage = VarQualifier {
+            storage_class: StorageClass::Storage,
+            access_mode: None,
+        };
+
+        assert_eq!(
+            ModuleBuilder::new()
+                .add_binding(0, 1, storage.clone(), "a", ScalarType::I32)
+                .add_binding(0, 1, storage, "b", ScalarType::I32)
+                .add_function(main())
+                .build(),
+            Err(BuildError::DuplicateBinding("a".to_owned(), "b".to_owned()))
+        );
+    }
+}
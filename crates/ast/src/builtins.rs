@@ -3,6 +3,7 @@ use crate::{DataType, ScalarType};
 #[derive(
     Clone, Copy, Debug, PartialEq, Eq, Hash, strum::AsRefStr, strum::EnumIter, strum::EnumString,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[strum(serialize_all = "camelCase")]
 pub enum BuiltinFn {
     Abs,
@@ -113,7 +114,7 @@ impl BuiltinFn {
             CountTrailingZeros => param(1)?,
             Cross => param(1)?,
             Degrees => param(1)?,
-            Distance => F32.into(),
+            Distance => param(1)?.as_scalar()?.into(),
             Dot => param(1)?.as_scalar()?.into(),
             ExtractBits => param(1)?,
             Exp => param(1)?,
@@ -127,7 +128,7 @@ impl BuiltinFn {
             InsertBits => param(1)?,
             InverseSqrt => param(1)?,
             Ldexp => param(1)?,
-            Length => F32.into(),
+            Length => param(1)?.as_scalar()?.into(),
             Log => param(1)?,
             Log2 => param(1)?,
             Max => param(1)?,
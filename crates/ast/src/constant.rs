@@ -0,0 +1,473 @@
+//! Detection of shaders whose output is fully determined at compile time.
+//!
+//! A shader like this is useless for differential testing, since every backend will trivially
+//! agree on its output. [`has_constant_output`] finds these by tracking which variables may hold a
+//! value that isn't known at compile time (because it was derived from an input buffer, a builtin
+//! input, an atomic operation or floating point arithmetic) and checking whether any reachable
+//! write to an output buffer depends on such a value.
+//!
+//! The analysis is flow-insensitive and errs on the side of reporting shaders as non-constant:
+//! a variable is considered non-constant if *any* assignment to it is, and writes that are
+//! control-dependent on a non-constant condition are treated as non-constant too. Branches whose
+//! conditions can be evaluated with [`try_const_eval`] are only analysed if they are reachable.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::eval::try_const_eval;
+use crate::{
+    AccessMode, AssignmentLhs, AssignmentStatement, Else, Expr, ExprNode, FnDecl, ForLoopInit,
+    ForLoopUpdate, LhsExpr, LhsExprNode, Lit, LoopStatement, Module, Postfix, ScalarType,
+    Statement, StorageClass, WhileStatement,
+};
+
+/// Checks whether the output of `module` is fully determined at compile time.
+///
+/// Returns `true` if it is, along with a human readable reason explaining the decision either way.
+pub fn has_constant_output(module: &Module) -> (bool, String) {
+    let mut analysis = Analysis::new(module);
+
+    // Keep propagating until we reach a fixpoint. Facts are only ever added, so this terminates.
+    loop {
+        let before = analysis.facts();
+        for decl in &module.functions {
+            analysis.analyse_fn(decl);
+        }
+
+        if analysis.facts() == before {
+            break;
+        }
+    }
+
+    if let Some(reason) = analysis.unsupported {
+        return (false, reason);
+    }
+
+    if let Some(reason) = analysis.output_dependency {
+        return (false, reason);
+    }
+
+    if analysis.output_written {
+        (
+            true,
+            "output only depends on compile-time constants".to_owned(),
+        )
+    } else {
+        (true, "output buffers are never written".to_owned())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Global(String),
+    Local(String, String),
+    Return(String),
+    /// Set if a function may be called from non-constant control flow.
+    Context(String),
+}
+
+struct Analysis<'a> {
+    module: &'a Module,
+    inputs: HashSet<String>,
+    outputs: HashSet<String>,
+    locals: HashMap<String, HashSet<String>>,
+    /// Keys that may hold a non-constant value, along with the source of the value.
+    tainted: HashMap<Key, String>,
+    function: String,
+    /// Source of non-constant control flow at the statement currently being analysed.
+    context: Option<String>,
+    output_written: bool,
+    output_dependency: Option<String>,
+    unsupported: Option<String>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(module: &'a Module) -> Analysis<'a> {
+        let mut inputs = HashSet::new();
+        let mut outputs = HashSet::new();
+
+        for decl in &module.vars {
+            let qualifier = match &decl.qualifier {
+                Some(qualifier) => qualifier,
+                None => continue,
+            };
+
+            let access_mode = qualifier
+                .access_mode
+                .unwrap_or_else(|| qualifier.storage_class.default_access_mode());
+
+            match qualifier.storage_class {
+                StorageClass::Uniform => {
+                    inputs.insert(decl.name.clone());
+                }
+                StorageClass::Storage if access_mode == AccessMode::Read => {
+                    inputs.insert(decl.name.clone());
+                }
+                StorageClass::Storage => {
+                    outputs.insert(decl.name.clone());
+                }
+                _ => {}
+            }
+        }
+
+        let mut tainted = HashMap::new();
+        let mut locals = HashMap::new();
+
+        for decl in &module.functions {
+            let mut names = HashSet::new();
+
+            for input in &decl.inputs {
+                names.insert(input.name.clone());
+                if !input.attrs.is_empty() {
+                    tainted.insert(
+                        Key::Local(decl.name.clone(), input.name.clone()),
+                        format!("builtin input `{}`", input.name),
+                    );
+                }
+            }
+
+            collect_locals(&decl.body, &mut names);
+            locals.insert(decl.name.clone(), names);
+        }
+
+        Analysis {
+            module,
+            inputs,
+            outputs,
+            locals,
+            tainted,
+            function: String::new(),
+            context: None,
+            output_written: false,
+            output_dependency: None,
+            unsupported: None,
+        }
+    }
+
+    fn facts(&self) -> (usize, bool) {
+        (self.tainted.len(), self.output_written)
+    }
+
+    fn key(&self, ident: &str) -> Key {
+        if self.locals[&self.function].contains(ident) {
+            Key::Local(self.function.clone(), ident.to_owned())
+        } else {
+            Key::Global(ident.to_owned())
+        }
+    }
+
+    fn taint(&mut self, key: Key, source: Option<String>) {
+        if let Some(source) = source {
+            self.tainted.entry(key).or_insert(source);
+        }
+    }
+
+    fn analyse_fn(&mut self, decl: &FnDecl) {
+        self.function = decl.name.clone();
+        let context = self.tainted.get(&Key::Context(decl.name.clone())).cloned();
+        self.analyse_block(&decl.body, context);
+    }
+
+    /// Analyses a block, returning the (non-constant) source that may have caused it to exit early.
+    fn analyse_block(&mut self, block: &[Statement], context: Option<String>) -> Option<String> {
+        let mut context = context;
+        let mut early_exit = None;
+
+        for stmt in block {
+            if let Some(source) = self.analyse_stmt(stmt, context.clone()) {
+                // Any statements after a conditional exit are control-dependent on its condition.
+                context.get_or_insert_with(|| source.clone());
+                early_exit.get_or_insert(source);
+            }
+
+            if matches!(
+                stmt,
+                Statement::Return(_) | Statement::Break | Statement::Continue
+            ) {
+                // The rest of the block is unreachable.
+                break;
+            }
+        }
+
+        early_exit
+    }
+
+    fn analyse_stmt(&mut self, stmt: &Statement, context: Option<String>) -> Option<String> {
+        let prev_context = std::mem::replace(&mut self.context, context.clone());
+        let exit = self.analyse_stmt_inner(stmt, context);
+        self.context = prev_context;
+        exit
+    }
+
+    fn analyse_stmt_inner(&mut self, stmt: &Statement, context: Option<String>) -> Option<String> {
+        match stmt {
+            Statement::LetDecl(stmt) => {
+                let source = self.analyse_expr(&stmt.initializer).or(context);
+                self.taint(self.key(&stmt.ident), source);
+                None
+            }
+            Statement::VarDecl(stmt) => {
+                if let Some(initializer) = &stmt.initializer {
+                    let source = self.analyse_expr(initializer).or(context);
+                    self.taint(self.key(&stmt.ident), source);
+                }
+                None
+            }
+            Statement::Assignment(stmt) => {
+                self.analyse_assignment(stmt, context);
+                None
+            }
+            Statement::Compound(block) => self.analyse_block(block, context),
+            Statement::If(stmt) => {
+                let mut exit = None;
+                let mut context = context;
+                let mut stmt = Some(stmt);
+                let mut else_ = None;
+
+                while let Some(if_) = stmt.take() {
+                    match try_const_eval(&if_.condition) {
+                        Some(Lit::Bool(true)) => {
+                            exit = exit.or(self.analyse_block(&if_.body, context.clone()));
+                            break;
+                        }
+                        Some(Lit::Bool(false)) => {}
+                        _ => {
+                            self.context = context.clone();
+                            let source = self.analyse_expr(&if_.condition);
+                            context = context.or(source);
+                            exit = exit.or(self.analyse_block(&if_.body, context.clone()));
+                        }
+                    }
+
+                    match if_.else_.as_deref() {
+                        Some(Else::If(next)) => stmt = Some(next),
+                        Some(Else::Else(block)) => else_ = Some(block),
+                        None => {}
+                    }
+                }
+
+                if let Some(block) = else_ {
+                    exit = exit.or(self.analyse_block(block, context.clone()));
+                }
+
+                exit
+            }
+            Statement::Return(stmt) => {
+                if let Some(value) = &stmt.value {
+                    let source = self.analyse_expr(value).or_else(|| context.clone());
+                    self.taint(Key::Return(self.function.clone()), source);
+                }
+                context
+            }
+            Statement::Loop(stmt) => self.analyse_loop(None, &stmt.body, context),
+            Statement::Break | Statement::Continue | Statement::Fallthrough => context,
+            Statement::Comment(_) => None,
+            Statement::Switch(stmt) => {
+                let source = self.analyse_expr(&stmt.selector);
+                let context = context.or(source);
+                let mut exit = None;
+
+                for case in &stmt.cases {
+                    exit = exit.or(self.analyse_block(&case.body, context.clone()));
+                }
+
+                exit.or(self.analyse_block(&stmt.default, context))
+            }
+            Statement::ForLoop(stmt) => {
+                if let Some(ForLoopInit::VarDecl(decl)) = &stmt.header.init {
+                    if let Some(initializer) = &decl.initializer {
+                        let source = self.analyse_expr(initializer).or_else(|| context.clone());
+                        self.taint(self.key(&decl.ident), source);
+                    }
+                }
+
+                if let Some(ForLoopUpdate::Assignment(update)) = &stmt.header.update {
+                    self.analyse_assignment(update, context.clone());
+                }
+
+                self.analyse_loop(stmt.header.condition.as_ref(), &stmt.body, context)
+            }
+            Statement::While(stmt) => self.analyse_loop(Some(&stmt.condition), &stmt.body, context),
+            Statement::FnCall(stmt) => {
+                self.analyse_call(&stmt.ident, &stmt.args);
+                None
+            }
+        }
+    }
+
+    fn analyse_loop(
+        &mut self,
+        condition: Option<&ExprNode>,
+        body: &[Statement],
+        context: Option<String>,
+    ) -> Option<String> {
+        let source = condition.and_then(|condition| self.analyse_expr(condition));
+        let context = context.or(source);
+
+        // If the loop may exit early based on a non-constant condition, then the number of
+        // iterations isn't known and the whole loop body is non-constant.
+        let exit = self.analyse_block(body, context.clone());
+        let context = context.or(exit);
+        self.analyse_block(body, context.clone());
+
+        context
+    }
+
+    fn analyse_assignment(&mut self, stmt: &AssignmentStatement, context: Option<String>) {
+        let source = self.analyse_expr(&stmt.rhs).or(context);
+        if let AssignmentLhs::Expr(lhs) = &stmt.lhs {
+            self.analyse_lhs(lhs, source);
+        }
+    }
+
+    fn analyse_lhs(&mut self, node: &LhsExprNode, source: Option<String>) {
+        match &node.expr {
+            LhsExpr::Ident(ident) => {
+                if self.outputs.contains(ident) && self.key(ident) == Key::Global(ident.clone()) {
+                    self.output_written = true;
+                    if let Some(source) = &source {
+                        self.output_dependency
+                            .get_or_insert_with(|| format!("output `{ident}` depends on {source}"));
+                    }
+                }
+
+                self.taint(self.key(ident), source);
+            }
+            LhsExpr::Postfix(inner, postfix) => {
+                let index = match postfix {
+                    Postfix::Index(index) => self.analyse_expr(index),
+                    Postfix::Member(_) => None,
+                };
+
+                self.analyse_lhs(inner, source.or(index));
+            }
+            LhsExpr::Deref(_) | LhsExpr::AddressOf(_) => {
+                self.unsupported
+                    .get_or_insert_with(|| "writes through pointers are not analysed".to_owned());
+            }
+        }
+    }
+
+    /// Analyses an expression, returning the source of its value if it may be non-constant.
+    fn analyse_expr(&mut self, node: &ExprNode) -> Option<String> {
+        let source = match &node.expr {
+            Expr::Lit(_) => None,
+            Expr::TypeCons(expr) => self.analyse_exprs(&expr.args),
+            Expr::Var(expr) => {
+                if self.inputs.contains(&expr.ident)
+                    && self.key(&expr.ident) == Key::Global(expr.ident.clone())
+                {
+                    Some(format!("input `{}`", expr.ident))
+                } else {
+                    self.tainted.get(&self.key(&expr.ident)).cloned()
+                }
+            }
+            Expr::Postfix(expr) => {
+                let inner = self.analyse_expr(&expr.inner);
+                let index = match &expr.postfix {
+                    Postfix::Index(index) => self.analyse_expr(index),
+                    Postfix::Member(_) => None,
+                };
+                inner.or(index)
+            }
+            Expr::UnOp(expr) => self.analyse_expr(&expr.inner),
+            Expr::BinOp(expr) => {
+                let left = self.analyse_expr(&expr.left);
+                let right = self.analyse_expr(&expr.right);
+                left.or(right)
+            }
+            Expr::FnCall(expr) => self.analyse_call(&expr.ident, &expr.args),
+        };
+
+        if source.is_none()
+            && matches!(node.expr, Expr::UnOp(_) | Expr::BinOp(_) | Expr::FnCall(_))
+            && matches!(
+                node.data_type.dereference().as_scalar(),
+                Some(ScalarType::F32 | ScalarType::F16)
+            )
+        {
+            // Floating point expressions may be evaluated differently by each implementation.
+            return Some("floating point arithmetic".to_owned());
+        }
+
+        source
+    }
+
+    fn analyse_exprs(&mut self, nodes: &[ExprNode]) -> Option<String> {
+        nodes
+            .iter()
+            .map(|node| self.analyse_expr(node))
+            .fold(None, Option::or)
+    }
+
+    fn analyse_call(&mut self, ident: &str, args: &[ExprNode]) -> Option<String> {
+        let sources = args
+            .iter()
+            .map(|arg| self.analyse_expr(arg))
+            .collect::<Vec<_>>();
+
+        if ident.starts_with("atomic") {
+            return Some(format!("atomic operation `{ident}`"));
+        }
+
+        let decl = match self.module.functions.iter().find(|decl| decl.name == ident) {
+            Some(decl) => decl,
+            // Builtin functions are pure, so their result only depends on their arguments.
+            None => return sources.into_iter().fold(None, Option::or),
+        };
+
+        for (input, source) in decl.inputs.iter().zip(sources) {
+            self.taint(Key::Local(decl.name.clone(), input.name.clone()), source);
+        }
+
+        self.taint(Key::Context(decl.name.clone()), self.context.clone());
+        self.tainted.get(&Key::Return(decl.name.clone())).cloned()
+    }
+}
+
+fn collect_locals(block: &[Statement], names: &mut HashSet<String>) {
+    for stmt in block {
+        match stmt {
+            Statement::LetDecl(stmt) => {
+                names.insert(stmt.ident.clone());
+            }
+            Statement::VarDecl(stmt) => {
+                names.insert(stmt.ident.clone());
+            }
+            Statement::Compound(block)
+            | Statement::Loop(LoopStatement { body: block })
+            | Statement::While(WhileStatement { body: block, .. }) => collect_locals(block, names),
+            Statement::If(stmt) => {
+                collect_locals(&stmt.body, names);
+
+                let mut else_ = stmt.else_.as_deref();
+                while let Some(e) = else_ {
+                    match e {
+                        Else::If(stmt) => {
+                            collect_locals(&stmt.body, names);
+                            else_ = stmt.else_.as_deref();
+                        }
+                        Else::Else(block) => {
+                            collect_locals(block, names);
+                            else_ = None;
+                        }
+                    }
+                }
+            }
+            Statement::Switch(stmt) => {
+                for case in &stmt.cases {
+                    collect_locals(&case.body, names);
+                }
+
+                collect_locals(&stmt.default, names);
+            }
+            Statement::ForLoop(stmt) => {
+                if let Some(ForLoopInit::VarDecl(decl)) = &stmt.header.init {
+                    names.insert(decl.ident.clone());
+                }
+
+                collect_locals(&stmt.body, names);
+            }
+            _ => {}
+        }
+    }
+}
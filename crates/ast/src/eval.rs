@@ -0,0 +1,237 @@
+//! Evaluation of constant scalar expressions.
+//!
+//! Integer arithmetic follows the WGSL rules for runtime evaluation, so that replacing an
+//! expression with its folded value never changes the behaviour of a shader: `i32` wraps on
+//! overflow (two's complement), `u32` is modular, shift amounts are masked to the bit width of the
+//! operand, and division or remainder by zero (or `i32::MIN / -1`) produce the values given by the
+//! spec rather than trapping.
+//!
+//! Floating point arithmetic is never folded, since implementations are free to evaluate it with
+//! different precision.
+
+use crate::{BinOp, Expr, ExprNode, Lit, UnOp};
+
+/// Attempts to evaluate `node` to a single literal value.
+///
+/// Returns `None` if the expression depends on anything other than literals, or if it contains an
+/// operation that can't be evaluated exactly (e.g. floating point arithmetic).
+pub fn try_const_eval(node: &ExprNode) -> Option<Lit> {
+    match &node.expr {
+        Expr::Lit(lit) => Some(*lit),
+        Expr::UnOp(expr) => eval_un_op(expr.op, try_const_eval(&expr.inner)?),
+        Expr::BinOp(expr) => eval_bin_op(
+            expr.op,
+            try_const_eval(&expr.left)?,
+            try_const_eval(&expr.right)?,
+        ),
+        _ => None,
+    }
+}
+
+/// Evaluates a unary operator applied to a literal.
+pub fn eval_un_op(op: UnOp, value: Lit) -> Option<Lit> {
+    let result = match (op, value) {
+        (UnOp::Neg, Lit::I32(v)) => Lit::I32(v.wrapping_neg()),
+        (UnOp::Not, Lit::Bool(v)) => Lit::Bool(!v),
+        (UnOp::BitNot, Lit::I32(v)) => Lit::I32(!v),
+        (UnOp::BitNot, Lit::U32(v)) => Lit::U32(!v),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+/// Evaluates a binary operator applied to two literals.
+pub fn eval_bin_op(op: BinOp, left: Lit, right: Lit) -> Option<Lit> {
+    use BinOp::*;
+
+    let result = match (left, right) {
+        (Lit::Bool(l), Lit::Bool(r)) => match op {
+            LogAnd | BitAnd => Lit::Bool(l && r),
+            LogOr | BitOr => Lit::Bool(l || r),
+            Equal => Lit::Bool(l == r),
+            NotEqual => Lit::Bool(l != r),
+            _ => return None,
+        },
+        (Lit::I32(l), Lit::I32(r)) => match op {
+            Plus => Lit::I32(l.wrapping_add(r)),
+            Minus => Lit::I32(l.wrapping_sub(r)),
+            Times => Lit::I32(l.wrapping_mul(r)),
+            // If the divisor is zero or the division overflows, the result is the dividend.
+            Divide => Lit::I32(l.checked_div(r).unwrap_or(l)),
+            // If the divisor is zero or the division overflows, the result is zero.
+            Mod => Lit::I32(l.checked_rem(r).unwrap_or(0)),
+            BitAnd => Lit::I32(l & r),
+            BitOr => Lit::I32(l | r),
+            BitXOr => Lit::I32(l ^ r),
+            _ => compare(op, l, r)?,
+        },
+        (Lit::U32(l), Lit::U32(r)) => match op {
+            Plus => Lit::U32(l.wrapping_add(r)),
+            Minus => Lit::U32(l.wrapping_sub(r)),
+            Times => Lit::U32(l.wrapping_mul(r)),
+            Divide => Lit::U32(l.checked_div(r).unwrap_or(l)),
+            Mod => Lit::U32(l.checked_rem(r).unwrap_or(0)),
+            BitAnd => Lit::U32(l & r),
+            BitOr => Lit::U32(l | r),
+            BitXOr => Lit::U32(l ^ r),
+            LShift => Lit::U32(l.wrapping_shl(r)),
+            RShift => Lit::U32(l.wrapping_shr(r)),
+            _ => compare(op, l, r)?,
+        },
+        // The shift amount is masked to the bit width of the left operand, which is exactly what
+        // the `wrapping_sh*` methods do. Right shifts of signed values are arithmetic.
+        (Lit::I32(l), Lit::U32(r)) => match op {
+            LShift => Lit::I32(l.wrapping_shl(r)),
+            RShift => Lit::I32(l.wrapping_shr(r)),
+            _ => return None,
+        },
+        // Comparisons are exact, so these are the only float operations we can safely evaluate.
+        (Lit::F32(l), Lit::F32(r)) | (Lit::F16(l), Lit::F16(r)) => compare(op, l, r)?,
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn compare<T: PartialOrd>(op: BinOp, l: T, r: T) -> Option<Lit> {
+    let result = match op {
+        BinOp::Equal => l == r,
+        BinOp::NotEqual => l != r,
+        BinOp::Less => l < r,
+        BinOp::LessEqual => l <= r,
+        BinOp::Greater => l > r,
+        BinOp::GreaterEqual => l >= r,
+        _ => return None,
+    };
+
+    Some(Lit::Bool(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bin(op: BinOp, l: Lit, r: Lit) -> Lit {
+        eval_bin_op(op, l, r).unwrap()
+    }
+
+    #[test]
+    fn i32_arithmetic_wraps() {
+        use BinOp::*;
+
+        assert_eq!(
+            bin(Plus, Lit::I32(i32::MAX), Lit::I32(1)),
+            Lit::I32(i32::MIN)
+        );
+        assert_eq!(
+            bin(Minus, Lit::I32(i32::MIN), Lit::I32(1)),
+            Lit::I32(i32::MAX)
+        );
+        assert_eq!(bin(Times, Lit::I32(i32::MAX), Lit::I32(2)), Lit::I32(-2));
+        assert_eq!(
+            bin(Times, Lit::I32(i32::MIN), Lit::I32(-1)),
+            Lit::I32(i32::MIN)
+        );
+        assert_eq!(
+            eval_un_op(UnOp::Neg, Lit::I32(i32::MIN)),
+            Some(Lit::I32(i32::MIN))
+        );
+        assert_eq!(
+            eval_un_op(UnOp::BitNot, Lit::I32(i32::MAX)),
+            Some(Lit::I32(i32::MIN))
+        );
+    }
+
+    #[test]
+    fn i32_division_edge_cases() {
+        use BinOp::*;
+
+        // WGSL: if e2 is zero, or e1 is the most negative value and e2 is -1, the result is e1
+        assert_eq!(
+            bin(Divide, Lit::I32(i32::MIN), Lit::I32(-1)),
+            Lit::I32(i32::MIN)
+        );
+        assert_eq!(
+            bin(Divide, Lit::I32(i32::MAX), Lit::I32(0)),
+            Lit::I32(i32::MAX)
+        );
+        assert_eq!(bin(Divide, Lit::I32(-7), Lit::I32(2)), Lit::I32(-3));
+
+        // WGSL: under the same conditions, the remainder is zero
+        assert_eq!(bin(Mod, Lit::I32(i32::MIN), Lit::I32(-1)), Lit::I32(0));
+        assert_eq!(bin(Mod, Lit::I32(i32::MAX), Lit::I32(0)), Lit::I32(0));
+        assert_eq!(bin(Mod, Lit::I32(-7), Lit::I32(2)), Lit::I32(-1));
+    }
+
+    #[test]
+    fn u32_arithmetic_is_modular() {
+        use BinOp::*;
+
+        assert_eq!(bin(Plus, Lit::U32(u32::MAX), Lit::U32(1)), Lit::U32(0));
+        assert_eq!(bin(Minus, Lit::U32(0), Lit::U32(1)), Lit::U32(u32::MAX));
+        assert_eq!(
+            bin(Times, Lit::U32(u32::MAX), Lit::U32(u32::MAX)),
+            Lit::U32(1)
+        );
+        assert_eq!(
+            bin(Divide, Lit::U32(u32::MAX), Lit::U32(0)),
+            Lit::U32(u32::MAX)
+        );
+        assert_eq!(bin(Mod, Lit::U32(u32::MAX), Lit::U32(0)), Lit::U32(0));
+        assert_eq!(
+            eval_un_op(UnOp::BitNot, Lit::U32(u32::MAX)),
+            Some(Lit::U32(0))
+        );
+    }
+
+    #[test]
+    fn shift_amounts_are_masked() {
+        use BinOp::*;
+
+        // WGSL: the shift amount is taken modulo the bit width of e1
+        assert_eq!(bin(LShift, Lit::U32(1), Lit::U32(32)), Lit::U32(1));
+        assert_eq!(bin(LShift, Lit::U32(1), Lit::U32(33)), Lit::U32(2));
+        assert_eq!(bin(RShift, Lit::U32(u32::MAX), Lit::U32(63)), Lit::U32(1));
+        assert_eq!(
+            bin(LShift, Lit::U32(u32::MAX), Lit::U32(31)),
+            Lit::U32(1 << 31)
+        );
+
+        assert_eq!(bin(LShift, Lit::I32(1), Lit::U32(31)), Lit::I32(i32::MIN));
+        assert_eq!(bin(LShift, Lit::I32(i32::MAX), Lit::U32(1)), Lit::I32(-2));
+        assert_eq!(
+            bin(LShift, Lit::I32(1), Lit::U32(u32::MAX)),
+            Lit::I32(i32::MIN)
+        );
+
+        // Right shifts of signed values preserve the sign bit
+        assert_eq!(bin(RShift, Lit::I32(i32::MIN), Lit::U32(31)), Lit::I32(-1));
+        assert_eq!(
+            bin(RShift, Lit::I32(i32::MIN), Lit::U32(32)),
+            Lit::I32(i32::MIN)
+        );
+        assert_eq!(bin(RShift, Lit::I32(i32::MAX), Lit::U32(30)), Lit::I32(1));
+    }
+
+    #[test]
+    fn comparisons_at_boundaries() {
+        use BinOp::*;
+
+        assert_eq!(
+            bin(Less, Lit::I32(i32::MIN), Lit::I32(i32::MAX)),
+            Lit::Bool(true)
+        );
+        assert_eq!(
+            bin(Greater, Lit::U32(u32::MAX), Lit::U32(0)),
+            Lit::Bool(true)
+        );
+        assert_eq!(bin(Equal, Lit::F32(0.0), Lit::F32(-0.0)), Lit::Bool(true));
+    }
+
+    #[test]
+    fn float_arithmetic_is_not_folded() {
+        assert_eq!(eval_bin_op(BinOp::Plus, Lit::F32(1.0), Lit::F32(2.0)), None);
+        assert_eq!(eval_un_op(UnOp::Neg, Lit::F32(1.0)), None);
+    }
+}
@@ -5,11 +5,15 @@ use derive_more::{Display, From};
 use crate::types::{DataType, ScalarType};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Lit {
     Bool(bool),
     I32(i32),
     U32(u32),
     F32(f32),
+    /// An `f16` literal. The value is stored as an `f32`, and should be exactly representable as
+    /// an `f16`.
+    F16(f32),
 }
 
 impl Lit {
@@ -19,6 +23,7 @@ impl Lit {
             Lit::I32(_) => ScalarType::I32.into(),
             Lit::U32(_) => ScalarType::U32.into(),
             Lit::F32(_) => ScalarType::F32.into(),
+            Lit::F16(_) => ScalarType::F16.into(),
         }
     }
 }
@@ -43,11 +48,21 @@ impl Display for Lit {
 
                 Ok(())
             }
+            Lit::F16(v) => {
+                write!(f, "{v}")?;
+
+                if v.fract() == 0.0 {
+                    write!(f, ".0")?;
+                }
+
+                write!(f, "h")
+            }
         }
     }
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{data_type}({})", "crate::FmtArgs(args)")]
 pub struct TypeConsExpr {
     pub data_type: DataType,
@@ -60,7 +75,8 @@ impl TypeConsExpr {
     }
 }
 
-#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnOp {
     #[display(fmt = "-")]
     Neg,
@@ -99,7 +115,8 @@ impl UnOp {
     }
 }
 
-#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinOp {
     #[display(fmt = "+")]
     Plus,
@@ -201,6 +218,7 @@ impl BinOp {
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Postfix {
     #[display(fmt = "[{_0}]")]
     Index(Box<ExprNode>),
@@ -217,33 +235,59 @@ impl Postfix {
         Postfix::Member(member.into())
     }
 
-    pub fn type_eval(&self, ty: &DataType) -> DataType {
+    /// Returns the type produced by applying this postfix to a value of type `ty`, or `None` if it
+    /// can't be applied to that type.
+    ///
+    /// Indexing narrows an array to its element type and a vector to its component type. A member
+    /// access narrows a struct to the type of the member, and a swizzle narrows a vector to a
+    /// scalar or a vector with as many components as the swizzle. A reference is narrowed to a
+    /// reference to the result.
+    pub fn try_type_eval(&self, ty: &DataType) -> Option<DataType> {
         if let DataType::Ref(view) = ty {
-            return DataType::Ref(view.clone_with_type(self.type_eval(&view.inner)));
+            let inner = self.try_type_eval(&view.inner)?;
+            return Some(DataType::Ref(view.clone_with_type(inner)));
         }
 
-        match self {
-            Postfix::Index(_) => match ty {
-                DataType::Vector(_, t) => DataType::Scalar(*t),
-                DataType::Array(t, _) => (**t).clone(),
-                ty => panic!("index operator cannot be applied to type `{ty}`"),
-            },
-            Postfix::Member(ident) => match ty {
-                DataType::Struct(decl) => decl.member_type(ident).unwrap().clone(),
-                DataType::Vector(_, t) => {
-                    if ident.len() == 1 {
-                        DataType::Scalar(*t)
-                    } else {
-                        DataType::Vector(ident.len() as u8, *t)
-                    }
+        match (self, ty) {
+            (Postfix::Index(_), DataType::Vector(_, t)) => Some(DataType::Scalar(*t)),
+            (Postfix::Index(_), DataType::Array(t, _)) => Some((**t).clone()),
+            (Postfix::Member(ident), DataType::Struct(decl)) => decl.member_type(ident).cloned(),
+            (Postfix::Member(ident), DataType::Vector(n, t)) => {
+                let components: &[char] = match n {
+                    2 => &['x', 'y', 'r', 'g'],
+                    3 => &['x', 'y', 'z', 'r', 'g', 'b'],
+                    _ => &['x', 'y', 'z', 'w', 'r', 'g', 'b', 'a'],
+                };
+
+                if !(1..=4).contains(&ident.len())
+                    || !ident.chars().all(|c| components.contains(&c))
+                {
+                    return None;
                 }
-                ty => panic!("member access operator cannot be applied to type `{ty}`"),
-            },
+
+                if ident.len() == 1 {
+                    Some(DataType::Scalar(*t))
+                } else {
+                    Some(DataType::Vector(ident.len() as u8, *t))
+                }
+            }
+            _ => None,
         }
     }
+
+    /// Like [`Postfix::try_type_eval`], but panics if the postfix can't be applied to `ty`.
+    pub fn type_eval(&self, ty: &DataType) -> DataType {
+        self.try_type_eval(ty).unwrap_or_else(|| match self {
+            Postfix::Index(_) => panic!("index operator cannot be applied to type `{ty}`"),
+            Postfix::Member(ident) => {
+                panic!("member access operator `.{ident}` cannot be applied to type `{ty}`")
+            }
+        })
+    }
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{ident}")]
 pub struct VarExpr {
     pub ident: String,
@@ -265,6 +309,7 @@ impl VarExpr {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PostfixExpr {
     pub inner: Box<ExprNode>,
     pub postfix: Postfix,
@@ -279,10 +324,32 @@ impl PostfixExpr {
     }
 }
 
+/// Returns true if `node` is a zero literal, possibly negated any number of times.
+///
+/// Negated zeros are written as a plain `0` (see the `Display` impl for `UnOpExpr`), so they must
+/// not be wrapped in parentheses - otherwise the parentheses would disappear after a round trip
+/// through the parser.
+fn is_zero(node: &ExprNode) -> bool {
+    match &node.expr {
+        Expr::Lit(Lit::I32(0)) => true,
+        Expr::UnOp(UnOpExpr {
+            op: UnOp::Neg,
+            inner,
+        }) => is_zero(inner),
+        _ => false,
+    }
+}
+
+/// Returns true if `node` is a unary or binary operator expression which must be wrapped in
+/// parentheses when it appears as an operand.
+fn needs_parens(node: &ExprNode) -> bool {
+    matches!(node.expr, Expr::UnOp(_) | Expr::BinOp(_)) && !is_zero(node)
+}
+
 impl Display for PostfixExpr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let PostfixExpr { inner, postfix } = self;
-        if matches!(inner.expr, Expr::UnOp(_) | Expr::BinOp(_)) {
+        if needs_parens(inner) {
             write!(f, "({inner}){postfix}")
         } else {
             write!(f, "{inner}{postfix}")
@@ -291,6 +358,7 @@ impl Display for PostfixExpr {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnOpExpr {
     pub op: UnOp,
     pub inner: Box<ExprNode>,
@@ -308,12 +376,12 @@ impl UnOpExpr {
 impl Display for UnOpExpr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let UnOpExpr { op, inner } = self;
-        if matches!(op, UnOp::Neg) && matches!(inner.expr, Expr::Lit(Lit::I32(0))) {
+        if matches!(op, UnOp::Neg) && is_zero(inner) {
             return write!(f, "{inner}");
         }
-        if matches!(inner.expr, Expr::UnOp(_) | Expr::BinOp(_))
+        if needs_parens(inner)
             || matches!(inner.expr, Expr::Lit(Lit::I32(v)) if v < 0)
-            || matches!(inner.expr, Expr::Lit(Lit::F32(v)) if v < 0.0)
+            || matches!(inner.expr, Expr::Lit(Lit::F32(v) | Lit::F16(v)) if v.is_sign_negative())
         {
             write!(f, "{op}({inner})")
         } else {
@@ -323,6 +391,7 @@ impl Display for UnOpExpr {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinOpExpr {
     pub op: BinOp,
     pub left: Box<ExprNode>,
@@ -360,6 +429,7 @@ impl Display for BinOpExpr {
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{ident}({})", "crate::FmtArgs(args)")]
 pub struct FnCallExpr {
     pub ident: String,
@@ -383,6 +453,7 @@ impl FnCallExpr {
 }
 
 #[derive(Clone, Debug, Display, PartialEq, From)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Lit(Lit),
     TypeCons(TypeConsExpr),
@@ -394,6 +465,7 @@ pub enum Expr {
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{expr}")]
 pub struct ExprNode {
     pub data_type: DataType,
@@ -446,3 +518,63 @@ impl From<BinOpExpr> for ExprNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Lit, Postfix};
+    use crate::types::{DataType, MemoryViewType, ScalarType};
+    use crate::StorageClass;
+
+    #[test]
+    fn index_narrows_to_element_type() {
+        let index = Postfix::index(Lit::U32(0));
+
+        let array = DataType::array(DataType::Vector(2, ScalarType::I32), 4);
+        assert_eq!(
+            index.try_type_eval(&array),
+            Some(DataType::Vector(2, ScalarType::I32))
+        );
+
+        let vector = DataType::Vector(3, ScalarType::U32);
+        assert_eq!(
+            index.try_type_eval(&vector),
+            Some(DataType::Scalar(ScalarType::U32))
+        );
+
+        let reference = DataType::Ref(MemoryViewType::new(array, StorageClass::Function));
+        assert_eq!(
+            index.try_type_eval(&reference),
+            Some(DataType::Ref(MemoryViewType::new(
+                DataType::Vector(2, ScalarType::I32),
+                StorageClass::Function
+            )))
+        );
+
+        assert_eq!(
+            index.try_type_eval(&DataType::Scalar(ScalarType::I32)),
+            None
+        );
+    }
+
+    #[test]
+    fn swizzle_narrows_vector() {
+        let vector = DataType::Vector(3, ScalarType::F32);
+
+        assert_eq!(
+            Postfix::member("y").try_type_eval(&vector),
+            Some(DataType::Scalar(ScalarType::F32))
+        );
+        assert_eq!(
+            Postfix::member("zx").try_type_eval(&vector),
+            Some(DataType::Vector(2, ScalarType::F32))
+        );
+        assert_eq!(
+            Postfix::member("rgbr").try_type_eval(&vector),
+            Some(DataType::Vector(4, ScalarType::F32))
+        );
+
+        // `w` is out of range for a `vec3`, and a swizzle has at most 4 components
+        assert_eq!(Postfix::member("w").try_type_eval(&vector), None);
+        assert_eq!(Postfix::member("xyzxy").try_type_eval(&vector), None);
+    }
+}
@@ -6,6 +6,7 @@ use crate::stmt::Statement;
 use crate::types::DataType;
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ShaderStage {
     #[display(fmt = "compute")]
     Compute,
@@ -16,6 +17,7 @@ pub enum ShaderStage {
 }
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FnAttr {
     #[display(fmt = "stage({_0})")]
     Stage(ShaderStage),
@@ -26,15 +28,21 @@ pub enum FnAttr {
 }
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FnInputAttr {
     #[display(fmt = "builtin({_0})")]
     Builtin(String),
 }
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
-pub enum FnOutputAttr {}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FnOutputAttr {
+    #[display(fmt = "location({_0})")]
+    Location(u32),
+}
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{}{name}: {data_type}", "InlineAttrs(attrs)")]
 pub struct FnInput {
     pub attrs: Vec<FnInputAttr>,
@@ -53,6 +61,7 @@ impl FnInput {
 }
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{}{data_type}", "InlineAttrs(attrs)")]
 pub struct FnOutput {
     pub attrs: Vec<FnOutputAttr>,
@@ -69,6 +78,7 @@ impl FnOutput {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FnDecl {
     pub attrs: Vec<FnAttr>,
     pub name: String,
@@ -4,6 +4,7 @@ use crate::types::DataType;
 use crate::ExprNode;
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GlobalVarAttr {
     #[display(fmt = "binding({_0})")]
     Binding(i32),
@@ -11,7 +12,9 @@ pub enum GlobalVarAttr {
     Group(i32),
 }
 
-#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, strum::EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[strum(serialize_all = "lowercase")]
 pub enum StorageClass {
     #[display(fmt = "function")]
     Function,
@@ -37,7 +40,9 @@ impl StorageClass {
     }
 }
 
-#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, strum::EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[strum(serialize_all = "snake_case")]
 pub enum AccessMode {
     #[display(fmt = "read")]
     Read,
@@ -48,12 +53,14 @@ pub enum AccessMode {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VarQualifier {
     pub storage_class: StorageClass,
     pub access_mode: Option<AccessMode>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalVarDecl {
     pub attrs: Vec<GlobalVarAttr>,
     pub qualifier: Option<VarQualifier>,
@@ -85,6 +92,7 @@ impl GlobalVarDecl {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalConstDecl {
     pub name: String,
     pub data_type: DataType,
@@ -0,0 +1,1206 @@
+//! A reference interpreter for shaders.
+//!
+//! [`run`] executes the entrypoint of a module on the CPU and returns the contents that its
+//! storage buffers should hold afterwards, which gives an expected output that GPU executions can
+//! be checked against. Integer arithmetic is evaluated with the same rules as [`crate::eval`],
+//! which follow the WGSL spec for runtime evaluation, so a correct implementation must produce
+//! exactly the same bytes.
+//!
+//! Only the subset of the language that the generator produces with the `integer` profile is
+//! supported. Floating point values, pointers, atomics and entrypoint parameters are rejected with
+//! an [`InterpError`], as are out of bounds indices (since WGSL lets implementations choose what
+//! they evaluate to). The entrypoint is run as a single invocation.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::eval::{eval_bin_op, eval_un_op};
+use crate::layout::{size_of, stride_of, struct_layout, LayoutRules};
+use crate::{
+    AssignmentLhs, AssignmentOp, AssignmentStatement, BinOp, BinOpExpr, BuiltinFn, DataType, Else,
+    Expr, ExprNode, ForLoopInit, ForLoopStatement, ForLoopUpdate, IfStatement, LhsExpr,
+    LhsExprNode, Lit, Module, Postfix, ScalarType, Statement, StorageClass, SwitchStatement, UnOp,
+    VarDeclStatement,
+};
+
+/// Maximum number of statements and loop iterations to execute before giving up, in case the
+/// shader doesn't terminate.
+const MAX_STEPS: u64 = 1_000_000;
+
+/// The value of an expression or variable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Scalar(Lit),
+    Vector(Vec<Lit>),
+    Array(Vec<Value>),
+    /// The values of the members of a struct, in declaration order.
+    Struct(Vec<Value>),
+}
+
+/// The reason that a shader couldn't be interpreted.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InterpError {
+    pub message: String,
+}
+
+impl Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for InterpError {}
+
+type Result<T> = std::result::Result<T, InterpError>;
+
+fn error(message: impl Into<String>) -> InterpError {
+    InterpError {
+        message: message.into(),
+    }
+}
+
+fn unsupported(what: impl Display) -> InterpError {
+    error(format!("{what} are not supported"))
+}
+
+/// Runs the `main` function of `module` and returns the final contents of each storage buffer,
+/// in declaration order.
+///
+/// `inputs` maps the `(group, binding)` of a uniform or storage buffer to its initial contents,
+/// which are padded with zeros if needed. Buffers without an entry start out zeroed. Buffer
+/// contents use the memory layout rules of their storage class.
+pub fn run(
+    module: &Module,
+    inputs: &HashMap<(u32, u32), Vec<u8>>,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let entrypoint = module
+        .functions
+        .iter()
+        .find(|it| it.name == "main")
+        .ok_or_else(|| error("shader has no `main` function"))?;
+
+    if !entrypoint.inputs.is_empty() {
+        return Err(unsupported("entrypoint parameters"));
+    }
+
+    let mut interp = Interpreter {
+        module,
+        globals: HashMap::new(),
+        scopes: vec![],
+        steps: 0,
+    };
+
+    for decl in &module.consts {
+        let value = interp.eval(&decl.initializer)?;
+        interp.globals.insert(decl.name.clone(), value);
+    }
+
+    for var in &module.vars {
+        let value = match var.qualifier.as_ref().map(|it| it.storage_class) {
+            Some(storage_class @ (StorageClass::Uniform | StorageClass::Storage)) => {
+                let binding = var
+                    .group_index()
+                    .zip(var.binding_index())
+                    .ok_or_else(|| error(format!("`{}` has no binding", var.name)))?;
+                let bytes = inputs.get(&binding).map(Vec::as_slice).unwrap_or_default();
+                decode(&var.data_type, bytes, 0, storage_class.into())?
+            }
+            _ => match &var.initializer {
+                Some(initializer) => interp.eval(initializer)?,
+                None => zero_value(&var.data_type)?,
+            },
+        };
+
+        interp.globals.insert(var.name.clone(), value);
+    }
+
+    interp.call("main", vec![])?;
+
+    module
+        .vars
+        .iter()
+        .filter(|var| {
+            matches!(
+                var.qualifier.as_ref().map(|it| it.storage_class),
+                Some(StorageClass::Storage)
+            )
+        })
+        .map(|var| {
+            let rules = LayoutRules::Std430;
+            let mut bytes = vec![0; size_of(&var.data_type, rules) as usize];
+            encode(
+                &interp.globals[&var.name],
+                &var.data_type,
+                &mut bytes,
+                0,
+                rules,
+            )?;
+            Ok((var.name.clone(), bytes))
+        })
+        .collect()
+}
+
+/// How control leaves a statement.
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Fallthrough,
+    Return(Option<Value>),
+}
+
+struct Interpreter<'a> {
+    module: &'a Module,
+    globals: HashMap<String, Value>,
+    /// Scopes of the function that is currently executing, with the innermost scope last.
+    scopes: Vec<HashMap<String, Value>>,
+    steps: u64,
+}
+
+impl<'a> Interpreter<'a> {
+    fn tick(&mut self) -> Result<()> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(error(format!(
+                "shader didn't terminate within {MAX_STEPS} steps"
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, ident: &str) -> Result<&Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(ident))
+            .or_else(|| self.globals.get(ident))
+            .ok_or_else(|| error(format!("`{ident}` is not declared")))
+    }
+
+    fn lookup_mut(&mut self, ident: &str) -> Result<&mut Value> {
+        if let Some(value) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(ident))
+        {
+            return Ok(value);
+        }
+
+        self.globals
+            .get_mut(ident)
+            .ok_or_else(|| error(format!("`{ident}` is not declared")))
+    }
+
+    fn declare(&mut self, ident: &str, value: Value) {
+        self.scopes
+            .last_mut()
+            .expect("declarations must be inside a function")
+            .insert(ident.to_owned(), value);
+    }
+
+    /// Calls a user-defined or builtin function, returning its result if it has one.
+    fn call(&mut self, ident: &str, args: Vec<Value>) -> Result<Option<Value>> {
+        let decl = match self.module.functions.iter().find(|it| it.name == ident) {
+            Some(decl) => decl,
+            None => {
+                // The parser can't distinguish struct constructors from function calls
+                if let Some(decl) = self.module.structs.iter().find(|it| it.name == ident) {
+                    return construct(&DataType::Struct(decl.clone()), args).map(Some);
+                }
+
+                let builtin = BuiltinFn::from_str(ident)
+                    .map_err(|_| error(format!("`{ident}` is not declared")))?;
+                return call_builtin(builtin, &args).map(Some);
+            }
+        };
+
+        if decl.inputs.len() != args.len() {
+            return Err(error(format!(
+                "`{ident}` expects {} arguments",
+                decl.inputs.len()
+            )));
+        }
+
+        let frame = decl.inputs.iter().map(|it| it.name.clone()).zip(args);
+        let caller = std::mem::replace(&mut self.scopes, vec![frame.collect()]);
+        let flow = self.exec_stmts(&decl.body);
+        self.scopes = caller;
+
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(None),
+        }
+    }
+
+    fn exec_block(&mut self, block: &[Statement]) -> Result<Flow> {
+        self.scopes.push(HashMap::new());
+        let flow = self.exec_stmts(block);
+        self.scopes.pop();
+        flow
+    }
+
+    fn exec_stmts(&mut self, block: &[Statement]) -> Result<Flow> {
+        for stmt in block {
+            match self.exec_stmt(stmt)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn exec_stmt(&mut self, stmt: &Statement) -> Result<Flow> {
+        self.tick()?;
+
+        match stmt {
+            Statement::LetDecl(stmt) => {
+                let value = self.eval(&stmt.initializer)?;
+                self.declare(&stmt.ident, value);
+            }
+            Statement::VarDecl(stmt) => self.exec_var_decl(stmt)?,
+            Statement::Assignment(stmt) => self.exec_assignment(stmt)?,
+            Statement::Compound(block) => return self.exec_block(block),
+            Statement::If(stmt) => return self.exec_if(stmt),
+            Statement::Return(stmt) => {
+                let value = stmt.value.as_ref().map(|it| self.eval(it)).transpose()?;
+                return Ok(Flow::Return(value));
+            }
+            Statement::Loop(stmt) => return self.exec_loop(None, &stmt.body, None),
+            Statement::Break => return Ok(Flow::Break),
+            Statement::Continue => return Ok(Flow::Continue),
+            Statement::Switch(stmt) => return self.exec_switch(stmt),
+            Statement::Fallthrough => return Ok(Flow::Fallthrough),
+            Statement::ForLoop(stmt) => {
+                // The loop variable is in a scope of its own, enclosing the body
+                self.scopes.push(HashMap::new());
+                let flow = self.exec_for(stmt);
+                self.scopes.pop();
+                return flow;
+            }
+            Statement::While(stmt) => {
+                return self.exec_loop(Some(&stmt.condition), &stmt.body, None)
+            }
+            Statement::FnCall(stmt) => {
+                let args = self.eval_args(&stmt.args)?;
+                self.call(&stmt.ident, args)?;
+            }
+            Statement::Comment(_) => {}
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn exec_var_decl(&mut self, stmt: &VarDeclStatement) -> Result<()> {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.eval(initializer)?,
+            None => zero_value(stmt.inferred_type())?,
+        };
+
+        self.declare(&stmt.ident, value);
+
+        Ok(())
+    }
+
+    fn exec_assignment(&mut self, stmt: &AssignmentStatement) -> Result<()> {
+        let lhs = match &stmt.lhs {
+            AssignmentLhs::Phony => return self.eval(&stmt.rhs).map(drop),
+            AssignmentLhs::Expr(lhs) => lhs,
+        };
+
+        // The left hand side is evaluated before the right hand side
+        let (ident, path) = self.eval_place(lhs)?;
+        let rhs = self.eval(&stmt.rhs)?;
+
+        let op = match stmt.op {
+            AssignmentOp::Simple => None,
+            AssignmentOp::Plus => Some(BinOp::Plus),
+            AssignmentOp::Minus => Some(BinOp::Minus),
+            AssignmentOp::Times => Some(BinOp::Times),
+            AssignmentOp::Divide => Some(BinOp::Divide),
+            AssignmentOp::Mod => Some(BinOp::Mod),
+            AssignmentOp::And => Some(BinOp::BitAnd),
+            AssignmentOp::Or => Some(BinOp::BitOr),
+            AssignmentOp::Xor => Some(BinOp::BitXOr),
+        };
+
+        let value = match op {
+            Some(op) => bin_op(op, load(self.lookup(&ident)?, &path)?, rhs)?,
+            None => rhs,
+        };
+
+        store(self.lookup_mut(&ident)?, &path, value)
+    }
+
+    fn exec_if(&mut self, stmt: &IfStatement) -> Result<Flow> {
+        if self.eval_bool(&stmt.condition)? {
+            return self.exec_block(&stmt.body);
+        }
+
+        match stmt.else_.as_deref() {
+            Some(Else::If(stmt)) => self.exec_if(stmt),
+            Some(Else::Else(body)) => self.exec_block(body),
+            None => Ok(Flow::Next),
+        }
+    }
+
+    fn exec_loop(
+        &mut self,
+        condition: Option<&ExprNode>,
+        body: &[Statement],
+        update: Option<&AssignmentStatement>,
+    ) -> Result<Flow> {
+        loop {
+            self.tick()?;
+
+            if let Some(condition) = condition {
+                if !self.eval_bool(condition)? {
+                    break;
+                }
+            }
+
+            match self.exec_block(body)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Next | Flow::Continue | Flow::Fallthrough => {}
+            }
+
+            if let Some(update) = update {
+                self.exec_assignment(update)?;
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn exec_for(&mut self, stmt: &ForLoopStatement) -> Result<Flow> {
+        if let Some(ForLoopInit::VarDecl(decl)) = &stmt.header.init {
+            self.exec_var_decl(decl)?;
+        }
+
+        let update = stmt
+            .header
+            .update
+            .as_ref()
+            .map(|ForLoopUpdate::Assignment(it)| it);
+
+        self.exec_loop(stmt.header.condition.as_ref(), &stmt.body, update)
+    }
+
+    fn exec_switch(&mut self, stmt: &SwitchStatement) -> Result<Flow> {
+        let selector = self.eval(&stmt.selector)?;
+
+        let mut start = stmt.cases.len();
+        for (i, case) in stmt.cases.iter().enumerate() {
+            if self.eval(&case.selector)? == selector {
+                start = i;
+                break;
+            }
+        }
+
+        let bodies = stmt
+            .cases
+            .iter()
+            .map(|case| &case.body)
+            .chain(std::iter::once(&stmt.default));
+
+        // A `fallthrough` continues with the body of the next case
+        for body in bodies.skip(start) {
+            match self.exec_block(body)? {
+                Flow::Fallthrough => {}
+                Flow::Next | Flow::Break => return Ok(Flow::Next),
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    /// Evaluates the left hand side of an assignment to the variable it refers to, and the
+    /// indices of the element or member within that variable.
+    fn eval_place(&mut self, node: &LhsExprNode) -> Result<(String, Vec<usize>)> {
+        match &node.expr {
+            LhsExpr::Ident(ident) => Ok((ident.clone(), vec![])),
+            LhsExpr::Postfix(inner, postfix) => {
+                let (ident, mut path) = self.eval_place(inner)?;
+                let index = match postfix {
+                    Postfix::Index(index) => self.eval_index(index)?,
+                    Postfix::Member(member) => member_index(inner.data_type.dereference(), member)?,
+                };
+
+                path.push(index);
+                Ok((ident, path))
+            }
+            LhsExpr::Deref(_) | LhsExpr::AddressOf(_) => Err(unsupported("pointers")),
+        }
+    }
+
+    fn eval_args(&mut self, args: &[ExprNode]) -> Result<Vec<Value>> {
+        args.iter().map(|arg| self.eval(arg)).collect()
+    }
+
+    fn eval_bool(&mut self, node: &ExprNode) -> Result<bool> {
+        match self.eval(node)? {
+            Value::Scalar(Lit::Bool(value)) => Ok(value),
+            _ => Err(error(format!("`{node}` is not a bool"))),
+        }
+    }
+
+    fn eval_index(&mut self, node: &ExprNode) -> Result<usize> {
+        match self.eval(node)? {
+            Value::Scalar(Lit::I32(index)) => usize::try_from(index).map_err(|_| out_of_bounds()),
+            Value::Scalar(Lit::U32(index)) => Ok(index as usize),
+            _ => Err(error(format!("`{node}` is not an integer"))),
+        }
+    }
+
+    fn eval(&mut self, node: &ExprNode) -> Result<Value> {
+        match &node.expr {
+            Expr::Lit(lit) => scalar(*lit).map(Value::Scalar),
+            Expr::TypeCons(expr) => {
+                let args = self.eval_args(&expr.args)?;
+                construct(&expr.data_type, args)
+            }
+            Expr::Var(expr) => self.lookup(&expr.ident).cloned(),
+            Expr::Postfix(expr) => {
+                let inner = self.eval(&expr.inner)?;
+                match &expr.postfix {
+                    Postfix::Index(index) => load(&inner, &[self.eval_index(index)?]),
+                    Postfix::Member(member) => {
+                        access_member(inner, expr.inner.data_type.dereference(), member)
+                    }
+                }
+            }
+            Expr::UnOp(expr) => match expr.op {
+                UnOp::AddressOf | UnOp::Deref => Err(unsupported("pointers")),
+                op => map_components(&[self.eval(&expr.inner)?], |args| {
+                    eval_un_op(op, args[0])
+                        .ok_or_else(|| error(format!("can't evaluate `{op}{}`", args[0])))
+                }),
+            },
+            Expr::BinOp(expr) => self.eval_bin_op(expr),
+            Expr::FnCall(expr) => {
+                let args = self.eval_args(&expr.args)?;
+                self.call(&expr.ident, args)?
+                    .ok_or_else(|| error(format!("`{}` doesn't return a value", expr.ident)))
+            }
+        }
+    }
+
+    fn eval_bin_op(&mut self, expr: &BinOpExpr) -> Result<Value> {
+        let left = self.eval(&expr.left)?;
+
+        // The right operand of a short-circuiting operator is only evaluated if it's needed, which
+        // matters if it calls a function with side effects
+        match (expr.op, &left) {
+            (BinOp::LogAnd, Value::Scalar(Lit::Bool(false)))
+            | (BinOp::LogOr, Value::Scalar(Lit::Bool(true))) => return Ok(left),
+            _ => {}
+        }
+
+        let right = self.eval(&expr.right)?;
+        bin_op(expr.op, left, right)
+    }
+}
+
+fn out_of_bounds() -> InterpError {
+    error("index is out of bounds")
+}
+
+fn scalar(lit: Lit) -> Result<Lit> {
+    match lit {
+        Lit::Bool(_) | Lit::I32(_) | Lit::U32(_) => Ok(lit),
+        Lit::F32(_) | Lit::F16(_) => Err(unsupported("floating point values")),
+    }
+}
+
+fn zero_scalar(ty: ScalarType) -> Result<Lit> {
+    match ty {
+        ScalarType::Bool => Ok(Lit::Bool(false)),
+        ScalarType::I32 => Ok(Lit::I32(0)),
+        ScalarType::U32 => Ok(Lit::U32(0)),
+        ScalarType::F32 | ScalarType::F16 => Err(unsupported("floating point values")),
+        _ => Err(unsupported(format!("values of type `{ty}`"))),
+    }
+}
+
+/// Returns the zero value of a type, which is what variables without an initializer start with.
+fn zero_value(ty: &DataType) -> Result<Value> {
+    match ty {
+        DataType::Scalar(ty) => zero_scalar(*ty).map(Value::Scalar),
+        DataType::Vector(n, ty) => Ok(Value::Vector(vec![zero_scalar(*ty)?; *n as usize])),
+        DataType::Array(element, Some(n)) => {
+            Ok(Value::Array(vec![zero_value(element)?; *n as usize]))
+        }
+        DataType::Array(_, None) => Err(unsupported("runtime-sized arrays")),
+        DataType::Struct(decl) => decl
+            .members
+            .iter()
+            .map(|member| zero_value(&member.data_type))
+            .collect::<Result<_>>()
+            .map(Value::Struct),
+        DataType::Ptr(_) | DataType::Ref(_) => Err(unsupported("pointers")),
+    }
+}
+
+/// Converts a scalar to another scalar type, as done by a conversion like `u32(x)`.
+fn convert(lit: Lit, ty: ScalarType) -> Result<Lit> {
+    let value = match (lit, ty) {
+        (Lit::Bool(v), ScalarType::Bool) => Lit::Bool(v),
+        (Lit::I32(v), ScalarType::Bool) => Lit::Bool(v != 0),
+        (Lit::U32(v), ScalarType::Bool) => Lit::Bool(v != 0),
+        (Lit::Bool(v), ScalarType::I32) => Lit::I32(v as i32),
+        (Lit::I32(v), ScalarType::I32) => Lit::I32(v),
+        (Lit::U32(v), ScalarType::I32) => Lit::I32(v as i32),
+        (Lit::Bool(v), ScalarType::U32) => Lit::U32(v as u32),
+        (Lit::I32(v), ScalarType::U32) => Lit::U32(v as u32),
+        (Lit::U32(v), ScalarType::U32) => Lit::U32(v),
+        _ => {
+            return Err(unsupported(format!(
+                "conversions from `{}` to `{ty}`",
+                lit.data_type()
+            )))
+        }
+    };
+
+    Ok(value)
+}
+
+/// Evaluates a type constructor expression.
+fn construct(ty: &DataType, args: Vec<Value>) -> Result<Value> {
+    if args.is_empty() {
+        return zero_value(ty);
+    }
+
+    match ty {
+        DataType::Scalar(ty) => match args.as_slice() {
+            [Value::Scalar(value)] => convert(*value, *ty).map(Value::Scalar),
+            _ => Err(error(format!("invalid arguments for `{ty}`"))),
+        },
+        DataType::Vector(n, scalar_ty) => {
+            let mut components = vec![];
+            for arg in args {
+                match arg {
+                    Value::Scalar(value) => components.push(value),
+                    Value::Vector(values) => components.extend(values),
+                    _ => return Err(error(format!("invalid arguments for `{ty}`"))),
+                }
+            }
+
+            // A single scalar is splatted to every component
+            if let [value] = components.as_slice() {
+                components = vec![*value; *n as usize];
+            }
+
+            if components.len() != *n as usize {
+                return Err(error(format!("invalid arguments for `{ty}`")));
+            }
+
+            components
+                .into_iter()
+                .map(|value| convert(value, *scalar_ty))
+                .collect::<Result<_>>()
+                .map(Value::Vector)
+        }
+        DataType::Array(_, _) => Ok(Value::Array(args)),
+        DataType::Struct(_) => Ok(Value::Struct(args)),
+        DataType::Ptr(_) | DataType::Ref(_) => Err(unsupported("pointers")),
+    }
+}
+
+fn swizzle_index(component: char) -> Result<usize> {
+    match component {
+        'x' | 'r' => Ok(0),
+        'y' | 'g' => Ok(1),
+        'z' | 'b' => Ok(2),
+        'w' | 'a' => Ok(3),
+        _ => Err(error(format!("invalid vector component `{component}`"))),
+    }
+}
+
+/// Returns the index of a struct member, or of a single vector component, within a value of type
+/// `ty`.
+fn member_index(ty: &DataType, member: &str) -> Result<usize> {
+    match ty {
+        DataType::Struct(decl) => decl
+            .members
+            .iter()
+            .position(|it| it.name == member)
+            .ok_or_else(|| error(format!("`{}` has no member `{member}`", decl.name))),
+        DataType::Vector(_, _) if member.len() == 1 => {
+            swizzle_index(member.chars().next().unwrap())
+        }
+        _ => Err(error(format!("can't access `{member}` of `{ty}`"))),
+    }
+}
+
+/// Evaluates a member access or swizzle on `value`, which has type `ty`.
+fn access_member(value: Value, ty: &DataType, member: &str) -> Result<Value> {
+    match value {
+        Value::Struct(mut members) => {
+            let index = member_index(ty, member)?;
+            Ok(members.swap_remove(index))
+        }
+        Value::Vector(components) => {
+            let components = member
+                .chars()
+                .map(|it| {
+                    components
+                        .get(swizzle_index(it)?)
+                        .copied()
+                        .ok_or_else(out_of_bounds)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            match components.as_slice() {
+                [component] => Ok(Value::Scalar(*component)),
+                _ => Ok(Value::Vector(components)),
+            }
+        }
+        _ => Err(error(format!("can't access `{member}` of `{ty}`"))),
+    }
+}
+
+/// Reads the element at `path` within `value`, where each index selects an array element, struct
+/// member or vector component.
+fn load(value: &Value, path: &[usize]) -> Result<Value> {
+    let (&index, rest) = match path.split_first() {
+        Some(it) => it,
+        None => return Ok(value.clone()),
+    };
+
+    match value {
+        Value::Array(elements) | Value::Struct(elements) => {
+            load(elements.get(index).ok_or_else(out_of_bounds)?, rest)
+        }
+        Value::Vector(components) if rest.is_empty() => components
+            .get(index)
+            .map(|it| Value::Scalar(*it))
+            .ok_or_else(out_of_bounds),
+        _ => Err(error("invalid access")),
+    }
+}
+
+/// Replaces the element at `path` within `target` with `value`.
+fn store(target: &mut Value, path: &[usize], value: Value) -> Result<()> {
+    let (&index, rest) = match path.split_first() {
+        Some(it) => it,
+        None => {
+            *target = value;
+            return Ok(());
+        }
+    };
+
+    match (target, value) {
+        (Value::Array(elements) | Value::Struct(elements), value) => store(
+            elements.get_mut(index).ok_or_else(out_of_bounds)?,
+            rest,
+            value,
+        ),
+        (Value::Vector(components), Value::Scalar(value)) if rest.is_empty() => {
+            *components.get_mut(index).ok_or_else(out_of_bounds)? = value;
+            Ok(())
+        }
+        _ => Err(error("invalid access")),
+    }
+}
+
+/// Applies `f` to corresponding components of scalar or vector arguments.
+///
+/// If any of the arguments are vectors the result is a vector, with scalar arguments being passed
+/// to `f` for every component.
+fn map_components(args: &[Value], f: impl Fn(&[Lit]) -> Result<Lit>) -> Result<Value> {
+    let component = |arg: &Value, i: usize| match arg {
+        Value::Scalar(value) => Ok(*value),
+        Value::Vector(values) => values
+            .get(i)
+            .copied()
+            .ok_or_else(|| error("vector operands have different sizes")),
+        _ => Err(error("expected a scalar or vector")),
+    };
+
+    let components = |i| {
+        args.iter()
+            .map(|arg| component(arg, i))
+            .collect::<Result<Vec<_>>>()
+    };
+
+    let width = args.iter().find_map(|arg| match arg {
+        Value::Vector(values) => Some(values.len()),
+        _ => None,
+    });
+
+    match width {
+        Some(width) => (0..width)
+            .map(|i| f(&components(i)?))
+            .collect::<Result<_>>()
+            .map(Value::Vector),
+        None => f(&components(0)?).map(Value::Scalar),
+    }
+}
+
+fn bin_op(op: BinOp, left: Value, right: Value) -> Result<Value> {
+    map_components(&[left, right], |args| {
+        eval_bin_op(op, args[0], args[1])
+            .ok_or_else(|| error(format!("can't evaluate `{} {op} {}`", args[0], args[1])))
+    })
+}
+
+fn as_bool(lit: Lit) -> Result<bool> {
+    match lit {
+        Lit::Bool(value) => Ok(value),
+        _ => Err(error(format!("`{lit}` is not a bool"))),
+    }
+}
+
+fn as_u32(lit: Lit) -> Result<u32> {
+    match lit {
+        Lit::U32(value) => Ok(value),
+        _ => Err(error(format!("`{lit}` is not a u32"))),
+    }
+}
+
+/// Returns the bits of an integer, and whether it is signed.
+fn int_bits(lit: Lit) -> Result<(u32, bool)> {
+    match lit {
+        Lit::I32(value) => Ok((value as u32, true)),
+        Lit::U32(value) => Ok((value, false)),
+        _ => Err(error(format!("`{lit}` is not an integer"))),
+    }
+}
+
+fn int_from_bits(bits: u32, signed: bool) -> Lit {
+    if signed {
+        Lit::I32(bits as i32)
+    } else {
+        Lit::U32(bits)
+    }
+}
+
+/// Applies `f` to the bits of each component of an integer scalar or vector.
+fn map_bits(arg: &Value, f: impl Fn(u32, bool) -> u32) -> Result<Value> {
+    map_components(std::slice::from_ref(arg), |args| {
+        let (bits, signed) = int_bits(args[0])?;
+        Ok(int_from_bits(f(bits, signed), signed))
+    })
+}
+
+fn min_max(builtin: BuiltinFn, left: Lit, right: Lit) -> Result<Lit> {
+    let is_min = builtin == BuiltinFn::Min;
+    match (left, right) {
+        (Lit::I32(l), Lit::I32(r)) => Ok(Lit::I32(if is_min { l.min(r) } else { l.max(r) })),
+        (Lit::U32(l), Lit::U32(r)) => Ok(Lit::U32(if is_min { l.min(r) } else { l.max(r) })),
+        _ => Err(error(format!(
+            "invalid arguments for `{}`",
+            builtin.as_ref()
+        ))),
+    }
+}
+
+fn call_builtin(builtin: BuiltinFn, args: &[Value]) -> Result<Value> {
+    use BuiltinFn::*;
+
+    let arity = match builtin {
+        Abs | All | Any | CountLeadingZeros | CountOneBits | CountTrailingZeros
+        | FirstLeadingBit | FirstTrailingBit | ReverseBits => 1,
+        Dot | Max | Min => 2,
+        Clamp | ExtractBits | Select => 3,
+        InsertBits => 4,
+        _ => return Err(unsupported(format!("calls to `{}`", builtin.as_ref()))),
+    };
+
+    if args.len() != arity {
+        return Err(error(format!(
+            "`{}` expects {arity} arguments",
+            builtin.as_ref()
+        )));
+    }
+
+    match builtin {
+        Abs => map_components(args, |args| match args[0] {
+            Lit::I32(v) => Ok(Lit::I32(v.wrapping_abs())),
+            Lit::U32(v) => Ok(Lit::U32(v)),
+            lit => Err(error(format!("invalid argument for `abs`: `{lit}`"))),
+        }),
+        All | Any => {
+            let components = match &args[0] {
+                Value::Scalar(value) => vec![*value],
+                Value::Vector(values) => values.clone(),
+                _ => return Err(error("expected a scalar or vector")),
+            };
+
+            let values = components
+                .into_iter()
+                .map(as_bool)
+                .collect::<Result<Vec<_>>>()?;
+
+            let result = if builtin == All {
+                values.iter().all(|it| *it)
+            } else {
+                values.iter().any(|it| *it)
+            };
+
+            Ok(Value::Scalar(Lit::Bool(result)))
+        }
+        Select => map_components(args, |args| {
+            Ok(if as_bool(args[2])? { args[1] } else { args[0] })
+        }),
+        Min | Max => map_components(args, |args| min_max(builtin, args[0], args[1])),
+        Clamp => map_components(args, |args| {
+            min_max(Min, min_max(Max, args[0], args[1])?, args[2])
+        }),
+        CountOneBits => map_bits(&args[0], |bits, _| bits.count_ones()),
+        CountLeadingZeros => map_bits(&args[0], |bits, _| bits.leading_zeros()),
+        CountTrailingZeros => map_bits(&args[0], |bits, _| bits.trailing_zeros()),
+        ReverseBits => map_bits(&args[0], |bits, _| bits.reverse_bits()),
+        // For negative signed values, this finds the most significant zero bit instead
+        FirstLeadingBit => map_bits(&args[0], |bits, signed| {
+            let bits = if signed && (bits as i32) < 0 {
+                !bits
+            } else {
+                bits
+            };
+
+            match bits {
+                0 => u32::MAX,
+                _ => 31 - bits.leading_zeros(),
+            }
+        }),
+        FirstTrailingBit => map_bits(&args[0], |bits, _| match bits {
+            0 => u32::MAX,
+            _ => bits.trailing_zeros(),
+        }),
+        ExtractBits => map_components(args, |args| {
+            let (bits, signed) = int_bits(args[0])?;
+            let offset = as_u32(args[1])?.min(32);
+            let count = as_u32(args[2])?.min(32 - offset);
+
+            let result = if count == 0 {
+                0
+            } else if signed {
+                // Shift the field to the top, then back down to sign extend it
+                (((bits << (32 - offset - count)) as i32) >> (32 - count)) as u32
+            } else {
+                (bits >> offset) & (u32::MAX >> (32 - count))
+            };
+
+            Ok(int_from_bits(result, signed))
+        }),
+        InsertBits => map_components(args, |args| {
+            let (bits, signed) = int_bits(args[0])?;
+            let (new_bits, _) = int_bits(args[1])?;
+            let offset = as_u32(args[2])?.min(32);
+            let count = as_u32(args[3])?.min(32 - offset);
+
+            let result = if count == 0 {
+                bits
+            } else {
+                let mask = (u32::MAX >> (32 - count)) << offset;
+                (bits & !mask) | ((new_bits << offset) & mask)
+            };
+
+            Ok(int_from_bits(result, signed))
+        }),
+        Dot => {
+            let products = bin_op(BinOp::Times, args[0].clone(), args[1].clone())?;
+            match products {
+                Value::Vector(products) => products[1..]
+                    .iter()
+                    .try_fold(Value::Scalar(products[0]), |sum, product| {
+                        bin_op(BinOp::Plus, sum, Value::Scalar(*product))
+                    }),
+                _ => Err(error("invalid arguments for `dot`")),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn decode_scalar(ty: ScalarType, bytes: &[u8], offset: u32) -> Result<Lit> {
+    // Anything past the end of the input data reads as zero
+    let mut word = [0; 4];
+    for (i, byte) in word.iter_mut().enumerate() {
+        if let Some(value) = bytes.get(offset as usize + i) {
+            *byte = *value;
+        }
+    }
+
+    let bits = u32::from_le_bytes(word);
+    match ty {
+        ScalarType::I32 => Ok(Lit::I32(bits as i32)),
+        ScalarType::U32 => Ok(Lit::U32(bits)),
+        _ => Err(unsupported(format!("`{ty}` values in buffers"))),
+    }
+}
+
+/// Reads a value of type `ty` from the buffer contents `bytes`, starting at `offset`.
+fn decode(ty: &DataType, bytes: &[u8], offset: u32, rules: LayoutRules) -> Result<Value> {
+    match ty {
+        DataType::Scalar(ty) => decode_scalar(*ty, bytes, offset).map(Value::Scalar),
+        DataType::Vector(n, ty) => (0..*n as u32)
+            .map(|i| decode_scalar(*ty, bytes, offset + i * ty.size_in_bytes()))
+            .collect::<Result<_>>()
+            .map(Value::Vector),
+        DataType::Array(element, Some(n)) => {
+            let stride = stride_of(element, rules);
+            (0..*n)
+                .map(|i| decode(element, bytes, offset + i * stride, rules))
+                .collect::<Result<_>>()
+                .map(Value::Array)
+        }
+        DataType::Array(_, None) => Err(unsupported("runtime-sized arrays")),
+        DataType::Struct(decl) => {
+            let layout = struct_layout(decl, rules);
+            decl.members
+                .iter()
+                .zip(&layout.offsets)
+                .map(|(member, member_offset)| {
+                    decode(&member.data_type, bytes, offset + member_offset, rules)
+                })
+                .collect::<Result<_>>()
+                .map(Value::Struct)
+        }
+        DataType::Ptr(_) | DataType::Ref(_) => Err(unsupported("pointers")),
+    }
+}
+
+/// Writes `value`, which has type `ty`, to the buffer contents `bytes` starting at `offset`.
+fn encode(
+    value: &Value,
+    ty: &DataType,
+    bytes: &mut [u8],
+    offset: u32,
+    rules: LayoutRules,
+) -> Result<()> {
+    match (value, ty) {
+        (Value::Scalar(lit), _) => {
+            let word = match lit {
+                Lit::I32(v) => v.to_le_bytes(),
+                Lit::U32(v) => v.to_le_bytes(),
+                _ => return Err(unsupported(format!("`{ty}` values in buffers"))),
+            };
+
+            let offset = offset as usize;
+            bytes[offset..offset + 4].copy_from_slice(&word);
+        }
+        (Value::Vector(components), DataType::Vector(_, scalar_ty)) => {
+            let ty = DataType::Scalar(*scalar_ty);
+            for (i, component) in components.iter().enumerate() {
+                let offset = offset + i as u32 * scalar_ty.size_in_bytes();
+                encode(&Value::Scalar(*component), &ty, bytes, offset, rules)?;
+            }
+        }
+        (Value::Array(elements), DataType::Array(element_ty, _)) => {
+            let stride = stride_of(element_ty, rules);
+            for (i, element) in elements.iter().enumerate() {
+                encode(
+                    element,
+                    element_ty,
+                    bytes,
+                    offset + i as u32 * stride,
+                    rules,
+                )?;
+            }
+        }
+        (Value::Struct(members), DataType::Struct(decl)) => {
+            let layout = struct_layout(decl, rules);
+            for ((member, decl), member_offset) in
+                members.iter().zip(&decl.members).zip(&layout.offsets)
+            {
+                encode(
+                    member,
+                    &decl.data_type,
+                    bytes,
+                    offset + member_offset,
+                    rules,
+                )?;
+            }
+        }
+        _ => return Err(error(format!("value doesn't match type `{ty}`"))),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{FnBuilder, ModuleBuilder};
+    use crate::types::MemoryViewType;
+    use crate::{
+        FnCallExpr, IfStatement, LetDeclStatement, LoopStatement, PostfixExpr, ReturnStatement,
+        VarExpr, VarQualifier,
+    };
+
+    fn qualifier(storage_class: StorageClass) -> VarQualifier {
+        VarQualifier {
+            storage_class,
+            access_mode: None,
+        }
+    }
+
+    fn main_with_body(body: Vec<Statement>) -> Module {
+        ModuleBuilder::new()
+            .add_function(FnBuilder::new("main").entrypoint(1).stmts(body).build())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn runs_shader_with_buffers() {
+        let u32 = DataType::from(ScalarType::U32);
+        let vec2 = DataType::Vector(2, ScalarType::U32);
+        let output = DataType::array(u32.clone(), 3);
+        let output_ref = DataType::Ref(MemoryViewType::new(output.clone(), StorageClass::Storage));
+
+        let var = |name: &str, ty: &DataType| VarExpr::new(name).into_node(ty.clone());
+        let input = |member: &str| PostfixExpr::new(var("u_input", &vec2), Postfix::member(member));
+        let output_at = |index: u32| {
+            AssignmentLhs::array_index("s_output", output_ref.clone(), Lit::U32(index).into())
+        };
+
+        // var i = 0u;
+        // loop {
+        //     if (i >= u_input.y) { break; }
+        //     s_output[1] += inc(i);
+        //     i += 1u;
+        // }
+        // s_output[0] = ~u_input.x;
+        // s_output[2] = i;
+        let body = vec![
+            VarDeclStatement::new("i", None, Some(Lit::U32(0).into())).into(),
+            LoopStatement::new(vec![
+                IfStatement::new(
+                    BinOpExpr::new(BinOp::GreaterEqual, var("i", &u32), input("y")),
+                    vec![Statement::Break],
+                )
+                .into(),
+                AssignmentStatement::new(
+                    output_at(1),
+                    AssignmentOp::Plus,
+                    FnCallExpr::new("inc", vec![var("i", &u32)]).into_node(u32.clone()),
+                )
+                .into(),
+                AssignmentStatement::new(
+                    AssignmentLhs::name("i", u32.clone()),
+                    AssignmentOp::Plus,
+                    Lit::U32(1),
+                )
+                .into(),
+            ])
+            .into(),
+            AssignmentStatement::new(
+                output_at(0),
+                AssignmentOp::Simple,
+                crate::UnOpExpr::new(UnOp::BitNot, input("x")),
+            )
+            .into(),
+            AssignmentStatement::new(output_at(2), AssignmentOp::Simple, var("i", &u32)).into(),
+        ];
+
+        let module = ModuleBuilder::new()
+            .add_binding(
+                0,
+                0,
+                qualifier(StorageClass::Uniform),
+                "u_input",
+                vec2.clone(),
+            )
+            .add_binding(0, 1, qualifier(StorageClass::Storage), "s_output", output)
+            .add_function(
+                FnBuilder::new("inc")
+                    .param("x", u32.clone())
+                    .returns(u32.clone())
+                    .stmt(ReturnStatement::new(BinOpExpr::new(
+                        BinOp::Plus,
+                        var("x", &u32),
+                        Lit::U32(1),
+                    )))
+                    .build(),
+            )
+            .add_function(FnBuilder::new("main").entrypoint(1).stmts(body).build())
+            .build()
+            .unwrap();
+
+        let inputs = HashMap::from([((0, 0), vec![5, 0, 0, 0, 4, 0, 0, 0])]);
+        let buffers = run(&module, &inputs).unwrap();
+
+        let expected: Vec<u8> = [!5u32, 1 + 2 + 3 + 4, 4]
+            .iter()
+            .flat_map(|it| it.to_le_bytes())
+            .collect();
+
+        assert_eq!(buffers, vec![("s_output".to_owned(), expected)]);
+    }
+
+    #[test]
+    fn builtins() {
+        use BuiltinFn::*;
+
+        let i32 = |v: i32| Value::Scalar(Lit::I32(v));
+        let u32 = |v: u32| Value::Scalar(Lit::U32(v));
+        let call = |builtin, args: &[Value]| call_builtin(builtin, args).unwrap();
+
+        assert_eq!(call(FirstLeadingBit, &[i32(-1)]), i32(-1));
+        assert_eq!(call(FirstLeadingBit, &[i32(-16)]), i32(3));
+        assert_eq!(call(FirstLeadingBit, &[u32(0x40)]), u32(6));
+        assert_eq!(call(FirstTrailingBit, &[u32(0)]), u32(u32::MAX));
+        assert_eq!(call(ExtractBits, &[i32(0xb0), u32(4), u32(4)]), i32(-5));
+        assert_eq!(call(ExtractBits, &[u32(0xb0), u32(4), u32(4)]), u32(0xb));
+        assert_eq!(call(ExtractBits, &[u32(0xb0), u32(40), u32(4)]), u32(0));
+        assert_eq!(
+            call(InsertBits, &[u32(u32::MAX), u32(0), u32(8), u32(8)]),
+            u32(0xffff00ff)
+        );
+        assert_eq!(call(Clamp, &[i32(10), i32(0), i32(5)]), i32(5));
+        assert_eq!(call(Abs, &[i32(i32::MIN)]), i32(i32::MIN));
+
+        let vec = |values: &[i32]| Value::Vector(values.iter().map(|it| Lit::I32(*it)).collect());
+
+        assert_eq!(
+            call(
+                Select,
+                &[
+                    vec(&[1, 2]),
+                    vec(&[3, 4]),
+                    Value::Vector(vec![Lit::Bool(true), Lit::Bool(false)])
+                ]
+            ),
+            vec(&[3, 2])
+        );
+        assert_eq!(call(Dot, &[vec(&[i32::MAX, 1]), vec(&[2, 3])]), i32(1));
+    }
+
+    #[test]
+    fn rejects_unsupported_shaders() {
+        let err = run(
+            &main_with_body(vec![LetDeclStatement::new("x", Lit::F32(1.0)).into()]),
+            &HashMap::new(),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.message, "floating point values are not supported");
+
+        let array = DataType::array(ScalarType::I32, 2);
+        let err = run(
+            &main_with_body(vec![
+                VarDeclStatement::new("a", Some(array.clone()), None).into(),
+                LetDeclStatement::new(
+                    "x",
+                    PostfixExpr::new(
+                        VarExpr::new("a").into_node(array),
+                        Postfix::index(Lit::U32(2)),
+                    ),
+                )
+                .into(),
+            ]),
+            &HashMap::new(),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.message, "index is out of bounds");
+    }
+}
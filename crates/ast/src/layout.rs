@@ -0,0 +1,224 @@
+//! Memory layout computation for host-shareable types.
+//!
+//! This implements the alignment and size rules from the WGSL spec, which are needed whenever we
+//! have to match up the bytes in a buffer with the types declared in a shader (e.g. when
+//! uploading input data or interpreting the contents of an output buffer).
+
+use crate::types::DataType;
+use crate::{StorageClass, StructDecl, StructMemberAttr};
+
+/// The set of layout rules that apply to a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutRules {
+    /// Rules for the uniform storage class. Arrays and structs have their alignment rounded up to
+    /// 16 bytes, and array elements must be padded out to a multiple of 16 bytes.
+    Std140,
+    /// Rules for the storage storage class.
+    Std430,
+}
+
+impl From<StorageClass> for LayoutRules {
+    fn from(storage_class: StorageClass) -> Self {
+        match storage_class {
+            StorageClass::Uniform => LayoutRules::Std140,
+            _ => LayoutRules::Std430,
+        }
+    }
+}
+
+/// The byte offsets of the members of a struct, along with its total size and alignment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructLayout {
+    pub offsets: Vec<u32>,
+    pub size: u32,
+    pub align: u32,
+}
+
+fn round_up(value: u32, alignment: u32) -> u32 {
+    value.next_multiple_of(alignment)
+}
+
+/// Returns the required alignment in bytes of a type.
+///
+/// Panics if `ty` is not host-shareable (i.e. a pointer or reference).
+pub fn align_of(ty: &DataType, rules: LayoutRules) -> u32 {
+    match ty {
+        DataType::Scalar(t) => t.size_in_bytes(),
+        DataType::Vector(2, t) => 2 * t.size_in_bytes(),
+        DataType::Vector(3 | 4, t) => 4 * t.size_in_bytes(),
+        DataType::Vector(n, _) => panic!("invalid vector size: {n}"),
+        DataType::Array(element, _) => match rules {
+            LayoutRules::Std140 => round_up(align_of(element, rules), 16),
+            LayoutRules::Std430 => align_of(element, rules),
+        },
+        DataType::Struct(decl) => struct_layout(decl, rules).align,
+        DataType::Ptr(_) | DataType::Ref(_) => panic!("`{ty}` is not host-shareable"),
+    }
+}
+
+/// Returns the size in bytes of a type.
+///
+/// Runtime-sized arrays are treated as having a single element, which gives the minimum size of a
+/// buffer that can be bound to a variable of that type.
+///
+/// Panics if `ty` is not host-shareable (i.e. a pointer or reference).
+pub fn size_of(ty: &DataType, rules: LayoutRules) -> u32 {
+    match ty {
+        DataType::Scalar(t) => t.size_in_bytes(),
+        DataType::Vector(n, t) => *n as u32 * t.size_in_bytes(),
+        DataType::Array(element, size) => size.unwrap_or(1) * stride_of(element, rules),
+        DataType::Struct(decl) => struct_layout(decl, rules).size,
+        DataType::Ptr(_) | DataType::Ref(_) => panic!("`{ty}` is not host-shareable"),
+    }
+}
+
+/// Returns the stride in bytes between consecutive elements of an array with the given element
+/// type.
+pub fn stride_of(element: &DataType, rules: LayoutRules) -> u32 {
+    let stride = round_up(size_of(element, rules), align_of(element, rules));
+    match rules {
+        LayoutRules::Std140 => round_up(stride, 16),
+        LayoutRules::Std430 => stride,
+    }
+}
+
+/// Computes the byte offset of each member of a struct, taking any `@align` attributes into
+/// account.
+pub fn struct_layout(decl: &StructDecl, rules: LayoutRules) -> StructLayout {
+    let mut offsets = Vec::with_capacity(decl.members.len());
+    let mut offset = 0;
+    let mut align = 0;
+
+    for member in &decl.members {
+        let member_align = member
+            .attrs
+            .iter()
+            .map(|attr| match attr {
+                StructMemberAttr::Align(n) => *n as u32,
+            })
+            .next()
+            .unwrap_or_else(|| align_of(&member.data_type, rules));
+
+        offset = round_up(offset, member_align);
+        offsets.push(offset);
+        offset += size_of(&member.data_type, rules);
+        align = u32::max(align, member_align);
+    }
+
+    if rules == LayoutRules::Std140 {
+        align = round_up(align, 16);
+    }
+
+    StructLayout {
+        offsets,
+        size: round_up(offset, align.max(1)),
+        align,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ScalarType, StructMember};
+
+    fn vec3f() -> DataType {
+        DataType::Vector(3, ScalarType::F32)
+    }
+
+    #[test]
+    fn vec3_is_padded_to_16_bytes() {
+        assert_eq!(size_of(&vec3f(), LayoutRules::Std430), 12);
+        assert_eq!(align_of(&vec3f(), LayoutRules::Std430), 16);
+        assert_eq!(stride_of(&vec3f(), LayoutRules::Std430), 16);
+        assert_eq!(
+            size_of(&DataType::array(vec3f(), Some(3)), LayoutRules::Std430),
+            48
+        );
+    }
+
+    #[test]
+    fn std140_rounds_array_stride() {
+        let f32 = DataType::from(ScalarType::F32);
+        let vec2f = DataType::Vector(2, ScalarType::F32);
+
+        assert_eq!(stride_of(&f32, LayoutRules::Std430), 4);
+        assert_eq!(stride_of(&f32, LayoutRules::Std140), 16);
+        assert_eq!(stride_of(&vec2f, LayoutRules::Std430), 8);
+        assert_eq!(stride_of(&vec2f, LayoutRules::Std140), 16);
+
+        let array = DataType::array(f32, Some(4));
+        assert_eq!(size_of(&array, LayoutRules::Std430), 16);
+        assert_eq!(size_of(&array, LayoutRules::Std140), 64);
+        assert_eq!(align_of(&array, LayoutRules::Std140), 16);
+    }
+
+    #[test]
+    fn struct_member_offsets() {
+        // struct Inner { x: f32 }
+        let inner = StructDecl::new(
+            "Inner",
+            vec![StructMember::new(vec![], "x", ScalarType::F32.into())],
+        );
+
+        // struct S { a: f32, b: vec3<f32>, c: f32, d: Inner, @align(32) e: u32 }
+        let decl = StructDecl::new(
+            "S",
+            vec![
+                StructMember::new(vec![], "a", ScalarType::F32.into()),
+                StructMember::new(vec![], "b", vec3f()),
+                StructMember::new(vec![], "c", ScalarType::F32.into()),
+                StructMember::new(vec![], "d", DataType::Struct(inner)),
+                StructMember::new(
+                    vec![StructMemberAttr::Align(32)],
+                    "e",
+                    ScalarType::U32.into(),
+                ),
+            ],
+        );
+
+        // `c` fits in the padding after the vec3 `b`
+        assert_eq!(
+            struct_layout(&decl, LayoutRules::Std430),
+            StructLayout {
+                offsets: vec![0, 16, 28, 32, 64],
+                size: 96,
+                align: 32,
+            }
+        );
+    }
+
+    #[test]
+    fn std140_aligns_nested_structs() {
+        let inner = StructDecl::new(
+            "Inner",
+            vec![StructMember::new(vec![], "x", ScalarType::F32.into())],
+        );
+
+        // struct S { a: f32, b: Inner }
+        let decl = StructDecl::new(
+            "S",
+            vec![
+                StructMember::new(vec![], "a", ScalarType::F32.into()),
+                StructMember::new(vec![], "b", DataType::Struct(inner)),
+            ],
+        );
+
+        assert_eq!(
+            struct_layout(&decl, LayoutRules::Std430),
+            StructLayout {
+                offsets: vec![0, 4],
+                size: 8,
+                align: 4,
+            }
+        );
+
+        assert_eq!(
+            struct_layout(&decl, LayoutRules::Std140),
+            StructLayout {
+                offsets: vec![0, 16],
+                size: 32,
+                align: 16,
+            }
+        );
+    }
+}
@@ -1,3 +1,10 @@
+pub mod builder;
+pub mod constant;
+pub mod eval;
+pub mod interp;
+pub mod layout;
+pub mod metrics;
+pub mod typecheck;
 pub mod types;
 pub mod writer;
 
@@ -17,16 +24,46 @@ pub use structs::*;
 
 use std::rc::Rc;
 
+use derive_more::Display;
+
+pub use metrics::feature_tags;
 pub use types::{DataType, ScalarType};
 
+/// A language extension which can be turned on with an `enable` directive.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Extension {
+    #[display(fmt = "f16")]
+    F16,
+}
+
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub enables: Vec<Extension>,
     pub structs: Vec<Rc<StructDecl>>,
     pub consts: Vec<GlobalConstDecl>,
     pub vars: Vec<GlobalVarDecl>,
     pub functions: Vec<FnDecl>,
 }
 
+#[cfg(feature = "serde")]
+impl Module {
+    /// Serializes the module to JSON.
+    ///
+    /// Struct declarations are written out in full wherever they are referenced, so the output can
+    /// be consumed without resolving any names.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Deserializes a module that was serialized with [`Module::to_json`].
+    pub fn from_json(json: &str) -> serde_json::Result<Module> {
+        serde_json::from_str(json)
+    }
+}
+
 struct FmtArgs<'a>(&'a [ExprNode]);
 
 impl<'a> std::fmt::Display for FmtArgs<'a> {
@@ -0,0 +1,405 @@
+//! Structural complexity metrics for ranking shaders.
+//!
+//! [`complexity_score`] measures the shape of a module's AST so that large batches of generated (or
+//! reduced) shaders can be ranked, e.g. to keep only the most interesting ones from a generation
+//! run. All components are computed purely from the AST, so scores are deterministic and comparable
+//! across runs and machines.
+//!
+//! [`feature_tags`] describes which constructs a module uses, so that findings can be clustered and
+//! searched by the features they exercise.
+
+use std::collections::{BTreeSet, HashSet};
+
+use crate::types::DataType;
+use crate::{
+    AssignmentLhs, AssignmentOp, AssignmentStatement, BinOp, BuiltinFn, Else, Expr, ExprNode,
+    ForLoopInit, ForLoopUpdate, LhsExpr, LhsExprNode, Module, Postfix, Statement, UnOp,
+};
+
+/// Constructs that the generator produces rarely (or only when opted in), and whose presence makes
+/// a shader more likely to exercise less well-tested compiler paths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RareConstruct {
+    /// A `loop` statement.
+    Loop,
+    /// A `for` statement.
+    ForLoop,
+    /// A `while` statement.
+    While,
+    /// A `switch` statement.
+    Switch,
+    /// A `fallthrough` statement.
+    Fallthrough,
+    /// An address-of (`&`) or dereference (`*`) operation.
+    Pointer,
+    /// A call to `workgroupBarrier` or `storageBarrier`.
+    Barrier,
+    /// A multi-component vector swizzle such as `v.xy`.
+    Swizzle,
+    /// A phony assignment (`_ = e`).
+    PhonyAssignment,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of distinct operators used anywhere in the module. Unary operators, binary operators
+    /// and compound assignment operators (`+=` etc.) are each counted as separate operators, so
+    /// e.g. `-` (negation), `-` (subtraction) and `-=` count as three.
+    pub operator_diversity: usize,
+    /// Maximum nesting depth of any expression, where a literal or variable has depth 1 and every
+    /// other expression is one deeper than its deepest operand (including array indices).
+    pub max_expr_depth: usize,
+    /// Maximum nesting depth of statement blocks, where a function body has depth 1.
+    pub max_block_depth: usize,
+    /// Number of distinct variables referenced by expressions or assignment targets. Variables are
+    /// identified by `(function name, identifier)`, so a global referenced from two functions
+    /// counts twice, and declarations that are never referenced don't count at all.
+    pub distinct_vars: usize,
+    /// The set of rare constructs that appear at least once.
+    pub rare_constructs: BTreeSet<RareConstruct>,
+}
+
+impl Metrics {
+    /// Combines the components into a single score, where higher means more interesting.
+    ///
+    /// This is `operator_diversity + max_expr_depth + max_block_depth + distinct_vars + 4 * n`,
+    /// where `n` is the number of distinct rare constructs present.
+    pub fn score(&self) -> usize {
+        self.operator_diversity
+            + self.max_expr_depth
+            + self.max_block_depth
+            + self.distinct_vars
+            + 4 * self.rare_constructs.len()
+    }
+}
+
+/// Computes the complexity [`Metrics`] for `module`.
+pub fn complexity_score(module: &Module) -> Metrics {
+    let mut visitor = Visitor::default();
+    visitor.visit_module(module);
+
+    Metrics {
+        operator_diversity: visitor.operators.len(),
+        max_expr_depth: visitor.max_expr_depth,
+        max_block_depth: visitor.max_block_depth,
+        distinct_vars: visitor.vars.len(),
+        rare_constructs: visitor.rare_constructs,
+    }
+}
+
+/// Returns a set of tags describing the constructs that `module` uses, e.g. `{"u32", "rshift",
+/// "switch", "vec4"}`.
+///
+/// The tags are made up of:
+///
+/// - the scalar types (e.g. `u32`) and vector widths (e.g. `vec4`) of expressions
+/// - the operators, named after their variant in lowercase (e.g. `rshift` for `>>` and `neg` for
+///   unary `-`), with compound assignments suffixed with `-assign` (e.g. `plus-assign`)
+/// - the kinds of statement (`let`, `var`, `assignment`, `compound`, `if`, `return`, `loop`,
+///   `switch`, `fallthrough`, `for`, `break`, `continue`, `call` or `barrier`)
+/// - the builtin functions that are called (e.g. `countOneBits`)
+pub fn feature_tags(module: &Module) -> BTreeSet<String> {
+    let mut visitor = Visitor::default();
+    visitor.visit_module(module);
+    visitor.tags
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum Operator {
+    Un(UnOp),
+    Bin(BinOp),
+    Assign(AssignmentOp),
+}
+
+#[derive(Default)]
+struct Visitor {
+    function: String,
+    operators: HashSet<Operator>,
+    vars: HashSet<(String, String)>,
+    rare_constructs: BTreeSet<RareConstruct>,
+    max_expr_depth: usize,
+    max_block_depth: usize,
+    tags: BTreeSet<String>,
+}
+
+impl Visitor {
+    fn visit_module(&mut self, module: &Module) {
+        for decl in &module.consts {
+            self.visit_expr(&decl.initializer);
+        }
+
+        for decl in &module.vars {
+            if let Some(initializer) = &decl.initializer {
+                self.visit_expr(initializer);
+            }
+        }
+
+        for decl in &module.functions {
+            self.function = decl.name.clone();
+            self.visit_block(&decl.body, 1);
+        }
+    }
+
+    fn tag(&mut self, tag: impl Into<String>) {
+        self.tags.insert(tag.into());
+    }
+
+    fn tag_type(&mut self, ty: &DataType) {
+        match ty {
+            DataType::Scalar(ty) => self.tag(ty.to_string()),
+            DataType::Vector(n, ty) => {
+                self.tag(ty.to_string());
+                self.tag(format!("vec{n}"));
+            }
+            DataType::Array(ty, _) => self.tag_type(ty),
+            DataType::Struct(_) => {}
+            DataType::Ptr(view) | DataType::Ref(view) => self.tag_type(&view.inner),
+        }
+    }
+
+    fn tag_operator(&mut self, op: impl std::fmt::Debug) {
+        self.tag(format!("{op:?}").to_lowercase());
+    }
+    fn visit_block(&mut self, block: &[Statement], depth: usize) {
+        self.max_block_depth = self.max_block_depth.max(depth);
+        for stmt in block {
+            self.visit_stmt(stmt, depth);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Statement, depth: usize) {
+        if let Some(kind) = stmt_kind(stmt) {
+            self.tag(kind);
+        }
+
+        match stmt {
+            Statement::LetDecl(stmt) => {
+                self.visit_expr(&stmt.initializer);
+            }
+            Statement::VarDecl(stmt) => {
+                if let Some(initializer) = &stmt.initializer {
+                    self.visit_expr(initializer);
+                }
+            }
+            Statement::Assignment(stmt) => self.visit_assignment(stmt),
+            Statement::Compound(block) => self.visit_block(block, depth + 1),
+            Statement::If(stmt) => {
+                self.visit_expr(&stmt.condition);
+                self.visit_block(&stmt.body, depth + 1);
+
+                let mut else_ = stmt.else_.as_deref();
+                while let Some(e) = else_ {
+                    match e {
+                        Else::If(stmt) => {
+                            self.visit_expr(&stmt.condition);
+                            self.visit_block(&stmt.body, depth + 1);
+                            else_ = stmt.else_.as_deref();
+                        }
+                        Else::Else(block) => {
+                            self.visit_block(block, depth + 1);
+                            else_ = None;
+                        }
+                    }
+                }
+            }
+            Statement::Return(stmt) => {
+                if let Some(value) = &stmt.value {
+                    self.visit_expr(value);
+                }
+            }
+            Statement::Loop(stmt) => {
+                self.rare_constructs.insert(RareConstruct::Loop);
+                self.visit_block(&stmt.body, depth + 1);
+            }
+            Statement::Break | Statement::Continue => {}
+            Statement::Switch(stmt) => {
+                self.rare_constructs.insert(RareConstruct::Switch);
+                self.visit_expr(&stmt.selector);
+
+                for case in &stmt.cases {
+                    self.visit_expr(&case.selector);
+                    self.visit_block(&case.body, depth + 1);
+                }
+
+                self.visit_block(&stmt.default, depth + 1);
+            }
+            Statement::Fallthrough => {
+                self.rare_constructs.insert(RareConstruct::Fallthrough);
+            }
+            Statement::ForLoop(stmt) => {
+                self.rare_constructs.insert(RareConstruct::ForLoop);
+
+                if let Some(ForLoopInit::VarDecl(decl)) = &stmt.header.init {
+                    if let Some(initializer) = &decl.initializer {
+                        self.visit_expr(initializer);
+                    }
+                }
+
+                if let Some(condition) = &stmt.header.condition {
+                    self.visit_expr(condition);
+                }
+
+                if let Some(ForLoopUpdate::Assignment(update)) = &stmt.header.update {
+                    self.visit_assignment(update);
+                }
+
+                self.visit_block(&stmt.body, depth + 1);
+            }
+            Statement::While(stmt) => {
+                self.rare_constructs.insert(RareConstruct::While);
+                self.visit_expr(&stmt.condition);
+                self.visit_block(&stmt.body, depth + 1);
+            }
+            Statement::FnCall(stmt) => {
+                if is_barrier(&stmt.ident) {
+                    self.rare_constructs.insert(RareConstruct::Barrier);
+                } else {
+                    self.visit_fn_call(&stmt.ident);
+                }
+
+                for arg in &stmt.args {
+                    self.visit_expr(arg);
+                }
+            }
+            Statement::Comment(_) => {}
+        }
+    }
+
+    fn visit_assignment(&mut self, stmt: &AssignmentStatement) {
+        match &stmt.lhs {
+            AssignmentLhs::Phony => {
+                self.rare_constructs.insert(RareConstruct::PhonyAssignment);
+            }
+            AssignmentLhs::Expr(lhs) => self.visit_lhs_expr(lhs),
+        }
+
+        if stmt.op != AssignmentOp::Simple {
+            self.tag(format!("{:?}-assign", stmt.op).to_lowercase());
+            self.operators.insert(Operator::Assign(stmt.op.clone()));
+        }
+
+        self.visit_expr(&stmt.rhs);
+    }
+
+    fn visit_lhs_expr(&mut self, node: &LhsExprNode) {
+        match &node.expr {
+            LhsExpr::Ident(ident) => self.visit_var(ident),
+            LhsExpr::Postfix(inner, postfix) => {
+                self.visit_lhs_expr(inner);
+                self.visit_postfix(postfix, &inner.data_type);
+            }
+            LhsExpr::Deref(inner) => {
+                self.rare_constructs.insert(RareConstruct::Pointer);
+                self.tag_operator(UnOp::Deref);
+                self.visit_lhs_expr(inner);
+            }
+            LhsExpr::AddressOf(inner) => {
+                self.rare_constructs.insert(RareConstruct::Pointer);
+                self.tag_operator(UnOp::AddressOf);
+                self.visit_lhs_expr(inner);
+            }
+        }
+    }
+
+    /// Visits an expression tree, returning its depth.
+    fn visit_expr(&mut self, node: &ExprNode) -> usize {
+        self.tag_type(&node.data_type);
+
+        let depth = match &node.expr {
+            Expr::Lit(_) => 1,
+            Expr::TypeCons(expr) => 1 + self.visit_exprs(&expr.args),
+            Expr::Var(expr) => {
+                self.visit_var(&expr.ident);
+                1
+            }
+            Expr::Postfix(expr) => {
+                let inner = self.visit_expr(&expr.inner);
+                let index = self.visit_postfix(&expr.postfix, &expr.inner.data_type);
+                1 + inner.max(index)
+            }
+            Expr::UnOp(expr) => {
+                if matches!(expr.op, UnOp::AddressOf | UnOp::Deref) {
+                    self.rare_constructs.insert(RareConstruct::Pointer);
+                }
+
+                self.tag_operator(expr.op);
+                self.operators.insert(Operator::Un(expr.op));
+                1 + self.visit_expr(&expr.inner)
+            }
+            Expr::BinOp(expr) => {
+                self.tag_operator(expr.op);
+                self.operators.insert(Operator::Bin(expr.op));
+                let left = self.visit_expr(&expr.left);
+                let right = self.visit_expr(&expr.right);
+                1 + left.max(right)
+            }
+            Expr::FnCall(expr) => {
+                self.visit_fn_call(&expr.ident);
+                1 + self.visit_exprs(&expr.args)
+            }
+        };
+
+        self.max_expr_depth = self.max_expr_depth.max(depth);
+        depth
+    }
+
+    /// Visits a list of expressions, returning the maximum depth.
+    fn visit_exprs(&mut self, nodes: &[ExprNode]) -> usize {
+        nodes
+            .iter()
+            .map(|node| self.visit_expr(node))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Visits a postfix operator, returning the depth of its index expression (if any).
+    fn visit_postfix(&mut self, postfix: &Postfix, inner_type: &DataType) -> usize {
+        match postfix {
+            Postfix::Index(index) => self.visit_expr(index),
+            Postfix::Member(member) => {
+                if member.len() > 1 && matches!(inner_type.dereference(), DataType::Vector(..)) {
+                    self.rare_constructs.insert(RareConstruct::Swizzle);
+                }
+                0
+            }
+        }
+    }
+
+    fn visit_var(&mut self, ident: &str) {
+        self.vars.insert((self.function.clone(), ident.to_owned()));
+    }
+
+    fn visit_fn_call(&mut self, ident: &str) {
+        if ident.parse::<BuiltinFn>().is_ok() {
+            self.tag(ident);
+        }
+    }
+}
+
+/// Returns the tag for the kind of a statement, or `None` for comments.
+fn stmt_kind(stmt: &Statement) -> Option<&'static str> {
+    let kind = match stmt {
+        Statement::LetDecl(_) => "let",
+        Statement::VarDecl(_) => "var",
+        Statement::Assignment(_) => "assignment",
+        Statement::Compound(_) => "compound",
+        Statement::If(_) => "if",
+        Statement::Return(_) => "return",
+        Statement::Loop(_) => "loop",
+        Statement::Break => "break",
+        Statement::Continue => "continue",
+        Statement::Switch(_) => "switch",
+        Statement::Fallthrough => "fallthrough",
+        Statement::ForLoop(_) => "for",
+        Statement::While(_) => "while",
+        Statement::FnCall(stmt) if is_barrier(&stmt.ident) => "barrier",
+        Statement::FnCall(_) => "call",
+        Statement::Comment(_) => return None,
+    };
+
+    Some(kind)
+}
+
+fn is_barrier(ident: &str) -> bool {
+    ident == "workgroupBarrier" || ident == "storageBarrier"
+}
@@ -6,10 +6,12 @@ use indenter::indented;
 use crate::types::DataType;
 use crate::{ExprNode, Postfix};
 
-#[derive(Clone, Debug, Display, PartialEq)]
-#[display(fmt = "let {ident} = {initializer}")]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LetDeclStatement {
     pub ident: String,
+    /// Explicit type annotation, e.g. `let x: i32 = ...`.
+    pub data_type: Option<DataType>,
     pub initializer: ExprNode,
 }
 
@@ -17,11 +19,24 @@ impl LetDeclStatement {
     pub fn new(ident: impl Into<String>, initializer: impl Into<ExprNode>) -> Self {
         Self {
             ident: ident.into(),
+            data_type: None,
             initializer: initializer.into(),
         }
     }
 
+    /// Sets the explicit type annotation of the declaration.
+    pub fn with_type(self, data_type: impl Into<Option<DataType>>) -> Self {
+        Self {
+            data_type: data_type.into(),
+            ..self
+        }
+    }
+
     pub fn inferred_type(&self) -> &DataType {
+        if let Some(data_type) = &self.data_type {
+            return data_type;
+        }
+
         // If the type of the initializer expression is a reference, then we infer the declaration
         // type to be the target type of the reference. Otherwise it is simply the type of the initializer.
         if let DataType::Ref(view) = &self.initializer.data_type {
@@ -32,7 +47,20 @@ impl LetDeclStatement {
     }
 }
 
+impl Display for LetDeclStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "let {}", self.ident)?;
+
+        if let Some(data_type) = &self.data_type {
+            write!(f, ": {data_type}")?;
+        }
+
+        write!(f, " = {}", self.initializer)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VarDeclStatement {
     pub ident: String,
     pub data_type: Option<DataType>,
@@ -87,6 +115,7 @@ impl Display for VarDeclStatement {
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AssignmentLhs {
     #[display(fmt = "_")]
     Phony,
@@ -112,6 +141,7 @@ impl AssignmentLhs {
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LhsExpr {
     Ident(String),
     #[display(fmt = "({_0}){_1}")]
@@ -129,6 +159,7 @@ impl From<LhsExprNode> for AssignmentLhs {
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{expr}")]
 pub struct LhsExprNode {
     pub data_type: DataType,
@@ -186,7 +217,8 @@ impl LhsExprNode {
     }
 }
 
-#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[derive(Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AssignmentOp {
     #[display(fmt = "=")]
     Simple,
@@ -209,6 +241,7 @@ pub enum AssignmentOp {
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{lhs} {op} {rhs}")]
 pub struct AssignmentStatement {
     pub lhs: AssignmentLhs,
@@ -227,6 +260,7 @@ impl AssignmentStatement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Else {
     If(IfStatement),
     Else(Vec<Statement>),
@@ -254,6 +288,7 @@ impl Display for Else {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfStatement {
     pub condition: ExprNode,
     pub body: Vec<Statement>,
@@ -300,6 +335,7 @@ impl Display for IfStatement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReturnStatement {
     pub value: Option<ExprNode>,
 }
@@ -335,6 +371,7 @@ impl Display for ReturnStatement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LoopStatement {
     pub body: Vec<Statement>,
 }
@@ -358,6 +395,7 @@ impl Display for LoopStatement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SwitchStatement {
     pub selector: ExprNode,
     pub cases: Vec<SwitchCase>,
@@ -407,22 +445,26 @@ impl Display for SwitchStatement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SwitchCase {
     pub selector: ExprNode,
     pub body: Vec<Statement>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ForLoopInit {
     VarDecl(VarDeclStatement),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ForLoopUpdate {
     Assignment(AssignmentStatement),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ForLoopHeader {
     pub init: Option<ForLoopInit>,
     pub condition: Option<ExprNode>,
@@ -430,6 +472,7 @@ pub struct ForLoopHeader {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ForLoopStatement {
     pub header: Box<ForLoopHeader>,
     pub body: Vec<Statement>,
@@ -481,6 +524,35 @@ impl Display for ForLoopStatement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhileStatement {
+    pub condition: ExprNode,
+    pub body: Vec<Statement>,
+}
+
+impl WhileStatement {
+    pub fn new(condition: impl Into<ExprNode>, body: Vec<Statement>) -> Self {
+        Self {
+            condition: condition.into(),
+            body,
+        }
+    }
+}
+
+impl Display for WhileStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "while ({}) {{", self.condition)?;
+
+        for stmt in &self.body {
+            writeln!(indented(f), "{}", stmt)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FnCallStatement {
     pub ident: String,
     pub args: Vec<ExprNode>,
@@ -508,6 +580,7 @@ impl Display for FnCallStatement {
 }
 
 #[derive(Clone, Debug, PartialEq, From)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
     LetDecl(LetDeclStatement),
     VarDecl(VarDeclStatement),
@@ -521,7 +594,13 @@ pub enum Statement {
     Switch(SwitchStatement),
     Fallthrough,
     ForLoop(ForLoopStatement),
+    While(WhileStatement),
     FnCall(FnCallStatement),
+    /// A `//` comment written on its own line before the following statement.
+    ///
+    /// Comments have no effect on the program and aren't produced by the parser, which discards
+    /// them.
+    Comment(String),
 }
 
 impl Statement {
@@ -559,7 +638,9 @@ impl Display for Statement {
             Statement::Fallthrough => write!(f, "fallthrough;"),
             Statement::Switch(stmt) => stmt.fmt(f),
             Statement::ForLoop(stmt) => stmt.fmt(f),
+            Statement::While(stmt) => stmt.fmt(f),
             Statement::FnCall(stmt) => write!(f, "{stmt};"),
+            Statement::Comment(text) => write!(f, "// {text}"),
         }
     }
 }
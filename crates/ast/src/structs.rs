@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::hash::Hash;
 use std::rc::Rc;
 
@@ -7,12 +7,14 @@ use derive_more::Display;
 use crate::types::DataType;
 
 #[derive(Clone, Debug, Display, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StructMemberAttr {
     #[display(fmt = "align({_0})")]
     Align(u8),
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructMember {
     pub attrs: Vec<StructMemberAttr>,
     pub name: String,
@@ -34,12 +36,36 @@ impl StructMember {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "StructDeclRepr"))]
 pub struct StructDecl {
     pub name: String,
     pub members: Vec<Rc<StructMember>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     accessors: HashMap<DataType, Vec<Rc<StructMember>>>,
 }
 
+/// Serialized form of a [`StructDecl`], without the accessors map (which is rebuilt from the
+/// members when deserializing).
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct StructDeclRepr {
+    name: String,
+    members: Vec<Rc<StructMember>>,
+}
+
+#[cfg(feature = "serde")]
+impl From<StructDeclRepr> for StructDecl {
+    fn from(repr: StructDeclRepr) -> Self {
+        let accessors = collect_struct_accessors(&repr.members);
+        StructDecl {
+            name: repr.name,
+            members: repr.members,
+            accessors,
+        }
+    }
+}
+
 impl Hash for StructDecl {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.name.hash(state);
@@ -95,12 +121,17 @@ fn collect_struct_accessors(
 ) -> HashMap<DataType, Vec<Rc<StructMember>>> {
     let mut accessors = HashMap::new();
 
+    // Members are kept in declaration order (rather than collected into a set) so that random
+    // selection from the list is reproducible for a given seed.
     fn insert(
-        map: &mut HashMap<DataType, HashSet<Rc<StructMember>>>,
+        map: &mut HashMap<DataType, Vec<Rc<StructMember>>>,
         ty: &DataType,
         member: &Rc<StructMember>,
     ) {
-        map.entry(ty.clone()).or_default().insert(member.clone());
+        let members = map.entry(ty.clone()).or_default();
+        if !members.contains(member) {
+            members.push(member.clone());
+        }
     }
 
     for member in members {
@@ -130,10 +161,5 @@ fn collect_struct_accessors(
         }
     }
 
-    // Convert the sets into vectors
-    // We use vectors for more efficient random selection later on
     accessors
-        .into_iter()
-        .map(|(k, v)| (k, v.into_iter().collect()))
-        .collect()
 }
@@ -0,0 +1,757 @@
+//! Consistency checking for the types annotated on expression nodes.
+//!
+//! Every [`ExprNode`] carries the type that its producer (the generator or the parser) believes it
+//! has. [`typecheck`] re-derives the type of each node bottom-up from its operands and reports the
+//! first node whose annotation disagrees, which makes it easy to catch generator bugs that would
+//! otherwise only show up as a compiler error on the final shader.
+//!
+//! It also reports names that are declared twice in the same scope, which WGSL rejects, to catch
+//! bugs in the generator's name allocation.
+//!
+//! [`typecheck_expr`] does the same for a single expression without the module it belongs to,
+//! which lets the generator check each expression as soon as it's generated.
+//!
+//! Types are compared modulo references, since producers aren't consistent about whether a
+//! variable expression has a reference type or the type of its value (WGSL's load rule makes
+//! these interchangeable in most positions).
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::types::DataType;
+use crate::{
+    AssignmentLhs, BinOp, BuiltinFn, Else, Expr, ExprNode, FnDecl, ForLoopInit, ForLoopUpdate,
+    LhsExpr, LhsExprNode, Module, Postfix, ScalarType, Statement, StorageClass, UnOp,
+};
+
+#[derive(Debug)]
+pub struct TypeError {
+    /// Location of the offending node, e.g. `fn main > statement 3 > rhs > left`.
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// Checks that the type annotations in `module` are consistent with each other.
+pub fn typecheck(module: &Module) -> Result<(), TypeError> {
+    let mut checker = Checker {
+        module: Some(module),
+        path: vec![],
+        scopes: vec![HashMap::new()],
+        return_type: None,
+    };
+
+    for decl in &module.consts {
+        checker.in_path(format!("const {}", decl.name), |checker| {
+            checker.in_path("initializer", |checker| {
+                checker.check_expr_of_type(&decl.initializer, &decl.data_type)
+            })?;
+            checker.declare(&decl.name, decl.data_type.clone())
+        })?;
+    }
+
+    for decl in &module.vars {
+        checker.in_path(format!("var {}", decl.name), |checker| {
+            if let Some(initializer) = &decl.initializer {
+                checker.in_path("initializer", |checker| {
+                    checker.check_expr_of_type(initializer, &decl.data_type)
+                })?;
+            }
+
+            let storage_class = decl
+                .qualifier
+                .as_ref()
+                .map(|it| it.storage_class)
+                .unwrap_or(StorageClass::Private);
+
+            let mut view = crate::types::MemoryViewType::new(decl.data_type.clone(), storage_class);
+            if let Some(access_mode) = decl.qualifier.as_ref().and_then(|it| it.access_mode) {
+                view.access_mode = access_mode;
+            }
+
+            checker.declare(&decl.name, DataType::Ref(view))
+        })?;
+    }
+
+    for decl in &module.functions {
+        checker.in_path(format!("fn {}", decl.name), |checker| {
+            checker.check_fn(decl)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the type annotations in a single expression are consistent with each other.
+///
+/// Unlike [`typecheck`], this doesn't need the module that the expression belongs to. Variables and
+/// calls to user-defined functions can't be resolved without it, so their annotations are trusted,
+/// but every literal, constructor, accessor, operator and builtin call is checked against its
+/// operands.
+pub fn typecheck_expr(node: &ExprNode) -> Result<(), TypeError> {
+    let mut checker = Checker {
+        module: None,
+        path: vec!["expression".to_owned()],
+        scopes: vec![HashMap::new()],
+        return_type: None,
+    };
+
+    checker.check_expr(node)?;
+
+    Ok(())
+}
+
+struct Checker<'a> {
+    /// The module being checked, or `None` if checking a standalone expression.
+    module: Option<&'a Module>,
+    path: Vec<String>,
+    scopes: Vec<HashMap<String, DataType>>,
+    return_type: Option<DataType>,
+}
+
+impl<'a> Checker<'a> {
+    fn in_path<T>(
+        &mut self,
+        segment: impl Into<String>,
+        f: impl FnOnce(&mut Self) -> Result<T, TypeError>,
+    ) -> Result<T, TypeError> {
+        self.path.push(segment.into());
+        let res = f(self);
+        if res.is_ok() {
+            self.path.pop();
+        }
+        res
+    }
+
+    fn in_scope(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), TypeError>,
+    ) -> Result<(), TypeError> {
+        self.scopes.push(HashMap::new());
+        let res = f(self);
+        self.scopes.pop();
+        res
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, TypeError> {
+        Err(TypeError {
+            path: self.path.join(" > "),
+            message: message.into(),
+        })
+    }
+
+    /// Declares `name` in the innermost scope, failing if it's already declared there.
+    ///
+    /// Shadowing a name from an outer scope is allowed.
+    fn declare(&mut self, name: &str, data_type: DataType) -> Result<(), TypeError> {
+        if self.scopes.last().unwrap().contains_key(name) {
+            return self.error(format!("`{name}` is already declared in this scope"));
+        }
+
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_owned(), data_type);
+
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<&DataType> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn check_fn(&mut self, decl: &FnDecl) -> Result<(), TypeError> {
+        self.return_type = decl.output.as_ref().map(|it| it.data_type.clone());
+
+        self.in_scope(|checker| {
+            for param in &decl.inputs {
+                checker.in_path(format!("param {}", param.name), |checker| {
+                    checker.declare(&param.name, param.data_type.clone())
+                })?;
+            }
+
+            checker.check_stmts(&decl.body)
+        })
+    }
+
+    fn check_stmts(&mut self, stmts: &[Statement]) -> Result<(), TypeError> {
+        for (i, stmt) in stmts.iter().enumerate() {
+            self.in_path(format!("statement {i}"), |checker| checker.check_stmt(stmt))?;
+        }
+
+        Ok(())
+    }
+
+    fn check_block(&mut self, segment: &str, stmts: &[Statement]) -> Result<(), TypeError> {
+        self.in_path(segment, |checker| {
+            checker.in_scope(|checker| checker.check_stmts(stmts))
+        })
+    }
+
+    fn check_condition(&mut self, condition: &ExprNode) -> Result<(), TypeError> {
+        self.in_path("condition", |checker| {
+            checker.check_expr_of_type(condition, &ScalarType::Bool.into())
+        })?;
+
+        Ok(())
+    }
+
+    fn check_stmt(&mut self, stmt: &Statement) -> Result<(), TypeError> {
+        match stmt {
+            Statement::LetDecl(stmt) => {
+                self.in_path("initializer", |checker| match &stmt.data_type {
+                    Some(data_type) => checker.check_expr_of_type(&stmt.initializer, data_type),
+                    None => checker.check_expr(&stmt.initializer),
+                })?;
+                self.declare(&stmt.ident, stmt.inferred_type().clone())?;
+            }
+            Statement::VarDecl(stmt) => {
+                if let Some(initializer) = &stmt.initializer {
+                    self.in_path("initializer", |checker| {
+                        checker.check_expr_of_type(initializer, stmt.inferred_type())
+                    })?;
+                }
+
+                let view = crate::types::MemoryViewType::new(
+                    stmt.inferred_type().clone(),
+                    StorageClass::Function,
+                );
+
+                self.declare(&stmt.ident, DataType::Ref(view))?;
+            }
+            Statement::Assignment(stmt) => {
+                let lhs_type = match &stmt.lhs {
+                    AssignmentLhs::Phony => None,
+                    AssignmentLhs::Expr(lhs) => {
+                        Some(self.in_path("lhs", |checker| checker.check_lhs_expr(lhs))?)
+                    }
+                };
+
+                self.in_path("rhs", |checker| match lhs_type {
+                    Some(lhs_type) => checker.check_expr_of_type(&stmt.rhs, &lhs_type),
+                    None => checker.check_expr(&stmt.rhs),
+                })?;
+            }
+            Statement::Compound(stmts) => self.check_block("block", stmts)?,
+            Statement::If(stmt) => {
+                self.check_condition(&stmt.condition)?;
+                self.check_block("body", &stmt.body)?;
+
+                let mut else_ = stmt.else_.as_deref();
+                while let Some(e) = else_ {
+                    match e {
+                        Else::If(stmt) => {
+                            self.check_condition(&stmt.condition)?;
+                            self.check_block("else if", &stmt.body)?;
+                            else_ = stmt.else_.as_deref();
+                        }
+                        Else::Else(body) => {
+                            self.check_block("else", body)?;
+                            else_ = None;
+                        }
+                    }
+                }
+            }
+            Statement::Return(stmt) => match (&stmt.value, self.return_type.clone()) {
+                (Some(value), Some(return_type)) => {
+                    self.in_path("value", |checker| {
+                        checker.check_expr_of_type(value, &return_type)
+                    })?;
+                }
+                (None, None) => {}
+                (Some(_), None) => {
+                    return self.error("return with value in function without return type")
+                }
+                (None, Some(return_type)) => {
+                    return self.error(format!("missing return value of type `{return_type}`"))
+                }
+            },
+            Statement::Loop(stmt) => self.check_block("body", &stmt.body)?,
+            Statement::Switch(stmt) => {
+                let selector_type = self.in_path("selector", |checker| {
+                    let ty = checker.check_expr(&stmt.selector)?;
+                    if !matches!(
+                        ty.dereference(),
+                        DataType::Scalar(ScalarType::I32 | ScalarType::U32)
+                    ) {
+                        return checker.error(format!("switch selector has type `{ty}`"));
+                    }
+                    Ok(ty)
+                })?;
+
+                for (i, case) in stmt.cases.iter().enumerate() {
+                    self.in_path(format!("case {i}"), |checker| {
+                        checker.in_path("selector", |checker| {
+                            checker.check_expr_of_type(&case.selector, &selector_type)
+                        })?;
+                        checker.in_scope(|checker| checker.check_stmts(&case.body))
+                    })?;
+                }
+
+                self.check_block("default", &stmt.default)?;
+            }
+            Statement::ForLoop(stmt) => self.in_scope(|checker| {
+                if let Some(ForLoopInit::VarDecl(init)) = &stmt.header.init {
+                    checker.in_path("init", |checker| {
+                        checker.check_stmt(&Statement::VarDecl(init.clone()))
+                    })?;
+                }
+
+                if let Some(condition) = &stmt.header.condition {
+                    checker.check_condition(condition)?;
+                }
+
+                if let Some(ForLoopUpdate::Assignment(update)) = &stmt.header.update {
+                    checker.in_path("update", |checker| {
+                        checker.check_stmt(&Statement::Assignment(update.clone()))
+                    })?;
+                }
+
+                checker.check_block("body", &stmt.body)
+            })?,
+            Statement::While(stmt) => {
+                self.check_condition(&stmt.condition)?;
+                self.check_block("body", &stmt.body)?;
+            }
+            Statement::FnCall(stmt) => {
+                for (i, arg) in stmt.args.iter().enumerate() {
+                    self.in_path(format!("arg {i}"), |checker| checker.check_expr(arg))?;
+                }
+            }
+            Statement::Break | Statement::Continue | Statement::Fallthrough => {}
+            Statement::Comment(_) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Checks an assignment target, returning the type of value that can be stored to it.
+    fn check_lhs_expr(&mut self, node: &LhsExprNode) -> Result<DataType, TypeError> {
+        let derived = match &node.expr {
+            LhsExpr::Ident(ident) => match self.lookup(ident) {
+                Some(ty) => ty.clone(),
+                None => return self.error(format!("undeclared variable `{ident}`")),
+            },
+            LhsExpr::Postfix(inner, postfix) => {
+                let inner_type = self.in_path("inner", |checker| checker.check_lhs_expr(inner))?;
+                self.postfix_type(&inner_type, postfix)?
+            }
+            LhsExpr::Deref(inner) => {
+                let inner_type = self.in_path("inner", |checker| checker.check_lhs_expr(inner))?;
+                match inner_type {
+                    DataType::Ptr(view) => DataType::Ref(view),
+                    ty => return self.error(format!("cannot dereference `{ty}`")),
+                }
+            }
+            LhsExpr::AddressOf(inner) => {
+                let inner_type = self.in_path("inner", |checker| checker.check_lhs_expr(inner))?;
+                match inner_type {
+                    DataType::Ref(view) => DataType::Ptr(view),
+                    ty => return self.error(format!("cannot take the address of `{ty}`")),
+                }
+            }
+        };
+
+        self.check_annotation(&node.data_type, &derived)?;
+
+        Ok(derived.dereference().clone())
+    }
+
+    fn check_expr_of_type(
+        &mut self,
+        node: &ExprNode,
+        expected: &DataType,
+    ) -> Result<DataType, TypeError> {
+        let ty = self.check_expr(node)?;
+
+        if !same_type(&ty, expected) {
+            return self.error(format!("expected `{expected}`, found `{ty}`"));
+        }
+
+        Ok(ty)
+    }
+
+    /// Checks `node` and its children, returning the derived type of `node`.
+    fn check_expr(&mut self, node: &ExprNode) -> Result<DataType, TypeError> {
+        let derived = match &node.expr {
+            Expr::Lit(lit) => lit.data_type(),
+            Expr::TypeCons(expr) => {
+                let mut arg_types = vec![];
+                for (i, arg) in expr.args.iter().enumerate() {
+                    arg_types
+                        .push(self.in_path(format!("arg {i}"), |checker| checker.check_expr(arg))?);
+                }
+
+                self.check_type_cons(&expr.data_type, &arg_types)?;
+
+                expr.data_type.clone()
+            }
+            Expr::Var(expr) => match self.lookup(&expr.ident) {
+                Some(ty) => ty.clone(),
+                None if self.module.is_none() => node.data_type.clone(),
+                None => return self.error(format!("undeclared variable `{}`", expr.ident)),
+            },
+            Expr::Postfix(expr) => {
+                let inner = self.in_path("inner", |checker| checker.check_expr(&expr.inner))?;
+
+                if let Postfix::Index(index) = &expr.postfix {
+                    self.in_path("index", |checker| {
+                        let ty = checker.check_expr(index)?;
+                        if !matches!(
+                            ty.dereference(),
+                            DataType::Scalar(ScalarType::I32 | ScalarType::U32)
+                        ) {
+                            return checker.error(format!("index has type `{ty}`"));
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                self.postfix_type(&inner, &expr.postfix)?
+            }
+            Expr::UnOp(expr) => {
+                let inner = self.in_path("inner", |checker| checker.check_expr(&expr.inner))?;
+                self.un_op_type(expr.op, &inner)?
+            }
+            Expr::BinOp(expr) => {
+                let left = self.in_path("left", |checker| checker.check_expr(&expr.left))?;
+                let right = self.in_path("right", |checker| checker.check_expr(&expr.right))?;
+                self.bin_op_type(expr.op, &left, &right)?
+            }
+            Expr::FnCall(expr) => {
+                let mut arg_types = vec![];
+                for (i, arg) in expr.args.iter().enumerate() {
+                    arg_types
+                        .push(self.in_path(format!("arg {i}"), |checker| checker.check_expr(arg))?);
+                }
+
+                // A standalone expression may call functions that we know nothing about
+                if self.module.is_none() && BuiltinFn::from_str(&expr.ident).is_err() {
+                    node.data_type.clone()
+                } else {
+                    self.fn_call_type(&expr.ident, &arg_types)?
+                }
+            }
+        };
+
+        self.check_annotation(&node.data_type, &derived)?;
+
+        Ok(derived)
+    }
+
+    fn check_annotation(&self, annotated: &DataType, derived: &DataType) -> Result<(), TypeError> {
+        if !same_type(annotated, derived) {
+            return self.error(format!(
+                "node is annotated with `{annotated}` but has type `{derived}`"
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_type_cons(&self, ty: &DataType, args: &[DataType]) -> Result<(), TypeError> {
+        let args = args.iter().map(DataType::dereference).collect::<Vec<_>>();
+
+        let is_valid = match ty {
+            // Zero-value constructor
+            _ if args.is_empty() => true,
+            // Conversion between scalar types
+            DataType::Scalar(_) => matches!(args.as_slice(), [DataType::Scalar(_)]),
+            DataType::Vector(n, t) => match args.as_slice() {
+                // Splat
+                [DataType::Scalar(s)] => s == t,
+                // Conversion between vector types
+                [DataType::Vector(m, _)] => m == n,
+                args => {
+                    let mut count = 0;
+                    let mut is_valid = true;
+                    for arg in args {
+                        match arg {
+                            DataType::Scalar(s) if s == t => count += 1,
+                            DataType::Vector(m, s) if s == t => count += *m,
+                            _ => is_valid = false,
+                        }
+                    }
+                    is_valid && count == *n
+                }
+            },
+            DataType::Array(element, size) => {
+                *size == Some(args.len() as u32) && args.iter().all(|arg| same_type(arg, element))
+            }
+            DataType::Struct(decl) => {
+                args.len() == decl.members.len()
+                    && args
+                        .iter()
+                        .zip(&decl.members)
+                        .all(|(arg, member)| same_type(arg, &member.data_type))
+            }
+            DataType::Ptr(_) | DataType::Ref(_) => false,
+        };
+
+        if !is_valid {
+            let args = args
+                .iter()
+                .map(|it| format!("`{it}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return self.error(format!("invalid arguments for `{ty}` constructor: {args}"));
+        }
+
+        Ok(())
+    }
+
+    fn postfix_type(&self, ty: &DataType, postfix: &Postfix) -> Result<DataType, TypeError> {
+        match postfix.try_type_eval(ty) {
+            Some(result) => Ok(result),
+            None => self.error(format!("invalid accessor `{postfix}` for `{ty}`")),
+        }
+    }
+
+    fn un_op_type(&self, op: UnOp, ty: &DataType) -> Result<DataType, TypeError> {
+        let scalar = ty.dereference().as_scalar();
+        let is_scalar_or_vector = matches!(
+            ty.dereference(),
+            DataType::Scalar(_) | DataType::Vector(_, _)
+        );
+
+        let result = match op {
+            UnOp::Neg
+                if is_scalar_or_vector
+                    && matches!(
+                        scalar,
+                        Some(ScalarType::I32 | ScalarType::F32 | ScalarType::F16)
+                    ) =>
+            {
+                Some(ty.dereference().clone())
+            }
+            UnOp::Not if is_scalar_or_vector && scalar == Some(ScalarType::Bool) => {
+                Some(ty.dereference().clone())
+            }
+            UnOp::BitNot
+                if is_scalar_or_vector
+                    && matches!(scalar, Some(ScalarType::I32 | ScalarType::U32)) =>
+            {
+                Some(ty.dereference().clone())
+            }
+            UnOp::AddressOf => match ty {
+                DataType::Ref(view) => Some(DataType::Ptr(view.clone())),
+                _ => None,
+            },
+            UnOp::Deref => match ty {
+                DataType::Ptr(view) => Some(DataType::Ref(view.clone())),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match result {
+            Some(result) => Ok(result),
+            None => self.error(format!("operator `{op}` cannot be applied to `{ty}`")),
+        }
+    }
+
+    fn bin_op_type(
+        &self,
+        op: BinOp,
+        left: &DataType,
+        right: &DataType,
+    ) -> Result<DataType, TypeError> {
+        use ScalarType::*;
+
+        let l = left.dereference();
+        let r = right.dereference();
+
+        let is_scalar_or_vector =
+            |ty: &DataType| matches!(ty, DataType::Scalar(_) | DataType::Vector(_, _));
+        let is_numeric = |ty: &DataType| {
+            is_scalar_or_vector(ty) && matches!(ty.as_scalar(), Some(I32 | U32 | F32 | F16))
+        };
+        let is_integer = |ty: &DataType| is_scalar_or_vector(ty) && ty.is_integer();
+        let is_bool = |ty: &DataType| is_scalar_or_vector(ty) && ty.as_scalar() == Some(Bool);
+
+        let result = match op {
+            BinOp::Plus | BinOp::Minus | BinOp::Times | BinOp::Divide | BinOp::Mod => {
+                match (l, r) {
+                    _ if l == r && is_numeric(l) => Some(l.clone()),
+                    // Mixed vector/scalar operations apply the scalar to each component
+                    (DataType::Vector(_, t), DataType::Scalar(s)) if t == s && is_numeric(l) => {
+                        Some(l.clone())
+                    }
+                    (DataType::Scalar(s), DataType::Vector(_, t)) if t == s && is_numeric(r) => {
+                        Some(r.clone())
+                    }
+                    _ => None,
+                }
+            }
+            BinOp::BitAnd | BinOp::BitOr if l == r && (is_integer(l) || is_bool(l)) => {
+                Some(l.clone())
+            }
+            BinOp::BitXOr if l == r && is_integer(l) => Some(l.clone()),
+            BinOp::LShift | BinOp::RShift if is_integer(l) && *r == l.map(U32) => Some(l.clone()),
+            BinOp::LogAnd | BinOp::LogOr if *l == Bool.into() && *r == Bool.into() => {
+                Some(Bool.into())
+            }
+            BinOp::Less | BinOp::LessEqual | BinOp::Greater | BinOp::GreaterEqual
+                if l == r && is_numeric(l) =>
+            {
+                Some(l.map(Bool))
+            }
+            BinOp::Equal | BinOp::NotEqual if l == r && (is_numeric(l) || is_bool(l)) => {
+                Some(l.map(Bool))
+            }
+            _ => None,
+        };
+
+        match result {
+            Some(result) => Ok(result),
+            None => self.error(format!(
+                "operator `{op}` cannot be applied to `{left}` and `{right}`"
+            )),
+        }
+    }
+
+    fn fn_call_type(&self, ident: &str, args: &[DataType]) -> Result<DataType, TypeError> {
+        let (functions, structs) = match self.module {
+            Some(module) => (module.functions.as_slice(), module.structs.as_slice()),
+            None => (&[][..], &[][..]),
+        };
+
+        if let Some(decl) = functions.iter().find(|it| it.name == ident) {
+            if decl.inputs.len() != args.len() {
+                return self.error(format!(
+                    "`{ident}` expects {} arguments, found {}",
+                    decl.inputs.len(),
+                    args.len()
+                ));
+            }
+
+            for (i, (param, arg)) in decl.inputs.iter().zip(args).enumerate() {
+                if !same_type(&param.data_type, arg) {
+                    return self.error(format!(
+                        "argument {i} of `{ident}` has type `{arg}`, expected `{}`",
+                        param.data_type
+                    ));
+                }
+            }
+
+            return match &decl.output {
+                Some(output) => Ok(output.data_type.clone()),
+                None => self.error(format!("`{ident}` does not return a value")),
+            };
+        }
+
+        // Struct constructors may be parsed as function calls
+        if let Some(decl) = structs.iter().find(|it| it.name == ident) {
+            let ty = DataType::Struct(decl.clone());
+            self.check_type_cons(&ty, args)?;
+            return Ok(ty);
+        }
+
+        // Overload resolution for builtins only looks at the arguments that determine the return
+        // type, so we don't validate the argument types here.
+        match BuiltinFn::from_str(ident)
+            .ok()
+            .and_then(|builtin| builtin.return_type(args.iter()))
+        {
+            Some(ty) => Ok(ty),
+            None => self.error(format!("unknown function `{ident}`")),
+        }
+    }
+}
+
+/// Returns true if `a` and `b` are the same type, ignoring references.
+fn same_type(a: &DataType, b: &DataType) -> bool {
+    a.dereference() == b.dereference()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{FnBuilder, ModuleBuilder};
+    use crate::{BinOpExpr, LetDeclStatement, Lit, VarDeclStatement, VarExpr};
+
+    fn main_with_body(body: Vec<Statement>) -> Module {
+        ModuleBuilder::new()
+            .add_function(
+                FnBuilder::new("main")
+                    .entrypoint(1)
+                    .param("p", ScalarType::U32)
+                    .stmts(body)
+                    .build(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn mismatched_annotation() {
+        let sum: ExprNode = BinOpExpr::new(BinOp::Plus, Lit::I32(1), Lit::I32(2)).into();
+        let bad = ExprNode {
+            data_type: ScalarType::U32.into(),
+            expr: sum.expr,
+        };
+
+        let module = main_with_body(vec![LetDeclStatement::new("x", bad).into()]);
+
+        let err = typecheck(&module).unwrap_err();
+        assert_eq!(err.path, "fn main > statement 0 > initializer");
+        assert_eq!(
+            err.message,
+            "node is annotated with `u32` but has type `i32`"
+        );
+    }
+
+    #[test]
+    fn duplicate_declaration_in_scope() {
+        let module = main_with_body(vec![
+            LetDeclStatement::new("x", Lit::I32(1)).into(),
+            VarDeclStatement::new("x", None, Some(Lit::I32(2).into())).into(),
+        ]);
+
+        let err = typecheck(&module).unwrap_err();
+        assert_eq!(err.path, "fn main > statement 1");
+        assert_eq!(err.message, "`x` is already declared in this scope");
+
+        let module = main_with_body(vec![LetDeclStatement::new("p", Lit::I32(1)).into()]);
+        assert!(typecheck(&module).is_err());
+    }
+
+    #[test]
+    fn shadowing_in_nested_scope() {
+        let module = main_with_body(vec![
+            LetDeclStatement::new("x", Lit::I32(1)).into(),
+            Statement::Compound(vec![LetDeclStatement::new("x", Lit::U32(2)).into()]),
+        ]);
+
+        typecheck(&module).unwrap();
+    }
+
+    #[test]
+    fn standalone_expr() {
+        let x = VarExpr::new("x").into_node(ScalarType::U32.into());
+        let node: ExprNode = BinOpExpr::new(BinOp::LShift, Lit::I32(1), x).into();
+
+        // `x` isn't declared anywhere, so its annotation is trusted
+        typecheck_expr(&node).unwrap();
+
+        let bad = ExprNode {
+            data_type: ScalarType::U32.into(),
+            expr: node.expr,
+        };
+
+        let err = typecheck_expr(&bad).unwrap_err();
+        assert_eq!(err.path, "expression");
+        assert_eq!(
+            err.message,
+            "node is annotated with `u32` but has type `i32`"
+        );
+    }
+}
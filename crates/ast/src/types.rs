@@ -1,27 +1,86 @@
 use std::fmt::{self, Display};
 use std::rc::Rc;
-
-use derive_more::Display;
+use std::str::FromStr;
 
 use crate::{AccessMode, StorageClass, StructDecl};
 
-#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ScalarType {
-    #[display(fmt = "bool")]
     Bool,
-    #[display(fmt = "i32")]
     I32,
-    #[display(fmt = "u32")]
     U32,
-    #[display(fmt = "f32")]
     F32,
-    #[display(fmt = "atomic<u32>")]
+    /// Half-precision float, which requires the `f16` extension to be enabled.
+    F16,
     AU32,
-    #[display(fmt = "atomic<i32>")]
     AI32,
 }
 
+impl ScalarType {
+    /// Size of the type in bytes, when stored in a host-shareable buffer.
+    ///
+    /// Note that `bool` is not actually host-shareable, but it has the same size as the other
+    /// scalars when used in private or function variables.
+    pub fn size_in_bytes(&self) -> u32 {
+        match self {
+            ScalarType::Bool
+            | ScalarType::I32
+            | ScalarType::U32
+            | ScalarType::F32
+            | ScalarType::AU32
+            | ScalarType::AI32 => 4,
+            ScalarType::F16 => 2,
+        }
+    }
+
+    /// Returns how the type is spelled in WGSL source.
+    pub fn wgsl_name(&self) -> &'static str {
+        match self {
+            ScalarType::Bool => "bool",
+            ScalarType::I32 => "i32",
+            ScalarType::U32 => "u32",
+            ScalarType::F32 => "f32",
+            ScalarType::F16 => "f16",
+            ScalarType::AU32 => "atomic<u32>",
+            ScalarType::AI32 => "atomic<i32>",
+        }
+    }
+}
+
+impl Display for ScalarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.wgsl_name())
+    }
+}
+
+impl TryFrom<&str> for ScalarType {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "bool" => Ok(ScalarType::Bool),
+            "i32" => Ok(ScalarType::I32),
+            "u32" => Ok(ScalarType::U32),
+            "f32" => Ok(ScalarType::F32),
+            "f16" => Ok(ScalarType::F16),
+            "atomic<u32>" => Ok(ScalarType::AU32),
+            "atomic<i32>" => Ok(ScalarType::AI32),
+            _ => Err(format!("unknown scalar type `{value}`")),
+        }
+    }
+}
+
+impl FromStr for ScalarType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ScalarType::try_from(s)
+    }
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryViewType {
     pub inner: Rc<DataType>,
     pub storage_class: StorageClass,
@@ -56,6 +115,7 @@ impl Display for MemoryViewType {
 }
 
 #[derive(Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     Scalar(ScalarType),
     Vector(u8, ScalarType),
@@ -150,6 +210,9 @@ impl fmt::Debug for DataType {
     }
 }
 
+/// Types are written using WGSL syntax (e.g. `vec3<u32>` or `array<i32, 4>`), and can be parsed
+/// back using [`FromStr`]. Reference types can't be written in WGSL, so they are written like
+/// pointers but with `ref` in place of `ptr`.
 impl Display for DataType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -169,6 +232,104 @@ impl Display for DataType {
     }
 }
 
+impl FromStr for DataType {
+    type Err = String;
+
+    /// Parses a type written in the format produced by [`Display`].
+    ///
+    /// Struct types can't be parsed, since that requires the struct declaration.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Ok(scalar) = ScalarType::try_from(s) {
+            return Ok(DataType::Scalar(scalar));
+        }
+
+        let (name, args) = s
+            .strip_suffix('>')
+            .and_then(|s| s.split_once('<'))
+            .ok_or_else(|| format!("unknown type `{s}`"))?;
+
+        let name = name.trim();
+        let args = split_template_args(args);
+
+        if let Some(n) = name.strip_prefix("vec") {
+            let n = n
+                .parse::<u8>()
+                .ok()
+                .filter(|n| (2..=4).contains(n))
+                .ok_or_else(|| format!("unknown type `{s}`"))?;
+
+            return match args.as_slice() {
+                [ty] => match ScalarType::try_from(*ty)? {
+                    ScalarType::AU32 | ScalarType::AI32 => {
+                        Err(format!("invalid vector component type `{ty}`"))
+                    }
+                    ty => Ok(DataType::Vector(n, ty)),
+                },
+                _ => Err(format!("expected one template argument in `{s}`")),
+            };
+        }
+
+        match (name, args.as_slice()) {
+            ("array", [ty]) => Ok(DataType::array(ty.parse::<DataType>()?, None)),
+            ("array", [ty, n]) => {
+                let n = n
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid array size `{n}`"))?;
+                Ok(DataType::array(ty.parse::<DataType>()?, n))
+            }
+            ("ptr" | "ref", [storage_class, ty, access_mode @ ..]) if access_mode.len() <= 1 => {
+                let storage_class = storage_class
+                    .parse::<StorageClass>()
+                    .map_err(|_| format!("invalid storage class `{storage_class}`"))?;
+
+                let access_mode = match access_mode.first() {
+                    Some(access_mode) => access_mode
+                        .parse::<AccessMode>()
+                        .map_err(|_| format!("invalid access mode `{access_mode}`"))?,
+                    None => storage_class.default_access_mode(),
+                };
+
+                let view = MemoryViewType {
+                    inner: Rc::new(ty.parse()?),
+                    storage_class,
+                    access_mode,
+                };
+
+                Ok(if name == "ptr" {
+                    DataType::Ptr(view)
+                } else {
+                    DataType::Ref(view)
+                })
+            }
+            _ => Err(format!("unknown type `{s}`")),
+        }
+    }
+}
+
+/// Splits the template arguments of a type (e.g. `vec2<i32>, 4`) on top-level commas.
+fn split_template_args(args: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(args[start..].trim());
+    parts
+}
+
 impl From<ScalarType> for DataType {
     fn from(scalar: ScalarType) -> Self {
         DataType::Scalar(scalar)
@@ -180,3 +341,86 @@ impl From<&ScalarType> for DataType {
         DataType::Scalar(*scalar)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{DataType, MemoryViewType, ScalarType};
+    use crate::AccessMode::*;
+    use crate::StorageClass::*;
+
+    #[test]
+    fn scalar_type_names_round_trip() {
+        use ScalarType::*;
+
+        for ty in [Bool, I32, U32, F32, F16, AU32, AI32] {
+            assert_eq!(ScalarType::try_from(ty.wgsl_name()), Ok(ty));
+            assert_eq!(ty.to_string(), ty.wgsl_name());
+        }
+
+        assert!(ScalarType::try_from("f64").is_err());
+    }
+
+    #[test]
+    fn data_types_round_trip() {
+        use ScalarType::*;
+
+        let mut types = vec![];
+
+        for ty in [Bool, I32, U32, F32, F16, AU32, AI32] {
+            types.push(DataType::Scalar(ty));
+        }
+
+        for n in 2..=4 {
+            for ty in [Bool, I32, U32, F32, F16] {
+                types.push(DataType::Vector(n, ty));
+            }
+        }
+
+        for ty in types.clone() {
+            types.push(DataType::array(ty.clone(), 4));
+            types.push(DataType::array(ty, None));
+        }
+
+        types.push(DataType::array(
+            DataType::array(DataType::Vector(3, F32), 2),
+            8,
+        ));
+
+        for storage_class in [Function, Private, WorkGroup, Uniform, Storage] {
+            for access_mode in [Read, Write, ReadWrite] {
+                for ty in [
+                    DataType::Scalar(I32),
+                    DataType::array(DataType::Vector(2, U32), 4),
+                ] {
+                    let view = MemoryViewType {
+                        inner: Rc::new(ty),
+                        storage_class,
+                        access_mode,
+                    };
+                    types.push(DataType::Ptr(view.clone()));
+                    types.push(DataType::Ref(view));
+                }
+            }
+        }
+
+        for ty in types {
+            assert_eq!(ty.to_string().parse::<DataType>(), Ok(ty));
+        }
+
+        assert_eq!("vec3<u32>".parse(), Ok(DataType::Vector(3, U32)));
+        assert_eq!(
+            "array<vec2<f32>, 4>".parse(),
+            Ok(DataType::array(DataType::Vector(2, F32), 4))
+        );
+        assert_eq!(
+            DataType::Ptr(MemoryViewType::new(I32, Function)).to_string(),
+            "ptr<function, i32>"
+        );
+
+        assert!("vec5<i32>".parse::<DataType>().is_err());
+        assert!("vec2<atomic<u32>>".parse::<DataType>().is_err());
+        assert!("Struct_1".parse::<DataType>().is_err());
+    }
+}
@@ -1,9 +1,13 @@
+use std::borrow::Cow;
 use std::fmt::{Display, Result, Write};
 use std::io;
 
 use indenter::indented;
 
-use crate::{FnAttr, FnDecl, GlobalConstDecl, GlobalVarDecl, Module, StructDecl};
+use crate::{
+    Else, FnAttr, FnDecl, ForLoopInit, GlobalConstDecl, GlobalVarDecl, Module, Statement,
+    StructDecl,
+};
 
 #[derive(Default)]
 pub struct Writer {
@@ -15,6 +19,9 @@ pub struct Writer {
 pub struct Options {
     pub concise_stage_attrs: bool,
     pub module_scope_constants: bool,
+    /// Annotate every `let` and `var` declaration in function bodies with its type, e.g.
+    /// `let x: i32 = 1;`, instead of leaving it to be inferred from the initializer.
+    pub explicit_types: bool,
 }
 
 impl Writer {
@@ -22,6 +29,13 @@ impl Writer {
         Writer { options }
     }
 
+    /// Sets whether declarations are written with explicit types (see
+    /// [`Options::explicit_types`]).
+    pub fn explicit_types(mut self, explicit_types: bool) -> Writer {
+        self.options.explicit_types = explicit_types;
+        self
+    }
+
     pub fn write_module_default<'a>(&self, mut output: Box<dyn io::Write + 'a>, module: &Module) -> Result {
         struct Output<'a>(&'a mut dyn std::io::Write);
 
@@ -36,6 +50,14 @@ impl Writer {
     }
 
     pub fn write_module(&self, f: &mut dyn Write, module: &Module) -> Result {
+        for extension in &module.enables {
+            writeln!(f, "enable {extension};")?;
+        }
+
+        if !module.enables.is_empty() {
+            writeln!(f)?;
+        }
+
         for decl in &module.structs {
             self.write_struct(f, decl)?;
             writeln!(f)?;
@@ -145,7 +167,15 @@ impl Writer {
 
         writeln!(f, "{{")?;
 
-        for stmt in &func.body {
+        let body = if self.options.explicit_types {
+            let mut body = func.body.clone();
+            annotate_types(&mut body);
+            Cow::Owned(body)
+        } else {
+            Cow::Borrowed(&func.body)
+        };
+
+        for stmt in body.iter() {
             writeln!(indented(f), "{}", stmt)?;
         }
 
@@ -170,3 +200,54 @@ impl Writer {
         writeln!(f, "@{attr}")
     }
 }
+
+/// Sets the type of every declaration in `block` to the type inferred from its initializer.
+fn annotate_types(block: &mut [Statement]) {
+    for stmt in block {
+        match stmt {
+            Statement::LetDecl(stmt) => stmt.data_type = Some(stmt.inferred_type().clone()),
+            Statement::VarDecl(stmt) => stmt.data_type = Some(stmt.inferred_type().clone()),
+            Statement::Compound(block) => annotate_types(block),
+            Statement::If(stmt) => {
+                annotate_types(&mut stmt.body);
+
+                let mut else_ = stmt.else_.as_deref_mut();
+                while let Some(e) = else_ {
+                    match e {
+                        Else::If(stmt) => {
+                            annotate_types(&mut stmt.body);
+                            else_ = stmt.else_.as_deref_mut();
+                        }
+                        Else::Else(block) => {
+                            annotate_types(block);
+                            else_ = None;
+                        }
+                    }
+                }
+            }
+            Statement::Loop(stmt) => annotate_types(&mut stmt.body),
+            Statement::Switch(stmt) => {
+                for case in &mut stmt.cases {
+                    annotate_types(&mut case.body);
+                }
+
+                annotate_types(&mut stmt.default);
+            }
+            Statement::ForLoop(stmt) => {
+                if let Some(ForLoopInit::VarDecl(decl)) = &mut stmt.header.init {
+                    decl.data_type = Some(decl.inferred_type().clone());
+                }
+
+                annotate_types(&mut stmt.body);
+            }
+            Statement::While(stmt) => annotate_types(&mut stmt.body),
+            Statement::Assignment(_)
+            | Statement::Return(_)
+            | Statement::FnCall(_)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Fallthrough
+            | Statement::Comment(_) => {}
+        }
+    }
+}
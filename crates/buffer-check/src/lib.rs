@@ -1,4 +1,4 @@
-use common::Type;
+use common::{Type, Value};
 use reflection_types::{PipelineDescription, ResourceKind};
 
 pub fn compare<'a>(
@@ -15,17 +15,82 @@ pub fn compare<'a>(
                 .filter(|(_, it)| it.kind == ResourceKind::StorageBuffer)
                 .enumerate()
             {
-                for (offset, size) in type_descs[j].ranges() {
-                    let range = offset..(offset + size);
+                for field in type_descs[j].layout().fields {
+                    let range = field.range();
                     if execution[i][range.clone()] != prev[i][range] {
                         return false;
                     }
                 }
             }
 
+            // Any buffers after the storage buffers (i.e. the render target of a fragment shader)
+            // have no padding, so they are compared in full.
+            let storage_count = pipeline_desc
+                .resources
+                .iter()
+                .filter(|it| it.kind == ResourceKind::StorageBuffer)
+                .count();
+
+            if execution.get(storage_count..) != prev.get(storage_count..) {
+                return false;
+            }
+
             prev = execution;
         }
     }
 
     true
 }
+
+/// Compares the storage buffers from a single execution against a set of expected buffers.
+///
+/// Only the bytes belonging to fields of each buffer's type are compared, so padding is ignored.
+/// If `tolerance` is set, `f32` values are considered equal if they differ by at most that amount.
+pub fn compare_expected(
+    actual: &[Vec<u8>],
+    expected: &[Vec<u8>],
+    pipeline_desc: &PipelineDescription,
+    type_descs: &[Type],
+    tolerance: Option<f32>,
+) -> bool {
+    for (i, (j, _)) in pipeline_desc
+        .resources
+        .iter()
+        .enumerate()
+        .filter(|(_, it)| it.kind == ResourceKind::StorageBuffer)
+        .enumerate()
+    {
+        let (actual, expected) = match (actual.get(i), expected.get(i)) {
+            (Some(actual), Some(expected)) => (actual, expected),
+            _ => return false,
+        };
+
+        for field in type_descs[j].layout().fields {
+            let range = field.range();
+            if actual.get(range.clone()) == expected.get(range) {
+                continue;
+            }
+
+            let is_match = match (tolerance, field.read(actual), field.read(expected)) {
+                (Some(tolerance), Some(a), Some(b)) => values_match(&a, &b, tolerance),
+                _ => false,
+            };
+
+            if !is_match {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+fn values_match(a: &Value, b: &Value, tolerance: f32) -> bool {
+    match (a, b) {
+        (Value::F32(a), Value::F32(b)) => (a - b).abs() <= tolerance,
+        (Value::Vector(a), Value::Vector(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_match(a, b, tolerance))
+        }
+        (a, b) => a == b,
+    }
+}
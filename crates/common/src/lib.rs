@@ -1,13 +1,32 @@
-#[derive(Debug)]
+use std::rc::Rc;
+
+use ast::layout::{self, LayoutRules};
+use ast::StructDecl;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ScalarType {
     I32,
     U32,
     F32,
+    F16,
     AU32,
     AI32,
 }
 
-#[derive(Debug)]
+impl ScalarType {
+    pub fn size_in_bytes(&self) -> u32 {
+        match self {
+            ScalarType::I32
+            | ScalarType::U32
+            | ScalarType::F32
+            | ScalarType::AU32
+            | ScalarType::AI32 => 4,
+            ScalarType::F16 => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum VectorSize {
     N2,
     N3,
@@ -38,89 +57,254 @@ pub enum Type {
     },
 }
 
-fn aligned(size: u32, alignment: u32) -> u32 {
-    ((size + (alignment - 1)) / alignment) * alignment
-}
-
 impl Type {
     pub fn buffer_size(&self) -> u32 {
-        aligned(self.size(), self.alignment())
+        self.size().next_multiple_of(self.alignment())
     }
 
     pub fn size(&self) -> u32 {
+        layout::size_of(&self.data_type(), LayoutRules::Std430)
+    }
+
+    pub fn alignment(&self) -> u32 {
+        layout::align_of(&self.data_type(), LayoutRules::Std430)
+    }
+
+    /// Computes the layout of this type in a host-shareable buffer.
+    ///
+    /// The result is a flattened list of the scalar and vector fields contained in the type, each
+    /// with its byte offset from the start of the buffer (following std430 alignment rules).
+    pub fn layout(&self) -> BufferLayout {
+        fn collect_fields(acc: &mut Vec<LayoutField>, offset: u32, type_desc: &Type) {
+            match type_desc {
+                Type::Scalar { scalar_type } => acc.push(LayoutField {
+                    offset,
+                    kind: FieldKind::Scalar(*scalar_type),
+                }),
+                Type::Vector { size, scalar_type } => acc.push(LayoutField {
+                    offset,
+                    kind: FieldKind::Vector(*size, *scalar_type),
+                }),
+                Type::Array { size, element_type } => {
+                    let stride = layout::stride_of(&element_type.data_type(), LayoutRules::Std430);
+                    for i in 0..*size {
+                        collect_fields(acc, offset + i * stride, element_type);
+                    }
+                }
+                Type::Struct { members } => {
+                    let decl = struct_decl(members);
+                    let offsets = layout::struct_layout(&decl, LayoutRules::Std430).offsets;
+                    for (member, member_offset) in members.iter().zip(offsets) {
+                        collect_fields(acc, offset + member_offset, &member.type_desc);
+                    }
+                }
+            }
+        }
+
+        let mut fields = vec![];
+
+        collect_fields(&mut fields, 0, self);
+
+        BufferLayout {
+            fields,
+            size: self.buffer_size(),
+        }
+    }
+
+    pub fn ranges(&self) -> Vec<(usize, usize)> {
+        self.layout().ranges().collect()
+    }
+
+    /// Converts this type back into the AST type it describes, so that its layout can be computed
+    /// by [`ast::layout`].
+    fn data_type(&self) -> ast::DataType {
         match self {
-            Type::Scalar { .. } => 4,
-            Type::Vector { size, .. } => match size {
-                VectorSize::N2 => 8,
-                VectorSize::N3 => 12,
-                VectorSize::N4 => 16,
-            },
+            Type::Scalar { scalar_type } => ast::DataType::Scalar(scalar_type.into()),
+            Type::Vector { size, scalar_type } => {
+                ast::DataType::Vector(size.count() as u8, scalar_type.into())
+            }
             Type::Array { size, element_type } => {
-                size * aligned(element_type.size(), element_type.alignment())
+                ast::DataType::array(element_type.data_type(), Some(*size))
             }
-            Type::Struct { members } => {
-                let mut size = 0;
-                let mut alignment = 0;
-
-                for member in members {
-                    let member_alignment = member.type_desc.alignment();
-                    let member_size = member.type_desc.size();
-                    alignment = u32::max(alignment, member.type_desc.alignment());
-                    size = aligned(size, member_alignment) + member_size;
-                }
+            Type::Struct { members } => ast::DataType::Struct(struct_decl(members)),
+        }
+    }
+}
 
-                aligned(size, alignment)
-            }
+fn struct_decl(members: &[StructMember]) -> Rc<StructDecl> {
+    let members = members
+        .iter()
+        .map(|it| ast::StructMember::new(vec![], it.name.clone(), it.type_desc.data_type()))
+        .collect();
+
+    StructDecl::new("Struct", members)
+}
+
+impl VectorSize {
+    pub fn count(&self) -> u32 {
+        match self {
+            VectorSize::N2 => 2,
+            VectorSize::N3 => 3,
+            VectorSize::N4 => 4,
         }
     }
+}
 
-    pub fn alignment(&self) -> u32 {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    Scalar(ScalarType),
+    Vector(VectorSize, ScalarType),
+}
+
+impl FieldKind {
+    pub fn scalar_type(&self) -> ScalarType {
         match self {
-            Type::Scalar { .. } => 4,
-            Type::Vector { size, .. } => match size {
-                VectorSize::N2 => 8,
-                VectorSize::N3 => 16,
-                VectorSize::N4 => 16,
-            },
-            Type::Array { element_type, .. } => element_type.alignment(),
-            Type::Struct { members } => members
-                .iter()
-                .map(|it| it.type_desc.alignment())
-                .max()
-                .expect("struct must have at least one member"),
+            FieldKind::Scalar(scalar_type) => *scalar_type,
+            FieldKind::Vector(_, scalar_type) => *scalar_type,
         }
     }
 
-    pub fn ranges(&self) -> Vec<(usize, usize)> {
-        let mut ranges = vec![];
+    pub fn size(&self) -> u32 {
+        match self {
+            FieldKind::Scalar(scalar_type) => scalar_type.size_in_bytes(),
+            FieldKind::Vector(n, scalar_type) => n.count() * scalar_type.size_in_bytes(),
+        }
+    }
+}
 
-        fn collect_ranges(acc: &mut Vec<(usize, usize)>, mut offset: u32, type_desc: &Type) {
-            match type_desc {
-                Type::Scalar { .. } => acc.push((offset as _, type_desc.size() as _)),
-                Type::Vector { .. } => acc.push((offset as _, type_desc.size() as _)),
-                Type::Array { size, element_type } => {
-                    let element_size = element_type.size();
-                    let alignment = element_type.alignment();
-                    for _ in 0..*size {
-                        collect_ranges(acc, offset, element_type);
-                        offset = aligned(offset + element_size, alignment);
-                    }
-                }
-                Type::Struct { members } => {
-                    for member in members {
-                        let alignment = member.type_desc.alignment();
-                        offset = aligned(offset, alignment);
-                        collect_ranges(acc, offset, &member.type_desc);
-                        let size = member.type_desc.size();
-                        offset += size;
+/// A single scalar or vector field at a fixed offset within a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayoutField {
+    pub offset: u32,
+    pub kind: FieldKind,
+}
+
+impl LayoutField {
+    pub fn range(&self) -> std::ops::Range<usize> {
+        let offset = self.offset as usize;
+        offset..(offset + self.kind.size() as usize)
+    }
+
+    /// Interprets the bytes of this field within `buffer`.
+    ///
+    /// Returns `None` if the buffer is too small to contain the field.
+    pub fn read(&self, buffer: &[u8]) -> Option<Value> {
+        let bytes = buffer.get(self.range())?;
+        let scalar_type = self.kind.scalar_type();
+        let mut components = bytes
+            .chunks_exact(scalar_type.size_in_bytes() as usize)
+            .map(|it| Value::from_bytes(scalar_type, it));
+
+        Some(match self.kind {
+            FieldKind::Scalar(_) => components.next().unwrap(),
+            FieldKind::Vector(..) => Value::Vector(components.collect()),
+        })
+    }
+}
+
+/// Describes how the scalar and vector fields of a type are laid out in a host-shareable buffer.
+///
+/// Padding bytes are not covered by any field and so should be ignored when interpreting the
+/// contents of a buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferLayout {
+    pub fields: Vec<LayoutField>,
+    pub size: u32,
+}
+
+impl BufferLayout {
+    /// Returns the `(offset, size)` byte ranges covered by the fields of this layout.
+    pub fn ranges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.fields
+            .iter()
+            .map(|it| (it.offset as usize, it.kind.size() as usize))
+    }
+
+    /// Interprets the contents of `buffer` according to this layout.
+    pub fn read(&self, buffer: &[u8]) -> Option<Vec<Value>> {
+        self.fields.iter().map(|it| it.read(buffer)).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    I32(i32),
+    U32(u32),
+    F32(f32),
+    Vector(Vec<Value>),
+}
+
+impl Value {
+    /// Decodes a scalar from its bytes in a buffer.
+    ///
+    /// WebGPU buffers are always little-endian, so this doesn't depend on the host's byte order.
+    /// `f16` values are widened to `f32`, which can represent them exactly.
+    fn from_bytes(scalar_type: ScalarType, bytes: &[u8]) -> Value {
+        match scalar_type {
+            ScalarType::I32 | ScalarType::AI32 => {
+                Value::I32(i32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            ScalarType::U32 | ScalarType::AU32 => {
+                Value::U32(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            ScalarType::F32 => Value::F32(f32::from_le_bytes(bytes.try_into().unwrap())),
+            ScalarType::F16 => {
+                Value::F32(f16_to_f32(u16::from_le_bytes(bytes.try_into().unwrap())))
+            }
+        }
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let fraction = (bits & 0x3ff) as u32;
+
+    let magnitude = match exponent {
+        // Subnormal (or zero)
+        0 => fraction as f32 * 2f32.powi(-24),
+        0x1f if fraction == 0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        // Rebias the exponent from 15 to 127 and widen the fraction from 10 to 23 bits
+        _ => f32::from_bits(((exponent + 127 - 15) << 23) | (fraction << 13)),
+    };
+
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::I32(v) => write!(f, "{v}"),
+            Value::U32(v) => write!(f, "{v}u"),
+            Value::F32(v) => write!(f, "{v:?}"),
+            Value::Vector(components) => {
+                write!(f, "vec{}(", components.len())?;
+                for (i, component) in components.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
                     }
+                    write!(f, "{component}")?;
                 }
+                write!(f, ")")
             }
         }
+    }
+}
 
-        collect_ranges(&mut ranges, 0, self);
-
-        ranges
+impl From<&ScalarType> for ast::ScalarType {
+    fn from(value: &ScalarType) -> Self {
+        match value {
+            ScalarType::I32 => ast::ScalarType::I32,
+            ScalarType::U32 => ast::ScalarType::U32,
+            ScalarType::F32 => ast::ScalarType::F32,
+            ScalarType::F16 => ast::ScalarType::F16,
+            ScalarType::AU32 => ast::ScalarType::AU32,
+            ScalarType::AI32 => ast::ScalarType::AI32,
+        }
     }
 }
 
@@ -133,6 +317,7 @@ impl TryFrom<&ast::ScalarType> for ScalarType {
             ast::ScalarType::I32 => Ok(ScalarType::I32),
             ast::ScalarType::U32 => Ok(ScalarType::U32),
             ast::ScalarType::F32 => Ok(ScalarType::F32),
+            ast::ScalarType::F16 => Ok(ScalarType::F16),
             ast::ScalarType::AU32 => Ok(ScalarType::AU32),
             ast::ScalarType::AI32 => Ok(ScalarType::AI32),
         }
@@ -178,4 +363,123 @@ impl TryFrom<&ast::DataType> for Type {
             ast::DataType::Ref(_) => Err("references are not storable"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(name: &str, type_desc: Type) -> StructMember {
+        StructMember {
+            name: name.into(),
+            type_desc,
+        }
+    }
+
+    fn scalar(scalar_type: ScalarType) -> Type {
+        Type::Scalar { scalar_type }
+    }
+
+    fn vector(size: VectorSize, scalar_type: ScalarType) -> Type {
+        Type::Vector { size, scalar_type }
+    }
+
+    #[test]
+    fn mixed_struct_layout() {
+        // struct S { a: i32, b: vec4<f32>, c: vec3<u32>, d: u32, e: array<vec3<f32>, 2> }
+        let ty = Type::Struct {
+            members: vec![
+                member("a", scalar(ScalarType::I32)),
+                member("b", vector(VectorSize::N4, ScalarType::F32)),
+                member("c", vector(VectorSize::N3, ScalarType::U32)),
+                member("d", scalar(ScalarType::U32)),
+                member(
+                    "e",
+                    Type::Array {
+                        size: 2,
+                        element_type: Box::new(vector(VectorSize::N3, ScalarType::F32)),
+                    },
+                ),
+            ],
+        };
+
+        let layout = ty.layout();
+        let offsets = layout.fields.iter().map(|it| it.offset).collect::<Vec<_>>();
+
+        // `b` is aligned to 16 bytes, `d` fills the padding after the vec3 `c`, and each vec3
+        // array element is padded out to 16 bytes
+        assert_eq!(offsets, [0, 16, 32, 44, 48, 64]);
+        assert_eq!(layout.size, 80);
+        assert_eq!(
+            layout.ranges().collect::<Vec<_>>(),
+            [(0, 4), (16, 16), (32, 12), (44, 4), (48, 12), (64, 12)]
+        );
+    }
+
+    #[test]
+    fn f16_layout() {
+        // struct S { a: f16, b: vec3<f16>, c: f16 }
+        let ty = Type::Struct {
+            members: vec![
+                member("a", scalar(ScalarType::F16)),
+                member("b", vector(VectorSize::N3, ScalarType::F16)),
+                member("c", scalar(ScalarType::F16)),
+            ],
+        };
+
+        let layout = ty.layout();
+        let offsets = layout.fields.iter().map(|it| it.offset).collect::<Vec<_>>();
+
+        assert_eq!(offsets, [0, 8, 14]);
+        assert_eq!(layout.size, 16);
+
+        let mut buffer = [0u8; 16];
+        buffer[0..2].copy_from_slice(&0x3e00u16.to_le_bytes()); // 1.5
+        buffer[8..10].copy_from_slice(&0xc000u16.to_le_bytes()); // -2.0
+        buffer[14..16].copy_from_slice(&0x0001u16.to_le_bytes()); // smallest subnormal
+
+        assert_eq!(
+            layout.read(&buffer),
+            Some(vec![
+                Value::F32(1.5),
+                Value::Vector(vec![Value::F32(-2.0), Value::F32(0.0), Value::F32(0.0)]),
+                Value::F32(2f32.powi(-24)),
+            ])
+        );
+    }
+
+    #[test]
+    fn read_is_little_endian() {
+        let buffer = [
+            0x78, 0x56, 0x34, 0x12, // 0x12345678u
+            0xfe, 0xff, 0xff, 0xff, // -2
+            0x00, 0x00, 0xc0, 0x3f, // 1.5
+        ];
+
+        let field = |offset, kind| LayoutField { offset, kind };
+
+        assert_eq!(
+            field(0, FieldKind::Scalar(ScalarType::U32)).read(&buffer),
+            Some(Value::U32(0x12345678))
+        );
+        assert_eq!(
+            field(4, FieldKind::Scalar(ScalarType::I32)).read(&buffer),
+            Some(Value::I32(-2))
+        );
+        assert_eq!(
+            field(8, FieldKind::Scalar(ScalarType::F32)).read(&buffer),
+            Some(Value::F32(1.5))
+        );
+        assert_eq!(
+            field(0, FieldKind::Vector(VectorSize::N2, ScalarType::U32)).read(&buffer),
+            Some(Value::Vector(vec![
+                Value::U32(0x12345678),
+                Value::U32(0xfffffffe)
+            ]))
+        );
+        assert_eq!(
+            field(8, FieldKind::Scalar(ScalarType::U32)).read(&buffer[..10]),
+            None
+        );
+    }
+}
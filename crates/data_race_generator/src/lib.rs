@@ -481,12 +481,14 @@ impl<'a> Generator<'a> {
 
         Shaders {
             safe: Module {
+                enables: vec![],
                 structs: vec![],
                 consts: vec![],
                 vars: global_vars.clone(),
                 functions: safe_functions,
             },
             race: Module {
+                enables: vec![],
                 structs: vec![],
                 consts: vec![],
                 vars: global_vars.clone(),
@@ -54,6 +54,7 @@ pub fn execute(
             let safe_output = harness::execute_config(
                 &safe_shader,
                 exec_options.workgroups,
+                true,
                 &safe_pipeline_desc,
                 &config,
             )
@@ -61,6 +62,7 @@ pub fn execute(
             let race_output = harness::execute_config(
                 &racy_shader,
                 exec_options.workgroups,
+                true,
                 &race_pipeline_desc,
                 &config,
             )
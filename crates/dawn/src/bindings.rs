@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::ffi::{c_void, CStr, CString};
 use std::mem::zeroed;
 use std::os::raw::c_char;
@@ -6,6 +7,7 @@ use std::ptr::{null, null_mut};
 use crate::dawn;
 use crate::webgpu::*;
 use futures::channel::oneshot;
+use types::{CompilationInfo, CompilationMessage, HarnessError, MessageSeverity};
 
 pub struct Instance(*mut c_void);
 
@@ -20,17 +22,23 @@ impl Instance {
         Instance(unsafe { dawn::new_instance() })
     }
 
-    pub fn enumerate_adapters(&self) -> Vec<AdapterProperties> {
+    pub fn enumerate_adapters(&self) -> Result<Vec<AdapterProperties>, HarnessError> {
         #[allow(non_upper_case_globals)]
         unsafe extern "C" fn cb(info: *const WGPUAdapterProperties, userdata: *mut c_void) {
-            (userdata as *mut Vec<AdapterProperties>)
+            let adapters = (userdata as *mut Vec<Result<AdapterProperties, HarnessError>>)
                 .as_mut()
-                .unwrap()
-                .push(AdapterProperties {
-                    name: CStr::from_ptr((*info).name).to_str().unwrap().to_owned(),
-                    backend: (*info).backendType,
-                    device_id: (*info).deviceID,
-                });
+                .unwrap();
+
+            adapters.push(
+                CStr::from_ptr((*info).name)
+                    .to_str()
+                    .map(|name| AdapterProperties {
+                        name: name.to_owned(),
+                        backend: (*info).backendType,
+                        device_id: (*info).deviceID,
+                    })
+                    .map_err(HarnessError::from),
+            );
         }
 
         let mut adapters = vec![];
@@ -39,25 +47,32 @@ impl Instance {
             dawn::enumerate_adapters(self.0, Some(cb), &mut adapters as *mut _ as *mut c_void);
         }
 
-        adapters
+        adapters.into_iter().collect()
     }
 
+    /// Creates a device for the adapter with the given backend and id, returning `None` if there
+    /// is no such adapter.
     pub fn create_device(self, backend: WGPUBackendType, device_id: u32) -> Option<Device> {
         let handle = unsafe { dawn::create_device(self.0, backend, device_id) };
 
         if handle.is_null() {
-            panic!("failed to create dawn device");
-        }
-
-        unsafe {
-            wgpuDeviceSetUncapturedErrorCallback(handle, Some(default_error_callback), null_mut());
+            return None;
         }
 
         let device = Device {
             _instance: self,
             handle,
+            error: Box::new(RefCell::new(None)),
         };
 
+        unsafe {
+            wgpuDeviceSetUncapturedErrorCallback(
+                handle,
+                Some(uncaptured_error_callback),
+                &*device.error as *const _ as *mut c_void,
+            );
+        }
+
         Some(device)
     }
 }
@@ -79,6 +94,9 @@ impl Drop for Instance {
 pub struct Device {
     _instance: Instance,
     handle: *mut crate::webgpu::WGPUDeviceImpl,
+    /// The first error that wasn't caught by an error scope. This is boxed so that its address
+    /// doesn't change when the device is moved, since dawn holds a pointer to it.
+    error: Box<RefCell<Option<HarnessError>>>,
 }
 
 impl Device {
@@ -88,9 +106,9 @@ impl Device {
         }
     }
 
-    pub fn create_shader_module(&self, source: &str) -> ShaderModule {
-        let source = CString::new(source).unwrap();
-        ErrorScope::new(self, "shader module creation failed").execute(|| unsafe {
+    pub fn create_shader_module(&self, source: &str) -> Result<ShaderModule, HarnessError> {
+        let source = CString::new(source)?;
+        ErrorScope::new(self, HarnessError::Compilation).execute(|| unsafe {
             let wgsl_descriptor = WGPUShaderModuleWGSLDescriptor {
                 chain: WGPUChainedStruct {
                     sType: WGPUSType_WGPUSType_ShaderModuleWGSLDescriptor,
@@ -114,9 +132,9 @@ impl Device {
         &self,
         shader_module: &ShaderModule,
         entrypoint: &str,
-    ) -> ComputePipeline {
-        ErrorScope::new(self, "compute pipeline creation failed").execute(|| unsafe {
-            let entrypoint = CString::new(entrypoint).unwrap();
+    ) -> Result<ComputePipeline, HarnessError> {
+        let entrypoint = CString::new(entrypoint)?;
+        ErrorScope::new(self, HarnessError::Compilation).execute(|| unsafe {
             ComputePipeline {
                 handle: wgpuDeviceCreateComputePipeline(
                     self.handle,
@@ -137,13 +155,95 @@ impl Device {
         })
     }
 
+    pub fn create_render_pipeline(
+        &self,
+        vertex_module: &ShaderModule,
+        vertex_entrypoint: &str,
+        fragment_module: &ShaderModule,
+        fragment_entrypoint: &str,
+        format: WGPUTextureFormat,
+    ) -> Result<RenderPipeline, HarnessError> {
+        let vertex_entrypoint = CString::new(vertex_entrypoint)?;
+        let fragment_entrypoint = CString::new(fragment_entrypoint)?;
+        ErrorScope::new(self, HarnessError::Compilation).execute(|| unsafe {
+            let target = WGPUColorTargetState {
+                format,
+                writeMask: WGPUColorWriteMask_WGPUColorWriteMask_All as _,
+                ..zeroed()
+            };
+
+            let fragment = WGPUFragmentState {
+                module: fragment_module.handle,
+                entryPoint: fragment_entrypoint.as_ptr(),
+                targetCount: 1,
+                targets: &target,
+                ..zeroed()
+            };
+
+            RenderPipeline {
+                handle: wgpuDeviceCreateRenderPipeline(
+                    self.handle,
+                    &WGPURenderPipelineDescriptor {
+                        vertex: WGPUVertexState {
+                            module: vertex_module.handle,
+                            entryPoint: vertex_entrypoint.as_ptr(),
+                            ..zeroed()
+                        },
+                        primitive: WGPUPrimitiveState {
+                            topology: WGPUPrimitiveTopology_WGPUPrimitiveTopology_TriangleList,
+                            ..zeroed()
+                        },
+                        multisample: WGPUMultisampleState {
+                            count: 1,
+                            mask: !0,
+                            ..zeroed()
+                        },
+                        fragment: &fragment,
+                        ..zeroed()
+                    },
+                )
+                .assert_not_null(),
+            }
+        })
+    }
+
+    pub fn create_texture(
+        &self,
+        width: u32,
+        height: u32,
+        format: WGPUTextureFormat,
+        usage: TextureUsage,
+    ) -> Result<Texture, HarnessError> {
+        ErrorScope::new(self, HarnessError::Validation).execute(|| unsafe {
+            Texture {
+                handle: wgpuDeviceCreateTexture(
+                    self.handle,
+                    &WGPUTextureDescriptor {
+                        usage: usage.bits as _,
+                        dimension: WGPUTextureDimension_WGPUTextureDimension_2D,
+                        size: WGPUExtent3D {
+                            width,
+                            height,
+                            depthOrArrayLayers: 1,
+                        },
+                        format,
+                        mipLevelCount: 1,
+                        sampleCount: 1,
+                        ..zeroed()
+                    },
+                )
+                .assert_not_null(),
+            }
+        })
+    }
+
     pub fn create_buffer(
         &self,
         mapped: bool,
         size: usize,
         usage: DeviceBufferUsage,
-    ) -> DeviceBuffer {
-        ErrorScope::new(self, "buffer creation failed").execute(|| unsafe {
+    ) -> Result<DeviceBuffer, HarnessError> {
+        ErrorScope::new(self, HarnessError::Validation).execute(|| unsafe {
             DeviceBuffer {
                 handle: wgpuDeviceCreateBuffer(
                     self.handle,
@@ -164,8 +264,8 @@ impl Device {
         &self,
         layout: &BindGroupLayout,
         entries: &[BindGroupEntry],
-    ) -> BindGroup {
-        ErrorScope::new(self, "bind group creation failed").execute(|| unsafe {
+    ) -> Result<BindGroup, HarnessError> {
+        ErrorScope::new(self, HarnessError::Validation).execute(|| unsafe {
             let entries = entries.iter().map(|e| e.into()).collect::<Vec<_>>();
             BindGroup {
                 handle: wgpuDeviceCreateBindGroup(
@@ -183,8 +283,20 @@ impl Device {
         })
     }
 
-    pub fn create_command_encoder(&self) -> CommandEncoder {
-        ErrorScope::new(self, "command encoder creation failed").execute(|| unsafe {
+    pub fn get_limits(&self) -> WGPULimits {
+        unsafe {
+            let mut supported: WGPUSupportedLimits = zeroed();
+            wgpuDeviceGetLimits(self.handle, &mut supported);
+            supported.limits
+        }
+    }
+
+    pub fn has_feature(&self, feature: WGPUFeatureName) -> bool {
+        unsafe { wgpuDeviceHasFeature(self.handle, feature) }
+    }
+
+    pub fn create_command_encoder(&self) -> Result<CommandEncoder, HarnessError> {
+        ErrorScope::new(self, HarnessError::Validation).execute(|| unsafe {
             CommandEncoder {
                 handle: wgpuDeviceCreateCommandEncoder(self.handle, &zeroed()).assert_not_null(),
             }
@@ -196,6 +308,15 @@ impl Device {
             wgpuDeviceTick(self.handle);
         }
     }
+
+    /// Returns the first error that dawn reported outside of an error scope since the last call,
+    /// e.g. a validation error during submission or the device being lost.
+    pub fn check_errors(&self) -> Result<(), HarnessError> {
+        match self.error.borrow_mut().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Drop for Device {
@@ -230,6 +351,76 @@ pub struct ShaderModule {
     handle: WGPUShaderModule,
 }
 
+impl ShaderModule {
+    /// Returns the errors, warnings and info messages reported by Dawn's front-end while
+    /// compiling the shader.
+    pub fn get_compilation_info(&self) -> Result<CompilationInfo, HarnessError> {
+        unsafe extern "C" fn callback(
+            status: WGPUCompilationInfoRequestStatus,
+            info: *const WGPUCompilationInfo,
+            userdata: *mut c_void,
+        ) {
+            let result = (userdata as *mut Option<Result<CompilationInfo, HarnessError>>)
+                .as_mut()
+                .unwrap();
+
+            if status != WGPUCompilationInfoRequestStatus_WGPUCompilationInfoRequestStatus_Success
+                || info.is_null()
+            {
+                *result = Some(Err(HarnessError::Internal(format!(
+                    "failed to get compilation info (status {status})"
+                ))));
+                return;
+            }
+
+            let info = &*info;
+            let messages = if info.messages.is_null() {
+                &[]
+            } else {
+                std::slice::from_raw_parts(info.messages, info.messageCount as usize)
+            };
+
+            #[allow(non_upper_case_globals)]
+            let messages = messages
+                .iter()
+                .map(|it| CompilationMessage {
+                    severity: match it.type_ {
+                        WGPUCompilationMessageType_WGPUCompilationMessageType_Error => {
+                            MessageSeverity::Error
+                        }
+                        WGPUCompilationMessageType_WGPUCompilationMessageType_Warning => {
+                            MessageSeverity::Warning
+                        }
+                        _ => MessageSeverity::Info,
+                    },
+                    message: error_message(it.message),
+                    line: it.lineNum,
+                    column: it.linePos,
+                })
+                .collect();
+
+            *result = Some(Ok(CompilationInfo { messages }));
+        }
+
+        let mut result: Option<Result<CompilationInfo, HarnessError>> = None;
+
+        // Like popping an error scope, the callback is called before this returns
+        unsafe {
+            wgpuShaderModuleGetCompilationInfo(
+                self.handle,
+                Some(callback),
+                &mut result as *mut _ as *mut c_void,
+            );
+        }
+
+        result.unwrap_or_else(|| {
+            Err(HarnessError::Internal(
+                "compilation info callback wasn't called".to_owned(),
+            ))
+        })
+    }
+}
+
 impl Drop for ShaderModule {
     fn drop(&mut self) {
         unsafe {
@@ -260,6 +451,69 @@ impl Drop for ComputePipeline {
     }
 }
 
+pub struct RenderPipeline {
+    handle: WGPURenderPipeline,
+}
+
+impl RenderPipeline {
+    pub fn get_bind_group_layout(&self, index: u32) -> BindGroupLayout {
+        unsafe {
+            BindGroupLayout {
+                handle: wgpuRenderPipelineGetBindGroupLayout(self.handle, index).assert_not_null(),
+            }
+        }
+    }
+}
+
+impl Drop for RenderPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            wgpuRenderPipelineRelease(self.handle);
+        }
+    }
+}
+
+pub struct Texture {
+    handle: WGPUTexture,
+}
+
+bitflags::bitflags! {
+    pub struct TextureUsage: WGPUTextureUsage {
+        const COPY_SRC = WGPUTextureUsage_WGPUTextureUsage_CopySrc;
+        const RENDER_ATTACHMENT = WGPUTextureUsage_WGPUTextureUsage_RenderAttachment;
+    }
+}
+
+impl Texture {
+    pub fn create_view(&self) -> TextureView {
+        unsafe {
+            TextureView {
+                handle: wgpuTextureCreateView(self.handle, null()).assert_not_null(),
+            }
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            wgpuTextureRelease(self.handle);
+        }
+    }
+}
+
+pub struct TextureView {
+    handle: WGPUTextureView,
+}
+
+impl Drop for TextureView {
+    fn drop(&mut self) {
+        unsafe {
+            wgpuTextureViewRelease(self.handle);
+        }
+    }
+}
+
 pub struct DeviceBuffer {
     handle: WGPUBuffer,
 }
@@ -281,22 +535,28 @@ bitflags::bitflags! {
 }
 
 impl DeviceBuffer {
-    pub fn map_async(&self, mode: DeviceBufferMapMode, size: usize) -> oneshot::Receiver<()> {
+    pub fn map_async(
+        &self,
+        mode: DeviceBufferMapMode,
+        size: usize,
+    ) -> oneshot::Receiver<Result<(), HarnessError>> {
         unsafe {
             unsafe extern "C" fn map_callback(
                 res: WGPUBufferMapAsyncStatus,
                 userdata: *mut c_void,
             ) {
-                assert_eq!(
-                    res,
-                    WGPUBufferMapAsyncStatus_WGPUBufferMapAsyncStatus_Success
-                );
-                let mut tx = Box::from_raw(userdata as *mut Option<oneshot::Sender<()>>);
-                (*tx).take().unwrap().send(()).unwrap();
+                let tx = Box::from_raw(userdata as *mut oneshot::Sender<Result<(), HarnessError>>);
+                let result = if res == WGPUBufferMapAsyncStatus_WGPUBufferMapAsyncStatus_Success {
+                    Ok(())
+                } else {
+                    Err(HarnessError::Mapping(format!("map status {res}")))
+                };
+                // The receiver is dropped if the caller stopped waiting for the buffer
+                let _ = tx.send(result);
             }
 
-            let (tx, rx) = oneshot::channel::<()>();
-            let tx = Box::new(Some(tx));
+            let (tx, rx) = oneshot::channel();
+            let tx = Box::new(tx);
 
             wgpuBufferMapAsync(
                 self.handle,
@@ -311,17 +571,23 @@ impl DeviceBuffer {
         }
     }
 
-    pub fn get_mapped_range(&mut self, size: usize) -> &mut [u8] {
+    pub fn get_mapped_range(&mut self, size: usize) -> Result<&mut [u8], HarnessError> {
         unsafe {
             let ptr = wgpuBufferGetMappedRange(self.handle, 0, size as _);
-            std::slice::from_raw_parts_mut(ptr as _, size)
+            if ptr.is_null() {
+                return Err(HarnessError::Mapping("buffer is not mapped".to_owned()));
+            }
+            Ok(std::slice::from_raw_parts_mut(ptr as _, size))
         }
     }
 
-    pub fn get_const_mapped_range(&self, size: usize) -> &[u8] {
+    pub fn get_const_mapped_range(&self, size: usize) -> Result<&[u8], HarnessError> {
         unsafe {
             let ptr = wgpuBufferGetConstMappedRange(self.handle, 0, size as _);
-            std::slice::from_raw_parts(ptr as _, size)
+            if ptr.is_null() {
+                return Err(HarnessError::Mapping("buffer is not mapped".to_owned()));
+            }
+            Ok(std::slice::from_raw_parts(ptr as _, size))
         }
     }
 
@@ -402,6 +668,64 @@ impl CommandEncoder {
         }
     }
 
+    /// Begins a render pass which clears `view` to zero and stores the result.
+    pub fn begin_render_pass(&self, view: &TextureView) -> RenderPassEncoder {
+        unsafe {
+            let attachment = WGPURenderPassColorAttachment {
+                view: view.handle,
+                loadOp: WGPULoadOp_WGPULoadOp_Clear,
+                storeOp: WGPUStoreOp_WGPUStoreOp_Store,
+                ..zeroed()
+            };
+
+            RenderPassEncoder {
+                handle: wgpuCommandEncoderBeginRenderPass(
+                    self.handle,
+                    &WGPURenderPassDescriptor {
+                        colorAttachmentCount: 1,
+                        colorAttachments: &attachment,
+                        ..zeroed()
+                    },
+                )
+                .assert_not_null(),
+            }
+        }
+    }
+
+    pub fn copy_texture_to_buffer(
+        &self,
+        src: &Texture,
+        dst: &DeviceBuffer,
+        width: u32,
+        height: u32,
+        bytes_per_row: u32,
+    ) {
+        unsafe {
+            wgpuCommandEncoderCopyTextureToBuffer(
+                self.handle,
+                &WGPUImageCopyTexture {
+                    texture: src.handle,
+                    aspect: WGPUTextureAspect_WGPUTextureAspect_All,
+                    ..zeroed()
+                },
+                &WGPUImageCopyBuffer {
+                    buffer: dst.handle,
+                    layout: WGPUTextureDataLayout {
+                        bytesPerRow: bytes_per_row,
+                        rowsPerImage: height,
+                        ..zeroed()
+                    },
+                    ..zeroed()
+                },
+                &WGPUExtent3D {
+                    width,
+                    height,
+                    depthOrArrayLayers: 1,
+                },
+            );
+        }
+    }
+
     pub fn copy_buffer_to_buffer(&self, src: &DeviceBuffer, dst: &DeviceBuffer, size: usize) {
         unsafe {
             wgpuCommandEncoderCopyBufferToBuffer(
@@ -465,6 +789,39 @@ impl Drop for ComputePassEncoder {
     }
 }
 
+pub struct RenderPassEncoder {
+    handle: WGPURenderPassEncoder,
+}
+
+impl RenderPassEncoder {
+    pub fn set_pipeline(&self, pipeline: &RenderPipeline) {
+        unsafe {
+            wgpuRenderPassEncoderSetPipeline(self.handle, pipeline.handle);
+        }
+    }
+
+    pub fn set_bind_group(&self, index: u32, group: &BindGroup) {
+        unsafe {
+            wgpuRenderPassEncoderSetBindGroup(self.handle, index, group.handle, 0, [].as_ptr());
+        }
+    }
+
+    pub fn draw(&self, vertex_count: u32, instance_count: u32) {
+        unsafe {
+            wgpuRenderPassEncoderDraw(self.handle, vertex_count, instance_count, 0, 0);
+        }
+    }
+}
+
+impl Drop for RenderPassEncoder {
+    fn drop(&mut self) {
+        unsafe {
+            wgpuRenderPassEncoderEnd(self.handle);
+            wgpuRenderPassEncoderRelease(self.handle);
+        }
+    }
+}
+
 pub struct CommandBuffer {
     handle: WGPUCommandBuffer,
 }
@@ -503,17 +860,24 @@ impl<T> PointerExt for *mut T {
     }
 }
 
+/// Catches validation errors raised by the calls in a block, turning them into an error of the
+/// kind given by `error`.
 struct ErrorScope<'a> {
     device: &'a Device,
-    message: &'a str,
+    error: fn(String) -> HarnessError,
+    result: Option<HarnessError>,
 }
 
 impl<'a> ErrorScope<'a> {
-    fn new(device: &'a Device, message: &'a str) -> Self {
-        ErrorScope { device, message }
+    fn new(device: &'a Device, error: fn(String) -> HarnessError) -> Self {
+        ErrorScope {
+            device,
+            error,
+            result: None,
+        }
     }
 
-    fn execute<T>(mut self, block: impl FnOnce() -> T) -> T {
+    fn execute<T>(mut self, block: impl FnOnce() -> T) -> Result<T, HarnessError> {
         unsafe {
             wgpuDevicePushErrorScope(
                 self.device.handle,
@@ -532,12 +896,7 @@ impl<'a> ErrorScope<'a> {
                 return;
             }
 
-            if !message.is_null() {
-                let message = CStr::from_ptr(message).to_string_lossy();
-                eprintln!("{message}");
-            }
-
-            panic!("{}", scope.message);
+            scope.result = Some((scope.error)(error_message(message)));
         }
 
         let result = block();
@@ -550,34 +909,44 @@ impl<'a> ErrorScope<'a> {
             );
         }
 
-        result
+        match self.result {
+            Some(e) => Err(e),
+            None => Ok(result),
+        }
+    }
+}
+
+unsafe fn error_message(message: *const c_char) -> String {
+    if message.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(message).to_string_lossy().into_owned()
     }
 }
 
-unsafe extern "C" fn default_error_callback(
+unsafe extern "C" fn uncaptured_error_callback(
     error_type: WGPUErrorType,
     message: *const c_char,
-    _: *mut c_void,
+    userdata: *mut c_void,
 ) {
-    if !message.is_null() {
-        let message = CStr::from_ptr(message).to_string_lossy();
-        eprintln!("{message}");
-    }
+    let slot = (userdata as *const RefCell<Option<HarnessError>>)
+        .as_ref()
+        .unwrap();
+
+    let message = error_message(message);
 
     #[allow(non_upper_case_globals)]
-    match error_type {
-        WGPUErrorType_WGPUErrorType_Validation => {
-            panic!("validation error");
-        }
-        WGPUErrorType_WGPUErrorType_OutOfMemory => {
-            panic!("out of memory");
-        }
-        WGPUErrorType_WGPUErrorType_DeviceLost => {
-            panic!("the dawn device was lost");
-        }
-        WGPUErrorType_WGPUErrorType_Unknown => {
-            panic!("an unknown error occurred");
-        }
-        _ => {}
+    let error = match error_type {
+        WGPUErrorType_WGPUErrorType_Validation => HarnessError::Validation(message),
+        WGPUErrorType_WGPUErrorType_OutOfMemory => HarnessError::OutOfMemory,
+        WGPUErrorType_WGPUErrorType_DeviceLost => HarnessError::DeviceLost(message),
+        WGPUErrorType_WGPUErrorType_Unknown => HarnessError::Internal(message),
+        _ => return,
+    };
+
+    // Later errors are usually a consequence of the first one, so only that one is kept
+    let mut slot = slot.borrow_mut();
+    if slot.is_none() {
+        *slot = Some(error);
     }
 }
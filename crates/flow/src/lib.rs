@@ -223,6 +223,15 @@ impl Flow {
                 ])];
                 ForLoopStatement::new(*header, new_body).into()
             }
+            Statement::While(WhileStatement { condition, body }) => {
+                let mod_body: Vec<Statement> =
+                    body.into_iter().map(|it| self.analyze_stmt(it)).collect();
+                let new_body = vec![Statement::Compound(vec![
+                    self.build_assign().into(),
+                    mod_body.into(),
+                ])];
+                WhileStatement::new(condition, new_body).into()
+            }
             Statement::Continue => Statement::Continue,
             Statement::Fallthrough => Statement::Fallthrough,
             _ => stmt,
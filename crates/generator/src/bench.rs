@@ -0,0 +1,122 @@
+//! Benchmark of generation throughput.
+//!
+//! This only measures generating the AST of each shader, without reconditioning, formatting or
+//! executing it, so results are stable enough to compare between commits.
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use rand::SeedableRng;
+
+use crate::{GenRng, GenerationConfig, Generator, Timings};
+
+/// Number of the slowest shaders to report.
+const SLOWEST_COUNT: usize = 5;
+
+#[derive(Parser)]
+pub struct Options {
+    /// Seed of the first shader to generate.
+    #[clap(long, action, default_value = "0")]
+    pub seed: u64,
+
+    /// Number of shaders to generate, from consecutive seeds.
+    #[clap(long, action, default_value = "1000")]
+    pub count: u64,
+
+    /// Path to a JSON file with generation settings (see `GenerationConfig`).
+    ///
+    /// The default settings are used if this isn't set.
+    #[clap(long, action, value_parser = GenerationConfig::load)]
+    pub gen_config: Option<GenerationConfig>,
+}
+
+struct Sample {
+    seed: u64,
+    time: Duration,
+    nodes: u64,
+}
+
+pub fn run(options: Options) -> eyre::Result<()> {
+    let config = options.gen_config.unwrap_or_default();
+    config.validate()?;
+
+    let config = Rc::new(config);
+    let mut total = Timings::default();
+    let mut samples = vec![];
+
+    for seed in options.seed..options.seed + options.count {
+        let start = Instant::now();
+
+        let mut generator = Generator::new(&mut GenRng::seed_from_u64(seed), config.clone());
+        generator.enable_timings();
+        generator.gen_module();
+
+        let time = start.elapsed();
+        let timings = generator.take_timings().unwrap();
+
+        samples.push(Sample {
+            seed,
+            time,
+            nodes: timings.exprs + timings.stmts,
+        });
+
+        total += &timings;
+    }
+
+    if samples.is_empty() {
+        return Ok(());
+    }
+
+    let time = samples.iter().map(|it| it.time).sum::<Duration>();
+    let secs = time.as_secs_f64();
+    let nodes = total.exprs + total.stmts;
+    let other_time = time.saturating_sub(total.expr_time + total.stmt_time);
+    let percent = |part: Duration| 100.0 * part.as_secs_f64() / secs;
+
+    println!(
+        "generated {} shaders in {time:.2?} ({:.1} shaders/s)",
+        samples.len(),
+        samples.len() as f64 / secs
+    );
+
+    println!(
+        "generated {nodes} nodes ({} expressions, {} statements) at {:.0} nodes/s",
+        total.exprs,
+        total.stmts,
+        nodes as f64 / secs
+    );
+
+    println!(
+        "time in expressions: {:.2?} ({:.1}%), statements: {:.2?} ({:.1}%), other: {:.2?} ({:.1}%)",
+        total.expr_time,
+        percent(total.expr_time),
+        total.stmt_time,
+        percent(total.stmt_time),
+        other_time,
+        percent(other_time)
+    );
+
+    samples.sort_by_key(|it| it.time);
+
+    let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p) as usize].time;
+
+    println!(
+        "time per shader: median {:.2?}, p99 {:.2?}, max {:.2?}",
+        percentile(0.5),
+        percentile(0.99),
+        percentile(1.0)
+    );
+
+    // There's no garbage collector to pause generation, but the slowest shaders show whether
+    // outliers are explained by their size or by something else (e.g. the allocator)
+    println!("slowest shaders:");
+    for sample in samples.iter().rev().take(SLOWEST_COUNT) {
+        println!(
+            "  seed {}: {:.2?} for {} nodes",
+            sample.seed, sample.time, sample.nodes
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use ast::{BuiltinFn, ScalarType};
+use eyre::bail;
+use harness_types::DeviceCapabilities;
+use serde::{Deserialize, Deserializer};
+
+use crate::{gen, ExprType, Profile, Stage, StmtWeights};
+
+/// Settings that control the shape of generated shaders.
+///
+/// This can be loaded from a JSON file with `--gen-config`, so that a fuzzing campaign can be
+/// reproduced from a single file. Fields that are missing from the file keep their default value,
+/// and options passed on the command line override the values from the file.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationConfig {
+    /// Built-in functions to enable in addition to the default ones.
+    #[serde(deserialize_with = "seq_from_str")]
+    pub enabled_fns: Vec<BuiltinFn>,
+    pub enable_pointers: bool,
+    pub enable_barriers: bool,
+    pub enable_builtin_inputs: bool,
+    pub enable_f16: bool,
+    #[serde(deserialize_with = "from_str")]
+    pub stage: Stage,
+    /// Number of bind groups that buffers are spread between.
+    pub bind_groups: u32,
+    /// Workgroup size of compute shaders. With more than one invocation, `s_output` becomes an
+    /// array with an element for each invocation.
+    pub invocations: u32,
+    pub straight_line: bool,
+    pub fn_min_stmts: u32,
+    pub fn_max_stmts: u32,
+    pub block_min_stmts: u32,
+    pub block_max_stmts: u32,
+    pub min_expr_depth: u32,
+    /// Number of `select` calls to nest in a chain. 0 disables chains.
+    pub select_depth: u32,
+    pub max_block_depth: u32,
+    pub max_compound_depth: u32,
+    pub max_total_vars: Option<u32>,
+    pub max_fns: u32,
+    pub min_structs: u32,
+    pub max_structs: u32,
+    pub min_struct_members: u32,
+    pub max_struct_members: u32,
+    pub vector_probability: Option<f64>,
+    /// Restricts the types of variables, functions and buffers to a single scalar type and its
+    /// vectors, e.g. `"u32"`.
+    #[serde(deserialize_with = "opt_from_str")]
+    pub scalar_type: Option<ScalarType>,
+    /// Relative weights of `vec2`, `vec3` and `vec4`.
+    pub vector_width_weights: [u32; 3],
+    /// Written as a map from statement kind to weight, e.g. `{"if": 0}`. Kinds that aren't in the
+    /// map keep their default weight.
+    #[serde(deserialize_with = "stmt_weights")]
+    pub stmt_weights: StmtWeights,
+    /// Kinds of expression that are never generated, e.g. `["call"]`.
+    #[serde(deserialize_with = "seq_from_str")]
+    pub excluded_exprs: Vec<ExprType>,
+    /// Inclusive bounds for generated integer literals.
+    pub lit_range: Option<(i32, i32)>,
+    #[serde(deserialize_with = "from_str")]
+    pub profile: Profile,
+    pub capabilities: Option<DeviceCapabilities>,
+    /// Prefix each generated statement with a `// stmt N: kind` comment.
+    pub annotate: bool,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        GenerationConfig {
+            enabled_fns: vec![],
+            enable_pointers: false,
+            enable_barriers: false,
+            enable_builtin_inputs: false,
+            enable_f16: false,
+            stage: Stage::Compute,
+            bind_groups: 1,
+            invocations: 1,
+            straight_line: false,
+            fn_min_stmts: 5,
+            fn_max_stmts: 5,
+            block_min_stmts: 0,
+            block_max_stmts: 5,
+            min_expr_depth: 0,
+            select_depth: 0,
+            max_block_depth: 3,
+            max_compound_depth: 2,
+            max_total_vars: None,
+            max_fns: 5,
+            min_structs: 1,
+            max_structs: 5,
+            min_struct_members: 1,
+            max_struct_members: 5,
+            vector_probability: None,
+            scalar_type: None,
+            vector_width_weights: [1, 1, 1],
+            stmt_weights: StmtWeights::default(),
+            excluded_exprs: vec![],
+            lit_range: None,
+            profile: Profile::FULL,
+            capabilities: None,
+            annotate: false,
+        }
+    }
+}
+
+impl GenerationConfig {
+    /// Reads a config from a JSON file.
+    pub fn load(path: &str) -> Result<GenerationConfig, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read `{path}`: {e}"))?;
+        serde_json::from_str(&json).map_err(|e| format!("invalid config in `{path}`: {e}"))
+    }
+
+    /// Returns whether values of the scalar type `ty` may be generated, according to the profile,
+    /// `enable_f16`, `scalar_type` and the capabilities of the target device.
+    pub fn allows_scalar(&self, ty: ScalarType) -> bool {
+        if self.scalar_type.is_some_and(|it| it != ty) {
+            return false;
+        }
+
+        match ty {
+            ScalarType::F16 => {
+                self.enable_f16
+                    && self.profile.allows_scalar(ty)
+                    && self.capabilities.as_ref().is_some_and(|it| it.shader_f16)
+            }
+            _ => self.profile.allows_scalar(ty),
+        }
+    }
+
+    /// Checks for settings that the generator can't satisfy.
+    pub fn validate(&self) -> eyre::Result<()> {
+        if self.min_expr_depth > gen::MAX_EXPR_DEPTH {
+            bail!(
+                "minimum expression depth must be at most {}",
+                gen::MAX_EXPR_DEPTH
+            );
+        }
+
+        if self.select_depth > gen::MAX_EXPR_DEPTH {
+            bail!("select depth must be at most {}", gen::MAX_EXPR_DEPTH);
+        }
+
+        if self.stage == Stage::Fragment && self.enable_builtin_inputs {
+            bail!("built-in inputs are only supported for compute shaders");
+        }
+
+        let capabilities = self.capabilities.clone().unwrap_or_default();
+        let max_bind_groups = capabilities.max_bind_groups;
+
+        if self.bind_groups == 0 || self.bind_groups > max_bind_groups {
+            bail!("number of bind groups must be between 1 and {max_bind_groups}");
+        }
+
+        let max_invocations = capabilities
+            .max_compute_workgroup_size_x
+            .min(capabilities.max_compute_invocations_per_workgroup);
+
+        if self.invocations == 0 || self.invocations > max_invocations {
+            bail!("number of invocations must be between 1 and {max_invocations}");
+        }
+
+        if self.stage == Stage::Fragment && self.invocations > 1 {
+            bail!("multiple invocations are only supported for compute shaders");
+        }
+
+        if self.stmt_weights.let_decl == 0 && self.stmt_weights.var_decl == 0 {
+            // Declarations are the only statements that can be generated anywhere
+            bail!("at least one of the `let` and `var` statement weights must be non-zero");
+        }
+
+        if self.excluded_exprs.iter().any(|it| it.is_leaf()) {
+            // Every expression must eventually end in a literal or constructor
+            bail!("literal and constructor expressions can't be excluded");
+        }
+
+        if let Some(p) = self.vector_probability {
+            // The command line checks this when parsing, but a config file doesn't
+            if !(0.0..=1.0).contains(&p) {
+                bail!("vector probability must be between 0 and 1, got {p}");
+            }
+        }
+
+        if self.vector_width_weights.iter().all(|w| *w == 0) {
+            bail!("at least one vector width weight must be non-zero");
+        }
+
+        if let Some(ty) = self.scalar_type {
+            if !matches!(
+                ty,
+                ScalarType::I32 | ScalarType::U32 | ScalarType::Bool | ScalarType::F32
+            ) {
+                bail!("scalar type must be one of {{i32, u32, bool, f32}}");
+            }
+
+            if !self.profile.allows_scalar(ty) {
+                bail!("scalar type `{ty}` is not allowed by the profile");
+            }
+        }
+
+        if let Some((min, max)) = self.lit_range {
+            if min > max {
+                bail!("invalid literal range: {min} is greater than {max}");
+            }
+
+            if max < 0 {
+                bail!("invalid literal range: unsigned literals can't have negative bounds");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserializes a value from a string in the same format that is accepted on the command line.
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
+fn opt_from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn seq_from_str<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn stmt_weights<'de, D>(deserializer: D) -> Result<StmtWeights, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut weights = StmtWeights::default();
+    for (kind, weight) in BTreeMap::<String, u32>::deserialize(deserializer)? {
+        weights
+            .set(&kind, weight)
+            .map_err(serde::de::Error::custom)?;
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::BuiltinFn;
+    use clap::Parser;
+
+    use super::GenerationConfig;
+    use crate::{Options, Profile};
+
+    #[test]
+    fn command_line_overrides_config_file() {
+        let path = std::env::temp_dir().join(format!(
+            "wgslsmith-gen-config-test-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"{
+                "fn_max_stmts": 10,
+                "max_fns": 2,
+                "profile": "integer",
+                "enabled_fns": ["countLeadingZeros"],
+                "stmt_weights": {"if": 0},
+                "lit_range": [-5, 5]
+            }"#,
+        )
+        .unwrap();
+
+        let options = Options::parse_from([
+            "generator",
+            "--gen-config",
+            path.to_str().unwrap(),
+            "--max-fns",
+            "3",
+            "--stmt-weight",
+            "loop=0",
+        ]);
+
+        let config = options.generation_config();
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.fn_max_stmts, 10);
+        assert_eq!(config.max_fns, 3);
+        assert_eq!(config.profile, Profile::INTEGER);
+        assert_eq!(config.enabled_fns, [BuiltinFn::CountLeadingZeros]);
+        assert_eq!(config.stmt_weights.if_, 0);
+        assert_eq!(config.stmt_weights.loop_, 0);
+        assert_eq!(config.stmt_weights.let_decl, 10);
+        assert_eq!(config.lit_range, Some((-5, 5)));
+        assert_eq!(
+            config.fn_min_stmts,
+            GenerationConfig::default().fn_min_stmts
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err = serde_json::from_str::<GenerationConfig>(r#"{"max_fn": 3}"#).unwrap_err();
+        assert!(err.to_string().contains("unknown field `max_fn`"), "{err}");
+
+        let err = serde_json::from_str::<GenerationConfig>(r#"{"stmt_weights": {"iff": 0}}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown statement kind"), "{err}");
+    }
+
+    #[test]
+    fn rejects_out_of_range_vector_probability() {
+        let config: GenerationConfig =
+            serde_json::from_str(r#"{"vector_probability": 1.5}"#).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("between 0 and 1"), "{err}");
+    }
+}
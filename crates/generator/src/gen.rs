@@ -1,60 +1,105 @@
 mod cx;
 mod expr;
 mod fns;
+mod rng;
 mod scope;
 mod stmt;
 mod structs;
 mod utils;
 
 pub mod builtins;
+pub mod timings;
+pub mod trace;
 
 use std::rc::Rc;
 
 use ast::types::{DataType, MemoryViewType};
 use ast::{
-    AccessMode, AssignmentLhs, AssignmentOp, AssignmentStatement, FnAttr, FnDecl, GlobalVarAttr,
-    GlobalVarDecl, LetDeclStatement, Module, Postfix, PostfixExpr, ShaderStage, Statement,
-    StorageClass, VarExpr, VarQualifier,
+    AccessMode, AssignmentLhs, AssignmentOp, AssignmentStatement, BinOp, BinOpExpr, ExprNode,
+    Extension, FnAttr, FnDecl, FnInput, FnInputAttr, FnOutput, FnOutputAttr, GlobalVarAttr,
+    GlobalVarDecl, IfStatement, LetDeclStatement, Lit, Module, Postfix, PostfixExpr,
+    ReturnStatement, ScalarType, ShaderStage, Statement, StorageClass, StructDecl, VarExpr,
+    VarQualifier,
 };
-use rand::prelude::{SliceRandom, StdRng};
+use rand::prelude::SliceRandom;
 use rand::Rng;
 use rand_distr::{Binomial, Distribution, StandardNormal};
 
 use crate::gen::scope::Scope;
-use crate::Options;
+use crate::{GenerationConfig, Stage, StmtWeights};
+
+pub use self::expr::ExprType;
+pub use self::rng::GenRng;
 
 use self::cx::Context;
+use self::rng::RngSplitter;
 use self::structs::StructKind;
+use self::timings::Timings;
+use self::trace::Tracer;
+
+/// Depth beyond which the generator only produces leaf expressions (literals, variables, etc).
+pub(crate) const MAX_EXPR_DEPTH: u32 = 5;
 
 #[derive(Default)]
 struct FnState {
     is_loop: bool,
     block_depth: u32,
+    /// Number of bare compound blocks enclosing the current statement.
+    compound_depth: u32,
+    /// Variables from enclosing scopes which may be shadowed by declarations in the current block.
+    shadowable: Vec<String>,
     expression_depth: u32,
+    /// Whether control flow at the current point is known to be uniform. Barriers may only be
+    /// generated when this is true.
+    is_uniform: bool,
+    /// Whether some invocations may have left the current function early through a non-uniform
+    /// `return`, which makes control flow non-uniform for the rest of the function.
+    has_returned: bool,
+    /// Whether some invocations may have left the innermost enclosing loop early through a
+    /// non-uniform `break` or `continue`. This is reset when leaving the loop.
+    has_exited_loop: bool,
 }
 
-pub struct Generator<'a> {
-    rng: &'a mut StdRng,
-    options: Rc<Options>,
+pub struct Generator {
+    /// RNG for the current generation site (see the `rng` module).
+    rng: GenRng,
+    rngs: RngSplitter,
+    config: Rc<GenerationConfig>,
+    stmt_weights: StmtWeights,
     cx: Context,
     return_type: Option<DataType>,
     fn_state: FnState,
+    /// Highest expression depth reached so far, across all functions.
+    max_depth_reached: u32,
     global_scope: Scope,
     scope: Scope,
     current_block: Vec<Statement>,
     f32_dist: StandardNormal,
     i32_dist: Binomial,
     u32_dist: Binomial,
+    tracer: Option<Tracer>,
+    timings: Option<Timings>,
+    /// Number of statements generated so far, used to number annotations.
+    next_stmt_id: u32,
+    /// Key of a dummy generation site to run in the middle of `gen_module`, for testing that
+    /// adding a site doesn't change the output of the others.
+    #[cfg(test)]
+    extra_site: Option<&'static str>,
 }
 
-impl<'a> Generator<'a> {
-    pub fn new(rng: &'a mut StdRng, options: Rc<Options>) -> Self {
+impl Generator {
+    /// Creates a generator whose output is determined by the next value drawn from `rng`.
+    pub fn new(rng: &mut GenRng, config: Rc<GenerationConfig>) -> Self {
+        let rngs = RngSplitter::new(rng.gen());
         Generator {
-            rng,
-            options: options.clone(),
-            cx: Context::new(options),
+            rng: rngs.child("root"),
+            rngs,
+            config: config.clone(),
+            stmt_weights: config.stmt_weights,
+            cx: Context::new(config),
             return_type: None,
             fn_state: FnState::default(),
+            max_depth_reached: 0,
             global_scope: Scope::empty(),
             scope: Scope::empty(),
             current_block: vec![],
@@ -63,32 +108,95 @@ impl<'a> Generator<'a> {
                 .expect("failed to create binomial distribution"),
             u32_dist: Binomial::new(u32::MAX as u64 * 2, 0.5)
                 .expect("failed to create binomial distribution"),
+            tracer: None,
+            timings: None,
+            next_stmt_id: 0,
+            #[cfg(test)]
+            extra_site: None,
         }
     }
 
+    /// Sets a tracer to record every expression and statement that is generated.
+    ///
+    /// Tracing never consumes randomness, so the generated module is the same with or without it.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// Starts counting the expressions and statements that are generated, and the time spent on
+    /// each.
+    pub fn enable_timings(&mut self) {
+        self.timings = Some(Timings::default());
+    }
+
+    pub fn take_timings(&mut self) -> Option<Timings> {
+        self.timings.take()
+    }
+
+    /// Returns the deepest level of expression nesting reached while generating, where a
+    /// top-level expression has depth 0.
+    ///
+    /// This can be compared against the expression depth limit to see how often the limit is
+    /// actually hit.
+    pub fn max_depth_reached(&self) -> u32 {
+        self.max_depth_reached
+    }
+
     #[tracing::instrument(skip(self))]
     pub fn gen_module(&mut self) -> Module {
-        let struct_count = self
-            .rng
-            .gen_range(self.options.min_structs..=self.options.max_structs);
-
-        for i in 1..=struct_count {
-            let name = format!("Struct_{}", i);
-            let decl = self.gen_struct(name);
-            self.cx.types.insert(decl);
-        }
+        self.with_site("structs", |this| {
+            let struct_count = this
+                .rng
+                .gen_range(this.config.min_structs..=this.config.max_structs);
+
+            for i in 1..=struct_count {
+                let name = format!("Struct_{}", i);
+                let decl = this.gen_struct(name);
+                this.cx.types.insert(decl);
+            }
+        });
 
-        let ub_type_decl =
-            self.gen_struct_with("UniformBuffer".to_owned(), StructKind::UniformBuffer);
-        let sb_type_decl =
-            self.gen_struct_with("StorageBuffer".to_owned(), StructKind::HostShareable);
+        let ub_type_decl = self.with_site("uniform_buffer", |this| {
+            this.gen_struct_with("UniformBuffer".to_owned(), StructKind::UniformBuffer)
+        });
+        let sb_type_decl = self.with_site("storage_buffer", |this| {
+            this.gen_struct_with("StorageBuffer".to_owned(), StructKind::HostShareable)
+        });
 
         self.global_scope
             .insert_readonly("u_input".to_owned(), DataType::Struct(ub_type_decl.clone()));
 
+        // Bindings are numbered in declaration order within each group
+        let (ub_attrs, sb_attrs) = self.with_site("bindings", |this| {
+            let mut next_binding = vec![0; this.config.bind_groups as usize];
+            let mut attrs = || {
+                let group = this.rng.gen_range(0..this.config.bind_groups);
+                let binding = &mut next_binding[group as usize];
+                *binding += 1;
+                vec![
+                    GlobalVarAttr::Group(group as i32),
+                    GlobalVarAttr::Binding(*binding - 1),
+                ]
+            };
+            (attrs(), attrs())
+        });
+
+        #[cfg(test)]
+        if let Some(key) = self.extra_site {
+            self.with_site(key, |this| {
+                for _ in 0..this.rng.gen_range(1..100) {
+                    this.rng.gen::<u64>();
+                }
+            });
+        }
+
         let mut global_vars = vec![
             GlobalVarDecl {
-                attrs: vec![GlobalVarAttr::Group(0), GlobalVarAttr::Binding(0)],
+                attrs: ub_attrs,
                 qualifier: Some(VarQualifier {
                     storage_class: StorageClass::Uniform,
                     access_mode: None,
@@ -98,35 +206,46 @@ impl<'a> Generator<'a> {
                 initializer: None,
             },
             GlobalVarDecl {
-                attrs: vec![GlobalVarAttr::Group(0), GlobalVarAttr::Binding(1)],
+                attrs: sb_attrs,
                 qualifier: Some(VarQualifier {
                     storage_class: StorageClass::Storage,
                     access_mode: Some(AccessMode::ReadWrite),
                 }),
                 name: "s_output".to_owned(),
-                data_type: DataType::Struct(sb_type_decl.clone()),
+                data_type: self.output_buffer_type(&sb_type_decl),
                 initializer: None,
             },
         ];
 
-        for i in 0..self.rng.gen_range(0..=5) {
-            let name = format!("global{i}");
-            global_vars.push(self.gen_global_var(name));
-        }
+        self.with_site("globals", |this| {
+            for i in 0..this.rng.gen_range(0..=5) {
+                let name = format!("global{i}");
+                global_vars.push(this.gen_global_var(name));
+            }
+        });
 
-        let entrypoint = self.gen_entrypoint_function(
-            DataType::Struct(ub_type_decl.clone()),
-            DataType::Struct(sb_type_decl.clone()),
-        );
+        let entrypoint = self.with_site("entrypoint", |this| {
+            this.gen_entrypoint_function(
+                DataType::Struct(ub_type_decl.clone()),
+                DataType::Struct(sb_type_decl.clone()),
+            )
+        });
 
         let Context { types, fns } =
-            std::mem::replace(&mut self.cx, Context::new(self.options.clone()));
+            std::mem::replace(&mut self.cx, Context::new(self.config.clone()));
 
         let mut functions = fns.into_fns();
 
         functions.push(entrypoint);
 
+        let enables = if self.config.allows_scalar(ScalarType::F16) {
+            vec![Extension::F16]
+        } else {
+            vec![]
+        };
+
         Module {
+            enables,
             structs: {
                 let mut structs = types.into_structs();
                 structs.push(ub_type_decl);
@@ -140,7 +259,7 @@ impl<'a> Generator<'a> {
     }
 
     fn gen_global_var(&mut self, name: String) -> GlobalVarDecl {
-        let mut data_type = self.cx.types.select(self.rng);
+        let mut data_type = self.cx.types.select(&mut self.rng);
 
         if self.rng.gen_bool(0.5) {
             data_type = DataType::Array(Rc::new(data_type), Some(self.rng.gen_range(1..=32)));
@@ -172,8 +291,46 @@ impl<'a> Generator<'a> {
     #[tracing::instrument(skip(self))]
     fn gen_entrypoint_function(&mut self, in_buf_type: DataType, out_buf_type: DataType) -> FnDecl {
         let stmt_count = self.rng.gen_range(5..10);
-        let (_, block) = self.with_scope(self.global_scope.clone(), |this| {
+
+        // Control flow is uniform at the start of the entrypoint. Other functions are never
+        // considered uniform since they may be called from non-uniform control flow.
+        //
+        // Barriers can't be used in fragment shaders, so control flow is treated as non-uniform
+        // there to avoid generating them.
+        self.fn_state.is_uniform = self.config.stage == Stage::Compute;
+
+        // Fragment shaders must return a value for the render target, including from any early
+        // returns in the body.
+        let output_type = match self.config.stage {
+            Stage::Compute => None,
+            Stage::Fragment => Some(DataType::Vector(4, ScalarType::U32)),
+        };
+
+        let mut inputs = if self.config.enable_builtin_inputs {
+            builtin_inputs()
+        } else {
+            vec![]
+        };
+
+        let mut scope = self.global_scope.clone();
+        for input in &inputs {
+            scope.insert_readonly(input.name.clone(), input.data_type.clone());
+        }
+
+        // The output index is only needed for the final write, so it isn't added to the scope
+        // unless built-in inputs are enabled
+        if self.config.invocations > 1 && !self.config.enable_builtin_inputs {
+            inputs.extend(
+                builtin_inputs()
+                    .into_iter()
+                    .filter(|it| it.name == "global_invocation_id"),
+            );
+        }
+
+        let (_, block) = self.with_scope(scope, |this| {
+            let prev_return_type = std::mem::replace(&mut this.return_type, output_type.clone());
             let (scope, mut block) = this.gen_stmt_block(stmt_count);
+            this.return_type = prev_return_type;
 
             if let Some(Statement::Return(_)) = block.last() {
                 block.pop();
@@ -193,27 +350,97 @@ impl<'a> Generator<'a> {
                     .into(),
                 );
 
-                let out_lhs = AssignmentLhs::name("s_output", out_buf_type.clone());
                 let out_rhs = this.gen_expr(&out_buf_type);
-                this.current_block
-                    .push(AssignmentStatement::new(out_lhs, AssignmentOp::Simple, out_rhs).into());
+                let out_write = this.gen_output_write(out_buf_type, out_rhs);
+                this.current_block.push(out_write);
+
+                if let Some(output_type) = &output_type {
+                    let value = this.gen_expr(output_type);
+                    this.current_block.push(ReturnStatement::new(value).into());
+                }
             });
 
             std::mem::replace(&mut this.current_block, prev_block)
         });
 
-        FnDecl {
-            attrs: vec![
+        self.fn_state = FnState::default();
+
+        let attrs = match self.config.stage {
+            Stage::Compute => vec![
                 FnAttr::Stage(ShaderStage::Compute),
-                FnAttr::LitWorkgroupSize(1),
+                FnAttr::LitWorkgroupSize(self.config.invocations),
             ],
+            Stage::Fragment => vec![FnAttr::Stage(ShaderStage::Fragment)],
+        };
+
+        FnDecl {
+            attrs,
             name: "main".to_owned(),
-            inputs: vec![],
-            output: None,
+            inputs,
+            output: output_type.map(|data_type| FnOutput {
+                attrs: vec![FnOutputAttr::Location(0)],
+                data_type,
+            }),
             body: block,
         }
     }
 
+    /// Returns the type of `s_output`, which has an element for each invocation if there is more
+    /// than one.
+    fn output_buffer_type(&self, sb_type_decl: &Rc<StructDecl>) -> DataType {
+        let data_type = DataType::Struct(sb_type_decl.clone());
+        match self.config.invocations {
+            1 => data_type,
+            n => DataType::array(data_type, n),
+        }
+    }
+
+    /// Returns the statement that writes `value` to the output buffer.
+    ///
+    /// With multiple invocations, each invocation writes to the element of `s_output` indexed by
+    /// its `global_invocation_id.x`, so that no two invocations write to the same element and the
+    /// result doesn't depend on the order they run in. Invocations from any workgroups after the
+    /// first have no element to write to, so they skip the write.
+    fn gen_output_write(&self, out_buf_type: DataType, value: ExprNode) -> Statement {
+        let invocations = self.config.invocations;
+        if invocations == 1 {
+            let lhs = AssignmentLhs::name("s_output", out_buf_type);
+            return AssignmentStatement::new(lhs, AssignmentOp::Simple, value).into();
+        }
+
+        let index = || -> ExprNode {
+            PostfixExpr::new(
+                VarExpr::new("global_invocation_id")
+                    .into_node(DataType::Vector(3, ScalarType::U32)),
+                Postfix::member("x"),
+            )
+            .into()
+        };
+
+        let array_type = DataType::Ref(MemoryViewType::new(
+            DataType::array(out_buf_type, invocations),
+            StorageClass::Storage,
+        ));
+
+        let lhs = AssignmentLhs::array_index("s_output", array_type, index());
+        let write = AssignmentStatement::new(lhs, AssignmentOp::Simple, value).into();
+
+        IfStatement::new(
+            BinOpExpr::new(BinOp::Less, index(), Lit::U32(invocations)),
+            vec![write],
+        )
+        .into()
+    }
+
+    /// Runs `block` with the RNG for the generation site named `key`, restoring the current RNG
+    /// afterwards.
+    fn with_site<T>(&mut self, key: &str, block: impl FnOnce(&mut Self) -> T) -> T {
+        let old_rng = std::mem::replace(&mut self.rng, self.rngs.child(key));
+        let res = block(self);
+        self.rng = old_rng;
+        res
+    }
+
     fn with_scope<T>(&mut self, scope: Scope, block: impl FnOnce(&mut Self) -> T) -> (Scope, T) {
         let old_scope = std::mem::replace(&mut self.scope, scope);
         let res = block(self);
@@ -221,22 +448,60 @@ impl<'a> Generator<'a> {
     }
 
     fn gen_i32(&mut self) -> i32 {
+        if let Some((min, max)) = self.config.lit_range {
+            return if self.rng.gen_bool(0.5) {
+                self.rng.gen_range(min..=max)
+            } else {
+                [0, 1, -1, min, max]
+                    .choose(&mut self.rng)
+                    .copied()
+                    .unwrap()
+                    .clamp(min, max)
+            };
+        }
+
         if self.rng.gen_bool(0.5) {
-            (self.i32_dist.sample(self.rng) as i64 - i32::MAX as i64) as i32
+            (self.i32_dist.sample(&mut self.rng) as i64 - i32::MAX as i64) as i32
         } else {
-            *[0, 1, -1, i32::MAX, i32::MIN].choose(self.rng).unwrap()
+            *[0, 1, -1, i32::MAX, i32::MIN]
+                .choose(&mut self.rng)
+                .unwrap()
         }
     }
 
     fn gen_u32(&mut self) -> u32 {
+        if let Some((min, max)) = self.config.lit_range {
+            let (min, max) = (min.max(0) as u32, max as u32);
+            return if self.rng.gen_bool(0.5) {
+                self.rng.gen_range(min..=max)
+            } else {
+                [0, 1, min, max]
+                    .choose(&mut self.rng)
+                    .copied()
+                    .unwrap()
+                    .clamp(min, max)
+            };
+        }
+
         if self.rng.gen_bool(0.5) {
-            (self.u32_dist.sample(self.rng) as i64 - u32::MAX as i64).unsigned_abs() as u32
+            (self.u32_dist.sample(&mut self.rng) as i64 - u32::MAX as i64).unsigned_abs() as u32
         } else {
-            *[0, 1, u32::MAX].choose(self.rng).unwrap()
+            *[0, 1, u32::MAX].choose(&mut self.rng).unwrap()
         }
     }
 
     fn gen_f32(&mut self) -> f32 {
+        self.gen_float(1000.0, 16777216.0)
+    }
+
+    /// Generates an `f16` value, which is always an integer so that it can be represented exactly.
+    fn gen_f16(&mut self) -> f32 {
+        self.gen_float(100.0, 2048.0)
+    }
+
+    /// Generates a non-zero integer valued float, scaled from a normal distribution and clamped
+    /// to `-max..=max`.
+    fn gen_float(&mut self, scale: f64, max: f32) -> f32 {
         let k: f64 = self.f32_dist.sample(&mut self.rng);
         let k = if k.abs() < 0.1 {
             if self.rng.gen_bool(0.5) {
@@ -247,7 +512,25 @@ impl<'a> Generator<'a> {
         } else {
             k
         };
-        let x = k * 1000.0;
-        f32::clamp(x.trunc() as f32, -16777216.0, 16777216.0)
+        let x = k * scale;
+        f32::clamp(x.trunc() as f32, -max, max)
     }
 }
+
+/// Compute shader built-in inputs which may be read by the entrypoint.
+fn builtin_inputs() -> Vec<FnInput> {
+    let builtins: [(&str, DataType); 3] = [
+        ("global_invocation_id", DataType::Vector(3, ScalarType::U32)),
+        ("local_invocation_index", ScalarType::U32.into()),
+        ("num_workgroups", DataType::Vector(3, ScalarType::U32)),
+    ];
+
+    builtins
+        .into_iter()
+        .map(|(name, data_type)| FnInput {
+            attrs: vec![FnInputAttr::Builtin(name.to_owned())],
+            name: name.to_owned(),
+            data_type,
+        })
+        .collect()
+}
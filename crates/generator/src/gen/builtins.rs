@@ -4,6 +4,7 @@ use std::rc::Rc;
 use ast::{BuiltinFn, DataType, ScalarType};
 
 use crate::gen::cx::Func;
+use crate::Profile;
 
 use super::cx::Overload;
 
@@ -20,7 +21,7 @@ pub const TINT_EXTRAS: &[BuiltinFn] = {
     &[CountLeadingZeros, CountTrailingZeros, Refract]
 };
 
-pub fn gen_builtins(enabled: &[BuiltinFn]) -> HashMap<DataType, Vec<Rc<Func>>> {
+pub fn gen_builtins(enabled: &[BuiltinFn], profile: Profile) -> HashMap<DataType, Vec<Rc<Func>>> {
     use BuiltinFn::*;
     use DataType::*;
     use ScalarType::*;
@@ -28,7 +29,10 @@ pub fn gen_builtins(enabled: &[BuiltinFn]) -> HashMap<DataType, Vec<Rc<Func>>> {
     let enabled: HashSet<BuiltinFn> = HashSet::from_iter(enabled.iter().copied());
     let mut map = HashMap::<DataType, Vec<Rc<Func>>>::new();
 
-    for s_ty in [I32, U32, F32] {
+    for s_ty in [I32, U32, F32]
+        .into_iter()
+        .filter(|it| profile.allows_scalar(*it))
+    {
         for ty in scalar_and_vectors_of(s_ty) {
             map.add(Abs, [ty.clone()], ty);
         }
@@ -39,7 +43,10 @@ pub fn gen_builtins(enabled: &[BuiltinFn]) -> HashMap<DataType, Vec<Rc<Func>>> {
         map.add(Any, [ty.clone()], Bool);
     }
 
-    for s_ty in [Bool, I32, U32, F32] {
+    for s_ty in [Bool, I32, U32, F32]
+        .into_iter()
+        .filter(|it| profile.allows_scalar(*it))
+    {
         for ty in scalar_and_vectors_of(s_ty) {
             map.add(Select, [ty.clone(), ty.clone(), Bool.into()], ty);
         }
@@ -103,7 +110,7 @@ pub fn gen_builtins(enabled: &[BuiltinFn]) -> HashMap<DataType, Vec<Rc<Func>>> {
         }
     }
 
-    for ty in scalar_and_vectors_of(F32) {
+    for ty in scalar_and_vectors_of(F32).filter(|_| profile.f32) {
         for builtin in [
             // Acos - // TODO: recondition,
             // Acosh - not implemented in tint/naga,
@@ -4,10 +4,11 @@ use std::rc::Rc;
 
 use ast::types::{DataType, ScalarType};
 use ast::{BuiltinFn, FnDecl, StructDecl};
-use rand::prelude::SliceRandom;
+use rand::distributions::WeightedIndex;
+use rand::prelude::{Distribution, SliceRandom};
 use rand::Rng;
 
-use crate::Options;
+use crate::GenerationConfig;
 
 use super::{builtins, utils};
 
@@ -17,10 +18,10 @@ pub struct Context {
 }
 
 impl Context {
-    pub fn new(options: Rc<Options>) -> Context {
+    pub fn new(config: Rc<GenerationConfig>) -> Context {
         Context {
-            types: TypeContext::new(),
-            fns: FnContext::new(options),
+            types: TypeContext::new(&config),
+            fns: FnContext::new(config),
         }
     }
 }
@@ -33,6 +34,10 @@ pub struct FnSignature {
 }
 
 pub struct TypeContext {
+    /// Scalar types that are allowed by the config.
+    scalars: Vec<ScalarType>,
+    vector_probability: Option<f64>,
+    vector_widths: WeightedIndex<u32>,
     types: Vec<Rc<StructDecl>>,
 }
 
@@ -44,8 +49,23 @@ pub enum SelectionFilter {
 }
 
 impl TypeContext {
-    pub fn new() -> Self {
-        TypeContext { types: Vec::new() }
+    pub fn new(config: &GenerationConfig) -> Self {
+        TypeContext {
+            scalars: [
+                ScalarType::I32,
+                ScalarType::U32,
+                ScalarType::F32,
+                ScalarType::F16,
+                ScalarType::Bool,
+            ]
+            .into_iter()
+            .filter(|it| config.allows_scalar(*it))
+            .collect(),
+            vector_probability: config.vector_probability,
+            vector_widths: WeightedIndex::new(config.vector_width_weights)
+                .expect("invalid vector width weights"),
+            types: Vec::new(),
+        }
     }
 
     pub fn insert(&mut self, decl: Rc<StructDecl>) {
@@ -62,12 +82,25 @@ impl TypeContext {
                 ScalarType::I32,
                 ScalarType::U32,
                 ScalarType::F32,
+                ScalarType::F16,
                 ScalarType::Bool,
             ],
             SelectionFilter::HostShareable => &[ScalarType::I32, ScalarType::U32, ScalarType::F32],
             SelectionFilter::Uniform => &[ScalarType::I32, ScalarType::U32],
         };
 
+        let mut allowed_scalars = allowed_scalars
+            .iter()
+            .copied()
+            .filter(|it| self.scalars.contains(it))
+            .collect::<Vec<_>>();
+
+        // This can only happen if the config restricts generation to a type that can't be used in
+        // this kind of buffer (e.g. `bool`), but buffers are still needed for inputs and outputs
+        if allowed_scalars.is_empty() {
+            allowed_scalars.push(ScalarType::U32);
+        }
+
         enum DataTypeKind {
             Scalar,
             Vector,
@@ -88,10 +121,22 @@ impl TypeContext {
             ]
         };
 
-        match allowed.choose(rng).unwrap() {
+        let kind = match self.vector_probability {
+            Some(p) if rng.gen_bool(p) => &DataTypeKind::Vector,
+            Some(_) => allowed
+                .iter()
+                .filter(|it| !matches!(it, DataTypeKind::Vector))
+                .collect::<Vec<_>>()
+                .choose(rng)
+                .copied()
+                .unwrap(),
+            None => allowed.choose(rng).unwrap(),
+        };
+
+        match kind {
             DataTypeKind::Scalar => DataType::Scalar(allowed_scalars.choose(rng).copied().unwrap()),
             DataTypeKind::Vector => DataType::Vector(
-                rng.gen_range(2..=4),
+                self.vector_widths.sample(rng) as u8 + 2,
                 allowed_scalars.choose(rng).copied().unwrap(),
             ),
             DataTypeKind::User => DataType::Struct(self.types.choose(rng).cloned().unwrap()),
@@ -131,9 +176,9 @@ pub struct FnContext {
 }
 
 impl FnContext {
-    pub fn new(options: Rc<Options>) -> Self {
+    pub fn new(config: Rc<GenerationConfig>) -> Self {
         FnContext {
-            map: builtins::gen_builtins(&options.enabled_fns),
+            map: builtins::gen_builtins(&config.enabled_fns, config.profile),
             decls: vec![],
             count: 0,
         }
@@ -190,3 +235,43 @@ impl FnContext {
         self.decls
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ast::types::DataType;
+    use clap::Parser;
+    use rand::SeedableRng;
+
+    use super::TypeContext;
+    use crate::gen::GenRng;
+    use crate::Options;
+
+    #[test]
+    fn vector_width_distribution_matches_weights() {
+        let options = Options::parse_from([
+            "generator",
+            "--vector-probability",
+            "1",
+            "--vector-width-weights",
+            "1",
+            "4",
+            "1",
+        ]);
+
+        let types = TypeContext::new(&options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut counts = [0; 3];
+
+        for _ in 0..6000 {
+            match types.select(&mut rng) {
+                DataType::Vector(n, _) => counts[n as usize - 2] += 1,
+                ty => panic!("expected a vector type, got {ty}"),
+            }
+        }
+
+        // Expected counts are 1000, 4000 and 1000
+        assert!((850..=1150).contains(&counts[0]), "{counts:?}");
+        assert!((3700..=4300).contains(&counts[1]), "{counts:?}");
+        assert!((850..=1150).contains(&counts[2]), "{counts:?}");
+    }
+}
@@ -1,16 +1,23 @@
-use rand::prelude::SliceRandom;
+use std::str::FromStr;
+
+use rand::prelude::{IteratorRandom, SliceRandom};
 use rand::Rng;
 
 use ast::types::{DataType, MemoryViewType, ScalarType};
 use ast::{
-    BinOp, BinOpExpr, Expr, ExprNode, FnCallExpr, FnInput, Lit, Postfix, PostfixExpr, StructDecl,
-    TypeConsExpr, UnOp, UnOpExpr, VarDeclStatement, VarExpr,
+    BinOp, BinOpExpr, BuiltinFn, Expr, ExprNode, FnCallExpr, FnInput, Lit, Postfix, PostfixExpr,
+    StructDecl, TypeConsExpr, UnOp, UnOpExpr, VarDeclStatement, VarExpr,
 };
 
 use super::cx::Func;
+use super::MAX_EXPR_DEPTH;
+
+/// Probability of starting a nested `select` chain when `select_depth` is enabled.
+const SELECT_CHAIN_PROBABILITY: f64 = 0.2;
 
-#[derive(Clone, Copy, Debug)]
-enum ExprType {
+/// A kind of expression that the generator can choose between.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExprType {
     Lit,
     TypeCons,
     Var,
@@ -19,8 +26,86 @@ enum ExprType {
     FnCall,
 }
 
-impl<'a> super::Generator<'a> {
+impl ExprType {
+    /// Names accepted by [`from_str`](Self::from_str), in the same order as the variants.
+    pub const KINDS: &'static [&'static str] = &[
+        "literal",
+        "constructor",
+        "variable",
+        "unary",
+        "binary",
+        "call",
+    ];
+
+    /// Returns whether every expression of some type can end in this kind of expression, so that
+    /// it must always be available.
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, ExprType::Lit | ExprType::TypeCons)
+    }
+}
+
+impl FromStr for ExprType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "literal" => Ok(ExprType::Lit),
+            "constructor" => Ok(ExprType::TypeCons),
+            "variable" => Ok(ExprType::Var),
+            "unary" => Ok(ExprType::UnOp),
+            "binary" => Ok(ExprType::BinOp),
+            "call" => Ok(ExprType::FnCall),
+            _ => Err(format!(
+                "unknown expression kind `{s}` - must be one of {{{}}}",
+                Self::KINDS.join(", ")
+            )),
+        }
+    }
+}
+
+impl super::Generator {
     pub fn gen_expr(&mut self, ty: &DataType) -> ExprNode {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.enter();
+        }
+
+        if let Some(timings) = &mut self.timings {
+            timings.enter_expr();
+        }
+
+        let is_root = self.fn_state.expression_depth == 0;
+        let node = self.gen_expr_inner(ty);
+
+        // A node whose annotated type doesn't match its operands would only show up later as a
+        // confusing compiler error, so catch it here. Subexpressions are checked as part of the
+        // root expression that contains them.
+        if cfg!(debug_assertions) && is_root {
+            if let Err(e) = ast::typecheck::typecheck_expr(&node) {
+                panic!("generated ill-typed expression `{node}`: {e}");
+            }
+        }
+
+        if let Some(timings) = &mut self.timings {
+            timings.exit();
+        }
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.exit_expr(&node);
+        }
+
+        node
+    }
+
+    fn gen_expr_inner(&mut self, ty: &DataType) -> ExprNode {
+        self.max_depth_reached = self.max_depth_reached.max(self.fn_state.expression_depth);
+
+        if self.config.select_depth > 0
+            && self.can_gen_select_chain(ty)
+            && self.rng.gen_bool(SELECT_CHAIN_PROBABILITY)
+        {
+            return self.gen_select_chain(ty, self.config.select_depth);
+        }
+
         let mut allowed = vec![];
 
         match ty {
@@ -32,7 +117,7 @@ impl<'a> super::Generator<'a> {
             DataType::Ref(_) => panic!("explicit request to generate ref expression: `{ty}`"),
         }
 
-        if self.fn_state.expression_depth < 5 {
+        if self.fn_state.expression_depth < MAX_EXPR_DEPTH {
             // Unary operators are available for all scalars and vectors.
             if matches!(ty, DataType::Scalar(_) | DataType::Vector(_, _)) {
                 allowed.push(ExprType::UnOp);
@@ -42,7 +127,10 @@ impl<'a> super::Generator<'a> {
             if matches!(
                 ty,
                 DataType::Scalar(_)
-                    | DataType::Vector(_, ScalarType::I32 | ScalarType::U32 | ScalarType::F32)
+                    | DataType::Vector(
+                        _,
+                        ScalarType::I32 | ScalarType::U32 | ScalarType::F32 | ScalarType::F16
+                    )
             ) {
                 allowed.push(ExprType::BinOp);
             }
@@ -63,6 +151,23 @@ impl<'a> super::Generator<'a> {
             allowed.push(ExprType::Var);
         }
 
+        allowed.retain(|it| !self.config.excluded_exprs.contains(it));
+
+        // Avoid leaves until the minimum depth is reached. Function calls don't count as
+        // non-leaves since they may not take any arguments. If there's nothing else we can
+        // generate for this type, we have no choice but to stop here.
+        if self.fn_state.expression_depth < self.config.min_expr_depth {
+            let non_leaves = allowed
+                .iter()
+                .copied()
+                .filter(|it| matches!(it, ExprType::TypeCons | ExprType::UnOp | ExprType::BinOp))
+                .collect::<Vec<_>>();
+
+            if !non_leaves.is_empty() {
+                allowed = non_leaves;
+            }
+        }
+
         tracing::info!("allowed constructions: {:?}", allowed);
 
         match *allowed.choose(&mut self.rng).unwrap() {
@@ -70,28 +175,71 @@ impl<'a> super::Generator<'a> {
             ExprType::TypeCons => self.gen_type_cons_expr(ty),
             ExprType::UnOp => self.gen_un_op_expr(ty),
             ExprType::BinOp => self.gen_bin_op_expr(ty),
-            ExprType::Var => self.gen_var_expr(ty),
+            ExprType::Var => match self.gen_var_expr(ty) {
+                Some(expr) => expr,
+                // Fall back to a constant if there turns out to be no usable variable
+                None => self.gen_const_expr(ty),
+            },
             ExprType::FnCall => self.gen_fn_call_expr(ty),
         }
     }
 
+    fn can_gen_select_chain(&self, ty: &DataType) -> bool {
+        let s_ty = match ty {
+            DataType::Scalar(t) | DataType::Vector(_, t) => *t,
+            _ => return false,
+        };
+
+        s_ty != ScalarType::F16
+            && self.fn_state.expression_depth < MAX_EXPR_DEPTH
+            && !self.config.excluded_exprs.contains(&ExprType::FnCall)
+    }
+
+    /// Generates a `select` call which has another `select` chain as its true or false operand,
+    /// until `depth` calls are nested or the maximum expression depth is reached.
+    ///
+    /// The condition of a vector `select` is a boolean vector of the same width, so that every
+    /// level picks components independently.
+    fn gen_select_chain(&mut self, ty: &DataType, depth: u32) -> ExprNode {
+        let cond_ty = match ty {
+            DataType::Vector(n, _) => DataType::Vector(*n, ScalarType::Bool),
+            _ => ScalarType::Bool.into(),
+        };
+
+        self.fn_state.expression_depth += 1;
+
+        let nested = self.rng.gen_range(0..2);
+        let mut args = vec![];
+
+        for i in 0..2 {
+            let arg = if i == nested && depth > 1 && self.can_gen_select_chain(ty) {
+                self.gen_select_chain(ty, depth - 1)
+            } else {
+                self.gen_expr(ty)
+            };
+
+            args.push(arg);
+        }
+
+        args.push(self.gen_expr(&cond_ty));
+
+        self.fn_state.expression_depth -= 1;
+
+        FnCallExpr::new(BuiltinFn::Select.as_ref(), args).into_node(ty.clone())
+    }
+
     fn can_gen_fn(&self, _return_type: &DataType) -> bool {
-        self.cx.fns.len() < self.options.max_fns
+        self.cx.fns.len() < self.config.max_fns
     }
 
     fn gen_pointer_expr(&mut self, mem_view: &MemoryViewType) -> ExprNode {
         let ref_type = DataType::Ref(mem_view.clone());
-        let available = self.scope.of_type(&ref_type);
+        let available = self.scope.vars_of_type(&ref_type);
 
         // If there is a variable available for the target type, we use that.
         // Otherwise we need to introduce a new local variable.
-        if let Some((name, data_type)) = available.choose(&mut self.rng) {
-            let mut var_expr = VarExpr::new(name).into_node(data_type.clone());
-
-            if var_expr.data_type.dereference() != mem_view.inner.as_ref() {
-                var_expr = self.gen_accessor(&ref_type, var_expr);
-            }
-
+        if let Some(name) = available.choose(&mut self.rng) {
+            let var_expr = VarExpr::new(name).into_node(ref_type.clone());
             UnOpExpr::new(UnOp::AddressOf, var_expr).into()
         } else {
             let ident = self.scope.next_name();
@@ -197,27 +345,25 @@ impl<'a> super::Generator<'a> {
             // These operators work on scalar/vector integers.
             // The number of components in the result type depends on the operands, but the
             // actual type does not.
-            BinOp::Less | BinOp::LessEqual | BinOp::Greater | BinOp::GreaterEqual => ty.map(
-                [ScalarType::I32, ScalarType::U32, ScalarType::F32]
-                    .choose(&mut self.rng)
-                    .copied()
-                    .unwrap(),
-            ),
+            BinOp::Less | BinOp::LessEqual | BinOp::Greater | BinOp::GreaterEqual => {
+                ty.map(self.choose_scalar(&[
+                    ScalarType::I32,
+                    ScalarType::U32,
+                    ScalarType::F32,
+                    ScalarType::F16,
+                ]))
+            }
 
             // These operators work on scalar/vector integers and bools.
             // The number of components in the result type depends on the operands, but the
             // actual type does not.
-            BinOp::Equal | BinOp::NotEqual => ty.map(
-                [
-                    ScalarType::I32,
-                    ScalarType::U32,
-                    ScalarType::F32,
-                    ScalarType::Bool,
-                ]
-                .choose(&mut self.rng)
-                .copied()
-                .unwrap(),
-            ),
+            BinOp::Equal | BinOp::NotEqual => ty.map(self.choose_scalar(&[
+                ScalarType::I32,
+                ScalarType::U32,
+                ScalarType::F32,
+                ScalarType::F16,
+                ScalarType::Bool,
+            ])),
         };
 
         let l = self.gen_expr(&l_ty);
@@ -230,24 +376,63 @@ impl<'a> super::Generator<'a> {
 
         let r = self.gen_expr(&r_ty);
 
+        // Operands with mismatched types (e.g. `i32 + u32`) are an instant validation failure, so
+        // make sure that the generated expressions really have the types that we asked for.
+        debug_assert_eq!(
+            l.data_type.dereference(),
+            &l_ty,
+            "bad left operand for `{op}`"
+        );
+        debug_assert_eq!(
+            r.data_type.dereference(),
+            &r_ty,
+            "bad right operand for `{op}`"
+        );
+
         self.fn_state.expression_depth -= 1;
 
         BinOpExpr::new(op, l, r).into()
     }
 
-    fn gen_var_expr(&mut self, ty: &DataType) -> ExprNode {
+    /// Returns whether any of the numeric scalar types are allowed by the config.
+    fn allows_numeric_scalar(&self) -> bool {
+        [
+            ScalarType::I32,
+            ScalarType::U32,
+            ScalarType::F32,
+            ScalarType::F16,
+        ]
+        .into_iter()
+        .any(|it| self.config.allows_scalar(it))
+    }
+
+    /// Chooses one of the given scalar types, excluding any that are disallowed by the config.
+    fn choose_scalar(&mut self, types: &[ScalarType]) -> ScalarType {
+        let types = types
+            .iter()
+            .copied()
+            .filter(|it| self.config.allows_scalar(*it))
+            .collect::<Vec<_>>();
+
+        types.choose(&mut self.rng).copied().unwrap()
+    }
+
+    /// Generates a reference to a variable in scope which is (or contains) the given type.
+    ///
+    /// Returns `None` if no such variable exists.
+    fn gen_var_expr(&mut self, ty: &DataType) -> Option<ExprNode> {
         tracing::info!("generating var with {:?}, scope={:?}", ty, self.scope);
 
-        let (name, data_type) = self.scope.of_type(ty).choose(&mut self.rng).unwrap();
+        let (name, data_type) = self.scope.choose_matching(ty, &mut self.rng)?;
         let expr = VarExpr::new(name).into_node(data_type.clone());
 
         if expr.data_type.dereference() == ty {
-            return expr;
+            return Some(expr);
         }
 
         // Variable does not have the same type as the target, so we need to generate an
         // accessor to get an appropriate field
-        self.gen_accessor(ty, expr)
+        Some(self.gen_accessor(ty, expr))
     }
 
     fn gen_fn_call_expr(&mut self, ty: &DataType) -> ExprNode {
@@ -264,8 +449,8 @@ impl<'a> super::Generator<'a> {
 
     fn gen_raw_fn_call_expr(&mut self, ty: &DataType) -> ExprNode {
         // Produce a function call with p=0.8 or p=1 if max functions reached
-        if self.cx.fns.len() > self.options.max_fns || self.rng.gen_bool(0.8) {
-            if let Some(func) = self.cx.fns.select(self.rng, ty) {
+        if self.cx.fns.len() > self.config.max_fns || self.rng.gen_bool(0.8) {
+            if let Some(func) = self.cx.fns.select(&mut self.rng, ty) {
                 let (name, params, return_type) = match func.as_ref() {
                     Func::Builtin(builtin, overload) => (
                         builtin.as_ref(),
@@ -295,16 +480,16 @@ impl<'a> super::Generator<'a> {
         let mut args = vec![];
 
         for i in 0..arg_count {
-            let expr = if self.options.enable_pointers
+            let expr = if self.config.enable_pointers
                 && self.scope.has_references()
                 && self.rng.gen_bool(0.2)
             {
-                let (name, mem_view) = self.scope.choose_reference(self.rng);
+                let (name, mem_view) = self.scope.choose_reference(&mut self.rng);
                 let var_expr = VarExpr::new(name).into_node(DataType::Ref(mem_view.clone()));
                 UnOpExpr::new(UnOp::AddressOf, var_expr).into()
             } else {
                 self.fn_state.expression_depth += 1;
-                let data_type = self.cx.types.select(self.rng);
+                let data_type = self.cx.types.select(&mut self.rng);
                 let expr = self.gen_expr(&data_type);
                 self.fn_state.expression_depth -= 1;
                 expr
@@ -339,7 +524,7 @@ impl<'a> super::Generator<'a> {
     }
 
     fn gen_vector_accessor(&mut self, size: u8, target: &DataType, expr: ExprNode) -> ExprNode {
-        let accessor = super::utils::gen_vector_accessor(self.rng, size, target);
+        let accessor = super::utils::gen_vector_accessor(&mut self.rng, size, target);
         PostfixExpr::new(expr, Postfix::member(accessor)).into()
     }
 
@@ -360,7 +545,7 @@ impl<'a> super::Generator<'a> {
         target: &DataType,
         expr: ExprNode,
     ) -> ExprNode {
-        let member = decl.accessors_of(target).choose(self.rng).unwrap();
+        let member = decl.accessors_of(target).choose(&mut self.rng).unwrap();
         let expr = PostfixExpr::new(expr, Postfix::member(&member.name)).into();
 
         if member.data_type.dereference() == target {
@@ -390,6 +575,7 @@ impl<'a> super::Generator<'a> {
                 ScalarType::I32 => Lit::I32(self.gen_i32()),
                 ScalarType::U32 => Lit::U32(self.gen_u32()),
                 ScalarType::F32 => Lit::F32(self.gen_f32()),
+                ScalarType::F16 => Lit::F16(self.gen_f16()),
                 ScalarType::AU32 => Lit::U32(self.gen_u32()),
                 ScalarType::AI32 => Lit::I32(self.gen_i32()), 
             },
@@ -417,7 +603,7 @@ impl<'a> super::Generator<'a> {
                 .choose(&mut self.rng)
                 .copied()
                 .unwrap(),
-            ScalarType::F32 => UnOp::Neg,
+            ScalarType::F32 | ScalarType::F16 => UnOp::Neg,
         }
     }
 
@@ -455,12 +641,24 @@ impl<'a> super::Generator<'a> {
                 BinOp::LShift,
                 BinOp::RShift,
             ],
-            ScalarType::F32 => &[BinOp::Plus, BinOp::Minus, BinOp::Times, BinOp::Divide],
+            ScalarType::F32 | ScalarType::F16 => {
+                &[BinOp::Plus, BinOp::Minus, BinOp::Times, BinOp::Divide]
+            }
             ScalarType::AU32 | ScalarType::AI32 => &[], // TODO: No allowed ops
         };
 
         let mut allowed = allowed.to_vec();
 
+        // Ordering comparisons need numeric operands, which may be ruled out by `--scalar-type`
+        if *scalar_ty == ScalarType::Bool && !self.allows_numeric_scalar() {
+            allowed.retain(|op| {
+                !matches!(
+                    op,
+                    BinOp::Less | BinOp::LessEqual | BinOp::Greater | BinOp::GreaterEqual
+                )
+            });
+        }
+
         if let DataType::Scalar(ScalarType::Bool) = ty {
             allowed.extend_from_slice(&[BinOp::LogAnd, BinOp::LogOr]);
         }
@@ -468,3 +666,248 @@ impl<'a> super::Generator<'a> {
         *allowed.choose(&mut self.rng).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use ast::types::DataType;
+    use ast::{BinOp, Expr, ExprNode, Lit, ScalarType};
+    use clap::Parser;
+    use rand::SeedableRng;
+
+    use crate::gen::{GenRng, Generator};
+    use crate::Options;
+
+    /// Checks that the operands of every binary operator in `node` have compatible types.
+    fn check_operands(node: &ExprNode, count: &mut usize) {
+        match &node.expr {
+            Expr::Lit(_) | Expr::Var(_) => {}
+            Expr::TypeCons(expr) => expr.args.iter().for_each(|e| check_operands(e, count)),
+            Expr::FnCall(expr) => expr.args.iter().for_each(|e| check_operands(e, count)),
+            Expr::Postfix(expr) => check_operands(&expr.inner, count),
+            Expr::UnOp(expr) => check_operands(&expr.inner, count),
+            Expr::BinOp(expr) => {
+                let l = expr.left.data_type.dereference();
+                let r = expr.right.data_type.dereference();
+
+                match expr.op {
+                    BinOp::LShift | BinOp::RShift => {
+                        assert!(l.is_integer(), "shifted operand must be an integer: {node}");
+                        assert_eq!(*r, l.map(ScalarType::U32), "bad shift amount: {node}");
+                    }
+                    _ => assert_eq!(l, r, "mismatched operands: {node}"),
+                }
+
+                *count += 1;
+                check_operands(&expr.left, count);
+                check_operands(&expr.right, count);
+            }
+        }
+    }
+
+    #[test]
+    fn bin_op_operands_agree() {
+        let options = Options::parse_from(["generator"]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut count = 0;
+
+        let types: Vec<DataType> = [ScalarType::I32, ScalarType::U32, ScalarType::F32]
+            .into_iter()
+            .flat_map(|t| [t.into(), DataType::Vector(2, t), DataType::Vector(4, t)])
+            .chain([ScalarType::Bool.into()])
+            .collect();
+
+        let mut gen = Generator::new(&mut rng, config);
+        for ty in types.iter().cycle().take(1000) {
+            check_operands(&gen.gen_bin_op_expr(ty), &mut count);
+        }
+
+        // Each expression contains at least one binary operator, plus any nested ones.
+        assert!(count >= 1000);
+    }
+
+    #[test]
+    fn lit_range_clamps_integer_literals() {
+        let options = Options::parse_from(["generator", "--lit-range", "-5", "10"]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut gen = Generator::new(&mut rng, config);
+
+        for _ in 0..1000 {
+            match gen.gen_lit(&ScalarType::I32.into()) {
+                Lit::I32(v) => assert!((-5..=10).contains(&v), "{v} out of range"),
+                lit => panic!("unexpected literal: {lit:?}"),
+            }
+
+            match gen.gen_lit(&ScalarType::U32.into()) {
+                Lit::U32(v) => assert!(v <= 10, "{v} out of range"),
+                lit => panic!("unexpected literal: {lit:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn var_expr_without_matching_variable() {
+        let options = Options::parse_from(["generator"]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut gen = Generator::new(&mut rng, config);
+
+        // The scope has an i32 vector, but none of the requested width
+        gen.scope
+            .insert_readonly("v".to_owned(), DataType::Vector(2, ScalarType::I32));
+
+        let vec4 = DataType::Vector(4, ScalarType::I32);
+        assert!(gen.gen_var_expr(&vec4).is_none());
+        assert!(gen.gen_var_expr(&ScalarType::I32.into()).is_some());
+
+        for _ in 0..100 {
+            assert_eq!(gen.gen_expr(&vec4).data_type, vec4);
+        }
+    }
+
+    /// Returns the length of the shortest path from `node` to a leaf.
+    fn min_leaf_depth(node: &ExprNode) -> u32 {
+        let children: Vec<&ExprNode> = match &node.expr {
+            Expr::Lit(_) | Expr::Var(_) => vec![],
+            Expr::TypeCons(expr) => expr.args.iter().collect(),
+            Expr::FnCall(expr) => expr.args.iter().collect(),
+            Expr::Postfix(expr) => vec![&expr.inner],
+            Expr::UnOp(expr) => vec![&expr.inner],
+            Expr::BinOp(expr) => vec![&expr.left, &expr.right],
+        };
+
+        1 + children.into_iter().map(min_leaf_depth).min().unwrap_or(0)
+    }
+
+    #[test]
+    fn min_expr_depth() {
+        let options = Options::parse_from(["generator", "--min-expr-depth", "3"]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut gen = Generator::new(&mut rng, config);
+
+        gen.scope
+            .insert_readonly("v".to_owned(), DataType::Vector(4, ScalarType::I32));
+
+        let types: Vec<DataType> = [ScalarType::I32, ScalarType::U32, ScalarType::Bool]
+            .into_iter()
+            .flat_map(|t| [t.into(), DataType::Vector(3, t)])
+            .collect();
+
+        for ty in types.iter().cycle().take(300) {
+            let node = gen.gen_expr(ty);
+            assert!(
+                min_leaf_depth(&node) > 3,
+                "expression is too shallow: {node}"
+            );
+        }
+
+        // Arrays can only be constructed with no arguments, so this must stop at a leaf
+        let array = DataType::Array(Rc::new(ScalarType::I32.into()), Some(4));
+        assert_eq!(min_leaf_depth(&gen.gen_expr(&array)), 1);
+    }
+
+    fn check_not_excluded(node: &ExprNode) {
+        match &node.expr {
+            Expr::Lit(_) | Expr::Var(_) => {}
+            Expr::TypeCons(expr) => expr.args.iter().for_each(check_not_excluded),
+            Expr::Postfix(expr) => check_not_excluded(&expr.inner),
+            Expr::UnOp(expr) => check_not_excluded(&expr.inner),
+            Expr::FnCall(_) | Expr::BinOp(_) => panic!("excluded expression: {node}"),
+        }
+    }
+
+    #[test]
+    fn excluded_exprs() {
+        let options = Options::parse_from([
+            "generator",
+            "--exclude-construct",
+            "binary",
+            "--exclude-construct",
+            "call",
+            "--min-expr-depth",
+            "3",
+        ]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut gen = Generator::new(&mut rng, config);
+
+        let types: Vec<DataType> = [ScalarType::I32, ScalarType::U32, ScalarType::Bool]
+            .into_iter()
+            .flat_map(|t| [t.into(), DataType::Vector(3, t)])
+            .collect();
+
+        for ty in types.iter().cycle().take(300) {
+            check_not_excluded(&gen.gen_expr(ty));
+        }
+    }
+
+    /// Returns the longest chain of `select` calls nested through their true or false operands,
+    /// checking that every condition is a `bool` or a boolean vector matching the width of the
+    /// selected values.
+    fn select_chain_depth(node: &ExprNode) -> u32 {
+        let children: Vec<&ExprNode> = match &node.expr {
+            Expr::Lit(_) | Expr::Var(_) => vec![],
+            Expr::TypeCons(expr) => expr.args.iter().collect(),
+            Expr::Postfix(expr) => vec![&expr.inner],
+            Expr::UnOp(expr) => vec![&expr.inner],
+            Expr::BinOp(expr) => vec![&expr.left, &expr.right],
+            Expr::FnCall(expr) if expr.ident == "select" => {
+                let cond_ty = &expr.args[2].data_type;
+                let valid = match &node.data_type {
+                    DataType::Vector(n, _) => {
+                        *cond_ty == DataType::Vector(*n, ScalarType::Bool)
+                            || *cond_ty == ScalarType::Bool.into()
+                    }
+                    _ => *cond_ty == ScalarType::Bool.into(),
+                };
+
+                assert!(valid, "bad condition in `{node}`");
+                select_chain_depth(&expr.args[2]);
+
+                let nested = expr.args[..2].iter().map(select_chain_depth).max();
+                return 1 + nested.unwrap();
+            }
+            Expr::FnCall(expr) => expr.args.iter().collect(),
+        };
+
+        children
+            .into_iter()
+            .map(select_chain_depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn select_chains() {
+        let options = Options::parse_from(["generator", "--select-depth", "3"]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut gen = Generator::new(&mut rng, config);
+
+        let types: Vec<DataType> = [ScalarType::I32, ScalarType::F32, ScalarType::Bool]
+            .into_iter()
+            .flat_map(|t| [t.into(), DataType::Vector(3, t)])
+            .collect();
+
+        let max_depth = types
+            .iter()
+            .cycle()
+            .take(300)
+            .map(|ty| select_chain_depth(&gen.gen_expr(ty)))
+            .max();
+
+        assert!(max_depth >= Some(3), "no chains of depth 3: {max_depth:?}");
+    }
+
+    #[test]
+    fn leaf_exprs_cant_be_excluded() {
+        for kind in ["literal", "constructor"] {
+            let result = Options::try_parse_from(["generator", "--exclude-construct", kind]);
+            assert!(result.is_err(), "{kind} was excluded");
+        }
+    }
+}
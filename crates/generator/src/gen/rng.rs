@@ -0,0 +1,146 @@
+//! Splitting of the generator's random number generator between generation sites.
+//!
+//! Each part of a module (the structs, the global variables, the body of each function, and so
+//! on) is generated from its own child RNG, seeded from the root seed and a key naming the site,
+//! instead of every decision being drawn from one shared stream. The contract is:
+//!
+//! - The RNG for a site depends only on the root seed and the site's key. Adding a site, removing
+//!   one, or changing how much randomness a site consumes doesn't change the randomness seen by
+//!   any other site.
+//! - Keys must be unique, and should be stable across versions since renaming a key changes the
+//!   output of that site.
+//! - Within a site, decisions are still drawn sequentially, so changing what a site generates can
+//!   change everything else generated by the same site.
+//! - A site can still be affected by the *state* left by earlier sites, e.g. which structs exist
+//!   or which variables are in scope, just not by how many random numbers they drew.
+//!
+//! Seeds are derived with a fixed hash rather than `std`'s `Hash`, whose output may change between
+//! Rust releases. For the same reason, every RNG is a [`GenRng`] rather than `rand`'s `StdRng`,
+//! whose algorithm may change between releases of `rand`.
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+/// The random number generator that shaders are generated from.
+///
+/// This is pinned to ChaCha12, which is what `StdRng` uses in `rand` 0.8, so the seeds from before
+/// it was pinned still produce the same shaders. Unlike `StdRng`, its output for a given seed is
+/// guaranteed not to change, so a seed recorded in a bug report keeps reproducing the same shader.
+/// Changing this type changes the shader generated from every seed.
+pub type GenRng = ChaCha12Rng;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Creates the RNGs for individual generation sites from a root seed.
+#[derive(Clone, Copy, Debug)]
+pub struct RngSplitter {
+    seed: u64,
+}
+
+impl RngSplitter {
+    pub fn new(seed: u64) -> RngSplitter {
+        RngSplitter { seed }
+    }
+
+    /// Returns the RNG for the generation site named `key`.
+    pub fn child(&self, key: &str) -> GenRng {
+        GenRng::seed_from_u64(derive_seed(self.seed, key))
+    }
+}
+
+fn derive_seed(seed: u64, key: &str) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS ^ mix(seed);
+    for byte in key.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    mix(hash)
+}
+
+/// The finalizer from SplitMix64, which spreads each input bit across the whole output.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use clap::Parser;
+    use rand::{Rng, SeedableRng};
+
+    use super::{GenRng, RngSplitter};
+    use crate::gen::Generator;
+    use crate::{GenerationConfig, Options};
+
+    #[test]
+    fn children_are_independent_of_other_sites() {
+        let rngs = RngSplitter::new(42);
+        let expected = rngs.child("a").gen::<[u64; 4]>();
+
+        let mut other = rngs.child("b");
+        for _ in 0..100 {
+            other.gen::<u64>();
+        }
+
+        assert_eq!(rngs.child("a").gen::<[u64; 4]>(), expected);
+        assert_ne!(rngs.child("b").gen::<[u64; 4]>(), expected);
+        assert_ne!(RngSplitter::new(43).child("a").gen::<[u64; 4]>(), expected);
+    }
+
+    #[test]
+    fn adding_a_site_doesnt_change_output() {
+        let config = Rc::new(GenerationConfig::default());
+
+        for seed in 0..20 {
+            let expected =
+                Generator::new(&mut GenRng::seed_from_u64(seed), config.clone()).gen_module();
+
+            // Stands in for a new part of the module being generated between two existing sites
+            let mut gen = Generator::new(&mut GenRng::seed_from_u64(seed), config.clone());
+            gen.extra_site = Some("dummy");
+            let module = gen.gen_module();
+
+            assert_eq!(module.structs, expected.structs, "seed {seed}");
+            assert_eq!(module.vars, expected.vars, "seed {seed}");
+            assert_eq!(module.functions, expected.functions, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn gen_rng_is_pinned() {
+        let values = GenRng::seed_from_u64(0).gen::<[u64; 2]>();
+        assert_eq!(values, [0xbb2a3fb2cd2c6f7f, 0xc6017c948e27697b]);
+    }
+
+    /// Checks that a fixed seed still generates the same shader. If the generator was changed on
+    /// purpose, check the differences with `wgslsmith diff-versions` and regenerate the golden.
+    #[test]
+    fn golden_output() {
+        let options = Options::parse_from([
+            "generator",
+            "--fn-min-stmts",
+            "1",
+            "--fn-max-stmts",
+            "2",
+            "--block-max-stmts",
+            "2",
+            "--max-fns",
+            "1",
+            "--max-structs",
+            "1",
+            "--max-struct-members",
+            "2",
+            "--max-block-depth",
+            "1",
+        ]);
+
+        // The writer ends the module with a blank line, which isn't kept in the golden file
+        let shader = crate::gen_to_string(options, 1).unwrap();
+        let shader = format!("{}\n", shader.trim_end());
+        assert_eq!(shader, include_str!("snapshots/golden_seed_1.wgsl"));
+    }
+}
@@ -1,7 +1,7 @@
 use std::iter;
 
 use ast::types::{DataType, MemoryViewType};
-use rand::prelude::IteratorRandom;
+use ast::StorageClass;
 use rand::Rng;
 use rpds::{HashTrieMap, Vector};
 
@@ -10,64 +10,144 @@ use super::utils;
 #[derive(Clone, Debug)]
 pub struct Scope {
     next_name: u32,
+    /// All variables in scope, in the order they were declared.
+    vars: Vector<Var>,
     symbols: HashTrieMap<DataType, Vec<(String, DataType)>>,
-    mutables: Vector<(String, DataType)>,
-    references: Vector<(String, MemoryViewType)>,
+    /// Number of variables in `vars` that are mutable.
+    mutables: usize,
+    /// Number of variables in `vars` that a pointer can be taken to.
+    references: usize,
 }
 
 impl Scope {
     pub fn empty() -> Scope {
         Scope {
             next_name: 0,
+            vars: Vector::new(),
             symbols: HashTrieMap::new(),
-            mutables: Vector::new(),
-            references: Vector::new(),
+            mutables: 0,
+            references: 0,
         }
     }
 
     pub fn has_mutables(&self) -> bool {
-        !self.mutables.is_empty()
+        self.mutables > 0
     }
 
     pub fn has_references(&self) -> bool {
-        !self.references.is_empty()
+        self.references > 0
+    }
+
+    /// Returns the number of variables in scope.
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.vars.iter().any(|var| var.name == name)
+    }
+
+    pub fn idents(&self) -> impl Iterator<Item = &String> {
+        self.vars.iter().map(|var| &var.name)
     }
 
     pub fn of_type(&self, ty: &DataType) -> &[(String, DataType)] {
         self.symbols.get(ty).map(Vec::as_slice).unwrap_or(&[])
     }
 
-    pub fn choose_mutable(&self, rng: &mut impl Rng) -> (&String, &DataType) {
-        self.mutables
+    /// Returns the names of variables whose type is exactly `ty`.
+    ///
+    /// Unlike [`Scope::of_type`], this doesn't include variables that only contain `ty` (e.g. a
+    /// struct with a member of that type).
+    pub fn vars_of_type<'a>(&'a self, ty: &'a DataType) -> impl Iterator<Item = &'a String> {
+        self.of_type(ty)
             .iter()
-            .choose(rng)
-            .map(|(n, t)| (n, t))
+            .filter(move |(_, var_type)| var_type == ty)
+            .map(|(name, _)| name)
+    }
+
+    /// Chooses a variable uniformly at random from those which are (or contain) `ty`, or returns
+    /// `None` if there aren't any.
+    ///
+    /// This uses reservoir sampling, so it only takes a single pass over the matching variables
+    /// and doesn't collect them.
+    pub fn choose_matching(
+        &self,
+        ty: &DataType,
+        rng: &mut impl Rng,
+    ) -> Option<(&String, &DataType)> {
+        choose_uniform(self.of_type(ty).iter(), rng).map(|(name, data_type)| (name, data_type))
+    }
+
+    pub fn choose_mutable(&self, rng: &mut impl Rng) -> (&String, &DataType) {
+        self.choose_var(|var| var.mutable, rng)
+            .map(|var| (&var.name, &var.data_type))
             .unwrap()
     }
 
+    /// Chooses a variable that a pointer can be taken to.
     pub fn choose_reference(&self, rng: &mut impl Rng) -> (&String, &MemoryViewType) {
-        self.references
-            .iter()
-            .choose(rng)
-            .map(|(n, t)| (n, t))
-            .unwrap()
+        match self.choose_var(is_reference, rng) {
+            Some(Var {
+                name,
+                data_type: DataType::Ref(mem_view),
+                ..
+            }) => (name, mem_view),
+            _ => panic!("no references in scope"),
+        }
     }
 
     pub fn insert_readonly(&mut self, name: String, data_type: DataType) {
-        self.insert_symbol(&name, &data_type);
+        self.insert_symbol(name, data_type, false);
     }
 
     pub fn insert_mutable(&mut self, name: String, data_type: DataType) {
-        self.insert_symbol(&name, &data_type);
-        if let DataType::Ref(mem_view) = &data_type {
-            self.references
-                .push_back_mut((name.clone(), mem_view.clone()));
+        self.insert_symbol(name, data_type, true);
+    }
+
+    /// Removes a variable from the scope, so that it can be shadowed by a new declaration.
+    pub fn remove(&mut self, name: &str) {
+        let var = match self.vars.iter().find(|var| var.name == name) {
+            Some(var) => var.clone(),
+            None => return,
+        };
+
+        self.vars = self
+            .vars
+            .iter()
+            .filter(|var| var.name != name)
+            .cloned()
+            .collect();
+
+        for key in symbol_keys(&var.data_type) {
+            if let Some(symbols) = self.symbols.get_mut(&key) {
+                symbols.retain(|(ident, _)| ident != name);
+                if symbols.is_empty() {
+                    self.symbols.remove_mut(&key);
+                }
+            }
         }
-        self.mutables.push_back_mut((name, data_type));
+
+        if var.mutable {
+            self.mutables -= 1;
+        }
+
+        if is_reference(&var) {
+            self.references -= 1;
+        }
+    }
+
+    /// Chooses a variable uniformly at random from those that satisfy `predicate`.
+    fn choose_var(
+        &self,
+        mut predicate: impl FnMut(&Var) -> bool,
+        rng: &mut impl Rng,
+    ) -> Option<&Var> {
+        choose_uniform(self.vars.iter().filter(|var| predicate(var)), rng)
     }
 
-    fn insert_symbol(&mut self, name: &str, ty: &DataType) {
-        for key in iter::once(ty.clone()).chain(utils::accessible_types_of(ty)) {
+    fn insert_symbol(&mut self, name: String, ty: DataType, mutable: bool) {
+        for key in symbol_keys(&ty) {
             let symbols = if let Some(symbols) = self.symbols.get_mut(&key) {
                 symbols
             } else {
@@ -75,8 +155,24 @@ impl Scope {
                 self.symbols.get_mut(&key).unwrap()
             };
 
-            symbols.push((name.to_owned(), ty.clone()));
+            symbols.push((name.clone(), ty.clone()));
+        }
+
+        let var = Var {
+            name,
+            data_type: ty,
+            mutable,
+        };
+
+        if var.mutable {
+            self.mutables += 1;
+        }
+
+        if is_reference(&var) {
+            self.references += 1;
         }
+
+        self.vars.push_back_mut(var);
     }
 
     pub fn next_name(&mut self) -> String {
@@ -85,3 +181,161 @@ impl Scope {
         format!("var_{}", next)
     }
 }
+
+/// Returns the types that a variable of type `ty` is listed under in the symbol table: its own
+/// type and every type accessible from it.
+fn symbol_keys(ty: &DataType) -> impl Iterator<Item = DataType> {
+    iter::once(ty.clone()).chain(utils::accessible_types_of(ty))
+}
+
+/// Chooses an item uniformly at random from `items` in a single pass, using reservoir sampling.
+fn choose_uniform<T>(items: impl Iterator<Item = T>, rng: &mut impl Rng) -> Option<T> {
+    let mut chosen = None;
+
+    for (i, item) in items.enumerate() {
+        // Replacing the choice with probability 1/(i+1) keeps every item seen so far equally
+        // likely to be chosen
+        if rng.gen_range(0..=i) == 0 {
+            chosen = Some(item);
+        }
+    }
+
+    chosen
+}
+
+#[derive(Clone, Debug)]
+struct Var {
+    name: String,
+    data_type: DataType,
+    mutable: bool,
+}
+
+/// Only function-local variables are used as pointer targets for now, since pointers to other
+/// address spaces have more restrictions on where they can be passed.
+fn is_reference(var: &Var) -> bool {
+    var.mutable
+        && matches!(
+            &var.data_type,
+            DataType::Ref(mem_view) if mem_view.storage_class == StorageClass::Function
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::types::{DataType, MemoryViewType};
+    use ast::{ScalarType, StorageClass};
+    use rand::SeedableRng;
+
+    use super::Scope;
+    use crate::gen::GenRng;
+
+    #[test]
+    fn choose_matching_is_uniform() {
+        let mut scope = Scope::empty();
+
+        // Vectors contain the scalar type through their components, so they match too
+        for i in 0..10 {
+            if i % 2 == 0 {
+                scope.insert_readonly(format!("a{i}"), ScalarType::I32.into());
+            } else {
+                scope.insert_readonly(format!("a{i}"), DataType::Vector(2, ScalarType::I32));
+            }
+        }
+
+        scope.insert_readonly("b".to_owned(), ScalarType::U32.into());
+
+        let i32_type = DataType::from(ScalarType::I32);
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut counts = [0; 10];
+        let samples = 50_000;
+
+        for _ in 0..samples {
+            let (name, _) = scope.choose_matching(&i32_type, &mut rng).unwrap();
+            counts[name[1..].parse::<usize>().unwrap()] += 1;
+        }
+
+        // Each variable is expected 5000 times, with a standard deviation of about 67, so this
+        // only fails if the choice is biased
+        for (i, count) in counts.into_iter().enumerate() {
+            assert!((4700..=5300).contains(&count), "a{i} chosen {count} times");
+        }
+
+        assert!(scope
+            .choose_matching(&ScalarType::F32.into(), &mut rng)
+            .is_none());
+    }
+
+    #[test]
+    fn choose_mutable_is_uniform() {
+        let mut scope = Scope::empty();
+
+        for i in 0..10 {
+            let ty = DataType::from(ScalarType::I32);
+            if i % 2 == 0 {
+                scope.insert_readonly(format!("a{i}"), ty);
+            } else {
+                let mem_view = MemoryViewType::new(ty, StorageClass::Function);
+                scope.insert_mutable(format!("a{i}"), DataType::Ref(mem_view));
+            }
+        }
+
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut counts = [0; 10];
+        let samples = 50_000;
+
+        for _ in 0..samples {
+            let (name, _) = scope.choose_mutable(&mut rng);
+            counts[name[1..].parse::<usize>().unwrap()] += 1;
+        }
+
+        // Each of the 5 mutable variables is expected 10000 times, with a standard deviation of
+        // about 90, so this only fails if the choice is biased
+        for (i, count) in counts.into_iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(count, 0, "a{i} isn't mutable");
+            } else {
+                assert!((9500..=10500).contains(&count), "a{i} chosen {count} times");
+            }
+        }
+    }
+
+    #[test]
+    fn vars_of_type_is_exact() {
+        let mut scope = Scope::empty();
+
+        scope.insert_readonly("a".to_owned(), ScalarType::I32.into());
+        scope.insert_readonly("b".to_owned(), DataType::Vector(2, ScalarType::I32));
+        scope.insert_readonly("c".to_owned(), ScalarType::I32.into());
+
+        let i32_type = DataType::from(ScalarType::I32);
+        assert_eq!(scope.of_type(&i32_type).len(), 3);
+        assert_eq!(
+            scope.vars_of_type(&i32_type).collect::<Vec<_>>(),
+            ["a", "c"]
+        );
+
+        scope.remove("a");
+        assert_eq!(scope.vars_of_type(&i32_type).collect::<Vec<_>>(), ["c"]);
+    }
+
+    #[test]
+    fn remove_updates_lookups() {
+        let mut scope = Scope::empty();
+
+        let i32_type = DataType::from(ScalarType::I32);
+        let mem_view = MemoryViewType::new(i32_type.clone(), StorageClass::Function);
+        scope.insert_mutable("a".to_owned(), DataType::Ref(mem_view));
+        scope.insert_readonly("b".to_owned(), DataType::Vector(2, ScalarType::I32));
+        assert!(scope.has_mutables());
+        assert!(scope.has_references());
+
+        scope.remove("a");
+        assert!(!scope.has_mutables());
+        assert!(!scope.has_references());
+        assert!(!scope.contains("a"));
+
+        scope.remove("b");
+        assert!(scope.of_type(&i32_type).is_empty());
+        assert_eq!(scope.len(), 0);
+    }
+}
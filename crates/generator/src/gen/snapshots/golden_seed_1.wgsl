@@ -0,0 +1,60 @@
+// {"0:0":[74,37,218,90,211,23,84,226]}
+// Seed: 1
+
+struct Struct_1 {
+    a: vec2<f32>,
+}
+
+struct UniformBuffer {
+    a: u32,
+    b: i32,
+}
+
+struct StorageBuffer {
+    a: u32,
+    b: vec4<i32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> u_input: UniformBuffer;
+
+@group(0)
+@binding(1)
+var<storage, read_write> s_output: StorageBuffer;
+
+var<private> global0: array<Struct_1, 13>;
+
+fn func_2(arg_0: vec2<u32>, arg_1: Struct_1, arg_2: Struct_1) -> f32 {
+    global0 = array<Struct_1, 13>();
+    return arg_1.a.x;
+}
+
+fn func_3(arg_0: vec4<bool>, arg_1: i32, arg_2: u32, arg_3: Struct_1) -> vec3<bool> {
+    global0 = array<Struct_1, 13>();
+    global0 = array<Struct_1, 13>();
+    return vec3<bool>(any(!arg_0.zzz), !true, arg_0.x);
+}
+
+fn func_1() -> vec3<bool> {
+    var var_0: f32;
+    return func_3(select(vec4<bool>(!(!false), true, func_2(vec2<u32>(0u, u_input.a), global0[0u], Struct_1(vec2<f32>(774.0, 597.0))) != (-216.0 - -522.0), ~(-1) < countOneBits(25841)), vec4<bool>(!all(vec2<bool>(false, true)), true, select(!false, any(vec2<bool>(true, true)), !true), !(false & false)), vec4<bool>((u_input.b != u_input.b) & select(false, true, true), (3168 / -1) <= max(u_input.b, u_input.b), -27360 >= -2147483648, all(!vec2<bool>(false, false)))), u_input.b, u_input.a, Struct_1(floor(vec2<f32>(exp2(644.0), -387.0))));
+}
+
+fn func_4(arg_0: vec3<bool>, arg_1: vec3<bool>, arg_2: bool, arg_3: vec4<f32>) -> f32 {
+    var var_0: vec4<f32> = exp2(-(vec4<f32>(trunc(arg_3.x), select(arg_3.x, arg_3.x, true), arg_3.x + arg_3.x, arg_3.x) * (vec4<f32>(arg_3.x, 1112.0, arg_3.x, 230.0) - round(vec4<f32>(arg_3.x, 969.0, -692.0, 1579.0)))));
+    return arg_3.x;
+}
+
+@compute
+@workgroup_size(1)
+fn main() {
+    let var_0 = Struct_1(vec2<f32>(-(-627.0), func_4(select(!vec3<bool>(false, true, false), func_1(), all(vec4<bool>(false, true, false, true))), !func_1(), !false, vec4<f32>(-1000.0, sign(487.0), -(-336.0), 366.0))));
+    var var_1: f32;
+    let var_2: vec4<bool> = !select(vec4<bool>(all(select(vec4<bool>(false, false, true, false), vec4<bool>(true, false, false, false), vec4<bool>(false, false, false, true))), !(!true), func_1().x, func_3(vec4<bool>(false, true, false, true), abs(58748), 4294967295u, Struct_1(var_0.a)).x), select(select(select(vec4<bool>(false, true, false, false), vec4<bool>(false, true, false, true), vec4<bool>(false, false, true, true)), !vec4<bool>(true, true, true, true), false), select(select(vec4<bool>(false, true, true, true), vec4<bool>(false, false, false, true), true), !vec4<bool>(false, false, true, true), !true), select(vec4<bool>(false, false, true, false), !vec4<bool>(false, true, true, false), true | false)), select(!select(vec4<bool>(false, true, false, true), vec4<bool>(false, true, true, false), true), select(select(vec4<bool>(false, true, false, true), vec4<bool>(true, true, true, true), false), !vec4<bool>(true, false, false, false), !vec4<bool>(true, false, true, true)), !vec4<bool>(true, false, false, false)));
+    var_1 = (round(-1264.0) + (-928.0 * 365.0)) * -137.0;
+    var var_3: Struct_1 = global0[firstLeadingBit(~u_input.a)];
+    let var_4: Struct_1 = Struct_1(vec2<f32>(abs(-var_3.a.x), sign(var_0.a.x)));
+    let x = u_input.a;
+    s_output = StorageBuffer(0u % u_input.a, ~(abs(abs(vec4<i32>(1, u_input.b, u_input.b, u_input.b))) - -vec4<i32>(-17327, 1, 42997, u_input.b)));
+}
@@ -4,38 +4,110 @@ use std::mem;
 use ast::types::{DataType, MemoryViewType, ScalarType};
 use ast::{
     AssignmentLhs, AssignmentOp, AssignmentStatement, BinOp, BinOpExpr, Expr, ExprNode,
-    ForLoopHeader, ForLoopInit, ForLoopStatement, ForLoopUpdate, IfStatement, LetDeclStatement,
-    LhsExprNode, Lit, LoopStatement, ReturnStatement, Statement, StorageClass, SwitchCase,
-    SwitchStatement, UnOp, UnOpExpr, VarDeclStatement, VarExpr,
+    FnCallStatement, ForLoopHeader, ForLoopInit, ForLoopStatement, ForLoopUpdate, IfStatement,
+    LetDeclStatement, LhsExpr, LhsExprNode, Lit, LoopStatement, Postfix, ReturnStatement,
+    Statement, StorageClass, SwitchCase, SwitchStatement, UnOp, UnOpExpr, VarDeclStatement,
+    VarExpr, WhileStatement,
 };
 use rand::prelude::SliceRandom;
 use rand::Rng;
 
 use super::scope::Scope;
-use super::utils::is_terminal_stmt;
+use super::utils::{is_terminal_stmt, is_uniform_expr};
+
+/// Maximum number of iterations allowed by the counter in the condition of a `while` loop.
+const MAX_WHILE_ITERATIONS: u32 = 16;
 
 #[derive(Clone, Copy)]
 enum StatementType {
     LetDecl,
     VarDecl,
     Assignment,
-    // Compound,
+    Compound,
     If,
     Return,
     Loop,
     Switch,
     ForLoop,
+    While,
     Break,
     Continue,
+    Barrier,
+}
+
+impl StatementType {
+    /// Returns the name of this kind of statement, as accepted by `--stmt-weight`.
+    fn name(self) -> &'static str {
+        match self {
+            StatementType::LetDecl => "let",
+            StatementType::VarDecl => "var",
+            StatementType::Assignment => "assignment",
+            StatementType::Compound => "compound",
+            StatementType::If => "if",
+            StatementType::Return => "return",
+            StatementType::Loop => "loop",
+            StatementType::Switch => "switch",
+            StatementType::ForLoop => "for",
+            StatementType::While => "while",
+            StatementType::Break => "break",
+            StatementType::Continue => "continue",
+            StatementType::Barrier => "barrier",
+        }
+    }
 }
 
-impl<'a> super::Generator<'a> {
+impl super::Generator {
+    /// Generates a statement, which the caller should append to `current_block`.
+    ///
+    /// If annotations are enabled, this first appends a comment naming the statement, after any
+    /// declarations that were inserted while generating it.
     pub fn gen_stmt(&mut self) -> Statement {
-        let mut allowed = vec![
-            StatementType::LetDecl,
-            StatementType::VarDecl,
-            StatementType::Return,
-        ];
+        let id = self.next_stmt_id;
+        self.next_stmt_id += 1;
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.enter();
+        }
+
+        if let Some(timings) = &mut self.timings {
+            timings.enter_stmt();
+        }
+
+        let (stmt_type, stmt) = self.gen_stmt_inner();
+
+        if let Some(timings) = &mut self.timings {
+            timings.exit();
+        }
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.exit_stmt(&stmt);
+        }
+
+        if self.config.annotate {
+            let text = format!("stmt {id}: {}", stmt_type.name());
+            self.current_block.push(Statement::Comment(text));
+        }
+
+        stmt
+    }
+
+    fn gen_stmt_inner(&mut self) -> (StatementType, Statement) {
+        let mut allowed = vec![];
+
+        let at_var_limit = match self.config.max_total_vars {
+            Some(max) => self.scope.len() >= max as usize,
+            None => false,
+        };
+
+        if !at_var_limit {
+            allowed.extend_from_slice(&[StatementType::LetDecl, StatementType::VarDecl]);
+        }
+
+        // Functions always end with a return statement, so there's no need for one here when
+        // generating straight-line code.
+        if !self.config.straight_line {
+            allowed.push(StatementType::Return);
+        }
 
         if self.fn_state.is_loop {
             allowed.push(StatementType::Break);
@@ -46,102 +118,286 @@ impl<'a> super::Generator<'a> {
             allowed.push(StatementType::Assignment);
         }
 
-        if self.fn_state.block_depth < self.options.max_block_depth {
-            allowed.extend_from_slice(&[
-                // StatementType::Compound,
-                StatementType::If,
-                StatementType::Loop,
-                StatementType::Switch,
-                StatementType::ForLoop,
-            ]);
+        if self.config.enable_barriers && self.fn_state.is_uniform {
+            allowed.push(StatementType::Barrier);
         }
 
+        if !self.config.straight_line && self.fn_state.block_depth < self.config.max_block_depth {
+            allowed.extend_from_slice(&[StatementType::If, StatementType::Loop]);
+
+            if self.fn_state.compound_depth < self.config.max_compound_depth {
+                allowed.push(StatementType::Compound);
+            }
+
+            if self.config.profile.switch {
+                allowed.push(StatementType::Switch);
+            }
+
+            if self.config.profile.for_loops {
+                allowed.push(StatementType::ForLoop);
+            }
+
+            // The loop counter is an extra variable
+            if self.config.profile.while_loops && !at_var_limit {
+                allowed.push(StatementType::While);
+            }
+        }
+
+        let stmt_weights = self.stmt_weights;
         let weights = |t: &StatementType| match t {
-            StatementType::LetDecl => 10,
-            StatementType::VarDecl => 10,
-            StatementType::Assignment => 10,
-            // StatementType::Compound => 1,
-            StatementType::If => 5,
-            StatementType::Return => 1,
-            StatementType::Loop => 5,
-            StatementType::Switch => 5,
-            StatementType::ForLoop => 5,
-            StatementType::Break => 5,
-            StatementType::Continue => 5,
+            StatementType::LetDecl => stmt_weights.let_decl,
+            StatementType::VarDecl => stmt_weights.var_decl,
+            StatementType::Assignment => stmt_weights.assignment,
+            StatementType::Compound => stmt_weights.compound,
+            StatementType::If => stmt_weights.if_,
+            StatementType::Return => stmt_weights.return_,
+            StatementType::Loop => stmt_weights.loop_,
+            StatementType::Switch => stmt_weights.switch,
+            StatementType::ForLoop => stmt_weights.for_loop,
+            StatementType::While => stmt_weights.while_,
+            StatementType::Break => stmt_weights.break_,
+            StatementType::Continue => stmt_weights.continue_,
+            StatementType::Barrier => stmt_weights.barrier,
         };
 
-        match allowed.choose_weighted(self.rng, weights).unwrap() {
+        // Declarations are the only statements that can be generated anywhere, so if nothing else
+        // is possible here we have to go over the variable limit
+        if allowed.iter().all(|t| weights(t) == 0) {
+            allowed.extend_from_slice(&[StatementType::LetDecl, StatementType::VarDecl]);
+        }
+
+        let stmt_type = *allowed.choose_weighted(&mut self.rng, weights).unwrap();
+        let stmt = match stmt_type {
             StatementType::LetDecl => self.gen_let_stmt(),
             StatementType::VarDecl => self.gen_var_stmt(),
             StatementType::Assignment => self.gen_assignment_stmt().into(),
-            // StatementType::Compound => self.gen_compound_stmt(),
+            StatementType::Compound => self.gen_compound_stmt(),
             StatementType::If => self.gen_if_stmt(),
-            StatementType::Return => self.gen_return_stmt(),
+            StatementType::Return => {
+                self.mark_returned();
+                self.gen_return_stmt()
+            }
             StatementType::Loop => self.gen_loop_stmt(),
             StatementType::Switch => self.gen_switch_stmt(),
             StatementType::ForLoop => self.gen_for_stmt(),
-            StatementType::Break => Statement::Break,
-            StatementType::Continue => Statement::Continue,
+            StatementType::While => self.gen_while_stmt(),
+            StatementType::Break => {
+                self.mark_exited_loop();
+                Statement::Break
+            }
+            StatementType::Continue => {
+                self.mark_exited_loop();
+                Statement::Continue
+            }
+            StatementType::Barrier => self.gen_barrier_stmt(),
+        };
+
+        (stmt_type, stmt)
+    }
+
+    /// Records that some invocations may be returning from the function early, if we are
+    /// currently in non-uniform control flow.
+    fn mark_returned(&mut self) {
+        if !self.fn_state.is_uniform {
+            self.fn_state.has_returned = true;
+        }
+    }
+
+    /// Records that some invocations may be leaving the current loop iteration early, if we are
+    /// currently in non-uniform control flow.
+    fn mark_exited_loop(&mut self) {
+        if !self.fn_state.is_uniform {
+            self.fn_state.has_exited_loop = true;
         }
     }
 
+    fn has_diverged(&self) -> bool {
+        self.fn_state.has_returned || self.fn_state.has_exited_loop
+    }
+
+    /// Runs `f` with control flow marked as non-uniform if `is_uniform` is false.
+    ///
+    /// If any invocations may have diverged while running `f`, control flow will remain
+    /// non-uniform afterwards.
+    fn with_uniformity<T>(&mut self, is_uniform: bool, f: impl FnOnce(&mut Self) -> T) -> T {
+        let prev = self.fn_state.is_uniform;
+
+        self.fn_state.is_uniform = prev && is_uniform;
+
+        let res = f(self);
+
+        self.fn_state.is_uniform = prev && !self.has_diverged();
+
+        res
+    }
+
+    /// Runs `f` to generate the body of a loop.
+    ///
+    /// Loop bodies are always treated as non-uniform, since the number of iterations may depend on
+    /// non-uniform values (including loop counters inserted by the reconditioner). A `break` or
+    /// `continue` in the body only makes the rest of the enclosing block non-uniform, so control
+    /// flow can become uniform again once the construct containing the loop ends.
+    ///
+    /// A loop whose `condition` is non-uniform may also be left early by some invocations.
+    fn with_loop_body<T>(
+        &mut self,
+        condition: Option<&ExprNode>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let exits_early = condition.is_some_and(|it| !is_uniform_expr(it));
+        let is_loop = mem::replace(&mut self.fn_state.is_loop, true);
+        let has_exited_loop = mem::replace(&mut self.fn_state.has_exited_loop, exits_early);
+
+        let res = self.with_uniformity(false, f);
+
+        self.fn_state.has_exited_loop = has_exited_loop;
+        self.fn_state.is_loop = is_loop;
+
+        res
+    }
+
+    fn gen_barrier_stmt(&mut self) -> Statement {
+        let ident = *["workgroupBarrier", "storageBarrier"]
+            .choose(&mut self.rng)
+            .unwrap();
+
+        FnCallStatement::new(ident.to_owned(), vec![]).into()
+    }
+
     fn gen_let_stmt(&mut self) -> Statement {
-        if self.options.enable_pointers && self.scope.has_mutables() && self.rng.gen_bool(0.2) {
-            let (ident, ty) = self.scope.choose_mutable(self.rng);
-            let initializer =
-                UnOpExpr::new(UnOp::AddressOf, VarExpr::new(ident).into_node(ty.clone()));
-            LetDeclStatement::new(self.scope.next_name(), initializer).into()
+        let name = self.gen_decl_name();
+        let stmt =
+            if self.config.enable_pointers && self.scope.has_references() && self.rng.gen_bool(0.2)
+            {
+                let (ident, mem_view) = self.scope.choose_reference(&mut self.rng);
+                let var_expr = VarExpr::new(ident).into_node(DataType::Ref(mem_view.clone()));
+                let initializer = UnOpExpr::new(UnOp::AddressOf, var_expr);
+                LetDeclStatement::new(name, initializer)
+            } else {
+                let ty = self.cx.types.select(&mut self.rng);
+                LetDeclStatement::new(name, self.gen_expr(&ty))
+            };
+
+        // Explicit type annotations go through a different path in compilers than inference
+        if self.rng.gen_bool(0.3) {
+            let data_type = stmt.inferred_type().clone();
+            stmt.with_type(data_type).into()
         } else {
-            let ty = self.cx.types.select(self.rng);
-            LetDeclStatement::new(self.scope.next_name(), self.gen_expr(&ty)).into()
+            stmt.into()
         }
     }
 
     fn gen_var_stmt(&mut self) -> Statement {
-        let ty = self.cx.types.select(self.rng);
-        VarDeclStatement::new(self.scope.next_name(), None, Some(self.gen_expr(&ty))).into()
+        let name = self.gen_decl_name();
+        let ty = self.cx.types.select(&mut self.rng);
+
+        // Variables without an initializer are zero-initialized, which is worth testing in itself
+        if self.rng.gen_bool(0.1) {
+            return VarDeclStatement::new(name, Some(ty), None).into();
+        }
+
+        let initializer = self.gen_expr(&ty);
+        let data_type = self.rng.gen_bool(0.3).then_some(ty);
+        VarDeclStatement::new(name, data_type, Some(initializer)).into()
+    }
+
+    /// Returns the name for a new variable declaration.
+    ///
+    /// Inside a compound block, this will sometimes reuse the name of a variable from an enclosing
+    /// scope to shadow it. The shadowed variable is removed from the scope first, so that it isn't
+    /// referenced by the initializer.
+    fn gen_decl_name(&mut self) -> String {
+        if !self.fn_state.shadowable.is_empty() && self.rng.gen_bool(0.5) {
+            let index = self.rng.gen_range(0..self.fn_state.shadowable.len());
+            // Each name can only be declared once in the same block
+            let name = self.fn_state.shadowable.swap_remove(index);
+            self.scope.remove(&name);
+            name
+        } else {
+            self.scope.next_name()
+        }
     }
 
     fn gen_assignment_stmt(&mut self) -> AssignmentStatement {
-        let (name, data_type) = self.scope.choose_mutable(self.rng);
-
-        let data_type = data_type.clone();
-        let lhs = match &data_type {
-            DataType::Vector(n, ty) if self.rng.gen_bool(0.7) => {
-                let accessor =
-                    super::utils::gen_vector_accessor(self.rng, *n, &DataType::Scalar(*ty));
-                LhsExprNode::member(name.clone(), data_type, accessor)
-            }
-            DataType::Array(_, _) => LhsExprNode::array_index(
-                name.clone(),
-                data_type,
-                self.gen_expr(&ScalarType::U32.into()),
-            ),
-            _ => LhsExprNode::name(name.clone(), data_type),
-        };
+        let (name, data_type) = self.scope.choose_mutable(&mut self.rng);
 
+        let lhs = LhsExprNode::name(name.clone(), data_type.clone());
+        let lhs = self.gen_lhs_postfix_chain(lhs);
         let rhs = self.gen_expr(lhs.data_type.dereference());
 
         AssignmentStatement::new(lhs.into(), AssignmentOp::Simple, rhs)
     }
 
-    // fn gen_compound_stmt(&mut self) -> Statement {
-    //     let max_count = self
-    //         .rng
-    //         .gen_range(self.options.block_min_stmts..=self.options.block_max_stmts);
-    //     Statement::Compound(self.gen_stmt_block(max_count).1)
-    // }
+    /// Randomly extends `lhs` with a chain of member accesses and array indices (e.g. `a.b[i].x`).
+    ///
+    /// Each step produces a reference into the root variable, so the result is still assignable.
+    /// Array indices are arbitrary expressions, which are clamped by the reconditioner.
+    fn gen_lhs_postfix_chain(&mut self, mut lhs: LhsExprNode) -> LhsExprNode {
+        while self.rng.gen_bool(0.6) {
+            let mem_view = match &lhs.data_type {
+                DataType::Ref(mem_view) => mem_view.clone(),
+                _ => break,
+            };
+
+            let (postfix, data_type) = match mem_view.inner.as_ref() {
+                DataType::Vector(n, ty) => {
+                    let target = DataType::Scalar(*ty);
+                    let accessor = super::utils::gen_vector_accessor(&mut self.rng, *n, &target);
+                    (Postfix::member(accessor), target)
+                }
+                // Runtime-sized arrays can't be indexed safely yet
+                DataType::Array(ty, Some(_)) => {
+                    let index = self.gen_expr(&ScalarType::U32.into());
+                    (Postfix::index(index), ty.as_ref().clone())
+                }
+                DataType::Struct(decl) => {
+                    let member = decl.members.choose(&mut self.rng).unwrap();
+                    (Postfix::member(&member.name), member.data_type.clone())
+                }
+                _ => break,
+            };
+
+            lhs = LhsExprNode {
+                data_type: DataType::Ref(mem_view.clone_with_type(data_type)),
+                expr: LhsExpr::Postfix(Box::new(lhs), postfix),
+            };
+        }
+
+        lhs
+    }
+
+    fn gen_compound_stmt(&mut self) -> Statement {
+        let max_count = self
+            .rng
+            .gen_range(self.config.block_min_stmts..=self.config.block_max_stmts);
+
+        // Any local variable that is visible here may be shadowed inside the block. Globals are
+        // left alone since some of them (e.g. `u_input`) are referred to by name.
+        let shadowable = self
+            .scope
+            .idents()
+            .filter(|it| !self.global_scope.contains(it))
+            .cloned()
+            .collect();
+
+        self.fn_state.compound_depth += 1;
+        let (_, block) = self.gen_stmt_block_with_shadowing(max_count, shadowable);
+        self.fn_state.compound_depth -= 1;
+
+        Statement::Compound(block)
+    }
 
     fn gen_if_stmt(&mut self) -> Statement {
         let max_count = self
             .rng
-            .gen_range(self.options.block_min_stmts..=self.options.block_max_stmts);
+            .gen_range(self.config.block_min_stmts..=self.config.block_max_stmts);
 
-        IfStatement::new(
-            self.gen_expr(&DataType::Scalar(ScalarType::Bool)),
-            self.gen_stmt_block(max_count).1,
-        )
-        .into()
+        let condition = self.gen_expr(&DataType::Scalar(ScalarType::Bool));
+        let body = self.with_uniformity(is_uniform_expr(&condition), |this| {
+            this.gen_stmt_block(max_count).1
+        });
+
+        IfStatement::new(condition, body).into()
     }
 
     fn gen_return_stmt(&mut self) -> Statement {
@@ -157,24 +413,23 @@ impl<'a> super::Generator<'a> {
     fn gen_loop_stmt(&mut self) -> Statement {
         let max_count = self
             .rng
-            .gen_range(self.options.block_min_stmts..=self.options.block_max_stmts);
+            .gen_range(self.config.block_min_stmts..=self.config.block_max_stmts);
 
-        let is_loop = mem::replace(&mut self.fn_state.is_loop, true);
-        let body = self.gen_stmt_block(max_count).1;
-        self.fn_state.is_loop = is_loop;
+        let body = self.with_loop_body(None, |this| this.gen_stmt_block(max_count).1);
 
         LoopStatement::new(body).into()
     }
 
     fn gen_switch_stmt(&mut self) -> Statement {
         let selector = self.gen_expr(&DataType::Scalar(ScalarType::I32));
+        let is_uniform = is_uniform_expr(&selector);
         let case_count: u32 = self.rng.gen_range(0..=4);
         let mut existing_cases = HashSet::new();
         let cases = (0..case_count)
             .map(|_| {
                 let block_size = self
                     .rng
-                    .gen_range(self.options.block_min_stmts..=self.options.block_max_stmts);
+                    .gen_range(self.config.block_min_stmts..=self.config.block_max_stmts);
 
                 let value = loop {
                     let value = self.gen_i32();
@@ -184,7 +439,8 @@ impl<'a> super::Generator<'a> {
                     }
                 };
 
-                let body = self.gen_stmt_block(block_size).1;
+                let body =
+                    self.with_uniformity(is_uniform, |this| this.gen_stmt_block(block_size).1);
 
                 // Fallthrough is broken on naga's HLSL backend: https://github.com/gfx-rs/naga/issues/1972
                 // if self.rng.gen_bool(0.2) && !is_terminal_stmt(body.last()) {
@@ -203,9 +459,12 @@ impl<'a> super::Generator<'a> {
 
         let default_block_size = self
             .rng
-            .gen_range(self.options.block_min_stmts..=self.options.block_max_stmts);
+            .gen_range(self.config.block_min_stmts..=self.config.block_max_stmts);
+
+        let default =
+            self.with_uniformity(is_uniform, |this| this.gen_stmt_block(default_block_size).1);
 
-        SwitchStatement::new(selector, cases, self.gen_stmt_block(default_block_size).1).into()
+        SwitchStatement::new(selector, cases, default).into()
     }
 
     fn gen_for_stmt(&mut self) -> Statement {
@@ -258,7 +517,7 @@ impl<'a> super::Generator<'a> {
                     2..=5 => Some(this.gen_expr(&DataType::Scalar(ScalarType::Bool))),
                     6..=9 => Some(
                         BinOpExpr::new(
-                            *COMPARISON_OPS.choose(this.rng).unwrap(),
+                            *COMPARISON_OPS.choose(&mut this.rng).unwrap(),
                             VarExpr::new(loop_var.clone()).into_node(loop_var_type.clone()),
                             Lit::I32(this.gen_i32()),
                         )
@@ -299,7 +558,10 @@ impl<'a> super::Generator<'a> {
 
             let body_size = this
                 .rng
-                .gen_range(this.options.block_min_stmts..=this.options.block_max_stmts);
+                .gen_range(this.config.block_min_stmts..=this.config.block_max_stmts);
+
+            let body =
+                this.with_loop_body(condition.as_ref(), |this| this.gen_stmt_block(body_size).1);
 
             let header = ForLoopHeader {
                 init,
@@ -307,20 +569,71 @@ impl<'a> super::Generator<'a> {
                 update,
             };
 
-            let is_loop = mem::replace(&mut this.fn_state.is_loop, true);
-            let body = this.gen_stmt_block(body_size).1;
-            this.fn_state.is_loop = is_loop;
-
             ForLoopStatement::new(header, body)
         });
 
         stmt.into()
     }
 
+    /// Generates a `while` loop whose condition also checks a counter, so that the loop is
+    /// guaranteed to terminate even without reconditioning.
+    ///
+    /// The counter is declared before the loop and incremented at the start of the body, before
+    /// anything that could `continue`. It is only added to the scope as a read-only variable, so
+    /// nothing else can modify it.
+    fn gen_while_stmt(&mut self) -> Statement {
+        let counter = self.scope.next_name();
+        let counter_type = DataType::Scalar(ScalarType::U32);
+
+        self.current_block
+            .push(VarDeclStatement::new(counter.clone(), None, Some(Lit::U32(0).into())).into());
+        self.scope
+            .insert_readonly(counter.clone(), counter_type.clone());
+
+        let limit = self.rng.gen_range(1..=MAX_WHILE_ITERATIONS);
+        let condition: ExprNode = BinOpExpr::new(
+            BinOp::LogAnd,
+            BinOpExpr::new(
+                BinOp::Less,
+                VarExpr::new(counter.clone()).into_node(counter_type.clone()),
+                Lit::U32(limit),
+            ),
+            self.gen_expr(&DataType::Scalar(ScalarType::Bool)),
+        )
+        .into();
+
+        let body_size = self
+            .rng
+            .gen_range(self.config.block_min_stmts..=self.config.block_max_stmts);
+
+        let mut body =
+            self.with_loop_body(Some(&condition), |this| this.gen_stmt_block(body_size).1);
+
+        let lhs = AssignmentLhs::name(counter, counter_type);
+        body.insert(
+            0,
+            AssignmentStatement::new(lhs, AssignmentOp::Plus, Lit::U32(1)).into(),
+        );
+
+        WhileStatement::new(condition, body).into()
+    }
+
     pub fn gen_stmt_block(&mut self, max_count: u32) -> (Scope, Vec<Statement>) {
+        self.gen_stmt_block_with_shadowing(max_count, vec![])
+    }
+
+    /// Like [`gen_stmt_block`](Self::gen_stmt_block), but declarations directly inside the block
+    /// may reuse the names in `shadowable`.
+    fn gen_stmt_block_with_shadowing(
+        &mut self,
+        max_count: u32,
+        shadowable: Vec<String>,
+    ) -> (Scope, Vec<Statement>) {
         self.with_scope(self.scope.clone(), |this| {
             this.fn_state.block_depth += 1;
 
+            let prev_shadowable = std::mem::replace(&mut this.fn_state.shadowable, shadowable);
+
             let prev_block = std::mem::take(&mut this.current_block);
 
             for _ in 0..max_count {
@@ -345,6 +658,7 @@ impl<'a> super::Generator<'a> {
             }
 
             this.fn_state.block_depth -= 1;
+            this.fn_state.shadowable = prev_shadowable;
 
             std::mem::replace(&mut this.current_block, prev_block)
         })
@@ -361,12 +675,741 @@ impl<'a> super::Generator<'a> {
 
         if let Some(return_type) = return_type {
             if !matches!(block.last(), Some(Statement::Return(_))) {
+                // Generating the return value may introduce new variable declarations (e.g. for
+                // pointer arguments), so these need to be inserted into the function body.
+                let prev_block = std::mem::replace(&mut self.current_block, block);
+
                 self.with_scope(scope, |this| {
-                    block.push(ReturnStatement::new(this.gen_expr(&return_type)).into())
+                    let value = this.gen_expr(&return_type);
+                    this.current_block.push(ReturnStatement::new(value).into());
                 });
+
+                block = std::mem::replace(&mut self.current_block, prev_block);
             }
         }
 
         block
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    use ast::types::DataType;
+    use ast::writer::Writer;
+    use ast::{
+        AssignmentLhs, AssignmentOp, AssignmentStatement, BinOp, BinOpExpr, Else, Expr, ExprNode,
+        FnAttr, ForLoopInit, LhsExpr, LhsExprNode, Module, Statement, WhileStatement,
+    };
+    use clap::Parser;
+    use rand::SeedableRng;
+
+    use crate::gen::utils::is_uniform_expr;
+    use crate::gen::{GenRng, Generator};
+    use crate::tests::{gen_modules, gen_modules_with};
+    use crate::Options;
+
+    /// Counts the declarations in `block` that shadow a variable from an enclosing block, checking
+    /// that no name is declared twice in the same block.
+    ///
+    /// `declared` contains names which are already declared in the scope of the block itself (e.g.
+    /// function parameters).
+    fn count_shadowing(
+        block: &[Statement],
+        declared: HashSet<String>,
+        scopes: &mut Vec<HashSet<String>>,
+    ) -> usize {
+        scopes.push(declared);
+
+        let mut count = 0;
+
+        for stmt in block {
+            let ident = match stmt {
+                Statement::LetDecl(stmt) => Some(&stmt.ident),
+                Statement::VarDecl(stmt) => Some(&stmt.ident),
+                _ => None,
+            };
+
+            if let Some(ident) = ident {
+                let (current, outer) = scopes.split_last_mut().unwrap();
+                assert!(current.insert(ident.clone()), "`{ident}` redeclared");
+                if outer.iter().any(|scope| scope.contains(ident)) {
+                    count += 1;
+                }
+            }
+
+            count += match stmt {
+                Statement::Compound(block) => count_shadowing(block, HashSet::new(), scopes),
+                Statement::If(stmt) => {
+                    let mut count = count_shadowing(&stmt.body, HashSet::new(), scopes);
+                    let mut else_ = stmt.else_.as_deref();
+                    while let Some(next) = else_ {
+                        match next {
+                            Else::If(stmt) => {
+                                count += count_shadowing(&stmt.body, HashSet::new(), scopes);
+                                else_ = stmt.else_.as_deref();
+                            }
+                            Else::Else(block) => {
+                                count += count_shadowing(block, HashSet::new(), scopes);
+                                else_ = None;
+                            }
+                        }
+                    }
+                    count
+                }
+                Statement::Loop(stmt) => count_shadowing(&stmt.body, HashSet::new(), scopes),
+                Statement::While(stmt) => count_shadowing(&stmt.body, HashSet::new(), scopes),
+                Statement::Switch(stmt) => {
+                    stmt.cases
+                        .iter()
+                        .map(|case| count_shadowing(&case.body, HashSet::new(), scopes))
+                        .sum::<usize>()
+                        + count_shadowing(&stmt.default, HashSet::new(), scopes)
+                }
+                Statement::ForLoop(stmt) => {
+                    let mut header = HashSet::new();
+                    if let Some(ForLoopInit::VarDecl(init)) = &stmt.header.init {
+                        header.insert(init.ident.clone());
+                    }
+                    scopes.push(header);
+                    let count = count_shadowing(&stmt.body, HashSet::new(), scopes);
+                    scopes.pop();
+                    count
+                }
+                _ => 0,
+            };
+        }
+
+        scopes.pop();
+
+        count
+    }
+
+    #[test]
+    fn compound_blocks_shadow_outer_variables() {
+        let mut shadowed = 0;
+
+        for module in gen_modules(&["generator", "--max-compound-depth", "3"], 0..20) {
+            // Inner declarations must not be visible once their block ends
+            ast::typecheck::typecheck(&module).unwrap();
+
+            for func in &module.functions {
+                let params = func.inputs.iter().map(|it| it.name.clone()).collect();
+                shadowed += count_shadowing(&func.body, params, &mut vec![]);
+            }
+        }
+
+        assert!(shadowed > 0);
+    }
+
+    fn collect_lhs<'a>(block: &'a [Statement], out: &mut Vec<&'a LhsExprNode>) {
+        for stmt in block {
+            match stmt {
+                Statement::Assignment(stmt) => {
+                    if let AssignmentLhs::Expr(lhs) = &stmt.lhs {
+                        out.push(lhs);
+                    }
+                }
+                Statement::Compound(block) => collect_lhs(block, out),
+                Statement::If(stmt) => collect_lhs(&stmt.body, out),
+                Statement::Loop(stmt) => collect_lhs(&stmt.body, out),
+                Statement::ForLoop(stmt) => collect_lhs(&stmt.body, out),
+                Statement::While(stmt) => collect_lhs(&stmt.body, out),
+                Statement::Switch(stmt) => {
+                    for case in &stmt.cases {
+                        collect_lhs(&case.body, out);
+                    }
+                    collect_lhs(&stmt.default, out);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn assignment_lhs_postfix_chains() {
+        let mut max_chain = 0;
+
+        for module in gen_modules(&["generator"], 0..20) {
+            // The type of each lhs must match its assigned value
+            ast::typecheck::typecheck(&module).unwrap();
+
+            let mut lhs = vec![];
+            for func in &module.functions {
+                collect_lhs(&func.body, &mut lhs);
+            }
+
+            for mut node in lhs {
+                let mut chain = 0;
+                while let LhsExpr::Postfix(inner, _) = &node.expr {
+                    assert!(matches!(node.data_type, DataType::Ref(_)));
+                    node = inner;
+                    chain += 1;
+                }
+
+                // The root of a chain must be a mutable variable
+                if chain > 0 {
+                    assert!(matches!(node.data_type, DataType::Ref(_)));
+                }
+
+                max_chain = max_chain.max(chain);
+            }
+        }
+
+        assert!(max_chain >= 2);
+    }
+
+    #[test]
+    fn zero_stmt_weights_disable_statements() {
+        let args = [
+            "generator",
+            "--stmt-weight",
+            "if=0",
+            "--stmt-weight",
+            "loop=0",
+            "--stmt-weight",
+            "switch=0",
+            "--stmt-weight",
+            "for=0",
+            "--stmt-weight",
+            "while=0",
+        ];
+
+        for module in gen_modules(&args, 0..20) {
+            let mut out = String::new();
+            Writer::default().write_module(&mut out, &module).unwrap();
+
+            for keyword in ["if (", "loop {", "switch ", "for (", "while ("] {
+                assert!(!out.contains(keyword), "found `{keyword}` in:\n{out}");
+            }
+        }
+    }
+
+    fn collect_whiles<'a>(block: &'a [Statement], out: &mut Vec<&'a WhileStatement>) {
+        for stmt in block {
+            match stmt {
+                Statement::Compound(block) => collect_whiles(block, out),
+                Statement::If(stmt) => collect_whiles(&stmt.body, out),
+                Statement::Loop(stmt) => collect_whiles(&stmt.body, out),
+                Statement::ForLoop(stmt) => collect_whiles(&stmt.body, out),
+                Statement::While(stmt) => {
+                    out.push(stmt);
+                    collect_whiles(&stmt.body, out);
+                }
+                Statement::Switch(stmt) => {
+                    for case in &stmt.cases {
+                        collect_whiles(&case.body, out);
+                    }
+                    collect_whiles(&stmt.default, out);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn while_loops_are_bounded_by_counter() {
+        let mut count = 0;
+
+        for module in gen_modules(&["generator", "--stmt-weight", "while=50"], 0..20) {
+            ast::typecheck::typecheck(&module).unwrap();
+
+            let mut whiles = vec![];
+            for func in &module.functions {
+                collect_whiles(&func.body, &mut whiles);
+            }
+
+            for stmt in whiles {
+                let counter = match &stmt.condition.expr {
+                    Expr::BinOp(BinOpExpr {
+                        op: BinOp::LogAnd,
+                        left,
+                        ..
+                    }) => match &left.expr {
+                        Expr::BinOp(BinOpExpr {
+                            op: BinOp::Less,
+                            left,
+                            ..
+                        }) => match &left.expr {
+                            Expr::Var(var) => &var.ident,
+                            _ => panic!("condition doesn't compare a counter: {}", stmt.condition),
+                        },
+                        _ => panic!("condition doesn't check a counter: {}", stmt.condition),
+                    },
+                    _ => panic!("condition isn't guarded by a counter: {}", stmt.condition),
+                };
+
+                // The counter must be incremented before anything in the body can `continue`
+                match stmt.body.first() {
+                    Some(Statement::Assignment(AssignmentStatement {
+                        lhs: AssignmentLhs::Expr(lhs),
+                        op: AssignmentOp::Plus,
+                        ..
+                    })) => assert!(matches!(&lhs.expr, LhsExpr::Ident(name) if name == counter)),
+                    other => panic!("expected counter increment, found {other:?}"),
+                }
+
+                count += 1;
+            }
+        }
+
+        assert!(count > 0);
+    }
+
+    /// Checks that every variable declared by a statement in `block` was declared while fewer than
+    /// `max` variables were in scope, returning the number of declarations that were checked.
+    ///
+    /// `scopes` holds the names in scope from each enclosing block. Only the generator's own
+    /// `var_N` declarations are checked, since e.g. the entrypoint's final `let x` is added
+    /// regardless of the limit.
+    fn check_var_limit(
+        block: &[Statement],
+        max: usize,
+        scopes: &mut Vec<HashSet<String>>,
+    ) -> usize {
+        scopes.push(HashSet::new());
+
+        let mut count = 0;
+
+        for stmt in block {
+            let ident = match stmt {
+                Statement::LetDecl(stmt) => Some(&stmt.ident),
+                Statement::VarDecl(stmt) => Some(&stmt.ident),
+                _ => None,
+            };
+
+            if let Some(ident) = ident.filter(|it| it.starts_with("var_")) {
+                // A shadowing declaration replaces the outer variable, so names are only counted
+                // once
+                let in_scope = scopes.iter().flatten().collect::<HashSet<_>>().len();
+                assert!(
+                    in_scope < max,
+                    "`{ident}` declared with {in_scope} variables in scope"
+                );
+                count += 1;
+            }
+
+            if let Some(ident) = ident {
+                scopes.last_mut().unwrap().insert(ident.clone());
+            }
+
+            count += match stmt {
+                Statement::Compound(block) => check_var_limit(block, max, scopes),
+                Statement::If(stmt) => {
+                    let mut count = check_var_limit(&stmt.body, max, scopes);
+                    let mut else_ = stmt.else_.as_deref();
+                    while let Some(next) = else_ {
+                        match next {
+                            Else::If(stmt) => {
+                                count += check_var_limit(&stmt.body, max, scopes);
+                                else_ = stmt.else_.as_deref();
+                            }
+                            Else::Else(block) => {
+                                count += check_var_limit(block, max, scopes);
+                                else_ = None;
+                            }
+                        }
+                    }
+                    count
+                }
+                Statement::Loop(stmt) => check_var_limit(&stmt.body, max, scopes),
+                Statement::While(stmt) => check_var_limit(&stmt.body, max, scopes),
+                Statement::Switch(stmt) => {
+                    stmt.cases
+                        .iter()
+                        .map(|case| check_var_limit(&case.body, max, scopes))
+                        .sum::<usize>()
+                        + check_var_limit(&stmt.default, max, scopes)
+                }
+                Statement::ForLoop(stmt) => {
+                    let mut header = HashSet::new();
+                    if let Some(ForLoopInit::VarDecl(init)) = &stmt.header.init {
+                        header.insert(init.ident.clone());
+                    }
+                    scopes.push(header);
+                    let count = check_var_limit(&stmt.body, max, scopes);
+                    scopes.pop();
+                    count
+                }
+                _ => 0,
+            };
+        }
+
+        scopes.pop();
+
+        count
+    }
+
+    #[test]
+    fn max_total_vars_limits_declarations() {
+        // While loop counters are declared regardless of the limit, and so are the temporaries for
+        // pointer arguments (which are disabled by default)
+        let args = [
+            "generator",
+            "--fn-min-stmts",
+            "50",
+            "--fn-max-stmts",
+            "50",
+            "--max-total-vars",
+            "10",
+            "--stmt-weight",
+            "while=0",
+        ];
+
+        let mut nested = 0;
+
+        for module in gen_modules(&args, 0..20) {
+            // The output buffer isn't in scope, since it's only written at the end of `main`
+            let globals = module
+                .vars
+                .iter()
+                .map(|var| var.name.clone())
+                .filter(|name| name != "s_output")
+                .collect::<HashSet<_>>();
+
+            for func in &module.functions {
+                let params = func.inputs.iter().map(|it| it.name.clone()).collect();
+                let mut scopes = vec![globals.clone(), params];
+                let top_level = func
+                    .body
+                    .iter()
+                    .filter(|stmt| match stmt {
+                        Statement::LetDecl(stmt) => stmt.ident.starts_with("var_"),
+                        Statement::VarDecl(stmt) => stmt.ident.starts_with("var_"),
+                        _ => false,
+                    })
+                    .count();
+                nested += check_var_limit(&func.body, 10, &mut scopes) - top_level;
+            }
+        }
+
+        // Declarations in nested blocks must be checked too
+        assert!(nested > 0);
+    }
+
+    #[test]
+    fn fragment_stage_returns_render_target_value() {
+        let args = ["generator", "--stage", "fragment", "--enable-barriers"];
+
+        for module in gen_modules(&args, 0..20) {
+            ast::typecheck::typecheck(&module).unwrap();
+
+            let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+            assert!(matches!(main.body.last(), Some(Statement::Return(_))));
+
+            let mut out = String::new();
+            Writer::default().write_module(&mut out, &module).unwrap();
+
+            assert!(out.contains("@fragment\nfn main() -> @location(0) vec4<u32> {"));
+            assert!(!out.contains("Barrier()"), "found barrier in:\n{out}");
+        }
+    }
+
+    #[test]
+    fn f16_requires_option_and_capability() {
+        let gen = |options: Options| {
+            gen_modules_with(options, 0..20)
+                .map(|module| {
+                    ast::typecheck::typecheck(&module).unwrap();
+                    let mut out = String::new();
+                    Writer::default().write_module(&mut out, &module).unwrap();
+                    (module, out)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (module, out) in gen(Options::parse_from(["generator", "--enable-f16"])) {
+            assert!(module.enables.is_empty());
+            assert!(!out.contains("f16"), "found f16 in:\n{out}");
+        }
+
+        let mut options = Options::parse_from(["generator", "--enable-f16"]);
+        options.capabilities = Some(harness_types::DeviceCapabilities {
+            shader_f16: true,
+            ..Default::default()
+        });
+
+        let modules = gen(options);
+
+        assert!(modules
+            .iter()
+            .all(|(module, _)| module.enables == [ast::Extension::F16]));
+        assert!(modules.iter().any(|(_, out)| out.contains("f16")));
+    }
+
+    #[test]
+    fn buffer_structs_fit_within_device_limits() {
+        let mut options = Options::parse_from([
+            "generator",
+            "--min-structs",
+            "5",
+            "--max-structs",
+            "8",
+            "--min-struct-members",
+            "8",
+            "--max-struct-members",
+            "10",
+        ]);
+        options.capabilities = Some(harness_types::DeviceCapabilities {
+            max_uniform_buffer_binding_size: 64,
+            max_storage_buffer_binding_size: 256,
+            ..Default::default()
+        });
+
+        for module in gen_modules_with(options, 0..20) {
+            for var in &module.vars {
+                let max_size = match var.qualifier.as_ref().map(|it| it.storage_class) {
+                    Some(ast::StorageClass::Uniform) => 64,
+                    Some(ast::StorageClass::Storage) => 256,
+                    _ => continue,
+                };
+
+                let size = common::Type::try_from(&var.data_type)
+                    .unwrap()
+                    .buffer_size();
+                assert!(size <= max_size, "`{}` is {size} bytes", var.name);
+            }
+        }
+    }
+
+    #[test]
+    fn buffers_are_spread_between_bind_groups() {
+        let bindings = |module: Module| {
+            module
+                .vars
+                .iter()
+                .filter_map(|var| Some((var.group_index()?, var.binding_index()?)))
+                .collect::<Vec<_>>()
+        };
+
+        for module in gen_modules(&["generator"], 0..20) {
+            assert_eq!(bindings(module), [(0, 0), (0, 1)]);
+        }
+
+        let mut groups = HashSet::new();
+        let modules = gen_modules(&["generator", "--bind-groups", "3"], 0..20);
+        for (seed, bindings) in modules.map(bindings).enumerate() {
+            for (i, (group, binding)) in bindings.iter().enumerate() {
+                assert!(*group < 3, "seed {seed}: {bindings:?}");
+                assert!(
+                    !bindings[..i].contains(&(*group, *binding)),
+                    "seed {seed}: {bindings:?}"
+                );
+                groups.insert(*group);
+            }
+        }
+
+        assert!(groups.len() > 1);
+
+        let options = Options::parse_from(["generator", "--bind-groups", "5"]);
+        assert!(options.generation_config().is_err());
+    }
+
+    #[test]
+    fn invocations_write_distinct_elements() {
+        let modules = gen_modules(&["generator", "--invocations", "4"], 0..20);
+
+        for (seed, module) in modules.enumerate() {
+            let output = module.vars.iter().find(|it| it.name == "s_output").unwrap();
+            assert!(
+                matches!(output.data_type, DataType::Array(_, Some(4))),
+                "seed {seed}: {}",
+                output.data_type
+            );
+
+            let main = module
+                .functions
+                .iter()
+                .find(|it| it.name == "main")
+                .unwrap();
+            assert!(
+                main.attrs.contains(&FnAttr::LitWorkgroupSize(4)),
+                "seed {seed}"
+            );
+
+            // Each invocation in the workgroup writes to the element at its own index, and any
+            // invocations beyond the end of the array don't write at all
+            let write = match main.body.last() {
+                Some(Statement::If(stmt)) => stmt,
+                stmt => panic!("seed {seed}: unexpected final statement: {stmt:?}"),
+            };
+
+            assert_eq!(write.condition.to_string(), "global_invocation_id.x < 4u");
+
+            match write.body.as_slice() {
+                [Statement::Assignment(stmt)] => {
+                    assert_eq!(stmt.lhs.to_string(), "(s_output)[global_invocation_id.x]")
+                }
+                body => panic!("seed {seed}: unexpected write: {body:?}"),
+            }
+        }
+
+        let options =
+            Options::parse_from(["generator", "--invocations", "4", "--stage", "fragment"]);
+        assert!(options.generation_config().is_err());
+    }
+
+    #[test]
+    fn annotations_dont_change_generated_code() {
+        let write = |module: Module| {
+            let mut out = String::new();
+            Writer::default().write_module(&mut out, &module).unwrap();
+            out
+        };
+
+        let annotated = gen_modules(&["generator", "--annotate"], 0..20).map(write);
+        let plain = gen_modules(&["generator"], 0..20).map(write);
+
+        for (seed, (annotated, plain)) in annotated.zip(plain).enumerate() {
+            assert!(annotated.contains("// stmt 0: "), "seed {seed}");
+
+            let stripped = annotated
+                .lines()
+                .filter(|line| !line.trim_start().starts_with("// stmt "))
+                .map(|line| format!("{line}\n"))
+                .collect::<String>();
+            assert_eq!(stripped, plain, "seed {seed}");
+        }
+    }
+
+    /// Non-uniform exits found by [`check_barriers`].
+    #[derive(Default)]
+    struct Exits {
+        returned: bool,
+        exited_loop: bool,
+    }
+
+    /// Walks `block` following WGSL's uniformity rules, panicking if a barrier is found in
+    /// non-uniform control flow. `uniform` is whether control flow is uniform at the start of the
+    /// block.
+    ///
+    /// Returns whether control flow is still uniform at the end of the block.
+    fn check_barriers(block: &[Statement], mut uniform: bool, exits: &mut Exits) -> bool {
+        for stmt in block {
+            match stmt {
+                Statement::FnCall(stmt) if stmt.ident.ends_with("Barrier") => {
+                    assert!(uniform, "`{}` in non-uniform control flow", stmt.ident);
+                }
+                Statement::Return(_) => exits.returned |= !uniform,
+                Statement::Break | Statement::Continue => exits.exited_loop |= !uniform,
+                Statement::Compound(block) => uniform = check_barriers(block, uniform, exits),
+                Statement::If(stmt) => {
+                    let mut is_uniform = uniform && is_uniform_expr(&stmt.condition);
+                    check_barriers(&stmt.body, is_uniform, exits);
+
+                    let mut else_ = stmt.else_.as_deref();
+                    while let Some(e) = else_ {
+                        match e {
+                            Else::If(stmt) => {
+                                is_uniform &= is_uniform_expr(&stmt.condition);
+                                check_barriers(&stmt.body, is_uniform, exits);
+                                else_ = stmt.else_.as_deref();
+                            }
+                            Else::Else(body) => {
+                                check_barriers(body, is_uniform, exits);
+                                else_ = None;
+                            }
+                        }
+                    }
+
+                    uniform &= !exits.returned && !exits.exited_loop;
+                }
+                Statement::Switch(stmt) => {
+                    let is_uniform = uniform && is_uniform_expr(&stmt.selector);
+                    for case in &stmt.cases {
+                        check_barriers(&case.body, is_uniform, exits);
+                    }
+                    check_barriers(&stmt.default, is_uniform, exits);
+
+                    uniform &= !exits.returned && !exits.exited_loop;
+                }
+                Statement::Loop(stmt) => uniform &= check_loop(&stmt.body, None, exits),
+                Statement::ForLoop(stmt) => {
+                    uniform &= check_loop(&stmt.body, stmt.header.condition.as_ref(), exits)
+                }
+                Statement::While(stmt) => {
+                    uniform &= check_loop(&stmt.body, Some(&stmt.condition), exits)
+                }
+                _ => {}
+            }
+        }
+
+        uniform
+    }
+
+    /// Checks the body of a loop, which is always treated as non-uniform. Returns whether control
+    /// flow is still uniform after the loop.
+    fn check_loop(body: &[Statement], condition: Option<&ExprNode>, exits: &mut Exits) -> bool {
+        let mut inner = Exits {
+            returned: exits.returned,
+            exited_loop: condition.is_some_and(|it| !is_uniform_expr(it)),
+        };
+
+        check_barriers(body, false, &mut inner);
+        exits.returned = inner.returned;
+
+        !inner.returned && !inner.exited_loop
+    }
+
+    #[test]
+    fn barriers_only_in_uniform_control_flow() {
+        let args = [
+            "generator",
+            "--enable-barriers",
+            "--stmt-weight",
+            "barrier=50",
+            "--stmt-weight",
+            "return=20",
+            "--stmt-weight",
+            "break=20",
+        ];
+
+        let mut barriers = 0;
+
+        for module in gen_modules(&args, 0..50) {
+            for func in &module.functions {
+                let is_entrypoint = func.attrs.iter().any(|it| matches!(it, FnAttr::Stage(_)));
+                check_barriers(&func.body, is_entrypoint, &mut Exits::default());
+            }
+
+            let mut out = String::new();
+            Writer::default().write_module(&mut out, &module).unwrap();
+            barriers += out.matches("Barrier()").count();
+        }
+
+        assert!(barriers > 0);
+    }
+
+    #[test]
+    fn loop_exits_only_diverge_until_end_of_loop() {
+        let options = Options::parse_from(["generator"]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(0);
+        let mut gen = Generator::new(&mut rng, config);
+
+        gen.fn_state.is_uniform = true;
+
+        // if (uniform) { loop { if (non_uniform) { break; } } ... }
+        gen.with_uniformity(true, |gen| {
+            gen.with_loop_body(None, |gen| {
+                gen.with_uniformity(false, |gen| gen.mark_exited_loop())
+            });
+
+            // The rest of the block containing the loop is non-uniform...
+            assert!(!gen.fn_state.is_uniform);
+        });
+
+        // ...but the `if` containing the loop isn't left early, so control flow reconverges
+        assert!(gen.fn_state.is_uniform);
+        assert!(!gen.fn_state.has_exited_loop);
+
+        // A non-uniform `return` makes the rest of the function non-uniform
+        gen.with_uniformity(false, |gen| gen.mark_returned());
+        assert!(!gen.fn_state.is_uniform);
+
+        gen.with_uniformity(true, |_| {});
+        assert!(!gen.fn_state.is_uniform);
+    }
+}
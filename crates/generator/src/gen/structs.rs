@@ -1,7 +1,7 @@
 use std::rc::Rc;
 
 use ast::types::DataType;
-use ast::{StructDecl, StructMember, StructMemberAttr};
+use ast::{ScalarType, StructDecl, StructMember, StructMemberAttr};
 use rand::Rng;
 
 use super::cx::SelectionFilter;
@@ -15,7 +15,7 @@ pub enum StructKind {
     UniformBuffer,
 }
 
-impl<'a> super::Generator<'a> {
+impl super::Generator {
     pub fn gen_struct(&mut self, name: String) -> Rc<StructDecl> {
         self.gen_struct_with(name, StructKind::Default)
     }
@@ -23,7 +23,7 @@ impl<'a> super::Generator<'a> {
     pub fn gen_struct_with(&mut self, name: String, kind: StructKind) -> Rc<StructDecl> {
         let member_count = self
             .rng
-            .gen_range(self.options.min_struct_members..=self.options.max_struct_members);
+            .gen_range(self.config.min_struct_members..=self.config.max_struct_members);
 
         let filter = match kind {
             StructKind::Default => SelectionFilter::Any,
@@ -31,14 +31,35 @@ impl<'a> super::Generator<'a> {
             StructKind::UniformBuffer => SelectionFilter::Uniform,
         };
 
-        let mut members = (0..member_count)
-            .map(|i| {
-                StructMember::new(
-                    vec![],
-                    FIELD_NAMES[i as usize].to_owned(),
-                    self.cx.types.select_with_filter(self.rng, filter),
-                )
-            })
+        // Buffer structs must fit within the device's limit on the size of a buffer binding
+        let capabilities = self.config.capabilities.clone().unwrap_or_default();
+        let max_size = match kind {
+            StructKind::Default => None,
+            StructKind::HostShareable => Some(capabilities.max_storage_buffer_binding_size),
+            StructKind::UniformBuffer => Some(capabilities.max_uniform_buffer_binding_size),
+        };
+
+        let mut member_types: Vec<DataType> = vec![];
+        for _ in 0..member_count {
+            let data_type = self.cx.types.select_with_filter(&mut self.rng, filter);
+            member_types.push(data_type);
+
+            if let Some(max_size) = max_size {
+                if buffer_struct_size(&member_types) > max_size {
+                    member_types.pop();
+                }
+            }
+        }
+
+        // Every member can be too big if the nested structs are large, so fall back to a scalar
+        if member_types.is_empty() {
+            member_types.push(ScalarType::U32.into());
+        }
+
+        let mut members = member_types
+            .into_iter()
+            .enumerate()
+            .map(|(i, data_type)| StructMember::new(vec![], FIELD_NAMES[i].to_owned(), data_type))
             .collect::<Vec<_>>();
 
         if matches!(kind, StructKind::HostShareable | StructKind::UniformBuffer) {
@@ -55,3 +76,29 @@ impl<'a> super::Generator<'a> {
         StructDecl::new(name, members)
     }
 }
+
+/// Returns the size in bytes of a buffer struct with members of the given types, taking into
+/// account the 16 byte alignment that is added to struct members.
+fn buffer_struct_size(member_types: &[DataType]) -> u64 {
+    let mut size = 0;
+    let mut alignment = 0;
+
+    for data_type in member_types {
+        let type_desc =
+            common::Type::try_from(data_type).expect("buffer struct members must be storable");
+
+        let member_alignment = match data_type {
+            DataType::Struct(_) => 16,
+            _ => type_desc.alignment() as u64,
+        };
+
+        size = align(size, member_alignment) + type_desc.size() as u64;
+        alignment = alignment.max(member_alignment);
+    }
+
+    align(size, alignment)
+}
+
+fn align(size: u64, alignment: u64) -> u64 {
+    size.div_ceil(alignment) * alignment
+}
@@ -0,0 +1,99 @@
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Expr,
+    Stmt,
+}
+
+/// Counts the expressions and statements produced by the generator, and the time spent
+/// generating each of them.
+///
+/// Times are exclusive: time spent generating an expression within a statement counts towards
+/// expressions but not statements, and the body of a function that is generated in the middle of
+/// an expression counts towards statements. Time spent outside of any expression or statement
+/// (e.g. generating structs or function signatures) isn't counted.
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    pub exprs: u64,
+    pub stmts: u64,
+    pub expr_time: Duration,
+    pub stmt_time: Duration,
+    stack: Vec<Kind>,
+    last: Option<Instant>,
+}
+
+impl Timings {
+    pub(super) fn enter_expr(&mut self) {
+        self.exprs += 1;
+        self.enter(Kind::Expr);
+    }
+
+    pub(super) fn enter_stmt(&mut self) {
+        self.stmts += 1;
+        self.enter(Kind::Stmt);
+    }
+
+    pub(super) fn exit(&mut self) {
+        self.switch();
+        self.stack.pop();
+    }
+
+    fn enter(&mut self, kind: Kind) {
+        self.switch();
+        self.stack.push(kind);
+    }
+
+    /// Attributes the time since the last switch to the innermost node being generated.
+    fn switch(&mut self) {
+        let now = Instant::now();
+
+        if let (Some(kind), Some(last)) = (self.stack.last(), self.last) {
+            match kind {
+                Kind::Expr => self.expr_time += now - last,
+                Kind::Stmt => self.stmt_time += now - last,
+            }
+        }
+
+        self.last = Some(now);
+    }
+}
+
+impl AddAssign<&Timings> for Timings {
+    fn add_assign(&mut self, other: &Timings) {
+        self.exprs += other.exprs;
+        self.stmts += other.stmts;
+        self.expr_time += other.expr_time;
+        self.stmt_time += other.stmt_time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use rand::SeedableRng;
+
+    use crate::gen::{GenRng, Generator};
+    use crate::GenerationConfig;
+
+    #[test]
+    fn timings_dont_change_output() {
+        let config = Rc::new(GenerationConfig::default());
+
+        for seed in 0..10 {
+            let expected =
+                Generator::new(&mut GenRng::seed_from_u64(seed), config.clone()).gen_module();
+
+            let mut gen = Generator::new(&mut GenRng::seed_from_u64(seed), config.clone());
+            gen.enable_timings();
+
+            assert_eq!(gen.gen_module(), expected, "seed {seed}");
+
+            let timings = gen.take_timings().unwrap();
+            assert!(timings.exprs > 0 && timings.stmts > 0, "seed {seed}");
+            assert!(timings.stack.is_empty(), "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+
+use ast::{Expr, ExprNode, Statement};
+use serde_json::json;
+
+/// Writes a JSONL record for every expression and statement produced by the generator.
+///
+/// Each record has the form:
+///
+/// ```json
+/// {"id": 12, "parent": 9, "depth": 3, "kind": "BinOp", "type": "vec2<i32>", "op": "+"}
+/// ```
+///
+/// Ids are assigned in the order that generation of a node begins, and `parent` is the id of the
+/// enclosing node (or `null` for top-level statements). Records are written once the node has
+/// been fully generated, so children appear before their parents.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    next_id: u64,
+    stack: Vec<u64>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>) -> Tracer {
+        Tracer {
+            out,
+            next_id: 0,
+            stack: vec![],
+            error: None,
+        }
+    }
+
+    /// Flushes the trace, returning the first error encountered while writing it.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+
+    pub(super) fn enter(&mut self) {
+        self.stack.push(self.next_id);
+        self.next_id += 1;
+    }
+
+    pub(super) fn exit_expr(&mut self, node: &ExprNode) {
+        let (kind, op) = match &node.expr {
+            Expr::Lit(_) => ("Lit", None),
+            Expr::TypeCons(_) => ("TypeCons", None),
+            Expr::Var(_) => ("Var", None),
+            Expr::Postfix(_) => ("Postfix", None),
+            Expr::UnOp(expr) => ("UnOp", Some(expr.op.to_string())),
+            Expr::BinOp(expr) => ("BinOp", Some(expr.op.to_string())),
+            Expr::FnCall(expr) => ("FnCall", Some(expr.ident.clone())),
+        };
+
+        self.exit(kind, Some(node.data_type.to_string()), op);
+    }
+
+    pub(super) fn exit_stmt(&mut self, stmt: &Statement) {
+        let kind = match stmt {
+            Statement::LetDecl(_) => "LetDecl",
+            Statement::VarDecl(_) => "VarDecl",
+            Statement::Assignment(_) => "Assignment",
+            Statement::Compound(_) => "Compound",
+            Statement::If(_) => "If",
+            Statement::Return(_) => "Return",
+            Statement::Loop(_) => "Loop",
+            Statement::Break => "Break",
+            Statement::Continue => "Continue",
+            Statement::Switch(_) => "Switch",
+            Statement::Fallthrough => "Fallthrough",
+            Statement::ForLoop(_) => "ForLoop",
+            Statement::While(_) => "While",
+            Statement::FnCall(_) => "FnCall",
+            Statement::Comment(_) => "Comment",
+        };
+
+        let op = match stmt {
+            Statement::Assignment(stmt) => Some(stmt.op.to_string()),
+            _ => None,
+        };
+
+        self.exit(kind, None, op);
+    }
+
+    fn exit(&mut self, kind: &str, ty: Option<String>, op: Option<String>) {
+        let id = self.stack.pop().expect("unbalanced trace");
+
+        if self.error.is_some() {
+            return;
+        }
+
+        let record = json!({
+            "id": id,
+            "parent": self.stack.last(),
+            "depth": self.stack.len(),
+            "kind": kind,
+            "type": ty,
+            "op": op,
+        });
+
+        if let Err(e) = writeln!(self.out, "{record}") {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use ast::writer::Writer;
+    use ast::Module;
+    use clap::Parser;
+    use rand::SeedableRng;
+
+    use super::Tracer;
+    use crate::gen::{GenRng, Generator};
+    use crate::Options;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn gen_module(seed: u64, tracer: Option<Tracer>) -> String {
+        let options = Options::parse_from(["generator"]);
+        let config = Rc::new(options.generation_config().unwrap());
+        let mut rng = GenRng::seed_from_u64(seed);
+        let mut gen = Generator::new(&mut rng, config);
+
+        if let Some(tracer) = tracer {
+            gen.set_tracer(tracer);
+        }
+
+        let module: Module = gen.gen_module();
+
+        if let Some(tracer) = gen.take_tracer() {
+            tracer.finish().unwrap();
+        }
+
+        let mut out = String::new();
+        Writer::default().write_module(&mut out, &module).unwrap();
+        out
+    }
+
+    #[test]
+    fn trace_does_not_affect_output() {
+        for seed in 0..10 {
+            let buf = SharedBuf::default();
+            let traced = gen_module(seed, Some(Tracer::new(Box::new(buf.clone()))));
+            assert_eq!(gen_module(seed, None), traced);
+
+            let trace = String::from_utf8(buf.0.take()).unwrap();
+            let records = trace
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .collect::<Vec<_>>();
+
+            assert!(!records.is_empty());
+
+            // Children are written before their parents, and are one level deeper
+            for (i, record) in records.iter().enumerate() {
+                if let Some(parent) = record["parent"].as_u64() {
+                    let parent = records[i..]
+                        .iter()
+                        .find(|it| it["id"].as_u64() == Some(parent))
+                        .expect("parent should be written after child");
+                    assert_eq!(
+                        record["depth"].as_u64(),
+                        parent["depth"].as_u64().map(|d| d + 1)
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use ast::types::DataType;
-use ast::Statement;
+use ast::{BuiltinFn, Expr, ExprNode, Postfix, Statement};
 use rand::prelude::SliceRandom;
 use rand::Rng;
 
@@ -58,3 +58,28 @@ pub fn is_terminal_stmt<'a>(last_statement: impl Into<Option<&'a Statement>>) ->
         )
     )
 }
+
+/// Conservatively determines whether an expression is guaranteed to evaluate to the same value in
+/// all invocations, for the purposes of WGSL uniformity analysis.
+///
+/// The only variable that is treated as uniform is the `u_input` uniform buffer. Local variables,
+/// private globals and calls to user defined functions are all assumed to be non-uniform.
+pub fn is_uniform_expr(node: &ExprNode) -> bool {
+    match &node.expr {
+        Expr::Lit(_) => true,
+        Expr::TypeCons(expr) => expr.args.iter().all(is_uniform_expr),
+        Expr::Var(expr) => expr.ident == "u_input",
+        Expr::Postfix(expr) => {
+            is_uniform_expr(&expr.inner)
+                && match &expr.postfix {
+                    Postfix::Index(index) => is_uniform_expr(index),
+                    Postfix::Member(_) => true,
+                }
+        }
+        Expr::UnOp(expr) => is_uniform_expr(&expr.inner),
+        Expr::BinOp(expr) => is_uniform_expr(&expr.left) && is_uniform_expr(&expr.right),
+        Expr::FnCall(expr) => {
+            expr.ident.parse::<BuiltinFn>().is_ok() && expr.args.iter().all(is_uniform_expr)
+        }
+    }
+}
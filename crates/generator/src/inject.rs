@@ -0,0 +1,171 @@
+use std::str::FromStr;
+
+use ast::types::{DataType, MemoryViewType};
+use ast::{
+    AssignmentOp, AssignmentStatement, BinOp, BinOpExpr, ExprNode, LhsExpr, LhsExprNode, Lit,
+    Module, Postfix, ScalarType, Statement, StorageClass, TypeConsExpr, VarDeclStatement, VarExpr,
+};
+use eyre::eyre;
+use rand::Rng;
+
+/// A pattern which is known to be compiled differently by some backends.
+///
+/// These are injected into otherwise valid shaders to check that the fuzzer detects and reduces
+/// mismatches, since without a known bug it's hard to tell whether a quiet fuzzing session means
+/// there are no bugs or that detection is broken. Each pattern is only divergent because it skips
+/// reconditioning, so it's inserted after the shader has been reconditioned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InjectedBug {
+    /// Shifting a `u32` by 32 or more. WGSL only uses the low 5 bits of the shift amount, but
+    /// shifting by the full amount is undefined in MSL.
+    ShiftOverflow,
+    /// Dividing `i32::MIN` by -1. WGSL defines the result as `i32::MIN` (or 0 for `%`), but it
+    /// overflows in HLSL and MSL.
+    IntMinDiv,
+    /// Converting an out of range float to an integer. WGSL saturates the result, but the
+    /// conversion is undefined in HLSL and MSL.
+    FloatToInt,
+}
+
+impl InjectedBug {
+    /// Every pattern in the catalog.
+    pub const ALL: &'static [InjectedBug] = &[
+        InjectedBug::ShiftOverflow,
+        InjectedBug::IntMinDiv,
+        InjectedBug::FloatToInt,
+    ];
+
+    /// Returns the name of this pattern, as accepted by `--inject-bug`.
+    pub fn name(self) -> &'static str {
+        match self {
+            InjectedBug::ShiftOverflow => "shift-overflow",
+            InjectedBug::IntMinDiv => "int-min-div",
+            InjectedBug::FloatToInt => "float-to-int",
+        }
+    }
+
+    /// Inserts an instance of this pattern at the end of the entrypoint, storing its result in
+    /// the first scalar of the output buffer.
+    ///
+    /// The operands are stored in variables first, so that frontends can't fold the pattern away
+    /// at compile time. Its parameters (e.g. the shift amount) are chosen with `rng`.
+    pub fn inject(self, module: &mut Module, rng: &mut impl Rng) -> eyre::Result<()> {
+        let output = module
+            .vars
+            .iter()
+            .find(|var| var.name == "s_output")
+            .ok_or_else(|| eyre!("shader has no `s_output` buffer to inject into"))?;
+
+        let lhs = output_scalar(LhsExprNode::name(
+            output.name.clone(),
+            DataType::Ref(MemoryViewType::new(
+                output.data_type.clone(),
+                StorageClass::Storage,
+            )),
+        ));
+
+        let mut stmts = vec![];
+        let mut var = |init: Lit| {
+            let ident = format!("injected_{}", stmts.len());
+            let data_type = init.data_type();
+            stmts.push(VarDeclStatement::new(ident.clone(), None, Some(init.into())).into());
+            VarExpr::new(ident).into_node(data_type)
+        };
+
+        let value: ExprNode = match self {
+            InjectedBug::ShiftOverflow => {
+                let value = var(Lit::U32(rng.gen::<u32>() | 1));
+                let amount = var(Lit::U32(rng.gen_range(32..64)));
+                BinOpExpr::new(BinOp::LShift, value, amount).into()
+            }
+            InjectedBug::IntMinDiv => {
+                let op = if rng.gen_bool(0.5) {
+                    BinOp::Divide
+                } else {
+                    BinOp::Mod
+                };
+                let dividend = var(Lit::I32(i32::MIN));
+                let divisor = var(Lit::I32(-1));
+                BinOpExpr::new(op, dividend, divisor).into()
+            }
+            InjectedBug::FloatToInt => {
+                let magnitude = rng.gen_range(3e9..1e12_f32).trunc();
+                let (target, value) = if rng.gen_bool(0.5) {
+                    (ScalarType::I32, magnitude)
+                } else {
+                    (ScalarType::U32, -magnitude)
+                };
+                let value = var(Lit::F32(value));
+                TypeConsExpr::new(DataType::Scalar(target), vec![value]).into()
+            }
+        };
+
+        let value = match lhs.data_type.dereference() {
+            data_type if data_type == &value.data_type => value,
+            data_type => TypeConsExpr::new(data_type.clone(), vec![value]).into(),
+        };
+
+        stmts.push(AssignmentStatement::new(lhs.into(), AssignmentOp::Simple, value).into());
+
+        let entrypoint = module
+            .functions
+            .iter_mut()
+            .find(|func| func.name == "main")
+            .ok_or_else(|| eyre!("shader has no `main` function to inject into"))?;
+
+        // Fragment shaders end by returning the render target value, so insert before that
+        let body = &mut entrypoint.body;
+        let index = match body.last() {
+            Some(Statement::Return(_)) => body.len() - 1,
+            _ => body.len(),
+        };
+
+        body.splice(index..index, stmts);
+
+        Ok(())
+    }
+}
+
+impl FromStr for InjectedBug {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InjectedBug::ALL
+            .iter()
+            .copied()
+            .find(|bug| bug.name() == s)
+            .ok_or_else(|| {
+                let names = InjectedBug::ALL.iter().map(|bug| bug.name());
+                format!(
+                    "unknown bug `{s}` - must be one of {{{}}}",
+                    names.collect::<Vec<_>>().join(", ")
+                )
+            })
+    }
+}
+
+/// Extends `lhs` to refer to its first scalar, taking the first element of arrays, the first
+/// member of structs and the `x` component of vectors.
+fn output_scalar(mut lhs: LhsExprNode) -> LhsExprNode {
+    loop {
+        let mem_view = match &lhs.data_type {
+            DataType::Ref(mem_view) => mem_view.clone(),
+            _ => unreachable!("output buffer must be a reference"),
+        };
+
+        let (postfix, data_type) = match mem_view.inner.as_ref() {
+            DataType::Array(ty, _) => (Postfix::index(Lit::U32(0)), ty.as_ref().clone()),
+            DataType::Struct(decl) => {
+                let member = &decl.members[0];
+                (Postfix::member(&member.name), member.data_type.clone())
+            }
+            DataType::Vector(_, ty) => (Postfix::member("x"), DataType::Scalar(*ty)),
+            _ => return lhs,
+        };
+
+        lhs = LhsExprNode {
+            data_type: DataType::Ref(mem_view.clone_with_type(data_type)),
+            expr: LhsExpr::Postfix(Box::new(lhs), postfix),
+        };
+    }
+}
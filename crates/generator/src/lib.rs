@@ -1,22 +1,35 @@
+pub mod bench;
+
+mod config;
 mod gen;
+mod inject;
+mod profile;
+mod weights;
 
 use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::fs::File;
 use std::hash::BuildHasher;
-use std::io::{self, BufWriter};
-use std::path::Path;
+use std::io::{self, BufWriter, Write as _};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::str::FromStr;
 
-use ast::{BuiltinFn, StorageClass, VarQualifier};
-use clap::Parser;
+use ast::{BuiltinFn, ScalarType, StorageClass, VarQualifier};
+use clap::{Parser, ValueEnum};
 use eyre::{bail, eyre};
+use harness_types::DeviceCapabilities;
 use hashers::fx_hash::FxHasher;
 
-pub use gen::{builtins, Generator};
-use rand::prelude::StdRng;
+pub use config::GenerationConfig;
+pub use gen::timings::Timings;
+pub use gen::trace::Tracer;
+pub use gen::{builtins, ExprType, GenRng, Generator};
+pub use inject::InjectedBug;
+pub use profile::Profile;
 use rand::rngs::OsRng;
 use rand::{Rng, SeedableRng};
+pub use weights::StmtWeights;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Preset {
@@ -35,6 +48,35 @@ impl FromStr for Preset {
     }
 }
 
+/// Format that the generated shader is written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Emit {
+    Wgsl,
+    /// The AST serialized as JSON (see `ast::Module::to_json`).
+    AstJson,
+}
+
+/// Shader stage of the generated entrypoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Compute,
+    /// A fragment shader which writes a `vec4<u32>` to a single render target, in addition to the
+    /// output buffer.
+    Fragment,
+}
+
+impl FromStr for Stage {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compute" => Ok(Stage::Compute),
+            "fragment" => Ok(Stage::Fragment),
+            _ => Err("invalid stage - must be one of {compute, fragment}"),
+        }
+    }
+}
+
 #[derive(Parser)]
 pub struct Options {
     /// Optional u64 to seed the random generator
@@ -45,6 +87,25 @@ pub struct Options {
     #[clap(short, long, action)]
     pub debug: bool,
 
+    /// Format to write the shader in.
+    ///
+    /// `ast-json` writes the AST as JSON for external tools, which can be turned back into WGSL
+    /// with `wgslsmith fmt --input-format ast-json`. Buffer inputs aren't written in this format.
+    #[clap(
+        long,
+        value_enum,
+        action,
+        default_value = "wgsl",
+        conflicts_with = "debug"
+    )]
+    pub emit: Emit,
+
+    /// Write the type of every `let` and `var` declaration explicitly, e.g. `let x: i32 = ...`.
+    ///
+    /// This avoids relying on each compiler to infer the same type from the initializer.
+    #[clap(long, action, conflicts_with = "debug")]
+    pub explicit_types: bool,
+
     /// Enable built-in functions that are disabled by default
     #[clap(long = "enable-fn", action)]
     pub enabled_fns: Vec<BuiltinFn>,
@@ -53,6 +114,68 @@ pub struct Options {
     #[clap(long, action)]
     pub enable_pointers: bool,
 
+    /// Whether to enable generating barriers.
+    ///
+    /// Barriers are only placed in the entrypoint where control flow is known to be uniform, so
+    /// that the generated shader still passes uniformity analysis.
+    #[clap(long, action)]
+    pub enable_barriers: bool,
+
+    /// Whether to make compute shader built-in inputs available to the entrypoint.
+    ///
+    /// This adds `global_invocation_id`, `local_invocation_index` and `num_workgroups` as
+    /// parameters of the entrypoint, which can then be read like any other variable.
+    #[clap(long, action)]
+    pub enable_builtin_inputs: bool,
+
+    /// Whether to enable generating `f16` scalars and vectors.
+    ///
+    /// This requires the `shader-f16` feature, so it only has an effect if `--capabilities`
+    /// describes a device which supports it. `f16` values are never stored in buffers.
+    #[clap(long, action)]
+    pub enable_f16: bool,
+
+    /// Shader stage to generate the entrypoint for - must be one of {compute, fragment}.
+    ///
+    /// Fragment shaders are run by drawing a single full-screen triangle to a 1x1 render target.
+    /// Barriers are never generated in fragment shaders, and built-in inputs aren't supported.
+    #[clap(long, action)]
+    pub stage: Option<Stage>,
+
+    /// Number of bind groups to spread the input and output buffers between.
+    ///
+    /// Each buffer is placed in a random group from `@group(0)` to `@group(N - 1)`, with bindings
+    /// numbered from 0 within each group. This exercises how backends lower multiple bind groups
+    /// (e.g. to descriptor sets or root signatures). Must be at most the device's `max_bind_groups`
+    /// (4 unless `--capabilities` says otherwise).
+    #[clap(long, action)]
+    pub bind_groups: Option<u32>,
+
+    /// Number of invocations in the compute shader's workgroup.
+    ///
+    /// With more than one, `s_output` is an array with an element for each invocation, and each
+    /// invocation only writes to the element at its `global_invocation_id.x`. This keeps the
+    /// output deterministic, so that it can still be compared between backends. Invocations in
+    /// any workgroups after the first skip the write.
+    #[clap(long, action)]
+    pub invocations: Option<u32>,
+
+    /// Only generate straight-line code.
+    ///
+    /// This disables all control flow statements (`if`, `loop`, `for`, `switch`) as well as early
+    /// returns, so that function bodies are a flat sequence of declarations and assignments.
+    #[clap(long, action)]
+    pub straight_line: bool,
+
+    /// Prefix each generated statement with a comment such as `// stmt 12: assignment`.
+    ///
+    /// Statements are numbered in the order that they were generated, and named by the kind of
+    /// statement that was chosen (as in `--stmt-weight`). This makes it easier to correlate a
+    /// location in a reduced shader with the generation decision that produced it. Comments are
+    /// discarded when a shader is parsed, so they don't affect any other tools.
+    #[clap(long, action)]
+    pub annotate: bool,
+
     /// Skips the static pointer aliasing checks.
     ///
     /// This is only useful if reconditioning and pointer support is enabled.
@@ -64,49 +187,177 @@ pub struct Options {
     pub log: Option<String>,
 
     /// Minimum number of statements to generate in function bodies
-    #[clap(long, action, default_value = "5")]
-    pub fn_min_stmts: u32,
+    #[clap(long, action)]
+    pub fn_min_stmts: Option<u32>,
 
     /// Maximum number of statements to generate in function bodies
-    #[clap(long, action, default_value = "5")]
-    pub fn_max_stmts: u32,
+    #[clap(long, action)]
+    pub fn_max_stmts: Option<u32>,
 
     /// Minimum number of statements to generate in blocks (if, loop, etc)
-    #[clap(long, action, default_value = "0")]
-    pub block_min_stmts: u32,
+    #[clap(long, action)]
+    pub block_min_stmts: Option<u32>,
 
     /// Maximum number of statements to generate in blocks (if, loop, etc)
-    #[clap(long, action, default_value = "5")]
-    pub block_max_stmts: u32,
+    #[clap(long, action)]
+    pub block_max_stmts: Option<u32>,
+
+    /// Minimum expression depth
+    ///
+    /// Expressions are built from operators and constructors (rather than literals and variables)
+    /// until this depth is reached, wherever the type allows it. Must be at most 5, which is the
+    /// maximum expression depth.
+    #[clap(long, action)]
+    pub min_expr_depth: Option<u32>,
+
+    /// Depth of nested `select` chains
+    ///
+    /// When set, expressions are sometimes generated as a chain of `select` calls, each taking
+    /// the next one as its true or false operand, e.g. `select(select(a, b, c), d, e)`. Chains
+    /// are cut short at the maximum expression depth, so this must be at most 5. 0 (the default)
+    /// disables chains.
+    #[clap(long, action)]
+    pub select_depth: Option<u32>,
 
     /// Maximum nested block depth
-    #[clap(long, action, default_value = "3")]
-    pub max_block_depth: u32,
+    #[clap(long, action)]
+    pub max_block_depth: Option<u32>,
+
+    /// Maximum nesting depth of bare compound blocks (`{ ... }`)
+    ///
+    /// Declarations inside a compound block may shadow local variables from enclosing scopes. Use
+    /// 0 to disable compound blocks.
+    #[clap(long, action)]
+    pub max_compound_depth: Option<u32>,
+
+    /// Maximum number of variables in scope at once, including globals and function parameters
+    ///
+    /// Once the limit is reached, no more variables are declared and statements are generated
+    /// using the existing variables instead. Unlimited by default.
+    #[clap(long, action)]
+    pub max_total_vars: Option<u32>,
 
     /// Maximum number of function to generate
-    #[clap(long, action, default_value = "5")]
-    pub max_fns: u32,
+    #[clap(long, action)]
+    pub max_fns: Option<u32>,
 
     /// Minimum number of structs to generate (excluding input and output)
-    #[clap(long, action, default_value = "1")]
-    pub min_structs: u32,
+    #[clap(long, action)]
+    pub min_structs: Option<u32>,
 
     /// Maximum number of structs to generate (excluding input and output)
-    #[clap(long, action, default_value = "5")]
-    pub max_structs: u32,
+    #[clap(long, action)]
+    pub max_structs: Option<u32>,
 
     /// Minimum number of members allowed in a struct
-    #[clap(long, action, default_value = "1")]
-    pub min_struct_members: u32,
+    #[clap(long, action)]
+    pub min_struct_members: Option<u32>,
 
     /// Maximum number of members allowed in a struct
-    #[clap(long, action, default_value = "5")]
-    pub max_struct_members: u32,
+    #[clap(long, action)]
+    pub max_struct_members: Option<u32>,
+
+    /// Probability of choosing a vector type (rather than a scalar or struct) when selecting a
+    /// type for a variable or function.
+    ///
+    /// By default, scalars, vectors and structs are all chosen with equal probability.
+    #[clap(long, action, value_parser = parse_probability)]
+    pub vector_probability: Option<f64>,
+
+    /// Only generate values of a single scalar type and its vectors - must be one of {i32, u32,
+    /// bool, f32}.
+    ///
+    /// This is useful for isolating bugs that only affect one type. Other types are still used
+    /// where WGSL requires them, e.g. `bool` for conditions and `u32` for array indices. Since
+    /// `bool` can't be stored in buffers, the input and output buffers use `u32` instead.
+    #[clap(long, action)]
+    pub scalar_type: Option<ScalarType>,
+
+    /// Path to a JSON file describing the capabilities of the target device, as printed by the
+    /// harness `capabilities` command.
+    ///
+    /// This is used to avoid generating features that the device doesn't support, or buffers that
+    /// are larger than its limits. If not set, the minimum capabilities guaranteed by WebGPU are
+    /// assumed.
+    #[clap(long, action, value_parser = parse_capabilities)]
+    pub capabilities: Option<DeviceCapabilities>,
+
+    /// Relative weights for choosing the width of a vector type, in the order `vec2 vec3 vec4`.
+    ///
+    /// For example, `--vector-width-weights 1 4 1` makes `vec3` four times as likely as the other
+    /// widths. At least one weight must be non-zero.
+    #[clap(
+        long,
+        action,
+        number_of_values = 3,
+        value_names = &["VEC2", "VEC3", "VEC4"]
+    )]
+    pub vector_width_weights: Option<Vec<u32>>,
+
+    /// Override the relative weight of a kind of statement, e.g. `--stmt-weight assignment=30`.
+    ///
+    /// The kind must be one of {let, var, assignment, compound, if, return, loop, switch, for,
+    /// while, break, continue, barrier}. May be passed multiple times. Kinds that aren't overridden keep
+    /// their default weight.
+    #[clap(
+        long = "stmt-weight",
+        action,
+        value_name = "KIND=WEIGHT",
+        value_parser = parse_stmt_weight
+    )]
+    pub stmt_weights: Vec<(String, u32)>,
+
+    /// Never generate a kind of statement or expression, e.g. `--exclude-construct switch`.
+    ///
+    /// The kind must be one of the statement kinds accepted by `--stmt-weight`, or one of the
+    /// expression kinds {variable, unary, binary, call}. May be passed multiple times. Literals
+    /// and constructors can't be excluded, since every expression ends in one of them.
+    #[clap(
+        long = "exclude-construct",
+        action,
+        value_name = "KIND",
+        value_parser = parse_construct
+    )]
+    pub excluded_constructs: Vec<String>,
+
+    /// Write a JSONL trace of every generated expression and statement to this path.
+    ///
+    /// This is intended for analysing the shape of generated programs, and doesn't affect the
+    /// generated shader.
+    #[clap(long, action)]
+    pub trace_json: Option<String>,
+
+    /// Restrict generated integer literals to the range `MIN..=MAX`.
+    ///
+    /// The range applies to both signed and unsigned literals. Unsigned literals use the
+    /// non-negative part of the range, so `MAX` must not be negative.
+    #[clap(
+        long,
+        action,
+        number_of_values = 2,
+        value_names = &["MIN", "MAX"],
+        allow_hyphen_values = true
+    )]
+    pub lit_range: Option<Vec<i32>>,
+
+    /// Path to a JSON file with generation settings (see `GenerationConfig`).
+    ///
+    /// Any generation options that are also passed on the command line override the values from
+    /// the file.
+    #[clap(long, action, value_parser = GenerationConfig::load)]
+    pub gen_config: Option<GenerationConfig>,
 
     /// Preset options configuration. Individual options may still be overridden.
     #[clap(long, action)]
     pub preset: Option<Preset>,
 
+    /// Language feature profile to target - must be one of {full, integer, minimal}.
+    ///
+    /// Features that are disabled by the profile will never be generated, even if they are
+    /// enabled by other options.
+    #[clap(long, action)]
+    pub profile: Option<Profile>,
+
     /// Recondition the resulting program to remove UB
     #[clap(long, action)]
     pub recondition: bool,
@@ -115,9 +366,191 @@ pub struct Options {
     #[clap(long, action)]
     pub flow: bool,
 
+    /// Inject a pattern that is known to be compiled differently by some backends - must be one
+    /// of {shift-overflow, int-min-div, float-to-int}.
+    ///
+    /// The pattern is added to the end of the entrypoint after reconditioning, and its result is
+    /// written to the output buffer. This is for checking that the fuzzer detects and reduces
+    /// mismatches.
+    #[clap(long, action)]
+    pub inject_bug: Option<InjectedBug>,
+
     /// Path to output file (use `-` for stdout)
+    ///
+    /// When writing to a file, the buffer inputs are also written alongside it with a `.json`
+    /// extension.
     #[clap(short, long, action, default_value = "-")]
     pub output: String,
+
+    /// Directory to write the shader to, named after the seed (e.g. `<dir>/1234.wgsl`)
+    #[clap(long, action, conflicts_with = "output")]
+    pub output_dir: Option<String>,
+
+    /// Number of shaders to generate
+    ///
+    /// Shader `i` is generated from the seed `seed + i`. Generating more than one shader requires
+    /// `--output-dir`.
+    #[clap(long, action, default_value = "1")]
+    pub count: u64,
+}
+
+impl Options {
+    /// Returns the generation settings, starting from `--gen-config` (or the defaults) and
+    /// applying any options passed on the command line, the preset and the profile.
+    pub fn generation_config(&self) -> eyre::Result<GenerationConfig> {
+        let mut config = self.gen_config.clone().unwrap_or_default();
+
+        for builtin in &self.enabled_fns {
+            if !config.enabled_fns.contains(builtin) {
+                config.enabled_fns.push(*builtin);
+            }
+        }
+
+        // Flags can only turn features on
+        config.enable_pointers |= self.enable_pointers;
+        config.enable_barriers |= self.enable_barriers;
+        config.enable_builtin_inputs |= self.enable_builtin_inputs;
+        config.enable_f16 |= self.enable_f16;
+        config.straight_line |= self.straight_line;
+        config.annotate |= self.annotate;
+
+        macro_rules! override_with {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    config.$field = value;
+                })*
+            };
+        }
+
+        override_with!(
+            stage,
+            bind_groups,
+            invocations,
+            fn_min_stmts,
+            fn_max_stmts,
+            block_min_stmts,
+            block_max_stmts,
+            min_expr_depth,
+            select_depth,
+            max_block_depth,
+            max_compound_depth,
+            max_fns,
+            min_structs,
+            max_structs,
+            min_struct_members,
+            max_struct_members,
+            profile
+        );
+
+        if let Some(max_total_vars) = self.max_total_vars {
+            config.max_total_vars = Some(max_total_vars);
+        }
+
+        if let Some(vector_probability) = self.vector_probability {
+            config.vector_probability = Some(vector_probability);
+        }
+
+        if let Some(scalar_type) = self.scalar_type {
+            config.scalar_type = Some(scalar_type);
+        }
+
+        if let Some(weights) = &self.vector_width_weights {
+            config.vector_width_weights = [weights[0], weights[1], weights[2]];
+        }
+
+        for (kind, weight) in &self.stmt_weights {
+            config
+                .stmt_weights
+                .set(kind, *weight)
+                .expect("statement kinds are validated when parsing options");
+        }
+
+        for kind in &self.excluded_constructs {
+            if StmtWeights::KINDS.contains(&kind.as_str()) {
+                config
+                    .stmt_weights
+                    .set(kind, 0)
+                    .expect("statement kinds are validated when parsing options");
+            } else {
+                let expr = kind
+                    .parse()
+                    .expect("expression kinds are validated when parsing options");
+                if !config.excluded_exprs.contains(&expr) {
+                    config.excluded_exprs.push(expr);
+                }
+            }
+        }
+
+        if let Some(range) = &self.lit_range {
+            config.lit_range = Some((range[0], range[1]));
+        }
+
+        if let Some(capabilities) = &self.capabilities {
+            config.capabilities = Some(capabilities.clone());
+        }
+
+        if self.preset == Some(Preset::Tint) {
+            for builtin in builtins::TINT_EXTRAS {
+                if !config.enabled_fns.contains(builtin) {
+                    config.enabled_fns.push(*builtin);
+                }
+            }
+
+            config.enable_pointers = true;
+        }
+
+        if !config.profile.pointers {
+            config.enable_pointers = false;
+        }
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+fn parse_stmt_weight(value: &str) -> Result<(String, u32), String> {
+    let (kind, weight) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected KIND=WEIGHT, got `{value}`"))?;
+
+    let weight = weight.parse().map_err(|e| format!("{e}"))?;
+
+    // Check that the kind is valid
+    StmtWeights::default().set(kind, weight)?;
+
+    Ok((kind.to_owned(), weight))
+}
+
+fn parse_construct(value: &str) -> Result<String, String> {
+    if StmtWeights::KINDS.contains(&value) {
+        return Ok(value.to_owned());
+    }
+
+    match value.parse::<ExprType>() {
+        Ok(expr) if expr.is_leaf() => Err(format!("`{value}` expressions can't be excluded")),
+        Ok(_) => Ok(value.to_owned()),
+        Err(_) => Err(format!(
+            "unknown construct `{value}` - must be a statement kind ({}) or an expression kind \
+             (variable, unary, binary, call)",
+            StmtWeights::KINDS.join(", ")
+        )),
+    }
+}
+
+fn parse_capabilities(path: &str) -> Result<DeviceCapabilities, String> {
+    let json =
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read `{path}`: {e}"))?;
+    serde_json::from_str(&json).map_err(|e| format!("invalid capabilities in `{path}`: {e}"))
+}
+
+fn parse_probability(value: &str) -> Result<f64, String> {
+    let value: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{value} is not between 0 and 1"))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -132,23 +565,9 @@ impl BuildHasher for BuildFxHasher {
 }
 
 pub fn run(mut options: Options) -> eyre::Result<()> {
-    if let Some(preset) = &options.preset {
-        match preset {
-            Preset::Tint => {
-                for builtin in builtins::TINT_EXTRAS {
-                    if !options.enabled_fns.iter().any(|it| it == builtin) {
-                        options.enabled_fns.push(builtin.to_owned());
-                    }
-                }
+    apply_preset(&mut options);
 
-                options.enable_pointers = true;
-                options.skip_pointer_checks = true;
-                options.recondition = true;
-            }
-        }
-    }
-
-    let options = Rc::new(options);
+    let config = Rc::new(options.generation_config()?);
 
     //tracing_subscriber::fmt()
     //    .compact()
@@ -163,18 +582,105 @@ pub fn run(mut options: Options) -> eyre::Result<()> {
     //    })
     //    .init();
 
+    if options.count > 1 {
+        if options.output_dir.is_none() {
+            bail!("--output-dir is required when generating more than one shader");
+        }
+
+        if options.trace_json.is_some() {
+            bail!("--trace-json can't be used when generating more than one shader");
+        }
+    }
+
     let seed = match options.seed {
         Some(seed) => seed,
         None => OsRng.gen(),
     };
 
+    for i in 0..options.count {
+        gen_shader(&options, &config, seed.wrapping_add(i))?;
+    }
+
+    Ok(())
+}
+
+/// Generates the shader for `seed` and returns it exactly as `run` would write it to stdout.
+///
+/// The seed and output options are ignored.
+pub fn gen_to_string(mut options: Options, seed: u64) -> eyre::Result<String> {
+    apply_preset(&mut options);
+
+    let config = Rc::new(options.generation_config()?);
+    let (shader, _) = render_shader(&options, &config, seed)?;
+
+    Ok(shader)
+}
+
+fn apply_preset(options: &mut Options) {
+    if options.preset == Some(Preset::Tint) {
+        options.skip_pointer_checks = true;
+        options.recondition = true;
+    }
+}
+
+fn gen_shader(options: &Options, config: &Rc<GenerationConfig>, seed: u64) -> eyre::Result<()> {
+    let (shader, init_data) = render_shader(options, config, seed)?;
+
+    let output_path = match &options.output_dir {
+        Some(dir) => Some(Path::new(dir).join(format!("{seed}.wgsl"))),
+        None if options.output == "-" => None,
+        None => Some(PathBuf::from(&options.output)),
+    };
+
+    if let Some(path) = &output_path {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        if let Some(init_data) = &init_data {
+            std::fs::write(path.with_extension("json"), init_data)?;
+        }
+
+        std::fs::write(path, shader)?;
+    } else {
+        io::stdout().write_all(shader.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Generates the shader for `seed`, returning the formatted output and the buffer inputs (unless
+/// printing the AST with `--debug` or `--emit ast-json`).
+fn render_shader(
+    options: &Options,
+    config: &Rc<GenerationConfig>,
+    seed: u64,
+) -> eyre::Result<(String, Option<String>)> {
     tracing::info!("generating shader from seed: {}", seed);
 
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut shader = Generator::new(&mut rng, options.clone()).gen_module();
+    let mut rng = GenRng::seed_from_u64(seed);
+    let mut generator = Generator::new(&mut rng, config.clone());
+
+    if let Some(path) = &options.trace_json {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        generator.set_tracer(Tracer::new(Box::new(BufWriter::new(File::create(path)?))));
+    }
+
+    let mut shader = generator.gen_module();
+
+    if let Some(tracer) = generator.take_tracer() {
+        tracer.finish()?;
+    }
+
+    tracing::info!(
+        "max expression depth reached: {}",
+        generator.max_depth_reached()
+    );
 
     if options.recondition {
-        if options.enable_pointers
+        if config.enable_pointers
             && !options.skip_pointer_checks
             && !reconditioner::analysis::analyse(&shader)
         {
@@ -193,17 +699,16 @@ pub fn run(mut options: Options) -> eyre::Result<()> {
         shader = flow::flow_with(shader, flow::Options::default());
     }
 
-    let mut output: Box<dyn io::Write> = if options.output == "-" {
-        Box::new(io::stdout())
-    } else {
-        if let Some(dir) = Path::new(&options.output).parent() {
-            std::fs::create_dir_all(dir)?;
-        }
-        Box::new(BufWriter::new(File::create(&options.output)?))
-    };
+    if let Some(bug) = options.inject_bug {
+        // Use a separate RNG so that the buffer inputs are the same as without the bug
+        bug.inject(&mut shader, &mut GenRng::seed_from_u64(seed))?;
+    }
 
-    if !options.debug {
-        let mut init_data = HashMap::new();
+    let mut output = String::new();
+    let mut init_data = None;
+
+    if !options.debug && options.emit == Emit::Wgsl {
+        let mut buffers = HashMap::new();
 
         for var in &shader.vars {
             if let Some(VarQualifier { storage_class, .. }) = &var.qualifier {
@@ -219,31 +724,137 @@ pub fn run(mut options: Options) -> eyre::Result<()> {
                 let size = type_desc.buffer_size();
                 let data: Vec<u8> = (0..size).map(|_| rng.gen()).collect();
 
-                init_data.insert(format!("{group}:{binding}"), data);
+                buffers.insert(format!("{group}:{binding}"), data);
             }
         }
 
-        let init_data = serde_json::to_string(&init_data)?;
+        let buffers = serde_json::to_string(&buffers)?;
 
-        writeln!(output, "// {init_data}")?;
+        writeln!(output, "// {buffers}")?;
         writeln!(output, "// Seed: {seed}")?;
         writeln!(output)?;
+
+        init_data = Some(buffers);
     }
 
     if options.debug {
         writeln!(output, "{shader:#?}")?;
     } else {
-        struct Output<'a>(&'a mut dyn std::io::Write);
+        match options.emit {
+            Emit::Wgsl => ast::writer::Writer::default()
+                .explicit_types(options.explicit_types)
+                .write_module(&mut output, &shader)?,
+            Emit::AstJson => writeln!(output, "{}", shader.to_json()?)?,
+        }
+    }
+
+    Ok((output, init_data))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+    use std::rc::Rc;
 
-        impl<'a> std::fmt::Write for Output<'a> {
-            fn write_str(&mut self, s: &str) -> std::fmt::Result {
-                self.0.write_all(s.as_bytes()).unwrap();
-                Ok(())
+    use ast::Module;
+    use clap::Parser;
+    use rand::SeedableRng;
+
+    use super::{gen_to_string, GenRng, Generator, InjectedBug, Options};
+
+    /// Generates a module for each seed in `seeds`, using the options parsed from `args`.
+    pub(crate) fn gen_modules(args: &[&str], seeds: Range<u64>) -> impl Iterator<Item = Module> {
+        gen_modules_with(Options::parse_from(args), seeds)
+    }
+
+    /// Like [`gen_modules`], for options that can't easily be passed as arguments.
+    pub(crate) fn gen_modules_with(
+        options: Options,
+        seeds: Range<u64>,
+    ) -> impl Iterator<Item = Module> {
+        let config = Rc::new(options.generation_config().unwrap());
+        seeds.map(move |seed| {
+            Generator::new(&mut GenRng::seed_from_u64(seed), config.clone()).gen_module()
+        })
+    }
+
+    #[test]
+    fn ast_json_round_trip() {
+        for (seed, expected) in (0..10).zip(gen_modules(&["generator"], 0..10)) {
+            let options = Options::parse_from(["generator", "--emit", "ast-json"]);
+            let json = gen_to_string(options, seed).unwrap();
+            let module = Module::from_json(&json).unwrap();
+
+            assert_eq!(module, expected, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn scalar_type_restricts_structs() {
+        for ty in ["i32", "u32", "bool", "f32"] {
+            let modules = gen_modules(&["generator", "--scalar-type", ty], 0..10);
+
+            for (seed, module) in modules.enumerate() {
+                if let Err(e) = ast::typecheck::typecheck(&module) {
+                    panic!("module for `{ty}` with seed {seed} is ill-typed: {e}");
+                }
+
+                let members = module
+                    .structs
+                    .iter()
+                    .filter(|decl| !["UniformBuffer", "StorageBuffer"].contains(&&*decl.name))
+                    .flat_map(|decl| &decl.members);
+
+                for member in members {
+                    if let Some(scalar) = member.data_type.as_scalar() {
+                        assert_eq!(scalar.to_string(), ty, "seed {seed}");
+                    }
+                }
             }
         }
+    }
+
+    #[test]
+    fn injected_bugs_are_well_typed() {
+        for bug in InjectedBug::ALL {
+            for stage in ["compute", "fragment"] {
+                let modules = gen_modules(&["generator", "--stage", stage], 0..10);
+
+                for (seed, mut module) in (0..10).zip(modules) {
+                    bug.inject(&mut module, &mut GenRng::seed_from_u64(seed))
+                        .unwrap();
+
+                    if let Err(e) = ast::typecheck::typecheck(&module) {
+                        panic!("`{}` with seed {seed} is ill-typed: {e}", bug.name());
+                    }
 
-        ast::writer::Writer::default().write_module(&mut Output(&mut output), &shader)?;
+                    let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+                    assert!(main.body.iter().any(|stmt| {
+                        matches!(stmt, ast::Statement::VarDecl(decl) if decl.ident == "injected_0")
+                    }));
+                }
+            }
+        }
     }
 
-    Ok(())
+    #[test]
+    fn explicit_types() {
+        for seed in 0..10 {
+            let options = Options::parse_from(["generator", "--explicit-types"]);
+            let shader = gen_to_string(options, seed).unwrap();
+
+            for line in shader.lines().map(str::trim) {
+                let decl = match line.strip_prefix("let ").or(line.strip_prefix("var ")) {
+                    Some(decl) => decl,
+                    None => continue,
+                };
+
+                let name = decl.split(" = ").next().unwrap();
+                assert!(
+                    name.contains(": "),
+                    "untyped declaration `{line}` in:\n{shader}"
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,75 @@
+use std::str::FromStr;
+
+use ast::ScalarType;
+
+/// Set of language features that the generator is allowed to use.
+///
+/// This is used to avoid generating shaders that will be trivially rejected by an implementation
+/// that doesn't support a particular feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// Allow floating point scalars and vectors. Note that `f16` must still be enabled with
+    /// `--enable-f16`.
+    pub f32: bool,
+    /// Allow pointers. Note that pointers must still be enabled with `--enable-pointers`.
+    pub pointers: bool,
+    /// Allow `switch` statements.
+    pub switch: bool,
+    /// Allow `for` loops.
+    pub for_loops: bool,
+    /// Allow `while` loops.
+    pub while_loops: bool,
+}
+
+impl Profile {
+    /// Profile allowing every feature supported by the generator.
+    pub const FULL: Profile = Profile {
+        f32: true,
+        pointers: true,
+        switch: true,
+        for_loops: true,
+        while_loops: true,
+    };
+
+    /// Profile which avoids floating point types, since they are a common source of false
+    /// positive mismatches between implementations.
+    pub const INTEGER: Profile = Profile {
+        f32: false,
+        ..Profile::FULL
+    };
+
+    /// Profile restricted to the most basic language features.
+    pub const MINIMAL: Profile = Profile {
+        f32: false,
+        pointers: false,
+        switch: false,
+        for_loops: false,
+        while_loops: false,
+    };
+
+    pub fn allows_scalar(&self, ty: ScalarType) -> bool {
+        match ty {
+            ScalarType::F32 | ScalarType::F16 => self.f32,
+            _ => true,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::FULL
+    }
+}
+
+impl FromStr for Profile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Profile::FULL),
+            "integer" => Ok(Profile::INTEGER),
+            "minimal" => Ok(Profile::MINIMAL),
+            _ => Err("invalid profile - must be one of {full, integer, minimal}"),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+/// Relative weights for choosing which kind of statement to generate.
+///
+/// Only the kinds of statement that are valid at the current point are considered, so for example
+/// `break_` is only used inside loops and `assignment` is only used once a mutable variable is in
+/// scope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StmtWeights {
+    pub let_decl: u32,
+    pub var_decl: u32,
+    pub assignment: u32,
+    pub compound: u32,
+    pub if_: u32,
+    pub return_: u32,
+    pub loop_: u32,
+    pub switch: u32,
+    pub for_loop: u32,
+    pub while_: u32,
+    pub break_: u32,
+    pub continue_: u32,
+    pub barrier: u32,
+}
+
+impl Default for StmtWeights {
+    fn default() -> Self {
+        StmtWeights {
+            let_decl: 10,
+            var_decl: 10,
+            assignment: 10,
+            compound: 1,
+            if_: 5,
+            return_: 1,
+            loop_: 5,
+            switch: 5,
+            for_loop: 5,
+            while_: 5,
+            break_: 5,
+            continue_: 5,
+            barrier: 5,
+        }
+    }
+}
+
+impl StmtWeights {
+    /// Names accepted by [`set`](Self::set), in the same order as the fields.
+    pub const KINDS: &'static [&'static str] = &[
+        "let",
+        "var",
+        "assignment",
+        "compound",
+        "if",
+        "return",
+        "loop",
+        "switch",
+        "for",
+        "while",
+        "break",
+        "continue",
+        "barrier",
+    ];
+
+    /// Sets the weight for the statement kind with the given name (e.g. `let` or `for`).
+    pub fn set(&mut self, kind: &str, weight: u32) -> Result<(), String> {
+        let field = match kind {
+            "let" => &mut self.let_decl,
+            "var" => &mut self.var_decl,
+            "assignment" => &mut self.assignment,
+            "compound" => &mut self.compound,
+            "if" => &mut self.if_,
+            "return" => &mut self.return_,
+            "loop" => &mut self.loop_,
+            "switch" => &mut self.switch,
+            "for" => &mut self.for_loop,
+            "while" => &mut self.while_,
+            "break" => &mut self.break_,
+            "continue" => &mut self.continue_,
+            "barrier" => &mut self.barrier,
+            _ => {
+                return Err(format!(
+                    "unknown statement kind `{kind}` - must be one of {{{}}}",
+                    Self::KINDS.join(", ")
+                ))
+            }
+        };
+
+        *field = weight;
+
+        Ok(())
+    }
+}
@@ -100,9 +100,34 @@ pub fn reflect_shader(
     (pipeline_desc, type_descs)
 }
 
+/// Exit code used by the `run` command if the executor gave up waiting for the execution to finish.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Exit code used by the `run` command if a configuration produced different outputs when the
+/// shader was executed repeatedly (see `--repeat`).
+pub const NONDETERMINISTIC_EXIT_CODE: i32 = 125;
+
+/// Exit code used by the `run` command if the shader needs more resources than a configuration's
+/// device limits allow, e.g. a buffer larger than the maximum binding size. This is also the exit
+/// code of the process that executes a single configuration in that case.
+pub const EXCEEDS_LIMITS_EXIT_CODE: i32 = 123;
+
+/// Exit code used by the `run` command if a configuration's device rejected the shader because it
+/// uses a feature that isn't enabled on the device, e.g. `f16` without the `shader-f16` feature.
+/// Like [`EXCEEDS_LIMITS_EXIT_CODE`], this is also used by the process executing a configuration.
+pub const UNSUPPORTED_FEATURE_EXIT_CODE: i32 = 122;
+
 #[derive(Debug)]
 pub enum ExecutionError {
     NoDefaultConfigs,
+    /// A configuration failed to execute the shader.
+    Failure,
+    /// The executor didn't receive a result before its deadline.
+    Timeout,
+    /// The shader exceeds the limits of a configuration's device.
+    ExceedsLimits(String),
+    /// The shader uses a feature that a configuration's device doesn't support.
+    UnsupportedFeature(String),
     Io(io::Error),
     Encode(bincode::error::EncodeError),
     Decode(bincode::error::DecodeError),
@@ -113,6 +138,10 @@ impl fmt::Display for ExecutionError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ExecutionError::NoDefaultConfigs => write!(f, "no suitable default configs found"),
+            ExecutionError::Failure => write!(f, "execution failed"),
+            ExecutionError::Timeout => write!(f, "timed out waiting for execution to finish"),
+            ExecutionError::ExceedsLimits(e) => e.fmt(f),
+            ExecutionError::UnsupportedFeature(e) => e.fmt(f),
             ExecutionError::Io(e) => e.fmt(f),
             ExecutionError::Encode(e) => e.fmt(f),
             ExecutionError::Decode(e) => e.fmt(f),
@@ -148,11 +177,13 @@ impl From<eyre::Report> for ExecutionError {
 }
 
 pub trait Executor {
+    #[allow(clippy::too_many_arguments)]
     fn execute(
         &self,
         shader: &str,
         workgroups: u32,
         flow: bool,
+        zero_init: bool,
         pipeline_desc: &PipelineDescription,
         configs: &[ConfigId],
         timeout: Option<Duration>,
@@ -160,12 +191,96 @@ pub trait Executor {
     ) -> Result<(), ExecutionError>;
 }
 
+/// Outputs of executing a shader several times with [`run_repeated`].
+pub struct RepeatedRun {
+    /// Output buffers of each configuration, with one entry per run.
+    pub outputs: Vec<(ConfigId, Vec<Vec<Vec<u8>>>)>,
+    /// Whether every configuration produced the same output on each run.
+    pub stable: bool,
+}
+
+/// Executes a shader `n` times with each of the given configurations, checking whether the outputs
+/// of each configuration are consistent across runs.
+///
+/// A shader that isn't self-consistent is likely to hit nondeterministic behaviour in the backend
+/// (or the shader itself), so output mismatches between configurations aren't meaningful.
+#[allow(clippy::too_many_arguments)]
+pub fn run_repeated(
+    executor: &dyn Executor,
+    shader: &str,
+    workgroups: u32,
+    pipeline_desc: &PipelineDescription,
+    type_descs: &[common::Type],
+    configs: &[ConfigId],
+    timeout: Option<Duration>,
+    zero_init: bool,
+    n: u32,
+) -> Result<RepeatedRun, ExecutionError> {
+    let mut outputs: Vec<(ConfigId, Vec<Vec<Vec<u8>>>)> = vec![];
+
+    for run in 0..n {
+        // Configurations are executed in the same order on every run
+        let mut index = 0;
+        let mut current_config = None;
+        let mut on_event = |event: ExecutionEvent| {
+            match event {
+                ExecutionEvent::Start(config) => current_config = Some(config),
+                ExecutionEvent::Success(buffers, _) => {
+                    let config = current_config.take().ok_or_else(|| {
+                        eyre!("received execution result before execution started")
+                    })?;
+                    if run == 0 {
+                        outputs.push((config, vec![buffers]));
+                    } else {
+                        outputs
+                            .get_mut(index)
+                            .ok_or_else(|| eyre!("`{config}` was not executed on the first run"))?
+                            .1
+                            .push(buffers);
+                    }
+                    index += 1;
+                }
+                ExecutionEvent::Failure(_) => return Err(ExecutionError::Failure),
+                ExecutionEvent::Timeout => return Err(ExecutionError::Timeout),
+                ExecutionEvent::ExceedsLimits(message) => {
+                    return Err(ExecutionError::ExceedsLimits(message))
+                }
+                ExecutionEvent::UnsupportedFeature(message) => {
+                    return Err(ExecutionError::UnsupportedFeature(message))
+                }
+                ExecutionEvent::UsingDefaultConfigs(_) => {}
+            }
+            Ok(())
+        };
+
+        executor.execute(
+            shader,
+            workgroups,
+            false,
+            zero_init,
+            pipeline_desc,
+            configs,
+            timeout,
+            &mut on_event,
+        )?;
+    }
+
+    let stable = outputs
+        .iter()
+        .all(|(_, runs)| buffer_check::compare(runs.iter(), pipeline_desc, type_descs));
+
+    Ok(RepeatedRun { outputs, stable })
+}
+
 pub mod cli {
     use std::time::Duration;
 
     use clap::Parser;
     use color_eyre::Help;
-    use eyre::eyre;
+    use common::{Type, Value};
+    use eyre::{eyre, Context};
+    use reflection::{PipelineDescription, ResourceKind};
+    use serde_json::json;
     use types::ConfigId;
 
     use crate::{ExecutionEvent, ExecutionResult, Executor};
@@ -202,6 +317,31 @@ pub mod cli {
         /// Number of workgroups
         #[clap(long, action, default_value = "1")]
         pub workgroups: u32,
+
+        /// Dump the contents of the output buffers to a file (use '-' for stdout).
+        ///
+        /// The dump is a JSON object mapping each configuration to its output buffers. Each buffer
+        /// contains its raw bytes as a hex string, along with the decoded values if the layout of
+        /// the buffer is known.
+        #[clap(long, action)]
+        pub dump_output: Option<String>,
+
+        /// Execute the shader this many times with each configuration before comparing outputs.
+        ///
+        /// If any configuration doesn't produce the same output on every run, the shader is
+        /// considered nondeterministic and the program exits with code 125 without comparing
+        /// configurations.
+        #[clap(long, action, default_value = "1")]
+        pub repeat: u32,
+
+        /// Don't zero the storage buffers before executing the shader.
+        ///
+        /// By default, storage buffers without input data are explicitly filled with zeros so that
+        /// any bytes which the shader doesn't write compare equal between configurations. This
+        /// option is for testing what implementations do with unwritten storage (note that
+        /// WebGPU implementations are still required to zero new buffers themselves).
+        #[clap(long, action)]
+        pub no_zero_init: bool,
     }
 
     pub fn run(options: RunOptions, executor: &dyn Executor) -> eyre::Result<()> {
@@ -211,11 +351,79 @@ pub mod cli {
 
         let printer = super::Printer::new();
 
+        let timeout = if options.timeout == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(options.timeout))
+        };
+
+        if options.repeat > 1 {
+            let result = super::run_repeated(
+                executor,
+                &shader,
+                options.workgroups,
+                &pipeline_desc,
+                &type_descs,
+                &options.configs,
+                timeout,
+                !options.no_zero_init,
+                options.repeat,
+            );
+
+            match result {
+                Err(crate::ExecutionError::Timeout) => {
+                    printer.print_execution_result(ExecutionResult::Timeout)?;
+                    std::process::exit(crate::TIMEOUT_EXIT_CODE);
+                }
+                Err(crate::ExecutionError::ExceedsLimits(message)) => {
+                    println!("{message}");
+                    printer.print_execution_result(ExecutionResult::ExceedsLimits)?;
+                    std::process::exit(crate::EXCEEDS_LIMITS_EXIT_CODE);
+                }
+                Err(crate::ExecutionError::UnsupportedFeature(message)) => {
+                    println!("{message}");
+                    printer.print_execution_result(ExecutionResult::UnsupportedFeature)?;
+                    std::process::exit(crate::UNSUPPORTED_FEATURE_EXIT_CODE);
+                }
+                // Crash like a normal run does, so a failure can't be mistaken for a mismatch
+                Err(crate::ExecutionError::Failure) => panic!("one or more executions failed"),
+                _ => {}
+            }
+
+            let repeated = result.map_err(execution_error)?;
+            if !repeated.stable {
+                for (config, runs) in &repeated.outputs {
+                    if !buffer_check::compare(runs.iter(), &pipeline_desc, &type_descs) {
+                        println!("outputs of `{config}` differ between runs");
+                    }
+                }
+
+                printer.print_execution_result(ExecutionResult::Nondeterministic)?;
+                std::process::exit(crate::NONDETERMINISTIC_EXIT_CODE);
+            }
+        }
+
         let mut executions = vec![];
+        let mut dumps = serde_json::Map::new();
+        let mut current_config = None;
         let mut is_fail = false;
+        let mut exceeds_limits = false;
+        let mut unsupported_feature = false;
         let mut on_event = |event: ExecutionEvent| {
-            printer.print_execution_event(&event, &pipeline_desc)?;
-            if let ExecutionEvent::Success(buffers, _) = event {
+            printer.print_execution_event(&event, &pipeline_desc, &type_descs)?;
+            if let ExecutionEvent::Start(config) = &event {
+                current_config = Some(config.clone());
+            } else if let ExecutionEvent::ExceedsLimits(_) = event {
+                exceeds_limits = true
+            } else if let ExecutionEvent::UnsupportedFeature(_) = event {
+                unsupported_feature = true
+            } else if let ExecutionEvent::Success(buffers, _) = event {
+                if let Some(config) = &current_config {
+                    dumps.insert(
+                        config.to_string(),
+                        dump_buffers(&buffers, &pipeline_desc, &type_descs),
+                    );
+                }
                 executions.push(buffers);
             } else if let ExecutionEvent::Failure(_) = event {
                 is_fail = true
@@ -223,36 +431,230 @@ pub mod cli {
             Ok(())
         };
 
+        let result = executor.execute(
+            &shader,
+            options.workgroups,
+            options.flow,
+            !options.no_zero_init,
+            &pipeline_desc,
+            &options.configs,
+            timeout,
+            &mut on_event,
+        );
+
+        if let Err(crate::ExecutionError::Timeout) = result {
+            printer.print_execution_result(ExecutionResult::Timeout)?;
+            std::process::exit(crate::TIMEOUT_EXIT_CODE);
+        }
+
+        result.map_err(execution_error)?;
+
+        if let Some(path) = &options.dump_output {
+            let dump = serde_json::to_string_pretty(&dumps)?;
+            if path == "-" {
+                println!("{dump}");
+            } else {
+                std::fs::write(path, dump)
+                    .wrap_err_with(|| eyre!("failed to write output dump to '{path}'"))?;
+            }
+        }
+
+        // A shader that can't run within a device's limits isn't a bug in the implementation, and
+        // there's nothing to compare its output against
+        if exceeds_limits {
+            printer.print_execution_result(ExecutionResult::ExceedsLimits)?;
+            std::process::exit(crate::EXCEEDS_LIMITS_EXIT_CODE);
+        }
+
+        // Similarly, a device rejecting a feature it doesn't support is expected
+        if unsupported_feature {
+            printer.print_execution_result(ExecutionResult::UnsupportedFeature)?;
+            std::process::exit(crate::UNSUPPORTED_FEATURE_EXIT_CODE);
+        }
+
+        if is_fail {
+            panic!("one or more executions failed");
+        }
+
+        if buffer_check::compare(executions.iter(), &pipeline_desc, &type_descs) {
+            printer.print_execution_result(ExecutionResult::Ok)?;
+        } else {
+            printer.print_execution_result(ExecutionResult::Mismatch)?;
+            std::process::exit(1);
+        }
+
+        Ok(())
+    }
+
+    #[derive(Parser)]
+    pub struct CheckOutputOptions {
+        /// Path to wgsl shader program to be executed (use '-' for stdin)
+        #[clap(action)]
+        pub shader: String,
+
+        /// Input data for uniform buffers.
+        #[clap(action)]
+        pub input_data: Option<String>,
+
+        /// Path to a file containing the expected contents of the storage buffers.
+        ///
+        /// This contains the raw bytes of each storage buffer, concatenated in binding order.
+        #[clap(long, action)]
+        pub expected: String,
+
+        /// Write the output of the execution to the expected file, instead of comparing against it.
+        #[clap(long, action)]
+        pub bless: bool,
+
+        /// Maximum absolute difference allowed between f32 values.
+        ///
+        /// If not set, all values must match exactly.
+        #[clap(long, action)]
+        pub tolerance: Option<f32>,
+
+        /// List of configurations to test.
+        ///
+        /// The output of each configuration is compared against the expected output separately.
+        /// If no configurations are provided, defaults will be selected for the execution platform.
+        #[clap(short, long = "config", action)]
+        pub configs: Vec<ConfigId>,
+
+        /// Timeout in seconds.
+        ///
+        /// Use 0 to disable the timeout. Note that the timeout is per-execution rather than a global timeout.
+        #[clap(long, action, default_value = "30")]
+        pub timeout: u64,
+
+        /// Number of workgroups
+        #[clap(long, action, default_value = "1")]
+        pub workgroups: u32,
+    }
+
+    pub fn check_output(options: CheckOutputOptions, executor: &dyn Executor) -> eyre::Result<()> {
+        let shader = super::read_shader_from_path(&options.shader)?;
+        let input_data = super::read_input_data(&options.shader, options.input_data.as_deref())?;
+        let (pipeline_desc, type_descs) = super::reflect_shader(&shader, input_data);
+
+        let printer = super::Printer::new();
+
+        let mut executions = vec![];
+        let mut current_config = None;
+        let mut is_fail = false;
+        let mut timed_out = false;
+        let mut exceeds_limits = false;
+        let mut unsupported_feature = false;
+        let mut on_event = |event: ExecutionEvent| {
+            printer.print_execution_event(&event, &pipeline_desc, &type_descs)?;
+            if let ExecutionEvent::Start(config) = &event {
+                current_config = Some(config.clone());
+            } else if let ExecutionEvent::Success(buffers, _) = event {
+                if let Some(config) = current_config.take() {
+                    executions.push((config, buffers));
+                }
+            } else if let ExecutionEvent::Timeout = event {
+                timed_out = true
+            } else if let ExecutionEvent::ExceedsLimits(_) = event {
+                exceeds_limits = true
+            } else if let ExecutionEvent::UnsupportedFeature(_) = event {
+                unsupported_feature = true
+            } else if let ExecutionEvent::Failure(_) = event {
+                is_fail = true
+            }
+            Ok(())
+        };
+
         let timeout = if options.timeout == 0 {
             None
         } else {
             Some(Duration::from_secs(options.timeout))
         };
 
-        executor
-            .execute(
-                &shader,
-                options.workgroups,
-                options.flow,
-                &pipeline_desc,
-                &options.configs,
-                timeout,
-                &mut on_event,
-            )
-            .map_err(|e| match e {
-                crate::ExecutionError::NoDefaultConfigs => {
-                    eyre!("failed to find any suitable default configurations")
-                        .with_note(|| "use the `list` command to see all available configurations")
-                }
-                crate::ExecutionError::Other(e) => e,
-                e => eyre!(e),
-            })?;
+        let result = executor.execute(
+            &shader,
+            options.workgroups,
+            false,
+            true,
+            &pipeline_desc,
+            &options.configs,
+            timeout,
+            &mut on_event,
+        );
+
+        // Results other than a mismatch are reported with the same exit codes as the `run`
+        // command, so that callers can tell them apart
+        if timed_out || matches!(result, Err(crate::ExecutionError::Timeout)) {
+            printer.print_execution_result(ExecutionResult::Timeout)?;
+            std::process::exit(crate::TIMEOUT_EXIT_CODE);
+        }
+
+        result.map_err(execution_error)?;
+
+        if exceeds_limits {
+            printer.print_execution_result(ExecutionResult::ExceedsLimits)?;
+            std::process::exit(crate::EXCEEDS_LIMITS_EXIT_CODE);
+        }
+
+        if unsupported_feature {
+            printer.print_execution_result(ExecutionResult::UnsupportedFeature)?;
+            std::process::exit(crate::UNSUPPORTED_FEATURE_EXIT_CODE);
+        }
 
         if is_fail {
             panic!("one or more executions failed");
         }
 
-        if buffer_check::compare(executions.iter(), &pipeline_desc, &type_descs) {
+        if options.bless {
+            if !buffer_check::compare(
+                executions.iter().map(|(_, buffers)| buffers),
+                &pipeline_desc,
+                &type_descs,
+            ) {
+                return Err(eyre!(
+                    "executions produced different outputs, refusing to write expected output"
+                ));
+            }
+
+            let (_, buffers) = executions
+                .first()
+                .ok_or_else(|| eyre!("no executions were run"))?;
+
+            std::fs::write(&options.expected, buffers.concat()).wrap_err_with(|| {
+                eyre!("failed to write expected output to '{}'", options.expected)
+            })?;
+
+            println!("wrote expected output to '{}'", options.expected);
+
+            return Ok(());
+        }
+
+        let expected = std::fs::read(&options.expected).wrap_err_with(|| {
+            eyre!("failed to read expected output from '{}'", options.expected)
+        })?;
+
+        let mut is_match = true;
+
+        for (config, buffers) in &executions {
+            let expected_buffers = split_buffers(&expected, buffers).ok_or_else(|| {
+                eyre!(
+                    "expected output is {} bytes, but `{config}` produced {} bytes",
+                    expected.len(),
+                    buffers.iter().map(|it| it.len()).sum::<usize>()
+                )
+            })?;
+
+            if !buffer_check::compare_expected(
+                buffers,
+                &expected_buffers,
+                &pipeline_desc,
+                &type_descs,
+                options.tolerance,
+            ) {
+                println!("output of `{config}` does not match expected output");
+                is_match = false;
+            }
+        }
+
+        if is_match {
             printer.print_execution_result(ExecutionResult::Ok)?;
         } else {
             printer.print_execution_result(ExecutionResult::Mismatch)?;
@@ -261,4 +663,76 @@ pub mod cli {
 
         Ok(())
     }
+
+    /// Splits the concatenated contents of `bytes` into buffers with the same sizes as `buffers`.
+    fn split_buffers(bytes: &[u8], buffers: &[Vec<u8>]) -> Option<Vec<Vec<u8>>> {
+        if bytes.len() != buffers.iter().map(|it| it.len()).sum::<usize>() {
+            return None;
+        }
+
+        let mut offset = 0;
+        let split = buffers
+            .iter()
+            .map(|buffer| {
+                let chunk = bytes[offset..offset + buffer.len()].to_vec();
+                offset += buffer.len();
+                chunk
+            })
+            .collect();
+
+        Some(split)
+    }
+
+    fn execution_error(e: crate::ExecutionError) -> eyre::Report {
+        match e {
+            crate::ExecutionError::NoDefaultConfigs => {
+                eyre!("failed to find any suitable default configurations")
+                    .with_note(|| "use the `list` command to see all available configurations")
+            }
+            crate::ExecutionError::Other(e) => e,
+            e => eyre!(e),
+        }
+    }
+
+    fn dump_buffers(
+        buffers: &[Vec<u8>],
+        pipeline_desc: &PipelineDescription,
+        type_descs: &[Type],
+    ) -> serde_json::Value {
+        let dumps = pipeline_desc
+            .resources
+            .iter()
+            .enumerate()
+            .filter(|(_, it)| it.kind == ResourceKind::StorageBuffer)
+            .zip(buffers)
+            .map(|((index, resource), buffer)| {
+                let hex = buffer
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<String>();
+                let values = type_descs
+                    .get(index)
+                    .and_then(|type_desc| type_desc.layout().read(buffer))
+                    .map(|values| values.iter().map(value_to_json).collect::<Vec<_>>());
+
+                json!({
+                    "group": resource.group,
+                    "binding": resource.binding,
+                    "hex": hex,
+                    "values": values,
+                })
+            })
+            .collect();
+
+        serde_json::Value::Array(dumps)
+    }
+
+    fn value_to_json(value: &Value) -> serde_json::Value {
+        match value {
+            Value::I32(v) => json!(v),
+            Value::U32(v) => json!(v),
+            Value::F32(v) => json!(v),
+            Value::Vector(components) => components.iter().map(value_to_json).collect(),
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use std::io::{self, Write};
 
 use bincode::{Decode, Encode};
-use reflection::{PipelineDescription, ResourceKind};
+use common::Type;
+use reflection::{PipelineDescription, PipelineStage, ResourceKind};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use types::{Config, ConfigId};
 
@@ -12,11 +13,19 @@ pub enum ExecutionEvent {
     Success(Vec<Vec<u8>>, Option<Vec<u32>>),
     Failure(Vec<u8>),
     Timeout,
+    /// The shader exceeds the device's limits, with a message naming the limit.
+    ExceedsLimits(String),
+    /// The device rejected the shader for using a feature it doesn't support.
+    UnsupportedFeature(String),
 }
 
 pub enum ExecutionResult {
     Ok,
     Mismatch,
+    Timeout,
+    Nondeterministic,
+    ExceedsLimits,
+    UnsupportedFeature,
 }
 
 #[derive(Default)]
@@ -136,25 +145,52 @@ impl Printer {
         buffers: &[Vec<u8>],
         flow: &Option<Vec<u32>>,
         pipeline_desc: &PipelineDescription,
+        type_descs: &[Type],
     ) -> io::Result<()> {
         let mut stdout = StandardStream::stdout(ColorChoice::Auto);
 
         writeln!(&mut stdout, "outputs:")?;
 
         let mut no_outputs = true;
-        for (index, resource) in pipeline_desc
+        for (index, (resource_index, resource)) in pipeline_desc
             .resources
             .iter()
-            .filter(|it| it.kind == ResourceKind::StorageBuffer)
+            .enumerate()
+            .filter(|(_, it)| it.kind == ResourceKind::StorageBuffer)
             .enumerate()
         {
             let group = resource.group;
             let binding = resource.binding;
             let buffer = &buffers[index];
             writeln!(&mut stdout, "  {group}:{binding} : {buffer:?}")?;
+
+            let values = type_descs
+                .get(resource_index)
+                .and_then(|type_desc| type_desc.layout().read(buffer));
+
+            if let Some(values) = values {
+                stdout.set_color(&dimmed())?;
+                write!(&mut stdout, "  {group}:{binding} : [")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(&mut stdout, ", ")?;
+                    }
+                    write!(&mut stdout, "{value}")?;
+                }
+                writeln!(&mut stdout, "]")?;
+                stdout.reset()?;
+            }
+
             no_outputs = false;
         }
 
+        if pipeline_desc.stage == PipelineStage::Fragment {
+            if let Some(target) = buffers.last() {
+                writeln!(&mut stdout, "  render target : {target:?}")?;
+                no_outputs = false;
+            }
+        }
+
         if no_outputs {
             writeln!(&mut stdout, "  none")?;
         }
@@ -172,12 +208,13 @@ impl Printer {
         &self,
         event: &ExecutionEvent,
         pipeline_desc: &PipelineDescription,
+        type_descs: &[Type],
     ) -> io::Result<()> {
         match event {
             ExecutionEvent::UsingDefaultConfigs(configs) => self.print_default_configs(configs),
             ExecutionEvent::Start(config) => self.print_pre_execution(config, pipeline_desc),
             ExecutionEvent::Success(buffers, flow) => {
-                self.print_post_execution(buffers, flow, pipeline_desc)
+                self.print_post_execution(buffers, flow, pipeline_desc, type_descs)
             }
             ExecutionEvent::Failure(stderr) => {
                 std::io::stdout().write_all(stderr)?;
@@ -192,6 +229,15 @@ impl Printer {
                 writeln!(stdout)?;
                 Ok(())
             }
+            ExecutionEvent::ExceedsLimits(message)
+            | ExecutionEvent::UnsupportedFeature(message) => {
+                let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+                stdout.set_color(&yellow())?;
+                writeln!(stdout, "{message}")?;
+                stdout.reset()?;
+                writeln!(stdout)?;
+                Ok(())
+            }
         }
     }
 
@@ -209,6 +255,26 @@ impl Printer {
                 writeln!(stdout, "mismatch")?;
                 stdout.reset()?;
             }
+            ExecutionResult::Timeout => {
+                stdout.set_color(&yellow())?;
+                writeln!(stdout, "timeout")?;
+                stdout.reset()?;
+            }
+            ExecutionResult::Nondeterministic => {
+                stdout.set_color(&yellow())?;
+                writeln!(stdout, "nondeterministic")?;
+                stdout.reset()?;
+            }
+            ExecutionResult::ExceedsLimits => {
+                stdout.set_color(&yellow())?;
+                writeln!(stdout, "exceeds limits")?;
+                stdout.reset()?;
+            }
+            ExecutionResult::UnsupportedFeature => {
+                stdout.set_color(&yellow())?;
+                writeln!(stdout, "unsupported feature")?;
+                stdout.reset()?;
+            }
         }
 
         Ok(())
@@ -20,6 +20,7 @@ pub struct RunRequest {
     pub shader: String,
     pub workgroups: u32,
     pub flow: bool,
+    pub zero_init: bool,
     pub pipeline_desc: PipelineDescription,
     pub configs: Vec<ConfigId>,
     pub timeout: Option<Duration>,
@@ -32,6 +33,8 @@ pub enum RunMessage {
     ExecSuccess(Vec<Vec<u8>>, Option<Vec<u32>>),
     ExecFailure(Vec<u8>),
     ExecTimeout,
+    ExecExceedsLimits(String),
+    ExecUnsupportedFeature(String),
     End(Result<(), RunError>),
 }
 
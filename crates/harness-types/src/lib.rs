@@ -1,8 +1,9 @@
+use std::ffi::NulError;
 use std::fmt::{Display, Write};
-use std::str::FromStr;
+use std::str::{FromStr, Utf8Error};
 
 use bincode::{Decode, Encode};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, Decode, Encode, PartialEq, Eq, Serialize)]
 pub enum Implementation {
@@ -85,6 +86,40 @@ impl Display for ConfigId {
     }
 }
 
+/// Limits and optional features of the device used by a configuration, which can be used to avoid
+/// generating shaders that the device would reject.
+///
+/// The default value has the minimum limits guaranteed by WebGPU, and no optional features. Fields
+/// that are missing when deserializing also take their default value.
+#[derive(Clone, Debug, Decode, Encode, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceCapabilities {
+    pub max_compute_workgroup_size_x: u32,
+    pub max_compute_invocations_per_workgroup: u32,
+    pub max_compute_workgroups_per_dimension: u32,
+    pub max_uniform_buffer_binding_size: u64,
+    pub max_storage_buffer_binding_size: u64,
+    pub max_storage_buffers_per_shader_stage: u32,
+    pub max_bind_groups: u32,
+    /// Whether shaders may use the `f16` type.
+    pub shader_f16: bool,
+}
+
+impl Default for DeviceCapabilities {
+    fn default() -> Self {
+        DeviceCapabilities {
+            max_compute_workgroup_size_x: 256,
+            max_compute_invocations_per_workgroup: 256,
+            max_compute_workgroups_per_dimension: 65535,
+            max_uniform_buffer_binding_size: 64 << 10,
+            max_storage_buffer_binding_size: 128 << 20,
+            max_storage_buffers_per_shader_stage: 8,
+            max_bind_groups: 4,
+            shader_f16: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Adapter {
     pub name: String,
@@ -110,3 +145,184 @@ impl Config {
         }
     }
 }
+
+/// Severity of a message reported by a shader compiler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Display for MessageSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageSeverity::Error => write!(f, "error"),
+            MessageSeverity::Warning => write!(f, "warning"),
+            MessageSeverity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// A message reported by a shader compiler while compiling a shader.
+#[derive(Clone, Debug)]
+pub struct CompilationMessage {
+    pub severity: MessageSeverity,
+    pub message: String,
+    /// 1-based line of the source that the message refers to, or 0 if there isn't one.
+    pub line: u64,
+    /// 1-based position within `line`.
+    pub column: u64,
+}
+
+impl Display for CompilationMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, self.severity, self.message
+        )
+    }
+}
+
+impl CompilationMessage {
+    /// Returns whether this is an error caused by the shader using a feature or extension that
+    /// isn't enabled on the device (see [`is_unsupported_feature`]).
+    pub fn is_unsupported_feature(&self) -> bool {
+        self.severity == MessageSeverity::Error && is_unsupported_feature(&self.message)
+    }
+}
+
+/// Returns whether a compiler error `message` reports that the shader uses a feature or extension
+/// which isn't enabled or supported on the device, rather than the shader being invalid.
+///
+/// Compilers don't report this with a separate error type, so it's recognised by the wording of
+/// the message, e.g. `f16 type used without 'f16' extension enabled` from Tint or `extension 'f16'
+/// is not allowed on the Device` from Dawn.
+pub fn is_unsupported_feature(message: &str) -> bool {
+    let message = message.to_lowercase();
+
+    let mentions_feature = ["feature", "extension", "enable"]
+        .iter()
+        .any(|it| message.contains(it));
+
+    let is_unavailable = [
+        "not enabled",
+        "not supported",
+        "not allowed",
+        "unsupported",
+        "without",
+    ]
+    .iter()
+    .any(|it| message.contains(it));
+
+    mentions_feature && is_unavailable
+}
+
+/// All the messages reported by a shader compiler for a shader.
+#[derive(Clone, Debug, Default)]
+pub struct CompilationInfo {
+    pub messages: Vec<CompilationMessage>,
+}
+
+impl CompilationInfo {
+    /// Returns whether compilation succeeded, which is the case if there were no errors.
+    ///
+    /// Warnings only count as failures if `warnings_as_errors` is set, and info messages never do.
+    pub fn success(&self, warnings_as_errors: bool) -> bool {
+        self.failures(warnings_as_errors).next().is_none()
+    }
+
+    /// Returns the messages that make compilation fail (see [`success`](Self::success)).
+    pub fn failures(&self, warnings_as_errors: bool) -> impl Iterator<Item = &CompilationMessage> {
+        self.messages.iter().filter(move |it| match it.severity {
+            MessageSeverity::Error => true,
+            MessageSeverity::Warning => warnings_as_errors,
+            MessageSeverity::Info => false,
+        })
+    }
+
+    /// Returns the messages with the given severity.
+    pub fn with_severity(
+        &self,
+        severity: MessageSeverity,
+    ) -> impl Iterator<Item = &CompilationMessage> {
+        self.messages
+            .iter()
+            .filter(move |it| it.severity == severity)
+    }
+
+    /// Returns the first error caused by the shader using a feature that isn't enabled on the
+    /// device, if there is one.
+    pub fn unsupported_feature(&self) -> Option<&CompilationMessage> {
+        self.messages.iter().find(|it| it.is_unsupported_feature())
+    }
+}
+
+/// An error that prevented the harness from executing a shader.
+#[derive(Debug)]
+pub enum HarnessError {
+    /// No adapter matched the requested configuration.
+    NoAdapter(ConfigId),
+    /// The adapter was found, but a device couldn't be created for it.
+    RequestDevice(String),
+    /// The shader module or pipeline couldn't be created, usually because the shader is invalid.
+    Compilation(String),
+    /// A WebGPU call other than shader compilation failed validation.
+    Validation(String),
+    OutOfMemory,
+    /// The device was lost, e.g. because the driver crashed or was reset.
+    DeviceLost(String),
+    /// The implementation reported an error of an unknown type.
+    Internal(String),
+    /// Execution didn't finish before the deadline.
+    Timeout,
+    /// An output buffer couldn't be mapped for reading.
+    Mapping(String),
+    /// A string returned by the implementation wasn't valid UTF-8.
+    InvalidUtf8(Utf8Error),
+    /// The input can't be passed to the implementation, e.g. a shader containing a nul byte.
+    InvalidInput(String),
+    /// Executing the shader would need more resources than the device's limits allow, e.g. a
+    /// buffer larger than the maximum binding size. The message names the limit.
+    ExceedsLimits(String),
+    /// The shader uses a feature or extension that the device doesn't support or that wasn't
+    /// enabled when creating it, e.g. `f16` without the `shader-f16` feature.
+    UnsupportedFeature(String),
+}
+
+impl Display for HarnessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HarnessError::NoAdapter(config) => write!(f, "no adapter found matching id: {config}"),
+            HarnessError::RequestDevice(e) => write!(f, "failed to create device: {e}"),
+            HarnessError::Compilation(e) => write!(f, "compilation failed: {e}"),
+            HarnessError::Validation(e) => write!(f, "validation error: {e}"),
+            HarnessError::OutOfMemory => write!(f, "out of memory"),
+            HarnessError::DeviceLost(e) => write!(f, "the device was lost: {e}"),
+            HarnessError::Internal(e) => write!(f, "an unknown error occurred: {e}"),
+            HarnessError::Timeout => write!(f, "timed out waiting for execution to finish"),
+            HarnessError::Mapping(e) => write!(f, "failed to map buffer: {e}"),
+            HarnessError::InvalidUtf8(e) => write!(f, "invalid utf-8 from implementation: {e}"),
+            HarnessError::InvalidInput(e) => write!(f, "invalid input: {e}"),
+            HarnessError::ExceedsLimits(e) => write!(f, "shader exceeds device limits: {e}"),
+            HarnessError::UnsupportedFeature(e) => {
+                write!(f, "shader uses an unsupported feature: {e}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HarnessError {}
+
+impl From<Utf8Error> for HarnessError {
+    fn from(e: Utf8Error) -> Self {
+        HarnessError::InvalidUtf8(e)
+    }
+}
+
+impl From<NulError> for HarnessError {
+    fn from(e: NulError) -> Self {
+        HarnessError::InvalidInput(e.to_string())
+    }
+}
@@ -2,12 +2,12 @@ use std::time::Duration;
 use std::{io::Cursor, marker::PhantomData};
 
 use clap::Parser;
-use frontend::cli::RunOptions;
+use frontend::cli::{CheckOutputOptions, RunOptions};
 use frontend::ExecutionError;
 use reflection::PipelineDescription;
 use types::ConfigId;
 
-use crate::{ExecutionEvent, ExecutionInput, ExecutionOutput, HarnessHost};
+use crate::{ExecutionEvent, ExecutionInput, ExecutionOutput, HarnessError, HarnessHost};
 
 #[derive(Parser)]
 pub enum Command {
@@ -17,6 +17,18 @@ pub enum Command {
     /// Runs a wgsl shader against one or more configurations.
     Run(RunOptions),
 
+    /// Runs a wgsl shader and compares its output against a stored expected output.
+    CheckOutput(CheckOutputOptions),
+
+    /// Prints the limits and optional features of the device used by a configuration, as JSON.
+    ///
+    /// The output can be passed to the generator with `--capabilities` to avoid generating shaders
+    /// that the device would reject.
+    Capabilities {
+        #[clap(action)]
+        config: ConfigId,
+    },
+
     #[clap(hide(true))]
     Exec {
         #[clap(action)]
@@ -31,6 +43,8 @@ pub fn run<Host: HarnessHost>(command: Command) -> eyre::Result<()> {
     match command {
         Command::List => list(),
         Command::Run(options) => execute::<Host>(options),
+        Command::CheckOutput(options) => check_output::<Host>(options),
+        Command::Capabilities { config } => capabilities(&config),
         Command::Exec { config } => internal_run(config),
         Command::Serve(options) => crate::server::run::<Host>(options),
     }
@@ -42,6 +56,12 @@ fn list() -> eyre::Result<()> {
     Ok(())
 }
 
+fn capabilities(config: &ConfigId) -> eyre::Result<()> {
+    let capabilities = crate::query_capabilities(config)?;
+    println!("{}", serde_json::to_string_pretty(&capabilities)?);
+    Ok(())
+}
+
 fn u8s_to_u32s(from: &Vec<u8>) -> Vec<u32> {
     use byteorder::{LittleEndian, ReadBytesExt};
     let mut rdr = Cursor::new(from);
@@ -55,9 +75,29 @@ fn u8s_to_u32s(from: &Vec<u8>) -> Vec<u32> {
 fn internal_run(config: ConfigId) -> eyre::Result<()> {
     let input: ExecutionInput =
         bincode::decode_from_std_read(&mut std::io::stdin(), bincode::config::standard())?;
-    let buffers = crate::execute_config(&input.shader, input.workgroups, &input.pipeline_desc, &config)?;
+    let buffers = match crate::execute_config(
+        &input.shader,
+        input.workgroups,
+        input.zero_init,
+        &input.pipeline_desc,
+        &config,
+    ) {
+        // Reported with a separate exit code so that the parent doesn't mistake it for a crash
+        Err(e @ HarnessError::ExceedsLimits(_)) => {
+            eprintln!("{e}");
+            std::process::exit(frontend::EXCEEDS_LIMITS_EXIT_CODE);
+        }
+        Err(e @ HarnessError::UnsupportedFeature(_)) => {
+            eprintln!("{e}");
+            std::process::exit(frontend::UNSUPPORTED_FEATURE_EXIT_CODE);
+        }
+        result => result?,
+    };
     let flow = if input.flow {
-        Some(u8s_to_u32s(buffers.last().expect("Missing Flow")))
+        let flow = buffers.last().ok_or_else(|| {
+            HarnessError::InvalidInput("flow tracking requires a flow buffer".to_owned())
+        })?;
+        Some(u8s_to_u32s(flow))
     } else {
         None
     };
@@ -68,29 +108,43 @@ fn internal_run(config: ConfigId) -> eyre::Result<()> {
     Ok(())
 }
 
-pub fn execute<Host: HarnessHost>(options: RunOptions) -> eyre::Result<()> {
-    struct Executor<Host>(PhantomData<Host>);
+struct Executor<Host>(PhantomData<Host>);
 
-    impl<Host> Executor<Host> {
-        fn new() -> Executor<Host> {
-            Executor(PhantomData)
-        }
+impl<Host> Executor<Host> {
+    fn new() -> Executor<Host> {
+        Executor(PhantomData)
     }
+}
 
-    impl<Host: HarnessHost> frontend::Executor for Executor<Host> {
-        fn execute(
-            &self,
-            shader: &str,
-            workgroups: u32,
-            flow: bool,
-            pipeline_desc: &PipelineDescription,
-            configs: &[ConfigId],
-            timeout: Option<Duration>,
-            on_event: &mut dyn FnMut(ExecutionEvent) -> Result<(), ExecutionError>,
-        ) -> Result<(), ExecutionError> {
-            crate::execute::<Host, _>(shader, workgroups, flow, pipeline_desc, configs, timeout, on_event)
-        }
+impl<Host: HarnessHost> frontend::Executor for Executor<Host> {
+    fn execute(
+        &self,
+        shader: &str,
+        workgroups: u32,
+        flow: bool,
+        zero_init: bool,
+        pipeline_desc: &PipelineDescription,
+        configs: &[ConfigId],
+        timeout: Option<Duration>,
+        on_event: &mut dyn FnMut(ExecutionEvent) -> Result<(), ExecutionError>,
+    ) -> Result<(), ExecutionError> {
+        crate::execute::<Host, _>(
+            shader,
+            workgroups,
+            flow,
+            zero_init,
+            pipeline_desc,
+            configs,
+            timeout,
+            on_event,
+        )
     }
+}
 
+pub fn execute<Host: HarnessHost>(options: RunOptions) -> eyre::Result<()> {
     frontend::cli::run(options, &Executor::<Host>::new())
 }
+
+pub fn check_output<Host: HarnessHost>(options: CheckOutputOptions) -> eyre::Result<()> {
+    frontend::cli::check_output(options, &Executor::<Host>::new())
+}
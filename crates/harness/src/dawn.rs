@@ -1,12 +1,17 @@
-use color_eyre::eyre::eyre;
+use std::time::Instant;
+
 use dawn::webgpu::{
-    WGPUBackendType_WGPUBackendType_D3D12, WGPUBackendType_WGPUBackendType_Metal,
-    WGPUBackendType_WGPUBackendType_Vulkan,
+    WGPUBackendType, WGPUBackendType_WGPUBackendType_D3D12, WGPUBackendType_WGPUBackendType_Metal,
+    WGPUBackendType_WGPUBackendType_Vulkan, WGPUFeatureName_WGPUFeatureName_ShaderF16,
+    WGPUTextureFormat_WGPUTextureFormat_RGBA32Uint,
 };
 use dawn::*;
-use reflection::{PipelineDescription, ResourceKind};
+use reflection::{PipelineDescription, PipelineStage, ResourceKind};
+use types::{CompilationInfo, HarnessError};
 
-use crate::ConfigId;
+use crate::{
+    ConfigId, FULLSCREEN_TRIANGLE_VS, MAP_TIMEOUT, RENDER_TARGET_BYTES_PER_ROW, RENDER_TARGET_SIZE,
+};
 
 enum BufferSet {
     Storage {
@@ -22,9 +27,23 @@ enum BufferSet {
     },
 }
 
-pub fn get_adapters() -> Vec<types::Adapter> {
-    Instance::new()
-        .enumerate_adapters()
+enum Pipeline {
+    Compute(ComputePipeline),
+    Render(RenderPipeline),
+}
+
+impl Pipeline {
+    fn get_bind_group_layout(&self, index: u32) -> BindGroupLayout {
+        match self {
+            Pipeline::Compute(pipeline) => pipeline.get_bind_group_layout(index),
+            Pipeline::Render(pipeline) => pipeline.get_bind_group_layout(index),
+        }
+    }
+}
+
+pub fn get_adapters() -> Result<Vec<types::Adapter>, HarnessError> {
+    Ok(Instance::new()
+        .enumerate_adapters()?
         .into_iter()
         .filter_map(|it| {
             #[allow(non_upper_case_globals)]
@@ -39,28 +58,125 @@ pub fn get_adapters() -> Vec<types::Adapter> {
                 },
             })
         })
-        .collect()
+        .collect())
 }
 
-pub async fn run(
-    shader: &str,
-    workgroups: u32,
-    meta: &PipelineDescription,
-    config: &ConfigId,
-) -> color_eyre::Result<Vec<Vec<u8>>> {
-    let backend = match config.backend {
+fn create_device(config: &ConfigId) -> Result<Device, HarnessError> {
+    let backend: WGPUBackendType = match config.backend {
         crate::BackendType::Dx12 => WGPUBackendType_WGPUBackendType_D3D12,
         crate::BackendType::Metal => WGPUBackendType_WGPUBackendType_Metal,
         crate::BackendType::Vulkan => WGPUBackendType_WGPUBackendType_Vulkan,
     };
 
-    let device = Instance::new()
+    Instance::new()
         .create_device(backend, config.device_id as u32)
-        .ok_or_else(|| eyre!("no adapter found matching id: {config}"))?;
+        .ok_or_else(|| HarnessError::NoAdapter(config.clone()))
+}
+
+/// Returns the capabilities of the device that would be used to execute shaders with `config`.
+///
+/// These are the limits and features of the device as created by the harness, which may be less
+/// than what the adapter supports.
+pub fn get_capabilities(config: &ConfigId) -> Result<types::DeviceCapabilities, HarnessError> {
+    Ok(device_capabilities(&create_device(config)?))
+}
+
+fn device_capabilities(device: &Device) -> types::DeviceCapabilities {
+    let limits = device.get_limits();
+
+    types::DeviceCapabilities {
+        max_compute_workgroup_size_x: limits.maxComputeWorkgroupSizeX,
+        max_compute_invocations_per_workgroup: limits.maxComputeInvocationsPerWorkgroup,
+        max_compute_workgroups_per_dimension: limits.maxComputeWorkgroupsPerDimension,
+        max_uniform_buffer_binding_size: limits.maxUniformBufferBindingSize,
+        max_storage_buffer_binding_size: limits.maxStorageBufferBindingSize,
+        max_storage_buffers_per_shader_stage: limits.maxStorageBuffersPerShaderStage,
+        max_bind_groups: limits.maxBindGroups,
+        shader_f16: device.has_feature(WGPUFeatureName_WGPUFeatureName_ShaderF16),
+    }
+}
+
+/// Creates a shader module for `shader` and returns it with its compilation info.
+///
+/// If the shader is rejected because it uses a feature that isn't enabled on the device, this
+/// fails with [`HarnessError::UnsupportedFeature`] rather than [`HarnessError::Compilation`], since
+/// that's a mismatch between the shader and the device's capabilities rather than a bug.
+fn create_shader_module(
+    device: &Device,
+    shader: &str,
+) -> Result<(ShaderModule, CompilationInfo), HarnessError> {
+    let shader_module = device.create_shader_module(shader).map_err(|e| match e {
+        HarnessError::Compilation(message) if types::is_unsupported_feature(&message) => {
+            HarnessError::UnsupportedFeature(message)
+        }
+        e => e,
+    })?;
+
+    let info = shader_module.get_compilation_info()?;
+
+    if let Some(message) = info.unsupported_feature() {
+        return Err(HarnessError::UnsupportedFeature(message.to_string()));
+    }
+
+    Ok((shader_module, info))
+}
+
+/// Compiles `shader` into a compute pipeline without executing it, so that errors from Dawn's
+/// shader front-end are reported as [`HarnessError::Compilation`].
+///
+/// If `warnings_as_errors` is set, warnings are also reported as errors. Otherwise, if the shader
+/// compiles, this returns the warnings and info messages reported for it.
+pub fn compile(
+    shader: &str,
+    config: &ConfigId,
+    warnings_as_errors: bool,
+) -> Result<CompilationInfo, HarnessError> {
+    let device = create_device(config)?;
+    let (shader_module, info) = create_shader_module(&device, shader)?;
+
+    if !info.success(warnings_as_errors) {
+        let failures = info
+            .failures(warnings_as_errors)
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>();
+        return Err(HarnessError::Compilation(failures.join("\n")));
+    }
+
+    device.create_compute_pipeline(&shader_module, "main")?;
+
+    Ok(info)
+}
+
+pub async fn run(
+    shader: &str,
+    workgroups: u32,
+    zero_init: bool,
+    meta: &PipelineDescription,
+    config: &ConfigId,
+) -> Result<Vec<Vec<u8>>, HarnessError> {
+    let device = create_device(config)?;
+
+    crate::check_limits(&device_capabilities(&device), workgroups, meta)?;
 
     let queue = device.create_queue();
-    let shader_module = device.create_shader_module(shader);
-    let pipeline = device.create_compute_pipeline(&shader_module, "main");
+    let (shader_module, _) = create_shader_module(&device, shader)?;
+
+    let vertex_module;
+    let pipeline = match meta.stage {
+        PipelineStage::Compute => {
+            Pipeline::Compute(device.create_compute_pipeline(&shader_module, "main")?)
+        }
+        PipelineStage::Fragment => {
+            vertex_module = device.create_shader_module(FULLSCREEN_TRIANGLE_VS)?;
+            Pipeline::Render(device.create_render_pipeline(
+                &vertex_module,
+                "main",
+                &shader_module,
+                "main",
+                WGPUTextureFormat_WGPUTextureFormat_RGBA32Uint,
+            )?)
+        }
+    };
 
     let mut buffer_sets = vec![];
 
@@ -72,11 +188,13 @@ pub async fn run(
                     true,
                     size,
                     DeviceBufferUsage::STORAGE | DeviceBufferUsage::COPY_SRC,
-                );
+                )?;
 
-                if let Some(init) = resource.init.as_deref() {
-                    storage.get_mapped_range(size).copy_from_slice(init);
-                }
+                crate::init_storage_buffer(
+                    storage.get_mapped_range(size)?,
+                    resource.init.as_deref(),
+                    zero_init,
+                );
 
                 storage.unmap();
 
@@ -84,7 +202,7 @@ pub async fn run(
                     false,
                     size,
                     DeviceBufferUsage::COPY_DST | DeviceBufferUsage::MAP_READ,
-                );
+                )?;
 
                 buffer_sets.push(BufferSet::Storage {
                     binding: resource.binding,
@@ -94,10 +212,10 @@ pub async fn run(
                 });
             }
             ResourceKind::UniformBuffer => {
-                let mut buffer = device.create_buffer(true, size, DeviceBufferUsage::UNIFORM);
+                let mut buffer = device.create_buffer(true, size, DeviceBufferUsage::UNIFORM)?;
 
                 if let Some(init) = resource.init.as_deref() {
-                    buffer.get_mapped_range(size).copy_from_slice(init);
+                    buffer.get_mapped_range(size)?.copy_from_slice(init);
                 }
 
                 buffer.unmap();
@@ -111,41 +229,91 @@ pub async fn run(
         }
     }
 
-    let bind_group_entries = buffer_sets
-        .iter()
-        .map(|buffers| match buffers {
-            BufferSet::Storage {
-                binding,
-                size,
-                storage,
-                ..
-            } => BindGroupEntry {
-                binding: *binding,
-                buffer: storage,
-                size: *size,
-            },
-            BufferSet::Uniform {
-                binding,
-                size,
-                buffer,
-            } => BindGroupEntry {
-                binding: *binding,
-                buffer,
-                size: *size,
-            },
+    // The pipeline's layout has a bind group layout for each index up to the highest group used,
+    // so a group without any buffers still needs an (empty) bind group
+    let group_count = crate::bind_group_count(meta);
+    let bind_groups = (0..group_count)
+        .map(|group| {
+            let entries = meta
+                .resources
+                .iter()
+                .zip(&buffer_sets)
+                .filter(|(resource, _)| resource.group == group)
+                .map(|(_, buffers)| match buffers {
+                    BufferSet::Storage {
+                        binding,
+                        size,
+                        storage,
+                        ..
+                    } => BindGroupEntry {
+                        binding: *binding,
+                        buffer: storage,
+                        size: *size,
+                    },
+                    BufferSet::Uniform {
+                        binding,
+                        size,
+                        buffer,
+                    } => BindGroupEntry {
+                        binding: *binding,
+                        buffer,
+                        size: *size,
+                    },
+                })
+                .collect::<Vec<_>>();
+
+            device.create_bind_group(&pipeline.get_bind_group_layout(group), &entries)
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Fragment shaders draw to a 1x1 render target, which is copied into `read` so that it can
+    // be returned as an extra output buffer.
+    let render_target = match &pipeline {
+        Pipeline::Compute(_) => None,
+        Pipeline::Render(_) => {
+            let texture = device.create_texture(
+                1,
+                1,
+                WGPUTextureFormat_WGPUTextureFormat_RGBA32Uint,
+                TextureUsage::RENDER_ATTACHMENT | TextureUsage::COPY_SRC,
+            )?;
 
-    let bind_group =
-        device.create_bind_group(&pipeline.get_bind_group_layout(0), &bind_group_entries);
+            let read = device.create_buffer(
+                false,
+                RENDER_TARGET_BYTES_PER_ROW as usize,
+                DeviceBufferUsage::COPY_DST | DeviceBufferUsage::MAP_READ,
+            )?;
 
-    let encoder = device.create_command_encoder();
+            Some((texture, read))
+        }
+    };
+
+    let encoder = device.create_command_encoder()?;
+
+    match &pipeline {
+        Pipeline::Compute(pipeline) => {
+            let compute_pass = encoder.begin_compute_pass();
+            compute_pass.set_pipeline(pipeline);
+            for (index, bind_group) in bind_groups.iter().enumerate() {
+                compute_pass.set_bind_group(index as u32, bind_group);
+            }
+            compute_pass.dispatch(workgroups, 1, 1);
+        }
+        Pipeline::Render(pipeline) => {
+            let (texture, read) = render_target.as_ref().unwrap();
+            let view = texture.create_view();
 
-    {
-        let compute_pass = encoder.begin_compute_pass();
-        compute_pass.set_pipeline(&pipeline);
-        compute_pass.set_bind_group(0, &bind_group);
-        compute_pass.dispatch(workgroups, 1, 1);
+            {
+                let render_pass = encoder.begin_render_pass(&view);
+                render_pass.set_pipeline(pipeline);
+                for (index, bind_group) in bind_groups.iter().enumerate() {
+                    render_pass.set_bind_group(index as u32, bind_group);
+                }
+                render_pass.draw(3, 1);
+            }
+
+            encoder.copy_texture_to_buffer(texture, read, 1, 1, RENDER_TARGET_BYTES_PER_ROW);
+        }
     }
 
     for buffers in &buffer_sets {
@@ -163,21 +331,46 @@ pub async fn run(
     let commands = encoder.finish();
 
     queue.submit(&commands);
+    device.check_errors()?;
+
+    let read_buffers = buffer_sets
+        .iter()
+        .filter_map(|buffers| match buffers {
+            BufferSet::Storage { read, size, .. } => Some((read, *size)),
+            BufferSet::Uniform { .. } => None,
+        })
+        .chain(
+            render_target
+                .iter()
+                .map(|(_, read)| (read, RENDER_TARGET_SIZE)),
+        );
+
+    let deadline = Instant::now() + MAP_TIMEOUT;
 
     let mut results = vec![];
-    for buffers in &buffer_sets {
-        if let BufferSet::Storage { read, size, .. } = buffers {
-            let mut rx = read.map_async(DeviceBufferMapMode::READ, *size);
+    for (read, size) in read_buffers {
+        let mut rx = read.map_async(DeviceBufferMapMode::READ, size);
 
-            while rx.try_recv().unwrap().is_none() {
-                device.tick();
-                std::thread::sleep(std::time::Duration::from_millis(16));
+        loop {
+            match rx.try_recv() {
+                Ok(Some(result)) => break result?,
+                Ok(None) => {}
+                Err(_) => return Err(HarnessError::Mapping("map callback was dropped".to_owned())),
             }
 
-            let bytes = read.get_const_mapped_range(*size);
+            device.check_errors()?;
 
-            results.push(bytes.to_vec());
+            if Instant::now() > deadline {
+                return Err(HarnessError::Timeout);
+            }
+
+            device.tick();
+            std::thread::sleep(std::time::Duration::from_millis(16));
         }
+
+        let bytes = read.get_const_mapped_range(size)?;
+
+        results.push(bytes.to_vec());
     }
 
     Ok(results)
@@ -10,8 +10,30 @@ use std::time::Duration;
 use frontend::{ExecutionError, ExecutionEvent};
 use futures::executor::block_on;
 use process_control::{ChildExt, Control};
-use reflection::PipelineDescription;
-use types::{BackendType, Config, ConfigId, Implementation};
+use reflection::{PipelineDescription, PipelineStage, ResourceKind};
+use types::{BackendType, CompilationInfo, Config, ConfigId, DeviceCapabilities, Implementation};
+
+pub use types::HarnessError;
+
+/// Vertex shader used to run fragment shaders, which draws a single triangle covering the whole
+/// render target.
+const FULLSCREEN_TRIANGLE_VS: &str = r#"@vertex
+fn main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+/// Size in bytes of the single `rgba32uint` texel of a fragment shader's render target.
+const RENDER_TARGET_SIZE: usize = 16;
+
+/// Row pitch of the buffer that the render target is copied into, since texture copies must be
+/// aligned to 256 bytes per row.
+const RENDER_TARGET_BYTES_PER_ROW: u32 = 256;
+
+/// How long to wait for the output buffers to be mapped after submitting a shader, before giving
+/// up on a device that has hung.
+const MAP_TIMEOUT: Duration = Duration::from_secs(300);
 
 pub trait HarnessHost {
     fn exec_command() -> Command;
@@ -26,11 +48,14 @@ pub fn query_configs() -> Vec<Config> {
             .map(|adapter| Config::new(Implementation::Wgpu, adapter)),
     );
 
-    configurations.extend(
-        dawn::get_adapters()
-            .into_iter()
-            .map(|adapter| Config::new(Implementation::Dawn, adapter)),
-    );
+    match dawn::get_adapters() {
+        Ok(adapters) => configurations.extend(
+            adapters
+                .into_iter()
+                .map(|adapter| Config::new(Implementation::Dawn, adapter)),
+        ),
+        Err(e) => log::warn!("failed to enumerate dawn adapters: {e}"),
+    }
 
     configurations
 }
@@ -65,6 +90,7 @@ struct ExecutionArgs<'a> {
     pub shader: &'a str,
     pub workgroups: u32,
     pub flow: bool,
+    pub zero_init: bool,
     pub pipeline_desc: &'a PipelineDescription,
 }
 
@@ -73,6 +99,7 @@ pub struct ExecutionInput {
     pub shader: String,
     pub workgroups: u32,
     pub flow: bool,
+    pub zero_init: bool,
     pub pipeline_desc: PipelineDescription,
 }
 
@@ -82,10 +109,12 @@ struct ExecutionOutput {
     pub flow: Option<Vec<u32>>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn execute<Host: HarnessHost, E: FnMut(ExecutionEvent) -> Result<(), ExecutionError>>(
     shader: &str,
     workgroups: u32,
     flow: bool,
+    zero_init: bool,
     pipeline_desc: &PipelineDescription,
     configs: &[ConfigId],
     timeout: Option<Duration>,
@@ -123,6 +152,7 @@ fn execute<Host: HarnessHost, E: FnMut(ExecutionEvent) -> Result<(), ExecutionEr
                 shader,
                 workgroups,
                 flow,
+                zero_init,
                 pipeline_desc,
             },
             &mut stdin,
@@ -143,20 +173,176 @@ fn execute<Host: HarnessHost, E: FnMut(ExecutionEvent) -> Result<(), ExecutionEr
             let (output, _): (ExecutionOutput, _) =
                 bincode::decode_from_slice(&output.stdout, bincode::config::standard())?;
             on_event(ExecutionEvent::Success(output.buffers, output.flow))
+        } else if output.status.code() == Some(frontend::EXCEEDS_LIMITS_EXIT_CODE) {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            on_event(ExecutionEvent::ExceedsLimits(message))
+        } else if output.status.code() == Some(frontend::UNSUPPORTED_FEATURE_EXIT_CODE) {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            on_event(ExecutionEvent::UnsupportedFeature(message))
         } else {
             on_event(ExecutionEvent::Failure(output.stderr))
         }
     })
 }
 
+/// Creates a device for `config` and returns its limits and optional features.
+pub fn query_capabilities(config: &ConfigId) -> Result<DeviceCapabilities, HarnessError> {
+    match config.implementation {
+        Implementation::Dawn => dawn::get_capabilities(config),
+        Implementation::Wgpu => block_on(wgpu::get_capabilities(config)),
+    }
+}
+
+/// Returns the number of bind groups needed to execute a shader with `pipeline_desc`, which is one
+/// more than the highest group index of its resources.
+fn bind_group_count(pipeline_desc: &PipelineDescription) -> u32 {
+    pipeline_desc
+        .resources
+        .iter()
+        .map(|resource| resource.group + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Fills the mapped contents of a newly created storage buffer with its init data.
+///
+/// A buffer without init data is explicitly zeroed (unless `zero_init` is false), so that any
+/// elements which aren't written by the shader are guaranteed to read back as zero.
+fn init_storage_buffer(mapped: &mut [u8], init: Option<&[u8]>, zero_init: bool) {
+    match init {
+        Some(init) => mapped.copy_from_slice(init),
+        None if zero_init => mapped.fill(0),
+        None => {}
+    }
+}
+
+/// Checks that executing a shader with `pipeline_desc` doesn't need more resources than the device
+/// allows, so that an oversized shader is reported as [`HarnessError::ExceedsLimits`] rather than
+/// making the implementation run out of memory or abort.
+fn check_limits(
+    capabilities: &DeviceCapabilities,
+    workgroups: u32,
+    pipeline_desc: &PipelineDescription,
+) -> Result<(), HarnessError> {
+    if pipeline_desc.stage == PipelineStage::Compute
+        && workgroups > capabilities.max_compute_workgroups_per_dimension
+    {
+        return Err(HarnessError::ExceedsLimits(format!(
+            "dispatching {workgroups} workgroups exceeds maxComputeWorkgroupsPerDimension ({})",
+            capabilities.max_compute_workgroups_per_dimension
+        )));
+    }
+
+    let bind_groups = bind_group_count(pipeline_desc);
+    if bind_groups > capabilities.max_bind_groups {
+        return Err(HarnessError::ExceedsLimits(format!(
+            "shader uses {bind_groups} bind groups, which exceeds maxBindGroups ({})",
+            capabilities.max_bind_groups
+        )));
+    }
+
+    let mut storage_buffers = 0;
+
+    for resource in &pipeline_desc.resources {
+        let (limit, limit_name) = match resource.kind {
+            ResourceKind::StorageBuffer => {
+                storage_buffers += 1;
+                (
+                    capabilities.max_storage_buffer_binding_size,
+                    "maxStorageBufferBindingSize",
+                )
+            }
+            ResourceKind::UniformBuffer => (
+                capabilities.max_uniform_buffer_binding_size,
+                "maxUniformBufferBindingSize",
+            ),
+        };
+
+        if u64::from(resource.size) > limit {
+            return Err(HarnessError::ExceedsLimits(format!(
+                "buffer `{}` is {} bytes, which exceeds {limit_name} ({limit})",
+                resource.name, resource.size
+            )));
+        }
+    }
+
+    if storage_buffers > capabilities.max_storage_buffers_per_shader_stage {
+        return Err(HarnessError::ExceedsLimits(format!(
+            "shader uses {storage_buffers} storage buffers, which exceeds \
+             maxStorageBuffersPerShaderStage ({})",
+            capabilities.max_storage_buffers_per_shader_stage
+        )));
+    }
+
+    Ok(())
+}
+
+/// Compiles `shader` with a single configuration without executing it.
+///
+/// This is only supported for Dawn, where it runs the shader through Dawn's integrated WGSL
+/// front-end. Errors are returned in [`HarnessError::Compilation`], and a shader which only has
+/// warnings compiles successfully, returning the warnings in its [`CompilationInfo`], unless
+/// `warnings_as_errors` is set.
+pub fn compile_only(
+    shader: &str,
+    config: &ConfigId,
+    warnings_as_errors: bool,
+) -> Result<CompilationInfo, HarnessError> {
+    match config.implementation {
+        Implementation::Dawn => dawn::compile(shader, config, warnings_as_errors),
+        Implementation::Wgpu => Err(HarnessError::InvalidInput(format!(
+            "compiling without executing is not supported for `{config}`"
+        ))),
+    }
+}
+
+/// Executes `shader` with a single configuration, returning the contents of its storage buffers.
+///
+/// If `zero_init` is true, storage buffers without input data are filled with zeros before
+/// execution so that bytes which the shader doesn't write are deterministic.
 pub fn execute_config(
     shader: &str,
     workgroups: u32,
+    zero_init: bool,
     pipeline_desc: &PipelineDescription,
     config: &ConfigId,
-) -> eyre::Result<Vec<Vec<u8>>> {
+) -> Result<Vec<Vec<u8>>, HarnessError> {
     match config.implementation {
-        Implementation::Dawn => block_on(dawn::run(shader, workgroups, pipeline_desc, config)),
-        Implementation::Wgpu => block_on(wgpu::run(shader, workgroups, pipeline_desc, config)),
+        Implementation::Dawn => block_on(dawn::run(
+            shader,
+            workgroups,
+            zero_init,
+            pipeline_desc,
+            config,
+        )),
+        Implementation::Wgpu => block_on(wgpu::run(
+            shader,
+            workgroups,
+            zero_init,
+            pipeline_desc,
+            config,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::init_storage_buffer;
+
+    #[test]
+    fn storage_buffer_without_init_data_is_zeroed() {
+        // Stands in for whatever the implementation happened to leave in the buffer's memory
+        let mut mapped = [0xaa; 8];
+        init_storage_buffer(&mut mapped, None, true);
+        assert_eq!(mapped, [0; 8]);
+
+        // With zero initialisation disabled, unwritten bytes are left as they are
+        let mut mapped = [0xaa; 8];
+        init_storage_buffer(&mut mapped, None, false);
+        assert_eq!(mapped, [0xaa; 8]);
+
+        let mut mapped = [0xaa; 4];
+        init_storage_buffer(&mut mapped, Some(&[1, 2, 3, 4]), true);
+        assert_eq!(mapped, [1, 2, 3, 4]);
     }
 }
@@ -1,5 +1,5 @@
 use std::io::{self, BufReader, BufWriter};
-use std::net::TcpListener;
+use std::net::{TcpListener, TcpStream};
 
 use clap::Parser;
 use color_eyre::eyre::{self, eyre};
@@ -23,30 +23,26 @@ pub struct Options {
 }
 
 pub fn run<Host: HarnessHost>(options: Options) -> eyre::Result<()> {
-    let parallelism = options
-        .parallelism
-        .unwrap_or_else(|| std::thread::available_parallelism().unwrap().get());
+    let parallelism = match options.parallelism {
+        Some(parallelism) => parallelism,
+        None => std::thread::available_parallelism()?.get(),
+    };
 
     let pool = ThreadPool::new(parallelism);
     println!("Using thread pool with {parallelism} threads");
 
-    let listener = TcpListener::bind(options.address).unwrap();
-    let address = listener.local_addr().unwrap();
+    let listener = TcpListener::bind(options.address)?;
+    let address = listener.local_addr()?;
     println!("Server listening at {address}");
 
     for stream in listener.incoming() {
         pool.execute(move || {
-            let stream = stream.unwrap();
-
-            let mut reader = BufReader::new(&stream);
-
-            let req =
-                bincode::decode_from_std_read(&mut reader, bincode::config::standard()).unwrap();
-
-            let writer = BufWriter::new(&stream);
-            match req {
-                Request::List => handle_list_request(writer).unwrap(),
-                Request::Run(req) => handle_run_request::<Host, _>(req, writer).unwrap(),
+            // A bad request or a dropped connection only affects that client, so just log it
+            if let Err(e) = stream
+                .map_err(eyre::Report::from)
+                .and_then(handle_connection::<Host>)
+            {
+                eprintln!("{e:?}");
             }
         });
     }
@@ -54,6 +50,18 @@ pub fn run<Host: HarnessHost>(options: Options) -> eyre::Result<()> {
     Ok(())
 }
 
+fn handle_connection<Host: HarnessHost>(stream: TcpStream) -> eyre::Result<()> {
+    let mut reader = BufReader::new(&stream);
+
+    let req = bincode::decode_from_std_read(&mut reader, bincode::config::standard())?;
+
+    let writer = BufWriter::new(&stream);
+    match req {
+        Request::List => handle_list_request(writer),
+        Request::Run(req) => handle_run_request::<Host, _>(req, writer),
+    }
+}
+
 fn handle_list_request(mut writer: impl io::Write) -> eyre::Result<()> {
     let configs = crate::query_configs();
     let res = ListResponse { configs };
@@ -74,6 +82,10 @@ fn handle_run_request<Host: HarnessHost, W: io::Write>(
             ExecutionEvent::Success(buffers, flow) => RunMessage::ExecSuccess(buffers, flow),
             ExecutionEvent::Failure(stderr) => RunMessage::ExecFailure(stderr),
             ExecutionEvent::Timeout => RunMessage::ExecTimeout,
+            ExecutionEvent::ExceedsLimits(message) => RunMessage::ExecExceedsLimits(message),
+            ExecutionEvent::UnsupportedFeature(message) => {
+                RunMessage::ExecUnsupportedFeature(message)
+            }
         };
         send(&mut writer, message)?;
         writer.flush()?;
@@ -84,6 +96,7 @@ fn handle_run_request<Host: HarnessHost, W: io::Write>(
         &req.shader,
         req.workgroups,
         req.flow,
+        req.zero_init,
         &req.pipeline_desc,
         &req.configs,
         req.timeout,
@@ -1,15 +1,43 @@
 use std::borrow::Cow;
+use std::num::NonZeroU32;
 
-use color_eyre::eyre::eyre;
-use color_eyre::Result;
-use reflection::{PipelineDescription, ResourceKind};
+use reflection::{PipelineDescription, PipelineStage, ResourceKind};
+use types::HarnessError;
 use wgpu::{
-    Backends, BindGroupDescriptor, BindGroupEntry, Buffer, BufferDescriptor, BufferUsages,
-    CommandEncoderDescriptor, ComputePassDescriptor, ComputePipelineDescriptor, DeviceDescriptor,
-    Instance, Limits, Maintain, MapMode, ShaderModuleDescriptor, ShaderSource,
+    Backends, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, DeviceDescriptor,
+    ErrorFilter, Extent3d, FragmentState, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    Instance, Limits, LoadOp, Maintain, MapMode, MultisampleState, Operations, Origin3d,
+    PrimitiveState, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+    VertexState,
 };
 
-use crate::ConfigId;
+use crate::{ConfigId, FULLSCREEN_TRIANGLE_VS, RENDER_TARGET_BYTES_PER_ROW, RENDER_TARGET_SIZE};
+
+const RENDER_TARGET_FORMAT: TextureFormat = TextureFormat::Rgba32Uint;
+
+const RENDER_TARGET_EXTENT: Extent3d = Extent3d {
+    width: 1,
+    height: 1,
+    depth_or_array_layers: 1,
+};
+
+enum Pipeline {
+    Compute(ComputePipeline),
+    Render(RenderPipeline),
+}
+
+impl Pipeline {
+    fn get_bind_group_layout(&self, index: u32) -> BindGroupLayout {
+        match self {
+            Pipeline::Compute(pipeline) => pipeline.get_bind_group_layout(index),
+            Pipeline::Render(pipeline) => pipeline.get_bind_group_layout(index),
+        }
+    }
+}
 
 pub fn get_adapters() -> Vec<types::Adapter> {
     Instance::new(Backends::all())
@@ -33,12 +61,7 @@ pub fn get_adapters() -> Vec<types::Adapter> {
         .collect()
 }
 
-pub async fn run(
-    shader: &str,
-    workgroups: u32,
-    meta: &PipelineDescription,
-    config: &ConfigId,
-) -> Result<Vec<Vec<u8>>> {
+async fn request_device(config: &ConfigId) -> Result<(Device, Queue), HarnessError> {
     let backend = match config.backend {
         crate::BackendType::Dx12 => wgpu::Backend::Dx12,
         crate::BackendType::Metal => wgpu::Backend::Metal,
@@ -52,7 +75,7 @@ pub async fn run(
             let info = adapter.get_info();
             info.device == config.device_id && info.backend == backend
         })
-        .ok_or_else(|| eyre!("no adapter found matching id: {config}"))?;
+        .ok_or_else(|| HarnessError::NoAdapter(config.clone()))?;
 
     let device_descriptor = DeviceDescriptor {
         limits: Limits {
@@ -63,11 +86,73 @@ pub async fn run(
         ..Default::default()
     };
 
-    let (device, queue) = adapter.request_device(&device_descriptor, None).await?;
+    adapter
+        .request_device(&device_descriptor, None)
+        .await
+        .map_err(|e| HarnessError::RequestDevice(e.to_string()))
+}
+
+/// Pops the innermost error scope, turning a validation error into an error of the kind given by
+/// `error`.
+async fn pop_error_scope(
+    device: &Device,
+    error: fn(String) -> HarnessError,
+) -> Result<(), HarnessError> {
+    match device.pop_error_scope().await {
+        None => Ok(()),
+        Some(wgpu::Error::OutOfMemory { .. }) => Err(HarnessError::OutOfMemory),
+        Some(wgpu::Error::Validation { description, .. }) => Err(error(description)),
+    }
+}
+
+/// Returns the capabilities of the device that would be used to execute shaders with `config`.
+///
+/// These are the limits and features of the device as created by the harness, which may be less
+/// than what the adapter supports.
+pub async fn get_capabilities(
+    config: &ConfigId,
+) -> Result<types::DeviceCapabilities, HarnessError> {
+    let (device, _) = request_device(config).await?;
+    Ok(device_capabilities(&device))
+}
+
+fn device_capabilities(device: &Device) -> types::DeviceCapabilities {
+    let limits = device.limits();
+
+    types::DeviceCapabilities {
+        max_compute_workgroup_size_x: limits.max_compute_workgroup_size_x,
+        max_compute_invocations_per_workgroup: limits.max_compute_invocations_per_workgroup,
+        max_compute_workgroups_per_dimension: limits.max_compute_workgroups_per_dimension,
+        max_uniform_buffer_binding_size: limits.max_uniform_buffer_binding_size.into(),
+        max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size.into(),
+        max_storage_buffers_per_shader_stage: limits.max_storage_buffers_per_shader_stage,
+        max_bind_groups: limits.max_bind_groups,
+        // naga can't parse `f16` yet, regardless of what the device supports
+        shader_f16: false,
+    }
+}
+
+pub async fn run(
+    shader: &str,
+    workgroups: u32,
+    zero_init: bool,
+    meta: &PipelineDescription,
+    config: &ConfigId,
+) -> Result<Vec<Vec<u8>>, HarnessError> {
+    let (device, queue) = request_device(config).await?;
+
+    crate::check_limits(&device_capabilities(&device), workgroups, meta)?;
+
+    // wgpu panics on errors that aren't caught by an error scope, so everything up to submission
+    // is wrapped in one, with a separate scope for compilation errors
+    device.push_error_scope(ErrorFilter::OutOfMemory);
+    device.push_error_scope(ErrorFilter::Validation);
+    device.push_error_scope(ErrorFilter::Validation);
 
     let preprocessor_opts = preprocessor::Options {
         concise_stage_attrs: true,
         module_scope_constants: false,
+        explicit_types: false,
     };
 
     let preprocessed = preprocessor::preprocess(preprocessor_opts, shader.to_owned());
@@ -76,12 +161,48 @@ pub async fn run(
         source: ShaderSource::Wgsl(Cow::Owned(preprocessed)),
     });
 
-    let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-        entry_point: "main",
-        label: None,
-        module: &shader,
-        layout: None,
-    });
+    let vertex_shader;
+    let pipeline = match meta.stage {
+        PipelineStage::Compute => {
+            Pipeline::Compute(device.create_compute_pipeline(&ComputePipelineDescriptor {
+                entry_point: "main",
+                label: None,
+                module: &shader,
+                layout: None,
+            }))
+        }
+        PipelineStage::Fragment => {
+            vertex_shader = device.create_shader_module(ShaderModuleDescriptor {
+                label: None,
+                source: ShaderSource::Wgsl(Cow::Borrowed(FULLSCREEN_TRIANGLE_VS)),
+            });
+
+            Pipeline::Render(device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: None,
+                layout: None,
+                vertex: VertexState {
+                    module: &vertex_shader,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point: "main",
+                    targets: &[Some(ColorTargetState {
+                        format: RENDER_TARGET_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            }))
+        }
+    };
+
+    pop_error_scope(&device, HarnessError::Compilation).await?;
 
     let mut buffer_sets = vec![];
 
@@ -109,12 +230,11 @@ pub async fn run(
                     mapped_at_creation: true,
                 });
 
-                if let Some(init) = resource.init.as_deref() {
-                    storage 
-                        .slice(..)
-                        .get_mapped_range_mut()
-                        .copy_from_slice(init);
-                }
+                crate::init_storage_buffer(
+                    &mut storage.slice(..).get_mapped_range_mut(),
+                    resource.init.as_deref(),
+                    zero_init,
+                );
 
                 storage.unmap();
 
@@ -157,41 +277,117 @@ pub async fn run(
         }
     }
 
-    let bind_group_entries = buffer_sets
-        .iter()
-        .map(|buffer| match buffer {
-          BufferSet::Storage { 
-            binding, 
-            storage, 
-            .. 
-          } => BindGroupEntry {
-            binding: *binding,
-            resource: storage.as_entire_binding()
-          },
-          BufferSet::Uniform { 
-            binding, 
-            buffer,
-            ..
-          } => BindGroupEntry { 
-            binding: *binding, 
-            resource: buffer.as_entire_binding() 
-          }
+    // The pipeline's layout has a bind group layout for each index up to the highest group used,
+    // so a group without any buffers still needs an (empty) bind group
+    let group_count = crate::bind_group_count(meta);
+    let bind_groups = (0..group_count)
+        .map(|group| {
+            let entries = meta
+                .resources
+                .iter()
+                .zip(&buffer_sets)
+                .filter(|(resource, _)| resource.group == group)
+                .map(|(_, buffer)| match buffer {
+                    BufferSet::Storage {
+                        binding, storage, ..
+                    } => BindGroupEntry {
+                        binding: *binding,
+                        resource: storage.as_entire_binding(),
+                    },
+                    BufferSet::Uniform { binding, buffer } => BindGroupEntry {
+                        binding: *binding,
+                        resource: buffer.as_entire_binding(),
+                    },
+                })
+                .collect::<Vec<_>>();
+
+            device.create_bind_group(&BindGroupDescriptor {
+                layout: &pipeline.get_bind_group_layout(group),
+                label: None,
+                entries: &entries,
+            })
         })
         .collect::<Vec<_>>();
 
-    let bind_group = device.create_bind_group(&BindGroupDescriptor {
-        layout: &pipeline.get_bind_group_layout(0),
-        label: None,
-        entries: &bind_group_entries,
-    });
+    // Fragment shaders draw to a 1x1 render target, which is copied into `read` so that it can
+    // be returned as an extra output buffer.
+    let render_target = match &pipeline {
+        Pipeline::Compute(_) => None,
+        Pipeline::Render(_) => {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: None,
+                size: RENDER_TARGET_EXTENT,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: RENDER_TARGET_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            });
+
+            let read = device.create_buffer(&BufferDescriptor {
+                label: None,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                size: RENDER_TARGET_BYTES_PER_ROW as u64,
+                mapped_at_creation: false,
+            });
+
+            Some((texture, read))
+        }
+    };
 
     let commands = {
         let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
-        {
-            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
-            pass.set_pipeline(&pipeline);
-            pass.set_bind_group(0, &bind_group, &[]);
-            pass.dispatch_workgroups(workgroups, 1, 1);
+        match &pipeline {
+            Pipeline::Compute(pipeline) => {
+                let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+                pass.set_pipeline(pipeline);
+                for (index, bind_group) in bind_groups.iter().enumerate() {
+                    pass.set_bind_group(index as u32, bind_group, &[]);
+                }
+                pass.dispatch_workgroups(workgroups, 1, 1);
+            }
+            Pipeline::Render(pipeline) => {
+                let (texture, read) = render_target.as_ref().unwrap();
+                let view = texture.create_view(&TextureViewDescriptor::default());
+
+                {
+                    let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[Some(RenderPassColorAttachment {
+                            view: &view,
+                            resolve_target: None,
+                            ops: Operations {
+                                load: LoadOp::Clear(Color::TRANSPARENT),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+                    pass.set_pipeline(pipeline);
+                    for (index, bind_group) in bind_groups.iter().enumerate() {
+                        pass.set_bind_group(index as u32, bind_group, &[]);
+                    }
+                    pass.draw(0..3, 0..1);
+                }
+
+                encoder.copy_texture_to_buffer(
+                    ImageCopyTexture {
+                        texture,
+                        mip_level: 0,
+                        origin: Origin3d::ZERO,
+                        aspect: TextureAspect::All,
+                    },
+                    ImageCopyBuffer {
+                        buffer: read,
+                        layout: ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: NonZeroU32::new(RENDER_TARGET_BYTES_PER_ROW),
+                            rows_per_image: None,
+                        },
+                    },
+                    RENDER_TARGET_EXTENT,
+                );
+            }
         }
         for buffer in &buffer_sets {
           if let BufferSet::Storage {
@@ -214,24 +410,40 @@ pub async fn run(
 
     queue.submit(std::iter::once(commands));
 
+    pop_error_scope(&device, HarnessError::Validation).await?;
+    pop_error_scope(&device, HarnessError::Validation).await?;
+
+    let read_buffers = buffer_sets
+        .iter()
+        .filter_map(|buffer| match buffer {
+            BufferSet::Storage { read, size, .. } => Some((read, *size)),
+            BufferSet::Uniform { .. } => None,
+        })
+        .chain(
+            render_target
+                .iter()
+                .map(|(_, read)| (read, RENDER_TARGET_SIZE)),
+        );
+
     let mut results = vec![];
 
-    for buffer in &buffer_sets {
-        if let BufferSet::Storage { read, .. } = buffer {
-            let slice = read.slice(..);
-            let (tx, rx) = futures::channel::oneshot::channel();
+    for (read, size) in read_buffers {
+        let slice = read.slice(..size as u64);
+        let (tx, rx) = futures::channel::oneshot::channel();
 
-            slice.map_async(MapMode::Read, move |res| {
-                tx.send(res).unwrap();
-            });
+        slice.map_async(MapMode::Read, move |res| {
+            // The receiver is dropped if the caller stopped waiting for the buffer
+            let _ = tx.send(res);
+        });
 
-            device.poll(Maintain::Wait);
-            rx.await??;
+        device.poll(Maintain::Wait);
+        rx.await
+            .map_err(|_| HarnessError::Mapping("map callback was dropped".to_owned()))?
+            .map_err(|e| HarnessError::Mapping(e.to_string()))?;
 
-            let bytes = slice.get_mapped_range();
+        let bytes = slice.get_mapped_range();
 
-            results.push(bytes.to_vec());
-        }
+        results.push(bytes.to_vec());
     }
 
     Ok(results)
@@ -93,8 +93,21 @@ pub fn parse_fn(input: &str, env: &mut Environment) -> FnDecl {
 }
 
 fn parse_translation_unit(pair: Pair<Rule>, env: &mut Environment) -> Module {
-    let decls = pair
-        .into_inner()
+    let mut pairs = pair.into_inner().peekable();
+
+    let enables = pairs
+        .by_ref()
+        .peeking_take_while(|pair| pair.as_rule() == Rule::enable_directive)
+        .map(|pair| {
+            let name = pair.into_inner().next().unwrap().as_str();
+            match name {
+                "f16" => Extension::F16,
+                _ => panic!("unsupported extension: {}", name),
+            }
+        })
+        .collect();
+
+    let decls = pairs
         .take_while(|pair| pair.as_rule() != Rule::EOI)
         .map(|pair| parse_global_decl(pair, env))
         .collect::<Vec<_>>();
@@ -114,6 +127,7 @@ fn parse_translation_unit(pair: Pair<Rule>, env: &mut Environment) -> Module {
     }
 
     Module {
+        enables,
         functions,
         structs,
         consts,
@@ -371,11 +385,28 @@ fn parse_function_decl(pair: Pair<Rule>, env: &mut Environment) -> FnDecl {
         })
         .collect::<Vec<_>>();
 
+    let output_attrs = pairs
+        .by_ref()
+        .peeking_take_while(|pair| pair.as_rule() == Rule::attribute)
+        .map(|pair| {
+            let mut pairs = pair.into_inner();
+            let name = pairs.next().unwrap().as_str();
+            match name {
+                "location" => match parse_literal_expression(pairs.next().unwrap()).expr {
+                    Expr::Lit(Lit::I32(v)) => FnOutputAttr::Location(v.try_into().unwrap()),
+                    Expr::Lit(Lit::U32(v)) => FnOutputAttr::Location(v),
+                    _ => panic!("invalid argument for location attr"),
+                },
+                _ => panic!("invalid return type attribute: {}", name),
+            }
+        })
+        .collect::<Vec<_>>();
+
     let output = pairs
         .by_ref()
         .peeking_take_while(|pair| pair.as_rule() == Rule::type_decl)
         .map(|pair| FnOutput {
-            attrs: vec![],
+            attrs: output_attrs.clone(),
             data_type: parse_type_decl(pair, env),
         })
         .next();
@@ -420,6 +451,7 @@ fn parse_statement(pair: Pair<Rule>, env: &mut Environment) -> Statement {
         Rule::fallthrough_statement => Statement::Fallthrough,
         Rule::switch_statement => parse_switch_statement(pair, env),
         Rule::for_statement => parse_for_statement(pair, env),
+        Rule::while_statement => parse_while_statement(pair, env),
         Rule::call_statement => parse_call_statement(pair, env),
         _ => unreachable!(),
     }
@@ -428,8 +460,19 @@ fn parse_statement(pair: Pair<Rule>, env: &mut Environment) -> Statement {
 fn parse_let_statement(pair: Pair<Rule>, env: &mut Environment) -> Statement {
     let mut pairs = pair.into_inner();
     let ident = pairs.next().unwrap().as_str().to_owned();
-    let initializer = parse_expression(pairs.next().unwrap(), env);
-    let stmt = LetDeclStatement::new(ident.clone(), initializer);
+
+    let mut pair = pairs.next().unwrap();
+
+    let specified_type = if pair.as_rule() == Rule::type_decl {
+        let ty = parse_type_decl(pair, env);
+        pair = pairs.next().unwrap();
+        Some(ty)
+    } else {
+        None
+    };
+
+    let initializer = parse_expression(pair, env);
+    let stmt = LetDeclStatement::new(ident.clone(), initializer).with_type(specified_type);
     env.insert_var(ident, stmt.inferred_type().clone());
     stmt.into()
 }
@@ -617,6 +660,13 @@ fn parse_for_statement(pair: Pair<Rule>, env: &mut Environment) -> Statement {
     ForLoopStatement::new(header, body.into_compount_statement()).into()
 }
 
+fn parse_while_statement(pair: Pair<Rule>, env: &Environment) -> Statement {
+    let mut pairs = pair.into_inner();
+    let condition = parse_paren_expression(pairs.next().unwrap(), env);
+    let body = parse_compound_statement(pairs.next().unwrap(), env).into_compount_statement();
+    WhileStatement::new(condition, body).into()
+}
+
 fn parse_call_statement(pair: Pair<Rule>, env: &Environment) -> Statement {
     let pair = pair.into_inner().next().unwrap();
     let mut pairs = pair.into_inner();
@@ -797,6 +847,10 @@ fn parse_literal_expression(pair: Pair<Rule>) -> ExprNode {
         ),
         Rule::int_literal => (ScalarType::I32, Lit::I32(pair.as_str().parse().unwrap())),
         Rule::float_literal => (ScalarType::F32, Lit::F32(pair.as_str().parse().unwrap())),
+        Rule::half_literal => (
+            ScalarType::F16,
+            Lit::F16(pair.as_str().trim_end_matches('h').parse().unwrap()),
+        ),
         _ => unreachable!(),
     };
 
@@ -952,6 +1006,7 @@ impl From<Rule> for ScalarType {
             Rule::t_i32 => ScalarType::I32,
             Rule::t_u32 => ScalarType::U32,
             Rule::t_f32 => ScalarType::F32,
+            Rule::t_f16 => ScalarType::F16,
             _ => unreachable!(),
         }
     }
@@ -988,4 +1043,337 @@ mod tests {
     test_case!(test_3);
     test_case!(test_4);
     test_case!(test_5);
+
+    const TEST_SRCS: &[(&str, &str)] = &[
+        ("calls", include_str!("tests/calls.wgsl")),
+        ("floats", include_str!("tests/floats.wgsl")),
+        ("loops", include_str!("tests/loops.wgsl")),
+        ("ptrs", include_str!("tests/ptrs.wgsl")),
+        ("structs", include_str!("tests/structs.wgsl")),
+        ("test_1", include_str!("tests/test_1.wgsl")),
+        ("test_2", include_str!("tests/test_2.wgsl")),
+        ("test_3", include_str!("tests/test_3.wgsl")),
+        ("test_4", include_str!("tests/test_4.wgsl")),
+        ("test_5", include_str!("tests/test_5.wgsl")),
+    ];
+
+    fn write(module: &Module) -> String {
+        let mut out = String::new();
+        ast::writer::Writer::default()
+            .write_module(&mut out, module)
+            .unwrap();
+        out
+    }
+
+    /// Checks that writing a generated module, parsing it and writing it again produces identical
+    /// output. The reducer relies on this to avoid introducing spurious changes to shaders.
+    #[test]
+    fn write_parse_write_is_fixpoint() {
+        use std::rc::Rc;
+
+        use clap::Parser;
+        use rand::SeedableRng;
+
+        // Keep the modules fairly small so that this doesn't take too long in debug builds
+        let options = generator::Options::parse_from([
+            "generator",
+            "--max-fns",
+            "2",
+            "--max-block-depth",
+            "2",
+            "--enable-pointers",
+        ]);
+        let config = Rc::new(options.generation_config().unwrap());
+
+        for seed in 0..20 {
+            let mut rng = generator::GenRng::seed_from_u64(seed);
+            let module = generator::Generator::new(&mut rng, config.clone()).gen_module();
+
+            let expected = write(&module);
+            let actual = write(&parse(&expected));
+
+            assert_eq!(expected, actual, "mismatch for seed {seed}");
+        }
+    }
+
+    /// Checks that the types annotated by the generator and the parser are consistent.
+    #[test]
+    fn annotations_typecheck() {
+        use std::rc::Rc;
+
+        use clap::Parser;
+        use rand::SeedableRng;
+
+        let options = generator::Options::parse_from([
+            "generator",
+            "--max-fns",
+            "2",
+            "--max-block-depth",
+            "2",
+            "--enable-pointers",
+        ]);
+        let config = Rc::new(options.generation_config().unwrap());
+
+        for seed in 0..20 {
+            let mut rng = generator::GenRng::seed_from_u64(seed);
+            let module = generator::Generator::new(&mut rng, config.clone()).gen_module();
+
+            if let Err(e) = ast::typecheck::typecheck(&module) {
+                panic!("generated module for seed {seed} is ill-typed: {e}");
+            }
+        }
+
+        for (name, src) in TEST_SRCS {
+            if let Err(e) = ast::typecheck::typecheck(&parse(src)) {
+                panic!("parsed module `{name}` is ill-typed: {e}");
+            }
+        }
+    }
+
+    /// Checks that modules survive a round trip through JSON, including the types of expressions.
+    #[test]
+    fn json_round_trip() {
+        for (_, src) in TEST_SRCS {
+            let module = parse(src);
+            let json = module.to_json().unwrap();
+            let deserialized = Module::from_json(&json).unwrap();
+
+            assert_eq!(module, deserialized);
+            assert_eq!(write(&module), write(&deserialized));
+        }
+    }
+
+    #[test]
+    fn complexity_metrics() {
+        use ast::metrics::{complexity_score, Metrics, RareConstruct};
+
+        let module = parse(
+            r#"
+fn f(x: i32) -> i32 {
+    var a: vec2<i32> = vec2<i32>(x, -x);
+    if (x > 0) {
+        loop {
+            a.x += (a.y * 2) - 1;
+            break;
+        }
+    }
+    let b = a.yx;
+    return b.x;
+}
+"#,
+        );
+
+        assert_eq!(
+            complexity_score(&module),
+            Metrics {
+                // unary -, >, +=, *, binary -
+                operator_diversity: 5,
+                // (a.y * 2) - 1
+                max_expr_depth: 4,
+                max_block_depth: 3,
+                // x, a, b
+                distinct_vars: 3,
+                rare_constructs: [RareConstruct::Loop, RareConstruct::Swizzle].into(),
+            }
+        );
+    }
+
+    #[test]
+    fn feature_tags() {
+        let module = parse(
+            r#"
+fn f(x: u32) -> vec4<u32> {
+    var a = countOneBits(x >> 2u);
+    switch (i32(a)) {
+        default {
+            a += 1u;
+        }
+    }
+    workgroupBarrier();
+    return vec4<u32>(a);
+}
+"#,
+        );
+
+        let expected = [
+            "assignment",
+            "barrier",
+            "countOneBits",
+            "i32",
+            "plus-assign",
+            "return",
+            "rshift",
+            "switch",
+            "u32",
+            "var",
+            "vec4",
+        ];
+
+        assert_eq!(
+            ast::feature_tags(&module),
+            expected.map(String::from).into()
+        );
+    }
+
+    #[test]
+    fn constant_output() {
+        use ast::constant::has_constant_output;
+
+        const PRELUDE: &str = r#"
+struct Buffer {
+    a: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> u_input: Buffer;
+
+@group(0) @binding(1)
+var<storage, read_write> s_output: Buffer;
+"#;
+
+        let check = |body: &str| {
+            let module = parse(&format!(
+                "{PRELUDE}\n@compute @workgroup_size(1)\nfn main() {{\n{body}\n}}\n"
+            ));
+            has_constant_output(&module).0
+        };
+
+        assert!(check("s_output.a = 1u + 2u;"));
+        assert!(check("var x = 1u; x = x * 2u; s_output.a = x;"));
+        assert!(!check("s_output.a = u_input.a;"));
+        assert!(!check(
+            "var x = 1u; if (u_input.a > 0u) { x = 2u; } s_output.a = x;"
+        ));
+        assert!(!check("if (u_input.a > 0u) { return; } s_output.a = 1u;"));
+        // Unreachable writes don't count.
+        assert!(check(
+            "if (false) { s_output.a = u_input.a; } s_output.a = 1u;"
+        ));
+    }
+
+    #[test]
+    fn explicitly_typed_declarations_round_trip() {
+        let src = r#"fn f() {
+    let a: vec2<i32> = vec2<i32>(1, 2);
+    let b = a.x;
+    var c: u32 = 3u;
+    var d = 4u;
+    var e: f32;
+}
+
+"#;
+
+        let module = parse(src);
+        assert_eq!(write(&module), src);
+
+        let types = module.functions[0]
+            .body
+            .iter()
+            .map(|stmt| match stmt {
+                Statement::LetDecl(stmt) => stmt.data_type.clone(),
+                Statement::VarDecl(stmt) => stmt.data_type.clone(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            types,
+            [
+                Some(DataType::Vector(2, ScalarType::I32)),
+                None,
+                Some(DataType::Scalar(ScalarType::U32)),
+                None,
+                Some(DataType::Scalar(ScalarType::F32)),
+            ]
+        );
+
+        ast::typecheck::typecheck(&module).unwrap();
+    }
+
+    #[test]
+    fn uninitialized_var_round_trips() {
+        let src = r#"fn f() -> u32 {
+    var a: u32;
+    var b: vec3<f32>;
+    a = 1u;
+    return a;
+}
+
+"#;
+
+        let module = parse(src);
+        assert_eq!(write(&module), src);
+
+        for stmt in &module.functions[0].body[..2] {
+            match stmt {
+                Statement::VarDecl(stmt) => assert!(stmt.initializer.is_none()),
+                _ => unreachable!(),
+            }
+        }
+
+        ast::typecheck::typecheck(&module).unwrap();
+    }
+
+    #[test]
+    fn fragment_entrypoint_round_trips() {
+        let src = r#"@fragment
+fn main() -> @location(0) vec4<u32> {
+    return vec4<u32>(1u, 2u, 3u, 4u);
+}
+
+"#;
+
+        let module = parse(src);
+        assert_eq!(write(&module), src);
+
+        let output = module.functions[0].output.as_ref().unwrap();
+        assert_eq!(output.attrs, vec![FnOutputAttr::Location(0)]);
+
+        ast::typecheck::typecheck(&module).unwrap();
+    }
+
+    #[test]
+    fn f16_round_trips() {
+        let src = r#"enable f16;
+
+fn f(a: f16) -> vec2<f16> {
+    let b: f16 = a * 1.5h;
+    return vec2<f16>(-(-2.0h), b);
+}
+
+"#;
+
+        let module = parse(src);
+        assert_eq!(write(&module), src);
+        assert_eq!(module.enables, vec![Extension::F16]);
+
+        ast::typecheck::typecheck(&module).unwrap();
+    }
+
+    #[test]
+    fn comment_statements_are_discarded() {
+        let src = r#"fn main() {
+    // stmt 0: let
+    let a: i32 = 1;
+    // stmt 1: if
+    if (a > 0) {
+        // stmt 2: assignment
+        _ = a;
+    }
+}
+
+"#;
+
+        let module = parse(src);
+        let body = &module.functions[0].body;
+        assert_eq!(body.len(), 2);
+        assert!(matches!(body[0], Statement::LetDecl(_)));
+
+        let expected = src
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        assert_eq!(write(&module), expected);
+    }
 }
@@ -3,4 +3,9 @@ fn main() {
         var x = 0;
         break;
     }
+
+    var i = 0;
+    while (i < 4) {
+        i = i + 1;
+    }
 }
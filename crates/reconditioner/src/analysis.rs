@@ -264,11 +264,16 @@ fn visit_stmt<'a>(
 
             visit_stmt_block(analysis, &mut scope, cx, &stmt.body);
         }
+        Statement::While(stmt) => {
+            visit_expr(analysis, scope, cx, &stmt.condition);
+            visit_stmt_block(analysis, scope, cx, &stmt.body);
+        }
         Statement::FnCall(stmt) => {
             visit_function_call(analysis, scope, cx, &stmt.ident, &stmt.args);
         }
         Statement::Continue => {}
         Statement::Fallthrough => {}
+        Statement::Comment(_) => {}
     }
 }
 
@@ -23,6 +23,10 @@ pub struct Options {
         require_value_delimiter(true)
     )]
     pub enable: Vec<Feature>,
+
+    /// Print a list of the applied transformations to stderr.
+    #[clap(long, action)]
+    pub report: bool,
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -47,7 +51,13 @@ pub fn run(options: Options) -> eyre::Result<()> {
         rec_opts.only_loops = true;
     }
 
-    let result = crate::recondition_with(ast, rec_opts);
+    let (result, report) = crate::recondition_impl(ast, rec_opts);
+
+    if options.report {
+        for transformation in &report.transformations {
+            eprintln!("{transformation}");
+        }
+    }
 
     struct Output(Box<dyn std::io::Write>);
 
@@ -14,6 +14,46 @@ pub struct ReconditionResult {
     pub loop_count: u32,
 }
 
+/// A record of the transformations applied by [`recondition_with_report`].
+#[derive(Debug, Default)]
+pub struct ReconditionReport {
+    pub transformations: Vec<Transformation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transformation {
+    /// Name of the function containing the transformed code.
+    pub function: String,
+    /// Index of the enclosing statement within the function, counting statements (including
+    /// nested ones) in source order starting from 0.
+    pub statement: usize,
+    pub kind: TransformationKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformationKind {
+    /// A loop was given an iteration limit, using the given index into `LOOP_COUNTERS`.
+    LoopLimit(u32),
+    /// The shift amount of a shift expression was reduced modulo 32.
+    MaskedShift,
+    /// A double negation was rewritten as a multiplication by -1.
+    DoubleNegation,
+    /// An expression was replaced by a call to the named safe wrapper function.
+    SafeWrapper(String),
+}
+
+impl Display for Transformation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} stmt {}: ", self.function, self.statement)?;
+        match &self.kind {
+            TransformationKind::LoopLimit(id) => write!(f, "limited loop (counter {id})"),
+            TransformationKind::MaskedShift => write!(f, "masked shift amount"),
+            TransformationKind::DoubleNegation => write!(f, "rewrote double negation"),
+            TransformationKind::SafeWrapper(name) => write!(f, "guarded with `{name}`"),
+        }
+    }
+}
+
 #[derive(Hash, PartialEq, Eq)]
 enum Wrapper {
     Clamp(DataType),
@@ -80,7 +120,16 @@ pub fn recondition(ast: Module) -> Module {
     recondition_with(ast, Options::default())
 }
 
-pub fn recondition_with(mut ast: Module, options: Options) -> Module {
+pub fn recondition_with(ast: Module, options: Options) -> Module {
+    recondition_impl(ast, options).0
+}
+
+/// Reconditions `ast`, additionally returning a report of every transformation that was applied.
+pub fn recondition_with_report(ast: Module) -> (Module, ReconditionReport) {
+    recondition_impl(ast, Options::default())
+}
+
+fn recondition_impl(mut ast: Module, options: Options) -> (Module, ReconditionReport) {
     let mut reconditioner = Reconditioner::new(options);
 
     let functions = ast
@@ -89,12 +138,16 @@ pub fn recondition_with(mut ast: Module, options: Options) -> Module {
         .map(|f| reconditioner.recondition_fn(f))
         .collect::<Vec<_>>();
 
-    ast.functions = reconditioner
+    // Sort the wrappers so that the output doesn't depend on the iteration order of the set
+    let mut wrappers = reconditioner
         .wrappers
         .iter()
         .map(Wrapper::gen_fn_decl)
-        .chain(functions)
-        .collect();
+        .collect::<Vec<_>>();
+
+    wrappers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    ast.functions = wrappers.into_iter().chain(functions).collect();
 
     if reconditioner.loop_var > 0 {
         ast.vars.push(GlobalVarDecl {
@@ -109,13 +162,21 @@ pub fn recondition_with(mut ast: Module, options: Options) -> Module {
         });
     }
 
-    ast
+    let report = ReconditionReport {
+        transformations: reconditioner.transformations,
+    };
+
+    (ast, report)
 }
 
 struct Reconditioner {
     loop_var: u32,
     wrappers: HashSet<Wrapper>,
     only_loops: bool,
+    function: String,
+    current_stmt: usize,
+    next_stmt: usize,
+    transformations: Vec<Transformation>,
 }
 
 impl Reconditioner {
@@ -124,10 +185,16 @@ impl Reconditioner {
             loop_var: 0,
             wrappers: HashSet::new(),
             only_loops: options.only_loops,
+            function: String::new(),
+            current_stmt: 0,
+            next_stmt: 0,
+            transformations: vec![],
         }
     }
 
     fn recondition_fn(&mut self, mut decl: FnDecl) -> FnDecl {
+        self.function = decl.name.clone();
+        self.next_stmt = 0;
         decl.body = decl
             .body
             .into_iter()
@@ -157,10 +224,25 @@ impl Reconditioner {
     }
 
     fn recondition_stmt(&mut self, stmt: Statement) -> Statement {
+        let index = self.next_stmt;
+        self.next_stmt += 1;
+
+        let prev_stmt = std::mem::replace(&mut self.current_stmt, index);
+        let stmt = self.recondition_stmt_inner(stmt);
+        self.current_stmt = prev_stmt;
+
+        stmt
+    }
+
+    fn recondition_stmt_inner(&mut self, stmt: Statement) -> Statement {
         match stmt {
-            Statement::LetDecl(LetDeclStatement { ident, initializer }) => {
-                LetDeclStatement::new(ident, self.recondition_expr(initializer)).into()
-            }
+            Statement::LetDecl(LetDeclStatement {
+                ident,
+                data_type,
+                initializer,
+            }) => LetDeclStatement::new(ident, self.recondition_expr(initializer))
+                .with_type(data_type)
+                .into(),
             Statement::VarDecl(VarDeclStatement {
                 ident,
                 data_type,
@@ -233,6 +315,11 @@ impl Reconditioner {
                 self.recondition_loop_body(body),
             )
             .into(),
+            Statement::While(WhileStatement { condition, body }) => WhileStatement::new(
+                self.recondition_expr(condition),
+                self.recondition_loop_body(body),
+            )
+            .into(),
             Statement::FnCall(FnCallStatement { ident, args }) => {
                 Statement::FnCall(FnCallStatement::new(
                     ident,
@@ -243,6 +330,7 @@ impl Reconditioner {
             }
             Statement::Continue => Statement::Continue,
             Statement::Fallthrough => Statement::Fallthrough,
+            Statement::Comment(text) => Statement::Comment(text),
         }
     }
 
@@ -274,6 +362,7 @@ impl Reconditioner {
 
     fn recondition_loop_body(&mut self, body: Vec<Statement>) -> Vec<Statement> {
         let id = self.loop_var();
+        self.record(TransformationKind::LoopLimit(id));
 
         let counters_ty = DataType::Ref(MemoryViewType::new(
             DataType::array(ScalarType::U32, None),
@@ -364,7 +453,10 @@ impl Reconditioner {
                     UnOp::Neg => {
                         let data_type = inner.data_type.dereference().clone();
                         let mut expr = self.recondition_negation(inner);
-                        if data_type.as_scalar().unwrap() == ScalarType::F32 {
+                        if matches!(
+                            data_type.as_scalar(),
+                            Some(ScalarType::F32 | ScalarType::F16)
+                        ) {
                             expr = FnCallExpr::new(
                                 self.safe_wrapper(Wrapper::FloatOp(data_type.clone())),
                                 vec![ExprNode { data_type, expr }],
@@ -400,7 +492,10 @@ impl Reconditioner {
                     _ => FnCallExpr::new(expr.ident, args),
                 };
 
-                if matches!(node.data_type.as_scalar(), Some(ScalarType::F32)) {
+                if matches!(
+                    node.data_type.as_scalar(),
+                    Some(ScalarType::F32 | ScalarType::F16)
+                ) {
                     FnCallExpr::new(
                         self.safe_wrapper(Wrapper::FloatOp(node.data_type.clone())),
                         vec![expr.into_node(node.data_type.clone())],
@@ -440,7 +535,7 @@ impl Reconditioner {
             // Recondition if inner is a unary negation or a negative literal
             matches!(expr, Expr::UnOp(UnOpExpr { op: UnOp::Neg, .. }))
                 || matches!(expr, Expr::Lit(Lit::I32(v)) if *v < 0)
-                || matches!(expr, Expr::Lit(Lit::F32(v)) if *v < 0.0)
+                || matches!(expr, Expr::Lit(Lit::F32(v) | Lit::F16(v)) if *v < 0.0)
         }
 
         let data_type = inner.data_type.dereference();
@@ -450,9 +545,12 @@ impl Reconditioner {
             return UnOpExpr::new(UnOp::Neg, inner).into();
         }
 
+        self.record(TransformationKind::DoubleNegation);
+
         let scalar_lit = match scalar_ty {
             ScalarType::I32 => Lit::I32(-1),
             ScalarType::F32 => Lit::F32(-1.0),
+            ScalarType::F16 => Lit::F16(-1.0),
             _ => unreachable!("negation can only be applied to signed integers and floats"),
         };
 
@@ -491,13 +589,17 @@ impl Reconditioner {
         operand: ExprNode,
         shift_value: ExprNode,
     ) -> ExprNode {
-        let shift_type = shift_value.data_type.dereference();
-        let shift_bound: ExprNode = match ty {
-            DataType::Scalar(_) => Lit::U32(32).into(),
-            DataType::Vector(_, _) => {
-                TypeConsExpr::new(shift_type.clone(), vec![Lit::U32(32).into()]).into()
+        self.record(TransformationKind::MaskedShift);
+
+        // The mask must have the same type as the shift amount - a scalar mask applied to a vector
+        // shift amount is rejected by some backends.
+        let shift_type = shift_value.data_type.dereference().clone();
+        let shift_bound: ExprNode = match (&ty, &shift_type) {
+            (DataType::Scalar(_), DataType::Scalar(ScalarType::U32)) => Lit::U32(32).into(),
+            (DataType::Vector(n, _), DataType::Vector(m, ScalarType::U32)) if n == m => {
+                TypeConsExpr::new(shift_type, vec![Lit::U32(32).into()]).into()
             }
-            _ => unreachable!(),
+            _ => panic!("invalid shift amount of type `{shift_type}` for shifted type `{ty}`"),
         };
 
         ExprNode::from(BinOpExpr::new(
@@ -522,10 +624,12 @@ impl Reconditioner {
             ScalarType::I32 | ScalarType::U32 | ScalarType::AU32 | ScalarType::AI32 => { // TODO: Check!!
                 self.recondition_integer_bin_op_expr(data_type, op, l, r)
             }
-            ScalarType::F32 if op == BinOp::Divide => {
+            ScalarType::F32 | ScalarType::F16 if op == BinOp::Divide => {
                 self.recondition_floating_point_div_expr(data_type, op, l, r)
             }
-            ScalarType::F32 => self.recondition_floating_point_bin_op_expr(data_type, op, l, r),
+            ScalarType::F32 | ScalarType::F16 => {
+                self.recondition_floating_point_bin_op_expr(data_type, op, l, r)
+            }
             ScalarType::Bool => BinOpExpr::new(op, l, r).into(),
         }
     }
@@ -585,7 +689,177 @@ impl Reconditioner {
 
     fn safe_wrapper(&mut self, wrapper: Wrapper) -> String {
         let ident = wrapper.to_string();
+        self.record(TransformationKind::SafeWrapper(ident.clone()));
         self.wrappers.insert(wrapper);
         ident
     }
+
+    fn record(&mut self, kind: TransformationKind) {
+        self.transformations.push(Transformation {
+            function: self.function.clone(),
+            statement: self.current_stmt,
+            kind,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::types::DataType;
+    use ast::{BinOp, Expr, ExprNode, ScalarType, Statement};
+
+    fn check_shifts(node: &ExprNode, count: &mut usize) {
+        match &node.expr {
+            Expr::Lit(_) | Expr::Var(_) => {}
+            Expr::TypeCons(expr) => expr.args.iter().for_each(|e| check_shifts(e, count)),
+            Expr::FnCall(expr) => expr.args.iter().for_each(|e| check_shifts(e, count)),
+            Expr::Postfix(expr) => check_shifts(&expr.inner, count),
+            Expr::UnOp(expr) => check_shifts(&expr.inner, count),
+            Expr::BinOp(expr) => {
+                if let BinOp::LShift | BinOp::RShift = expr.op {
+                    let amount_ty = node_ty(&expr.left).map(ScalarType::U32);
+                    assert_eq!(*node_ty(&expr.right), amount_ty, "bad shift amount: {node}");
+
+                    // The shift amount should be masked by a value of the same type
+                    match &expr.right.expr {
+                        Expr::BinOp(mask) if mask.op == BinOp::Mod => {
+                            assert_eq!(*node_ty(&mask.right), amount_ty, "bad mask: {node}");
+                        }
+                        _ => panic!("unmasked shift amount: {node}"),
+                    }
+
+                    *count += 1;
+                }
+
+                check_shifts(&expr.left, count);
+                check_shifts(&expr.right, count);
+            }
+        }
+    }
+
+    fn node_ty(node: &ExprNode) -> &DataType {
+        node.data_type.dereference()
+    }
+
+    #[test]
+    fn report_lists_transformations() {
+        use super::{Transformation, TransformationKind};
+
+        let module = parser::parse(
+            r#"
+@compute @workgroup_size(1)
+fn main() {
+    var a = array<i32, 4>(1, 2, 3, 4);
+    let x = 7 / 2;
+    let y = 1u << 2u;
+    let z = a[1];
+}
+"#,
+        );
+
+        let (_, report) = super::recondition_with_report(module);
+
+        let transformation = |statement, kind| Transformation {
+            function: "main".into(),
+            statement,
+            kind,
+        };
+
+        let wrapper = |name: &str| TransformationKind::SafeWrapper(name.to_owned());
+
+        assert_eq!(
+            report.transformations,
+            [
+                transformation(1, wrapper("_wgslsmith_div_i32")),
+                transformation(2, TransformationKind::MaskedShift),
+                transformation(3, wrapper("_wgslsmith_index_i32")),
+            ]
+        );
+    }
+
+    #[test]
+    fn vector_shift_masks_match_width() {
+        let module = parser::parse(
+            r#"
+@compute @workgroup_size(1)
+fn main() {
+    let a = 1u << 2u;
+    let b = vec2<i32>(1) << vec2<u32>(3u);
+    let c = vec3<u32>(1u) >> vec3<u32>(a);
+    let d = vec4<i32>(-1) >> vec4<u32>(1u, 2u, 3u, 4u);
+}
+"#,
+        );
+
+        let module = super::recondition(module);
+        let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+
+        let mut count = 0;
+        for stmt in &main.body {
+            if let Statement::LetDecl(decl) = stmt {
+                check_shifts(&decl.initializer, &mut count);
+            }
+        }
+
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn f16_float_wrappers_use_f16_literals() {
+        let module = parser::parse(
+            r#"
+enable f16;
+
+@compute @workgroup_size(1)
+fn main() {
+    let a = 1.5h * 2.0h;
+    let b = vec2<f16>(1.0h) / vec2<f16>(3.0h);
+}
+"#,
+        );
+
+        let module = super::recondition(module);
+        ast::typecheck::typecheck(&module).unwrap();
+
+        let mut out = String::new();
+        ast::writer::Writer::default()
+            .write_module(&mut out, &module)
+            .unwrap();
+
+        assert!(out.starts_with("enable f16;"), "{out}");
+        assert!(out.contains("f16(2048.0h)"), "{out}");
+        assert!(!out.contains("16777216.0"), "{out}");
+    }
+
+    #[test]
+    fn wrappers_are_sorted_by_name() {
+        let module = parser::parse(
+            r#"
+@compute
+@workgroup_size(1)
+fn main() {
+    let a = 1 + 2;
+    let b = 3u * 4u;
+    let c = 5 / 6;
+    let d = 7u - 8u;
+    let e = 9 % 10;
+}
+"#,
+        );
+
+        let module = super::recondition(module);
+
+        let wrappers = module
+            .functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .filter(|name| name.starts_with("_wgslsmith_"))
+            .collect::<Vec<_>>();
+
+        let mut sorted = wrappers.clone();
+        sorted.sort();
+
+        assert_eq!(wrappers.len(), 5);
+        assert_eq!(wrappers, sorted);
+    }
 }
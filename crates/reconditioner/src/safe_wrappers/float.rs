@@ -1,9 +1,17 @@
 use ast::{
-    BinOp, BinOpExpr, DataType, FnCallExpr, FnDecl, FnInput, FnOutput, Lit, ReturnStatement,
+    BinOp, BinOpExpr, DataType, FnCallExpr, FnDecl, FnInput, FnOutput, ReturnStatement, ScalarType,
     TypeConsExpr, VarExpr,
 };
 
+use super::float_lit;
+
 pub fn float(name: String, data_type: &DataType) -> FnDecl {
+    // Largest integer up to which every integer is exactly representable in the type.
+    let max_exact = match data_type.as_scalar() {
+        Some(ScalarType::F16) => 2048.0,
+        _ => 16777216.0,
+    };
+
     FnDecl {
         attrs: vec![],
         name,
@@ -14,7 +22,8 @@ pub fn float(name: String, data_type: &DataType) -> FnDecl {
                 "select".to_owned(),
                 vec![
                     VarExpr::new("v").into_node(data_type.clone()),
-                    TypeConsExpr::new(data_type.clone(), vec![Lit::F32(10.0).into()]).into(),
+                    TypeConsExpr::new(data_type.clone(), vec![float_lit(data_type, 10.0).into()])
+                        .into(),
                     BinOpExpr::new(
                         BinOp::LogOr,
                         super::any(BinOpExpr::new(
@@ -24,7 +33,10 @@ pub fn float(name: String, data_type: &DataType) -> FnDecl {
                                 vec![VarExpr::new("v").into_node(data_type.clone())],
                             )
                             .into_node(data_type.clone()),
-                            TypeConsExpr::new(data_type.clone(), vec![Lit::F32(0.1).into()]),
+                            TypeConsExpr::new(
+                                data_type.clone(),
+                                vec![float_lit(data_type, 0.1).into()],
+                            ),
                         )),
                         super::any(BinOpExpr::new(
                             BinOp::GreaterEqual,
@@ -33,7 +45,10 @@ pub fn float(name: String, data_type: &DataType) -> FnDecl {
                                 vec![VarExpr::new("v").into_node(data_type.clone())],
                             )
                             .into_node(data_type.clone()),
-                            TypeConsExpr::new(data_type.clone(), vec![Lit::F32(16777216.0).into()]),
+                            TypeConsExpr::new(
+                                data_type.clone(),
+                                vec![float_lit(data_type, max_exact).into()],
+                            ),
                         )),
                     )
                     .into(),
@@ -1,10 +1,14 @@
 use ast::*;
 
+use super::float_lit;
+
 pub fn float_divide(name: String, data_type: &DataType) -> FnDecl {
     let condition = gen_condition(data_type);
 
-    let correct_res = TypeConsExpr::new(data_type.clone(), vec![Lit::F32(42.0).into()]).into();
-    let incorrect_res = TypeConsExpr::new(data_type.clone(), vec![Lit::F32(-123.0).into()]).into();
+    let correct_res =
+        TypeConsExpr::new(data_type.clone(), vec![float_lit(data_type, 42.0).into()]).into();
+    let incorrect_res =
+        TypeConsExpr::new(data_type.clone(), vec![float_lit(data_type, -123.0).into()]).into();
 
     FnDecl {
         attrs: vec![],
@@ -27,14 +31,16 @@ fn gen_condition(data_type: &DataType) -> ExprNode {
         VarExpr::new("a").into_node(data_type.clone()),
         VarExpr::new("b").into_node(data_type.clone()),
         |a, b| {
+            // The closure is called with the individual components of vectors.
+            let component_type = a.data_type.clone();
             BinOpExpr::new(
                 BinOp::Greater,
                 FnCallExpr::new(
                     "abs",
                     vec![BinOpExpr::new(BinOp::Divide, a.clone(), b).into()],
                 )
-                .into_node(data_type.clone()),
-                FnCallExpr::new("abs", vec![a]).into_node(data_type.clone()),
+                .into_node(component_type.clone()),
+                FnCallExpr::new("abs", vec![a]).into_node(component_type),
             )
             .into()
         },
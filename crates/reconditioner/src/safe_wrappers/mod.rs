@@ -37,6 +37,14 @@ fn any(expr: impl Into<ExprNode>) -> ExprNode {
     }
 }
 
+/// Returns a float literal with the same scalar type as `data_type` (either `f32` or `f16`).
+fn float_lit(data_type: &DataType, value: f32) -> Lit {
+    match data_type.as_scalar() {
+        Some(ScalarType::F16) => Lit::F16(value),
+        _ => Lit::F32(value),
+    }
+}
+
 fn componentwise_or(
     a: impl Into<ExprNode>,
     b: impl Into<ExprNode>,
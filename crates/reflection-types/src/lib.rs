@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
@@ -9,28 +11,78 @@ pub struct ResourceData<'a> {
 
 #[derive(Clone, Debug, Decode, Encode)]
 pub struct PipelineDescription {
+    pub stage: PipelineStage,
     pub resources: Vec<PipelineResource>,
 }
 
+/// Shader stage of the `main` entrypoint, which determines the kind of pipeline it's run with.
+#[derive(Clone, Copy, Debug, Decode, Encode, PartialEq, Eq)]
+pub enum PipelineStage {
+    /// Dispatched with a compute pipeline.
+    Compute,
+    /// Drawn as a full-screen triangle to a 1x1 `rgba32uint` render target, which is read back
+    /// as an extra output buffer after the storage buffers.
+    Fragment,
+}
+
 #[derive(Clone, Debug, Decode, Encode, PartialEq, Eq)]
 pub enum ResourceKind {
     StorageBuffer,
     UniformBuffer,
 }
 
-#[derive(Clone, Debug, Decode, Encode, Serialize, Deserialize)]
+/// Initial state of a buffer, as given in an inputs file.
+///
+/// In JSON, initial contents can be given either as a plain array of bytes (this is what the
+/// generator writes) or as `{"Data": {"data": [...]}}`. A buffer that should only be allocated is
+/// given as `{"Size": {"size": n}}`.
+#[derive(Clone, Debug, Decode, Encode, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "BufferInitRepr")]
 pub enum BufferInitInfo {
-  Data { data: Vec<u8> },
-  Size { size: u32 }
+    Data { data: Vec<u8> },
+    Size { size: u32 },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BufferInitRepr {
+    Bytes(Vec<u8>),
+    Tagged(TaggedBufferInitInfo),
+}
+
+#[derive(Deserialize)]
+enum TaggedBufferInitInfo {
+    Data { data: Vec<u8> },
+    Size { size: u32 },
+}
+
+impl From<BufferInitRepr> for BufferInitInfo {
+    fn from(repr: BufferInitRepr) -> Self {
+        match repr {
+            BufferInitRepr::Bytes(data)
+            | BufferInitRepr::Tagged(TaggedBufferInitInfo::Data { data }) => {
+                BufferInitInfo::Data { data }
+            }
+            BufferInitRepr::Tagged(TaggedBufferInitInfo::Size { size }) => {
+                BufferInitInfo::Size { size }
+            }
+        }
+    }
+}
+
+/// Parses the contents of an inputs file, which maps `"{group}:{binding}"` to the initial state of
+/// the buffer with that group and binding.
+pub fn parse_input_data(json: &str) -> serde_json::Result<HashMap<String, BufferInitInfo>> {
+    serde_json::from_str(json)
 }
 
 impl BufferInitInfo {
-  pub fn size_in_elem(&self, bytes_per_elem: usize) -> u32 {
-    match self {
-      BufferInitInfo::Data { data } => u32::try_from(data.len() / bytes_per_elem ).unwrap(),
-      BufferInitInfo::Size { size } => size / u32::try_from(bytes_per_elem).unwrap()
+    pub fn size_in_elem(&self, bytes_per_elem: usize) -> u32 {
+        match self {
+            BufferInitInfo::Data { data } => u32::try_from(data.len() / bytes_per_elem).unwrap(),
+            BufferInitInfo::Size { size } => size / u32::try_from(bytes_per_elem).unwrap(),
+        }
     }
-  }
 }
 
 #[derive(Clone, Debug, Decode, Encode)]
@@ -42,3 +94,28 @@ pub struct PipelineResource {
     pub init: Option<Vec<u8>>,
     pub size: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_input_data_formats() {
+        let inputs = parse_input_data(
+            r#"{"0:0": [1, 2, 3], "0:1": {"Data": {"data": [4]}}, "0:2": {"Size": {"size": 8}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            inputs["0:0"],
+            BufferInitInfo::Data {
+                data: vec![1, 2, 3]
+            }
+        );
+        assert_eq!(inputs["0:1"], BufferInitInfo::Data { data: vec![4] });
+        assert_eq!(inputs["0:2"], BufferInitInfo::Size { size: 8 });
+
+        assert!(parse_input_data(r#"{"0:0": [256]}"#).is_err());
+        assert!(parse_input_data(r#"{"0:0": "abc"}"#).is_err());
+    }
+}
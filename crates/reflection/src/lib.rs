@@ -1,20 +1,22 @@
-use ast::{Module, StorageClass, VarQualifier};
+use ast::layout::{stride_of, LayoutRules};
+use ast::{FnAttr, Module, ShaderStage, StorageClass, VarQualifier};
 use types::BufferInitInfo;
-pub use types::{PipelineDescription, PipelineResource, ResourceData, ResourceKind};
+pub use types::{PipelineDescription, PipelineResource, PipelineStage, ResourceData, ResourceKind};
 
 
 
 // If the type is a runtime array, we populate the size from the buffer init info, which is passed as either a vector
 // of bytes or a size in bytes. The final size of the array is in elements, so bytes/bytes_per_element.
 fn update_size(value: &ast::DataType, init_data: &Option<BufferInitInfo>) -> ast::DataType {
-  match value {
-    ast::DataType::Array(inner, size) => {
-      ast::DataType::array (
-      inner.as_ref().clone(),
-      size.or(init_data.as_ref().map(|data| data.size_in_elem(inner.size_bytes()))))
-    },
-    other => other.clone()
-  }
+    match value {
+        ast::DataType::Array(inner, size) => ast::DataType::array(
+            inner.as_ref().clone(),
+            size.or(init_data
+                .as_ref()
+                .map(|data| data.size_in_elem(stride_of(inner, LayoutRules::Std430) as usize))),
+        ),
+        other => other.clone(),
+    }
 }
 
 pub fn reflect(
@@ -74,5 +76,18 @@ pub fn reflect(
         }
     }
 
-    (PipelineDescription { resources }, types)
+    let stage = module
+        .functions
+        .iter()
+        .find(|it| it.name == "main")
+        .and_then(|it| {
+            it.attrs.iter().find_map(|attr| match attr {
+                FnAttr::Stage(ShaderStage::Fragment) => Some(PipelineStage::Fragment),
+                FnAttr::Stage(_) => Some(PipelineStage::Compute),
+                _ => None,
+            })
+        })
+        .unwrap_or(PipelineStage::Compute);
+
+    (PipelineDescription { stage, resources }, types)
 }
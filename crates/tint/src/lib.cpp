@@ -9,6 +9,25 @@ bool validate_shader(const char* source) {
     return program->IsValid();
 }
 
+std::unique_ptr<std::string> get_shader_warnings(const char* source) {
+    auto source_file = std::make_unique<tint::Source::File>("[memory]", source);
+    auto program = std::make_unique<tint::Program>(tint::reader::wgsl::Parse(source_file.get()));
+
+    std::string warnings;
+
+    for (auto& diag : program->Diagnostics()) {
+        if (diag.severity != tint::diag::Severity::Warning) {
+            continue;
+        }
+
+        auto& begin = diag.source.range.begin;
+        warnings += std::to_string(begin.line) + ":" + std::to_string(begin.column) +
+                    ": warning: " + diag.message + "\n";
+    }
+
+    return std::make_unique<std::string>(std::move(warnings));
+}
+
 std::unique_ptr<std::string> compile_shader_to_hlsl(const char* source) {
     auto source_file = std::make_unique<tint::Source::File>("[memory]", source);
     auto program = std::make_unique<tint::Program>(tint::reader::wgsl::Parse(source_file.get()));
@@ -5,6 +5,8 @@
 
 bool validate_shader(const char* source);
 
+std::unique_ptr<std::string> get_shader_warnings(const char* source);
+
 std::unique_ptr<std::string> compile_shader_to_hlsl(const char* source);
 
 std::unique_ptr<std::string> compile_shader_to_msl(const char* source);
@@ -5,6 +5,7 @@ mod ffi {
     unsafe extern "C++" {
         include!("tint/src/lib.h");
         unsafe fn validate_shader(source: *const c_char) -> bool;
+        unsafe fn get_shader_warnings(source: *const c_char) -> UniquePtr<CxxString>;
         unsafe fn compile_shader_to_hlsl(source: *const c_char) -> UniquePtr<CxxString>;
         unsafe fn compile_shader_to_msl(source: *const c_char) -> UniquePtr<CxxString>;
     }
@@ -15,6 +16,16 @@ pub fn validate_shader(source: &str) -> bool {
     unsafe { ffi::validate_shader(source.as_ptr()) }
 }
 
+/// Returns the warnings reported by Tint's front-end for `source`, one per line.
+pub fn get_shader_warnings(source: &str) -> Vec<String> {
+    let source = CString::new(source).unwrap();
+    unsafe { ffi::get_shader_warnings(source.as_ptr()) }
+        .to_string()
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
 pub fn compile_shader_to_hlsl(source: &str) -> String {
     let source = CString::new(source).unwrap();
     unsafe { ffi::compile_shader_to_hlsl(source.as_ptr()) }.to_string()
@@ -119,6 +119,13 @@ fn visit_stmt(vars: &mut HashSet<String>, stmt: &Statement) {
                 visit_stmt(vars, stmt);
             }
         }
+        Statement::While(stmt) => {
+            visit_expr(vars, &stmt.condition);
+
+            for stmt in &stmt.body {
+                visit_stmt(vars, stmt);
+            }
+        }
         Statement::FnCall(stmt) => {
             for arg in &stmt.args {
                 visit_expr(vars, arg);
@@ -126,6 +133,7 @@ fn visit_stmt(vars: &mut HashSet<String>, stmt: &Statement) {
         }
         Statement::Continue => {}
         Statement::Fallthrough => {}
+        Statement::Comment(_) => {}
     }
 }
 
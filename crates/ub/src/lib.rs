@@ -254,6 +254,16 @@ impl UBInserter {
                 }
                 ForLoopStatement::new(*header, new_body).into()
             }
+            Statement::While(WhileStatement { condition, body }) => {
+                let mod_body: Vec<Statement> =
+                    body.into_iter().map(|it| self.analyze_stmt(it)).collect();
+                let new_body = if let Some(insertion) = self.build_assign() {
+                    vec![Statement::Compound(vec![insertion.into(), mod_body.into()])]
+                } else {
+                    mod_body
+                };
+                WhileStatement::new(condition, new_body).into()
+            }
             Statement::Continue => Statement::Continue,
             Statement::Fallthrough => Statement::Fallthrough,
             Statement::Compound(stmts) => {
@@ -0,0 +1,221 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use clap::Parser;
+use eyre::{eyre, Context};
+use harness_types::ConfigId;
+use serde_json::json;
+
+use crate::config::Config;
+use crate::harness_runner::{self, ExecutionResult, Harness};
+
+#[derive(Parser)]
+pub struct Options {
+    /// Path to the WGSL shader file to bundle.
+    #[clap(long, action)]
+    shader: PathBuf,
+
+    /// Path to the input data file.
+    ///
+    /// If not set, the program will look for a JSON file with the same name as the shader, or an
+    /// `inputs.json` file in the same directory.
+    #[clap(long, action)]
+    input_data: Option<PathBuf>,
+
+    /// Path at which to write the archive.
+    #[clap(short, long, action)]
+    out: PathBuf,
+
+    /// Address of harness server.
+    #[clap(short, long, action)]
+    server: Option<String>,
+
+    /// Specific harness configuration to execute the shader with.
+    #[clap(long, action)]
+    config: Option<ConfigId>,
+
+    /// Don't execute the shader. The bundle will not include the harness output.
+    #[clap(long, action)]
+    no_exec: bool,
+}
+
+pub fn run(config: &Config, options: Options) -> eyre::Result<()> {
+    let shader = std::fs::read_to_string(&options.shader)
+        .wrap_err_with(|| eyre!("failed to read `{}`", options.shader.display()))?;
+
+    let input_path = match options.input_data {
+        Some(path) => path,
+        None => find_input_data(&options.shader)?,
+    };
+
+    let metadata = std::fs::read_to_string(&input_path)
+        .wrap_err_with(|| eyre!("failed to read `{}`", input_path.display()))?;
+
+    let harness = if let Some(server) = options
+        .server
+        .as_deref()
+        .or_else(|| config.default_remote())
+    {
+        Harness::Remote(server.to_owned())
+    } else {
+        Harness::Local(
+            config
+                .harness
+                .path
+                .clone()
+                .map(Ok)
+                .unwrap_or_else(std::env::current_exe)?,
+        )
+    };
+
+    let shader_name = options
+        .shader
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("shader.wgsl");
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        (shader_name.to_owned(), shader.clone().into_bytes()),
+        ("inputs.json".to_owned(), metadata.clone().into_bytes()),
+    ];
+
+    // Include the reconditioned shader if it was saved alongside the original by the fuzzer
+    if let Some(dir) = options.shader.parent() {
+        let path = dir.join("reconditioned.wgsl");
+        if shader_name != "reconditioned.wgsl" && path.exists() {
+            files.push(("reconditioned.wgsl".to_owned(), std::fs::read(path)?));
+        }
+    }
+
+    match harness_runner::list_configs(&harness) {
+        Ok(configs) => files.push(("adapters.txt".to_owned(), configs.into_bytes())),
+        Err(e) => println!("> failed to collect adapter info: {e}"),
+    }
+
+    let result = if options.no_exec {
+        None
+    } else {
+        println!("> executing shader");
+
+        let mut output = String::new();
+        let result = harness_runner::exec_shader(
+            &harness,
+            options.config.clone(),
+            &shader,
+            &metadata,
+            |line| writeln!(output, "{line}").unwrap(),
+        )?;
+
+        println!("> result: {result}");
+
+        files.push(("output.txt".to_owned(), output.into_bytes()));
+
+        Some(result)
+    };
+
+    let info = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "shader": shader_name,
+        "config": options.config.as_ref().map(|it| it.to_string()),
+        "result": result.as_ref().map(|it| it.to_string()),
+    });
+
+    files.push((
+        "info.json".to_owned(),
+        serde_json::to_string_pretty(&info)?.into_bytes(),
+    ));
+
+    files.push((
+        "README.txt".to_owned(),
+        gen_readme(shader_name, options.config.as_ref(), result.as_ref()).into_bytes(),
+    ));
+
+    write_archive(&options.out, &files)
+        .wrap_err_with(|| eyre!("failed to write archive `{}`", options.out.display()))?;
+
+    println!("> wrote bundle to {}", options.out.display());
+
+    Ok(())
+}
+
+fn find_input_data(shader: &Path) -> eyre::Result<PathBuf> {
+    let dir = shader.parent().unwrap();
+
+    let try_path = dir.join(shader.file_stem().unwrap()).with_extension("json");
+    if try_path.exists() {
+        return Ok(try_path);
+    }
+
+    let try_path = dir.join("inputs.json");
+    if try_path.exists() {
+        return Ok(try_path);
+    }
+
+    Err(eyre!(
+        "couldn't determine path to inputs file, pass one explicitly"
+    ))
+}
+
+fn gen_readme(
+    shader_name: &str,
+    config: Option<&ConfigId>,
+    result: Option<&ExecutionResult>,
+) -> String {
+    let mut readme = String::new();
+
+    writeln!(readme, "wgslsmith {}", env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(readme).unwrap();
+
+    if let Some(result) = result {
+        writeln!(readme, "Execution result: {result}").unwrap();
+        writeln!(readme).unwrap();
+    }
+
+    writeln!(readme, "To reproduce, run:").unwrap();
+    writeln!(readme).unwrap();
+    write!(readme, "    wgslsmith run {shader_name} inputs.json").unwrap();
+
+    if let Some(config) = config {
+        write!(readme, " -c {config}").unwrap();
+    }
+
+    writeln!(readme).unwrap();
+    writeln!(readme).unwrap();
+    writeln!(
+        readme,
+        "The available harness configurations are listed in adapters.txt."
+    )
+    .unwrap();
+
+    if result.is_some() {
+        writeln!(
+            readme,
+            "The output of the original execution is in output.txt."
+        )
+        .unwrap();
+    }
+
+    readme
+}
+
+fn write_archive(path: &Path, files: &[(String, Vec<u8>)]) -> eyre::Result<()> {
+    let mtime = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+
+    let mut builder = tar::Builder::new(File::create(path)?);
+
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+
+    builder.into_inner()?;
+
+    Ok(())
+}
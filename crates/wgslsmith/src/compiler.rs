@@ -1,12 +1,25 @@
+use std::ffi::OsStr;
 use std::fmt::Display;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use clap::ValueEnum;
+use color_eyre::Help;
 use eyre::{eyre, Context};
+#[cfg(feature = "harness")]
+use harness_types::Implementation;
 
 #[derive(ValueEnum, Clone)]
 pub enum Compiler {
     Tint,
     Naga,
+    /// Dawn's integrated WGSL front-end, which is only available with the `harness` feature.
+    Dawn,
 }
 
 impl Display for Compiler {
@@ -14,6 +27,7 @@ impl Display for Compiler {
         let val = match self {
             Compiler::Tint => "tint",
             Compiler::Naga => "naga",
+            Compiler::Dawn => "dawn",
         };
 
         write!(f, "{val}")
@@ -39,18 +53,108 @@ impl Display for Backend {
     }
 }
 
+/// Timeout for compiler invocations in milliseconds, or zero for no timeout.
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Sets a timeout for every later compiler invocation in this process.
+///
+/// A compiler that doesn't finish in time fails with a [`CompilerTimeout`] error.
+pub fn set_timeout(timeout: Duration) {
+    TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+fn timeout() -> Option<Duration> {
+    match TIMEOUT_MS.load(Ordering::Relaxed) {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+/// Error returned when a compiler doesn't finish within the timeout set with [`set_timeout`].
+#[derive(Debug)]
+pub struct CompilerTimeout(pub Duration);
+
+impl Display for CompilerTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "compiler timed out after {}s", self.0.as_secs_f64())
+    }
+}
+
+impl std::error::Error for CompilerTimeout {}
+
 impl Compiler {
-    pub fn validate(&self, source: &str) -> eyre::Result<()> {
+    /// Name of the environment variable which can be set to the path of a compiler binary to use
+    /// instead of the version of the compiler that is built into wgslsmith.
+    ///
+    /// Dawn always runs in-process as part of the harness, so it can't be overridden.
+    pub fn path_var(&self) -> Option<&'static str> {
         match self {
-            Compiler::Tint => validate_tint(source).wrap_err("tint validation failed"),
-            Compiler::Naga => validate_naga(source).wrap_err("naga validation failed"),
+            Compiler::Tint => Some("WGSLSMITH_TINT_PATH"),
+            Compiler::Naga => Some("WGSLSMITH_NAGA_PATH"),
+            Compiler::Dawn => None,
         }
     }
 
+    /// Returns the path to the external binary set for this compiler, if there is one.
+    fn external_path(&self) -> Option<PathBuf> {
+        std::env::var_os(self.path_var()?).map(PathBuf::from)
+    }
+
+    pub fn validate(&self, source: &str) -> eyre::Result<()> {
+        self.validate_with(source, false)
+    }
+
+    /// Like [`validate`](Self::validate), but if `warnings_as_errors` is set then warnings from
+    /// the compiler's front-end also make validation fail, with the warnings as the error message.
+    ///
+    /// Naga doesn't report warnings, so this has no effect on it.
+    pub fn validate_with(&self, source: &str, warnings_as_errors: bool) -> eyre::Result<()> {
+        let source = source.to_owned();
+        self.run_with_timeout(move |compiler| match compiler {
+            Compiler::Tint => {
+                validate_tint(&source, warnings_as_errors).wrap_err("tint validation failed")
+            }
+            Compiler::Naga => validate_naga(&source).wrap_err("naga validation failed"),
+            Compiler::Dawn => {
+                validate_dawn(&source, warnings_as_errors).wrap_err("dawn validation failed")
+            }
+        })
+    }
+
     pub fn compile(&self, source: &str, backend: Backend) -> eyre::Result<String> {
-        match self {
-            Compiler::Tint => compile_tint(source, backend),
-            Compiler::Naga => compile_naga(source, backend),
+        let source = source.to_owned();
+        self.run_with_timeout(move |compiler| match compiler {
+            Compiler::Tint => compile_tint(&source, backend),
+            Compiler::Naga => compile_naga(&source, backend),
+            Compiler::Dawn => Err(eyre!("dawn can't be used to compile to {backend}")),
+        })
+    }
+
+    /// Runs `f` with this compiler, enforcing the timeout set with [`set_timeout`].
+    ///
+    /// External compiler binaries are killed by [`run_external`] when they time out. A compiler
+    /// that is built into wgslsmith can't be interrupted, so it runs on a separate thread which is
+    /// abandoned if it times out.
+    fn run_with_timeout<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Compiler) -> eyre::Result<T> + Send + 'static,
+    ) -> eyre::Result<T> {
+        let timeout = match timeout() {
+            Some(timeout) if self.external_path().is_none() => timeout,
+            _ => return f(self),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let compiler = self.clone();
+
+        thread::spawn(move || {
+            let _ = tx.send(f(&compiler));
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(CompilerTimeout(timeout).into()),
+            Err(RecvTimeoutError::Disconnected) => Err(eyre!("{self} panicked")),
         }
     }
 }
@@ -58,15 +162,75 @@ impl Compiler {
 fn validate_naga(source: &str) -> eyre::Result<()> {
     use naga::front::wgsl;
     use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    if let Some(path) = Compiler::Naga.external_path() {
+        let dir = TempDir::new()?;
+        let input = dir.write_shader(&source.replace("@stage(compute)", "@compute"))?;
+        run_external(&Compiler::Naga, &path, &[input.as_os_str()])?;
+        return Ok(());
+    }
+
     let module = wgsl::parse_str(&source.replace("@stage(compute)", "@compute"))?;
     Validator::new(ValidationFlags::default(), Capabilities::all()).validate(&module)?;
     Ok(())
 }
 
-fn validate_tint(source: &str) -> eyre::Result<()> {
-    tint::validate_shader(source)
-        .then(|| ())
-        .ok_or_else(|| eyre!("invalid wgsl"))
+fn validate_tint(source: &str, warnings_as_errors: bool) -> eyre::Result<()> {
+    if let Some(path) = Compiler::Tint.external_path() {
+        let dir = TempDir::new()?;
+        let input = dir.write_shader(source)?;
+        let output = run_external(
+            &Compiler::Tint,
+            &path,
+            &["--format".as_ref(), "wgsl".as_ref(), input.as_os_str()],
+        )?;
+
+        if warnings_as_errors {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let warnings = stderr
+                .lines()
+                .filter(|line| line.contains("warning:"))
+                .collect::<Vec<_>>();
+            if !warnings.is_empty() {
+                return Err(eyre!(warnings.join("\n")));
+            }
+        }
+
+        return Ok(());
+    }
+
+    if !tint::validate_shader(source) {
+        return Err(eyre!("invalid wgsl"));
+    }
+
+    if warnings_as_errors {
+        let warnings = tint::get_shader_warnings(source);
+        if !warnings.is_empty() {
+            return Err(eyre!(warnings.join("\n")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates `source` by compiling it with the first available Dawn adapter, so that errors come
+/// from Dawn's front-end rather than standalone Tint. Shaders with only warnings are valid unless
+/// `warnings_as_errors` is set.
+#[cfg(feature = "harness")]
+fn validate_dawn(source: &str, warnings_as_errors: bool) -> eyre::Result<()> {
+    let config = harness::query_configs()
+        .into_iter()
+        .find(|it| it.id.implementation == Implementation::Dawn)
+        .ok_or_else(|| eyre!("no dawn adapters available"))?;
+
+    harness::compile_only(source, &config.id, warnings_as_errors)?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "harness"))]
+fn validate_dawn(_source: &str, _warnings_as_errors: bool) -> eyre::Result<()> {
+    Err(eyre!("wgslsmith was built without the `harness` feature"))
 }
 
 fn compile_naga(source: &str, backend: Backend) -> eyre::Result<String> {
@@ -74,6 +238,27 @@ fn compile_naga(source: &str, backend: Backend) -> eyre::Result<String> {
     use naga::front::wgsl;
     use naga::valid::{Capabilities, ValidationFlags, Validator};
 
+    if let Some(path) = Compiler::Naga.external_path() {
+        // Naga picks the output language from the file extension
+        let extension = match backend {
+            Backend::Hlsl => "hlsl",
+            Backend::Msl => "metal",
+            // Naga writes binary SPIR-V, which can't be returned as text
+            Backend::Spirv => return Err(eyre!("naga can't compile to {backend} text")),
+        };
+
+        let dir = TempDir::new()?;
+        let input = dir.write_shader(&source.replace("@stage(compute)", "@compute"))?;
+        let output = dir.0.join(format!("out.{extension}"));
+        run_external(
+            &Compiler::Naga,
+            &path,
+            &[input.as_os_str(), output.as_os_str()],
+        )?;
+
+        return Ok(std::fs::read_to_string(output)?);
+    }
+
     let module = wgsl::parse_str(&source.replace("@stage(compute)", "@compute"))?;
     let validation =
         Validator::new(ValidationFlags::default(), Capabilities::all()).validate(&module)?;
@@ -99,6 +284,19 @@ fn compile_naga(source: &str, backend: Backend) -> eyre::Result<String> {
 }
 
 fn compile_tint(source: &str, backend: Backend) -> eyre::Result<String> {
+    if let Some(path) = Compiler::Tint.external_path() {
+        let dir = TempDir::new()?;
+        let input = dir.write_shader(source)?;
+        let format = backend.to_string();
+        let output = run_external(
+            &Compiler::Tint,
+            &path,
+            &["--format".as_ref(), format.as_ref(), input.as_os_str()],
+        )?;
+
+        return Ok(String::from_utf8(output.stdout)?);
+    }
+
     let out = match backend {
         Backend::Hlsl => tint::compile_shader_to_hlsl(source),
         Backend::Msl => tint::compile_shader_to_msl(source),
@@ -106,3 +304,107 @@ fn compile_tint(source: &str, backend: Backend) -> eyre::Result<String> {
     };
     Ok(out)
 }
+
+/// Runs an external compiler binary with `args`, failing with the compiler's error output if it
+/// exits unsuccessfully.
+fn run_external(compiler: &Compiler, path: &Path, args: &[&OsStr]) -> eyre::Result<Output> {
+    let output = match spawn_with_timeout(Command::new(path).args(args)) {
+        Ok(output) => output?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let var = compiler.path_var().unwrap_or_default();
+            return Err(eyre!("{compiler} binary `{}` not found", path.display())
+                .with_suggestion(|| format!("check the path set in `{var}`")));
+        }
+        Err(e) => {
+            return Err(eyre!(e).wrap_err(format!("failed to run `{}`", path.display())));
+        }
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => eyre!("`{}` failed with {}", path.display(), output.status),
+            stderr => eyre!("{stderr}"),
+        });
+    }
+
+    Ok(output)
+}
+
+/// Runs `cmd` to completion and collects its output, killing it if it doesn't finish within the
+/// timeout set with [`set_timeout`].
+///
+/// The outer error is from spawning the process, and the inner one is a [`CompilerTimeout`].
+fn spawn_with_timeout(cmd: &mut Command) -> io::Result<eyre::Result<Output>> {
+    let timeout = match timeout() {
+        Some(timeout) => timeout,
+        None => return cmd.output().map(Ok),
+    };
+
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    // The pipes are drained on separate threads so that the compiler can't block on a full pipe
+    // while it's being waited for
+    let read_pipe = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buf = vec![];
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+
+    let stdout = read_pipe(child.stdout.take().map(|it| Box::new(it) as _));
+    let stderr = read_pipe(child.stderr.take().map(|it| Box::new(it) as _));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(Err(CompilerTimeout(timeout).into()));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+/// Temporary directory for passing shaders to external compilers, which is deleted when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> io::Result<TempDir> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("wgslsmith-{}-{id}", std::process::id()));
+
+        std::fs::create_dir_all(&path)?;
+
+        Ok(TempDir(path))
+    }
+
+    /// Writes `source` to `shader.wgsl` in the directory, returning its path.
+    fn write_shader(&self, source: &str) -> io::Result<PathBuf> {
+        let path = self.0.join("shader.wgsl");
+        std::fs::write(&path, source)?;
+        Ok(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -28,6 +28,8 @@ pub struct Config {
 pub struct Harness {
     pub path: Option<PathBuf>,
     pub remote: Option<String>,
+    /// Maximum time in seconds to wait for a remote harness server to finish a request.
+    pub request_timeout: Option<u64>,
 }
 
 #[derive(Default, Deserialize)]
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use ast::{
+    AssignmentLhs, Else, Expr, ExprNode, ForLoopInit, ForLoopUpdate, Module, Postfix, Statement,
+};
+use clap::Parser;
+use eyre::{eyre, Context};
+use harness_types::ConfigId;
+use regex::Regex;
+
+use crate::config::Config;
+use crate::harness_runner::{self, ExecutionResult, Harness};
+
+#[derive(Parser)]
+pub struct Options {
+    /// Path to a directory of test cases.
+    ///
+    /// Each subdirectory is treated as a single test case, containing a shader and an
+    /// `inputs.json` file (this is the layout used by the fuzzer when saving test cases).
+    #[clap(action)]
+    dir: PathBuf,
+
+    /// Name of the shader file within each test case directory.
+    #[clap(long, action, default_value = "shader.wgsl")]
+    shader_name: String,
+
+    /// Regex used to compute the crash signature of a test case.
+    ///
+    /// Test cases are grouped by the text matched by the first regex that matches their crash
+    /// output. This may be passed multiple times.
+    #[clap(long, action, required = true)]
+    regex: Vec<Regex>,
+
+    /// Address of harness server.
+    #[clap(short, long, action)]
+    server: Option<String>,
+
+    /// Specific harness configuration to execute test cases with.
+    #[clap(long, action)]
+    config: Option<ConfigId>,
+
+    /// Don't recondition shaders before executing.
+    #[clap(long, action)]
+    no_recondition: bool,
+
+    /// Print the groups that were found without deleting anything.
+    #[clap(long, action)]
+    dry_run: bool,
+
+    /// Disable logging from harness.
+    #[clap(short, long, action)]
+    quiet: bool,
+}
+
+struct TestCase {
+    path: PathBuf,
+    nodes: usize,
+    len: usize,
+}
+
+pub fn run(config: &Config, options: Options) -> eyre::Result<()> {
+    let harness = if let Some(server) = options
+        .server
+        .as_deref()
+        .or_else(|| config.default_remote())
+    {
+        Harness::Remote(server.to_owned())
+    } else {
+        Harness::Local(
+            config
+                .harness
+                .path
+                .clone()
+                .map(Ok)
+                .unwrap_or_else(std::env::current_exe)?,
+        )
+    };
+
+    let mut dirs = std::fs::read_dir(&options.dir)
+        .wrap_err_with(|| eyre!("failed to read dir `{}`", options.dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join(&options.shader_name).exists())
+        .collect::<Vec<_>>();
+
+    dirs.sort();
+
+    let mut groups = BTreeMap::<String, Vec<TestCase>>::new();
+
+    for dir in dirs {
+        println!("> evaluating {}", dir.display());
+
+        match crash_signature(&options, &harness, &dir) {
+            Ok(Some((signature, test_case))) => {
+                groups.entry(signature).or_default().push(test_case)
+            }
+            Ok(None) => println!("> no matching crash, skipping"),
+            Err(e) => println!("> failed to evaluate test case, skipping: {e}"),
+        }
+    }
+
+    for (signature, mut test_cases) in groups {
+        test_cases.sort_by(|a, b| (a.nodes, a.len, &a.path).cmp(&(b.nodes, b.len, &b.path)));
+
+        let mut test_cases = test_cases.into_iter();
+        let representative = test_cases.next().unwrap();
+
+        println!();
+        println!("{signature}");
+        println!(
+            "  keep:   {} ({} nodes)",
+            representative.path.display(),
+            representative.nodes
+        );
+
+        for test_case in test_cases {
+            println!(
+                "  remove: {} ({} nodes)",
+                test_case.path.display(),
+                test_case.nodes
+            );
+            if !options.dry_run {
+                std::fs::remove_dir_all(&test_case.path).wrap_err_with(|| {
+                    eyre!("failed to remove dir `{}`", test_case.path.display())
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn crash_signature(
+    options: &Options,
+    harness: &Harness,
+    dir: &Path,
+) -> eyre::Result<Option<(String, TestCase)>> {
+    let source = std::fs::read_to_string(dir.join(&options.shader_name))?;
+    let metadata = std::fs::read_to_string(dir.join("inputs.json"))?;
+
+    let module = parser::parse(&source);
+    let nodes = count_nodes(&module);
+
+    let source = if options.no_recondition {
+        source
+    } else {
+        crate::test::recondition(module)
+    };
+
+    let result = harness_runner::exec_shader(
+        harness,
+        options.config.clone(),
+        &source,
+        &metadata,
+        |line| {
+            if !options.quiet {
+                println!("{line}");
+            }
+        },
+    )?;
+
+    let output = match result {
+        ExecutionResult::Crash(output) => output,
+        _ => return Ok(None),
+    };
+
+    let signature = options
+        .regex
+        .iter()
+        .find_map(|regex| regex.find(&output))
+        .map(|m| m.as_str().to_owned());
+
+    Ok(signature.map(|signature| {
+        let test_case = TestCase {
+            path: dir.to_owned(),
+            nodes,
+            len: source.len(),
+        };
+
+        (signature, test_case)
+    }))
+}
+
+/// Counts the number of statement and expression nodes in a module.
+///
+/// This is used as a measure of shader size that isn't affected by formatting or identifier
+/// lengths.
+pub fn count_nodes(module: &Module) -> usize {
+    module
+        .functions
+        .iter()
+        .map(|decl| count_stmts(&decl.body))
+        .sum()
+}
+
+fn count_stmts(stmts: &[Statement]) -> usize {
+    stmts.iter().map(count_stmt).sum()
+}
+
+fn count_stmt(stmt: &Statement) -> usize {
+    1 + match stmt {
+        Statement::LetDecl(stmt) => count_expr(&stmt.initializer),
+        Statement::VarDecl(stmt) => stmt.initializer.as_ref().map(count_expr).unwrap_or(0),
+        Statement::Assignment(stmt) => {
+            let lhs = match &stmt.lhs {
+                AssignmentLhs::Phony => 0,
+                AssignmentLhs::Expr(_) => 1,
+            };
+
+            lhs + count_expr(&stmt.rhs)
+        }
+        Statement::Compound(stmts) => count_stmts(stmts),
+        Statement::If(stmt) => {
+            let mut count = count_expr(&stmt.condition) + count_stmts(&stmt.body);
+
+            let mut else_ = stmt.else_.as_deref();
+            while let Some(e) = else_ {
+                match e {
+                    Else::If(stmt) => {
+                        count += count_expr(&stmt.condition) + count_stmts(&stmt.body);
+                        else_ = stmt.else_.as_deref();
+                    }
+                    Else::Else(body) => {
+                        count += count_stmts(body);
+                        else_ = None;
+                    }
+                }
+            }
+
+            count
+        }
+        Statement::Return(stmt) => stmt.value.as_ref().map(count_expr).unwrap_or(0),
+        Statement::Loop(stmt) => count_stmts(&stmt.body),
+        Statement::Switch(stmt) => {
+            count_expr(&stmt.selector)
+                + stmt
+                    .cases
+                    .iter()
+                    .map(|case| count_expr(&case.selector) + count_stmts(&case.body))
+                    .sum::<usize>()
+                + count_stmts(&stmt.default)
+        }
+        Statement::ForLoop(stmt) => {
+            let init = match &stmt.header.init {
+                Some(ForLoopInit::VarDecl(stmt)) => {
+                    1 + stmt.initializer.as_ref().map(count_expr).unwrap_or(0)
+                }
+                None => 0,
+            };
+
+            let update = match &stmt.header.update {
+                Some(ForLoopUpdate::Assignment(stmt)) => 1 + count_expr(&stmt.rhs),
+                None => 0,
+            };
+
+            init + stmt.header.condition.as_ref().map(count_expr).unwrap_or(0)
+                + update
+                + count_stmts(&stmt.body)
+        }
+        Statement::While(stmt) => count_expr(&stmt.condition) + count_stmts(&stmt.body),
+        Statement::FnCall(stmt) => stmt.args.iter().map(count_expr).sum(),
+        Statement::Break | Statement::Continue | Statement::Fallthrough => 0,
+        // Comments aren't nodes, so they shouldn't count towards the size of a shader
+        Statement::Comment(_) => return 0,
+    }
+}
+
+fn count_expr(node: &ExprNode) -> usize {
+    1 + match &node.expr {
+        Expr::Lit(_) | Expr::Var(_) => 0,
+        Expr::TypeCons(expr) => expr.args.iter().map(count_expr).sum(),
+        Expr::Postfix(expr) => {
+            count_expr(&expr.inner)
+                + match &expr.postfix {
+                    Postfix::Index(index) => count_expr(index),
+                    Postfix::Member(_) => 0,
+                }
+        }
+        Expr::UnOp(expr) => count_expr(&expr.inner),
+        Expr::BinOp(expr) => count_expr(&expr.left) + count_expr(&expr.right),
+        Expr::FnCall(expr) => expr.args.iter().map(count_expr).sum(),
+    }
+}
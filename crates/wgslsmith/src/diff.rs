@@ -0,0 +1,97 @@
+use std::fmt::Write;
+
+/// Number of unchanged lines to show around each change in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// Returns a line-based diff from `a` to `b` in unified format, without the file headers.
+///
+/// The result is empty if there are no differences.
+pub fn unified_diff(a: &str, b: &str) -> String {
+    let lines = diff_lines(a, b);
+    let is_change = |(kind, _): &(char, &str)| *kind != ' ';
+
+    let mut diff = String::new();
+    let mut start = 0;
+    while let Some(offset) = lines[start..].iter().position(is_change) {
+        let first_change = start + offset;
+
+        // Merge changes into the same hunk if their context would overlap
+        let mut end = first_change + 1;
+        while let Some(gap) = lines[end..].iter().position(is_change) {
+            if gap > 2 * DIFF_CONTEXT {
+                break;
+            }
+            end += gap + 1;
+        }
+
+        let hunk_start = first_change.saturating_sub(DIFF_CONTEXT).max(start);
+        let hunk_end = (end + DIFF_CONTEXT).min(lines.len());
+
+        let count = |range: &[(char, &str)], excluded: char| {
+            range.iter().filter(|(kind, _)| *kind != excluded).count()
+        };
+
+        // Unified diffs number an empty range by the line before it
+        let range = |before: usize, len: usize| match len {
+            0 => format!("{before},0"),
+            _ => format!("{},{len}", before + 1),
+        };
+
+        let before = &lines[..hunk_start];
+        let hunk = &lines[hunk_start..hunk_end];
+
+        writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            range(count(before, '+'), count(hunk, '+')),
+            range(count(before, '-'), count(hunk, '-'))
+        )
+        .unwrap();
+
+        for (kind, line) in hunk {
+            writeln!(diff, "{kind}{line}").unwrap();
+        }
+
+        start = hunk_end;
+    }
+
+    diff
+}
+
+/// Returns every line of `a` and `b`, marked with `-` if it was removed from `a`, `+` if it was
+/// added in `b` or a space if it's in both.
+fn diff_lines<'a>(a: &'a str, b: &'a str) -> Vec<(char, &'a str)> {
+    let a = a.lines().collect::<Vec<_>>();
+    let b = b.lines().collect::<Vec<_>>();
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push((' ', a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            // Prefer removals so that a changed line is shown as `-` followed by `+`
+            lines.push(('-', a[i]));
+            i += 1;
+        } else {
+            lines.push(('+', b[j]));
+            j += 1;
+        }
+    }
+
+    lines
+}
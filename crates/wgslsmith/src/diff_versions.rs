@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use eyre::{eyre, Context};
+
+use crate::diff::unified_diff;
+use crate::verify_recondition::collect_shaders;
+
+#[derive(Parser)]
+pub struct Options {
+    /// Paths to golden shaders, as written by `wgslsmith gen`.
+    ///
+    /// Directories are searched recursively for `.wgsl` files. Each shader is regenerated from
+    /// the seed in its `// Seed: N` header.
+    #[clap(action, required = true)]
+    goldens: Vec<PathBuf>,
+
+    /// Overwrite goldens that differ with the current output, instead of failing.
+    #[clap(long, action)]
+    update: bool,
+
+    /// Options to pass to the generator, e.g. `-- --recondition`.
+    ///
+    /// These must match the options that the goldens were generated with.
+    #[clap(action, last = true)]
+    gen_args: Vec<String>,
+}
+
+/// Regenerates each golden shader from its seed and prints a diff for every shader that the
+/// current version of the generator produces differently.
+pub fn run(options: Options) -> eyre::Result<()> {
+    let mut paths = vec![];
+
+    for path in &options.goldens {
+        collect_shaders(path, &mut paths)
+            .wrap_err_with(|| eyre!("failed to read `{}`", path.display()))?;
+    }
+
+    let mut changed = 0;
+
+    for path in &paths {
+        let golden = std::fs::read_to_string(path)
+            .wrap_err_with(|| eyre!("failed to read `{}`", path.display()))?;
+
+        let seed = read_seed(&golden)
+            .ok_or_else(|| eyre!("`{}` has no `// Seed: N` header", path.display()))?;
+
+        let gen_options = generator::Options::try_parse_from(
+            std::iter::once("gen").chain(options.gen_args.iter().map(String::as_str)),
+        )?;
+
+        let current = generator::gen_to_string(gen_options, seed)
+            .wrap_err_with(|| eyre!("failed to generate shader from seed {seed}"))?;
+
+        // Compare by line so that goldens checked out with different line endings still match
+        if golden.lines().eq(current.lines()) {
+            continue;
+        }
+
+        changed += 1;
+
+        println!("--- {}", path.display());
+        println!("+++ {} (seed {seed})", path.display());
+        print!("{}", unified_diff(&golden, &current));
+
+        if options.update {
+            std::fs::write(path, &current)?;
+        }
+    }
+
+    println!("> {changed} of {} shaders changed", paths.len());
+
+    if changed > 0 && !options.update {
+        return Err(eyre!(
+            "{changed} shaders are generated differently (see diffs above)"
+        ));
+    }
+
+    Ok(())
+}
+
+fn read_seed(shader: &str) -> Option<u64> {
+    shader
+        .lines()
+        .find_map(|line| line.strip_prefix("// Seed: "))?
+        .trim()
+        .parse()
+        .ok()
+}
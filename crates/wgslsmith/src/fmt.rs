@@ -1,7 +1,9 @@
 use std::fs::File;
 use std::io::Read;
 
-use clap::Parser;
+use ast::Module;
+use clap::{Parser, ValueEnum};
+use eyre::Context;
 
 #[derive(Parser)]
 pub struct Options {
@@ -12,11 +14,26 @@ pub struct Options {
     /// Path at which to write output (use '-' for stdout).
     #[clap(short, long, action, default_value = "-")]
     pub output: String,
+
+    /// Format of the input.
+    ///
+    /// `ast-json` reads an AST written by `wgslsmith gen --emit ast-json`.
+    #[clap(long, value_enum, action, default_value = "wgsl")]
+    pub input_format: InputFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum InputFormat {
+    Wgsl,
+    AstJson,
 }
 
 pub fn run(options: Options) -> eyre::Result<()> {
     let source = read_shader_from_path(&options.input)?;
-    let ast = parser::parse(&source);
+    let ast = match options.input_format {
+        InputFormat::Wgsl => parser::parse(&source),
+        InputFormat::AstJson => Module::from_json(&source).wrap_err("invalid AST JSON")?,
+    };
 
     struct Output(Box<dyn std::io::Write>);
 
@@ -0,0 +1,101 @@
+use ast::eval::try_const_eval;
+use ast::{Else, Expr, ExprNode, ForLoopInit, ForLoopUpdate, Module, Postfix, Statement};
+
+/// Replaces every constant subexpression in the functions of `module` with its value.
+///
+/// Returns the number of expressions that were folded.
+pub fn fold_module(module: &mut Module) -> usize {
+    module
+        .functions
+        .iter_mut()
+        .map(|decl| fold_stmts(&mut decl.body))
+        .sum()
+}
+
+fn fold_stmts(stmts: &mut [Statement]) -> usize {
+    stmts.iter_mut().map(fold_stmt).sum()
+}
+
+fn fold_stmt(stmt: &mut Statement) -> usize {
+    match stmt {
+        Statement::LetDecl(stmt) => fold_expr(&mut stmt.initializer),
+        Statement::VarDecl(stmt) => stmt.initializer.as_mut().map(fold_expr).unwrap_or(0),
+        Statement::Assignment(stmt) => fold_expr(&mut stmt.rhs),
+        Statement::Compound(stmts) => fold_stmts(stmts),
+        Statement::If(stmt) => {
+            let mut count = fold_expr(&mut stmt.condition) + fold_stmts(&mut stmt.body);
+
+            let mut else_ = stmt.else_.as_deref_mut();
+            while let Some(e) = else_ {
+                match e {
+                    Else::If(stmt) => {
+                        count += fold_expr(&mut stmt.condition) + fold_stmts(&mut stmt.body);
+                        else_ = stmt.else_.as_deref_mut();
+                    }
+                    Else::Else(body) => {
+                        count += fold_stmts(body);
+                        else_ = None;
+                    }
+                }
+            }
+
+            count
+        }
+        Statement::Return(stmt) => stmt.value.as_mut().map(fold_expr).unwrap_or(0),
+        Statement::Loop(stmt) => fold_stmts(&mut stmt.body),
+        Statement::Switch(stmt) => {
+            fold_expr(&mut stmt.selector)
+                + stmt
+                    .cases
+                    .iter_mut()
+                    .map(|case| fold_stmts(&mut case.body))
+                    .sum::<usize>()
+                + fold_stmts(&mut stmt.default)
+        }
+        Statement::ForLoop(stmt) => {
+            let init = match &mut stmt.header.init {
+                Some(ForLoopInit::VarDecl(stmt)) => {
+                    stmt.initializer.as_mut().map(fold_expr).unwrap_or(0)
+                }
+                None => 0,
+            };
+
+            let update = match &mut stmt.header.update {
+                Some(ForLoopUpdate::Assignment(stmt)) => fold_expr(&mut stmt.rhs),
+                None => 0,
+            };
+
+            init + stmt.header.condition.as_mut().map(fold_expr).unwrap_or(0)
+                + update
+                + fold_stmts(&mut stmt.body)
+        }
+        Statement::While(stmt) => fold_expr(&mut stmt.condition) + fold_stmts(&mut stmt.body),
+        Statement::FnCall(stmt) => stmt.args.iter_mut().map(fold_expr).sum(),
+        Statement::Break | Statement::Continue | Statement::Fallthrough => 0,
+        Statement::Comment(_) => 0,
+    }
+}
+
+fn fold_expr(node: &mut ExprNode) -> usize {
+    if !matches!(node.expr, Expr::Lit(_)) {
+        if let Some(lit) = try_const_eval(node) {
+            node.expr = Expr::Lit(lit);
+            return 1;
+        }
+    }
+
+    match &mut node.expr {
+        Expr::Lit(_) | Expr::Var(_) => 0,
+        Expr::TypeCons(expr) => expr.args.iter_mut().map(fold_expr).sum(),
+        Expr::Postfix(expr) => {
+            fold_expr(&mut expr.inner)
+                + match &mut expr.postfix {
+                    Postfix::Index(index) => fold_expr(index),
+                    Postfix::Member(_) => 0,
+                }
+        }
+        Expr::UnOp(expr) => fold_expr(&mut expr.inner),
+        Expr::BinOp(expr) => fold_expr(&mut expr.left) + fold_expr(&mut expr.right),
+        Expr::FnCall(expr) => expr.args.iter_mut().map(fold_expr).sum(),
+    }
+}
@@ -1,19 +1,28 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
 use std::io::{self, BufWriter, Write as _};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::{Arc, Mutex};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use clap::{Parser, ValueEnum};
 use crossbeam_channel::select;
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyModifiers};
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use eyre::eyre;
 use harness_types::ConfigId;
+use rand::SeedableRng;
+use reflection_types::{BufferInitInfo, ResourceKind};
 use regex::Regex;
+use serde::Serialize;
+use signal_hook::consts::SIGINT;
 use tap::Tap;
 use time::{format_description, OffsetDateTime, UtcOffset};
 use tui::backend::{Backend, CrosstermBackend};
@@ -72,9 +81,136 @@ pub struct Options {
     /// This is mostly for debugging.
     #[clap(long, action)]
     save_failures: bool,
+
+    /// Only process the seeds belonging to shard `I` out of `N` (e.g. `--shard 2/8`).
+    ///
+    /// Shard `I` generates shaders from the seeds `I`, `I + N`, `I + 2N`, etc. so that multiple
+    /// machines can fuzz the same seed space without overlap. Saved test cases are named after
+    /// their seed, which means that the output directories from different shards can be merged
+    /// without any conflicts.
+    #[clap(long, action)]
+    shard: Option<Shard>,
+
+    /// Skip shaders whose output is fully determined at compile time.
+    ///
+    /// Every backend will trivially agree on the output of such shaders, so executing them is a
+    /// waste of time.
+    #[clap(long, action)]
+    skip_constant: bool,
+
+    /// Number of worker threads to run in parallel.
+    ///
+    /// Each worker generates and executes its own shaders, so this can be used to saturate a GPU
+    /// (or several GPUs) when a single worker leaves it idle while generating. When combined with
+    /// `--shard`, the workers take seeds from the shard in turn.
+    #[clap(long, action, default_value = "1")]
+    workers: usize,
+
+    /// Reduce saved test cases as soon as they are found.
+    ///
+    /// The reduced shader is written to a `reduced` directory inside the test case directory.
+    /// Crashes can only be reduced if a specific harness configuration is being tested with
+    /// `--config`. This slows down fuzzing considerably, since the worker which found the test
+    /// case waits for the reducer to finish.
+    #[clap(long, action)]
+    reduce_on_find: bool,
+
+    /// Print a summary of the session when the fuzzer exits.
+    ///
+    /// This includes the number of shaders with each outcome, the average time spent generating
+    /// and executing shaders, and the overall throughput.
+    #[clap(long, action)]
+    stats: bool,
+
+    /// Print the `--stats` summary as JSON.
+    #[clap(long, action, requires = "stats")]
+    json: bool,
+
+    /// Stop at the first test case that is saved, printing its seed and the harness output and
+    /// exiting with an error.
+    ///
+    /// This is useful for triaging interactively. The test case is still saved to the output
+    /// directory.
+    #[clap(long, action, overrides_with = "collect-all")]
+    fail_fast: bool,
+
+    /// Keep fuzzing after a test case is saved, collecting every test case that is found.
+    ///
+    /// This is the default, and is useful for unattended fuzzing. It can be passed to override an
+    /// earlier `--fail-fast`.
+    #[clap(long, action, overrides_with = "fail-fast")]
+    collect_all: bool,
+
+    /// Save every crash, even if an earlier crash in this session had the same signature.
+    ///
+    /// By default, only the first crash with each signature is saved, and later ones are counted
+    /// as duplicates.
+    #[clap(long, action)]
+    keep_duplicates: bool,
+
+    /// Inject a pattern that is known to be compiled differently by some backends into every
+    /// shader - must be one of {shift-overflow, int-min-div, float-to-int}.
+    ///
+    /// The pattern is added after reconditioning, so every shader should produce a finding. This
+    /// is for checking that mismatches are detected and reduced. With `--reduce-on-find`, the
+    /// reconditioned shader is reduced without reconditioning it again, since that would remove
+    /// the pattern.
+    #[clap(long, action)]
+    inject_bug: Option<generator::InjectedBug>,
+
+    /// Check the output of each configuration against a reference output computed by the
+    /// interpreter, instead of only comparing configurations against each other.
+    ///
+    /// This catches bugs where every backend agrees on a wrong result. Shaders are generated with
+    /// the `integer` profile so that the interpreter can run them, and any shader that it still
+    /// can't run is skipped. The reference output is saved as `reference.bin` with each test case.
+    #[clap(long, action, conflicts_with_all = &["enable-pointers", "reduce-on-find"])]
+    reference: bool,
+
+    /// Instead of fuzzing, regenerate the shader for a single seed, execute it and print the
+    /// harness output.
+    ///
+    /// The shader is generated and reconditioned in exactly the same way as by a fuzzer worker, so
+    /// with the same generation options (e.g. `--enable-pointers`) it matches the shader that the
+    /// fuzzer produced for that seed. Nothing is written to the output directory.
+    #[clap(long, action, conflicts_with_all = &["shard", "workers", "reduce-on-find", "stats"])]
+    replay_seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Shard {
+    index: u64,
+    count: u64,
 }
 
-fn gen_shader(options: &Options) -> eyre::Result<String> {
+impl Shard {
+    /// Returns the `n`th seed belonging to this shard.
+    fn seed(self, n: u64) -> u64 {
+        self.index + n * self.count
+    }
+}
+
+impl FromStr for Shard {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (index, count) = value.split_once('/').ok_or("expected shard as `I/N`")?;
+        let index: u64 = index.parse().map_err(|_| "invalid shard index")?;
+        let count: u64 = count.parse().map_err(|_| "invalid shard count")?;
+
+        if count == 0 {
+            return Err("shard count must be greater than zero");
+        }
+
+        if index >= count {
+            return Err("shard index must be less than shard count");
+        }
+
+        Ok(Shard { index, count })
+    }
+}
+
+fn gen_shader(options: &Options, seed: Option<u64>) -> eyre::Result<String> {
     let output = Command::new(std::env::current_exe().unwrap())
         .arg("gen")
         .args(["--block-min-stmts", "1"])
@@ -84,6 +220,14 @@ fn gen_shader(options: &Options) -> eyre::Result<String> {
             if options.enable_pointers {
                 cmd.arg("--enable-pointers");
             }
+
+            if options.reference {
+                cmd.args(["--profile", "integer"]);
+            }
+
+            if let Some(seed) = seed {
+                cmd.arg(seed.to_string());
+            }
         })
         .stdout(Stdio::piped())
         .output()?;
@@ -95,6 +239,26 @@ fn gen_shader(options: &Options) -> eyre::Result<String> {
     Ok(String::from_utf8(output.stdout)?)
 }
 
+/// Splits the output of the generator into the JSON buffer metadata from its first line, and the
+/// shader itself.
+fn split_metadata(output: &str) -> eyre::Result<(&str, &str)> {
+    let (metadata, shader) = output
+        .split_once('\n')
+        .ok_or_else(|| eyre!("expected first line of shader to be a JSON metadata comment"))?;
+
+    Ok((metadata.trim_start_matches("//").trim(), shader))
+}
+
+/// Reads the seed from the `// Seed: N` header that the generator writes at the top of a shader.
+fn read_seed(shader: &str) -> Option<u64> {
+    shader
+        .lines()
+        .find_map(|line| line.strip_prefix("// Seed: "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
 fn recondition_shader(shader: &str) -> eyre::Result<String> {
     let mut reconditioner = Command::new(std::env::current_exe().unwrap())
         .arg("recondition")
@@ -117,6 +281,63 @@ fn recondition_shader(shader: &str) -> eyre::Result<String> {
     Ok(String::from_utf8(output.stdout)?)
 }
 
+/// Adds the pattern from `--inject-bug` (if any) to a reconditioned shader.
+///
+/// The parameters of the pattern are chosen from the shader's seed, so replaying a seed injects the
+/// same pattern.
+fn inject_bug(options: &Options, seed: Option<u64>, reconditioned: String) -> eyre::Result<String> {
+    let bug = match options.inject_bug {
+        Some(bug) => bug,
+        None => return Ok(reconditioned),
+    };
+
+    let mut module = parser::parse(&reconditioned);
+    let mut rng = generator::GenRng::seed_from_u64(seed.unwrap_or_default());
+    bug.inject(&mut module, &mut rng)?;
+
+    let mut injected = String::new();
+    ast::writer::Writer::default().write_module(&mut injected, &module)?;
+
+    Ok(injected)
+}
+
+/// Computes the expected contents of the storage buffers by running the shader in the interpreter.
+///
+/// The buffers are concatenated in the same order as the harness reports them, which only includes
+/// the storage buffers that the shader accesses.
+fn reference_output(shader: &str, metadata: &str) -> eyre::Result<Vec<u8>> {
+    let input_data = harness_frontend::read_input_data("-", Some(metadata))?;
+
+    let mut inputs = HashMap::new();
+    for (key, init) in input_data {
+        let (group, binding) = key
+            .split_once(':')
+            .ok_or_else(|| eyre!("invalid buffer binding `{key}`"))?;
+
+        if let BufferInitInfo::Data { data } = init {
+            inputs.insert((group.parse()?, binding.parse()?), data);
+        }
+    }
+
+    let outputs: HashMap<_, _> = ast::interp::run(&parser::parse(shader), &inputs)?
+        .into_iter()
+        .collect();
+
+    let (pipeline_desc, _) = harness_frontend::reflect_shader(shader, HashMap::new());
+
+    let mut reference = vec![];
+    for resource in &pipeline_desc.resources {
+        if resource.kind == ResourceKind::StorageBuffer {
+            let output = outputs
+                .get(&resource.name)
+                .ok_or_else(|| eyre!("interpreter didn't produce `{}`", resource.name))?;
+            reference.extend_from_slice(output);
+        }
+    }
+
+    Ok(reference)
+}
+
 impl ExecutionResult {
     fn should_save<'a>(
         &self,
@@ -125,7 +346,9 @@ impl ExecutionResult {
     ) -> bool {
         match self {
             ExecutionResult::Success => false,
-            // ExecutionResult::Timeout => false,
+            ExecutionResult::Timeout => false,
+            ExecutionResult::ExceedsLimits => false,
+            ExecutionResult::UnsupportedFeature => false,
             ExecutionResult::Crash(output) => {
                 matches!(strategy, SaveStrategy::All | SaveStrategy::Crashes)
                     && !ignore.any(|it| it.is_match(output))
@@ -141,19 +364,37 @@ static mut UTC_OFFSET: Option<UtcOffset> = None;
 
 fn save_shader(
     out: &Path,
+    seed: Option<u64>,
     shader: &str,
     reconditioned: &str,
     metadata: &str,
     output: Option<&str>,
-) -> eyre::Result<()> {
+) -> eyre::Result<PathBuf> {
     let now = OffsetDateTime::now_utc().to_offset(unsafe { UTC_OFFSET }.unwrap());
     let timestamp = now.format(&format_description::parse(
         "[year]-[month]-[day]-[hour]-[minute]-[second]",
     )?)?;
 
-    let out = out.join(&timestamp);
+    let name = match seed {
+        Some(seed) => format!("{timestamp}-{seed}"),
+        None => timestamp,
+    };
+
+    std::fs::create_dir_all(out)?;
+
+    // Multiple workers may save test cases within the same second, so add a suffix if needed
+    let mut dir = out.join(&name);
+    let mut suffix = 1;
+    while let Err(e) = std::fs::create_dir(&dir) {
+        if e.kind() != io::ErrorKind::AlreadyExists {
+            return Err(e.into());
+        }
+
+        dir = out.join(format!("{name}-{suffix}"));
+        suffix += 1;
+    }
 
-    std::fs::create_dir_all(&out)?;
+    let out = dir;
 
     std::fs::write(out.join("shader.wgsl"), shader)?;
     std::fs::write(out.join("reconditioned.wgsl"), reconditioned)?;
@@ -163,6 +404,130 @@ fn save_shader(
         std::fs::write(out.join("stderr.txt"), output.replace('\0', ""))?;
     }
 
+    Ok(out)
+}
+
+/// Summary of a saved test case, written to `manifest.json` in its directory.
+#[derive(Serialize)]
+struct Manifest {
+    seed: Option<u64>,
+    result: String,
+    /// Constructs used by the shader, from [`ast::feature_tags`].
+    tags: BTreeSet<String>,
+}
+
+fn write_manifest(
+    dir: &Path,
+    seed: Option<u64>,
+    result: &ExecutionResult,
+    shader: &str,
+) -> eyre::Result<()> {
+    // Prefer the reduced shader if there is one, since it only uses the constructs that matter
+    let reduced = std::fs::read_to_string(dir.join("reduced").join("shader.wgsl")).ok();
+    let module = parser::parse(reduced.as_deref().unwrap_or(shader));
+
+    let manifest = Manifest {
+        seed,
+        result: result.to_string(),
+        tags: ast::feature_tags(&module),
+    };
+
+    std::fs::write(
+        dir.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    Ok(())
+}
+
+/// Returns a line from the harness output which identifies a crash.
+///
+/// This prefers the panic message if there is one, and otherwise uses the first non-empty line.
+fn crash_signature(output: &str) -> Option<&str> {
+    let mut lines = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let first = lines.clone().next();
+    lines.find(|line| line.contains("panicked at")).or(first)
+}
+
+/// Normalises a crash signature so that crashes caused by the same bug compare equal, by masking
+/// out memory addresses and source positions, which vary between shaders and runs.
+fn normalize_signature(signature: &str) -> String {
+    // Compiled once, since this runs for every crash found by every worker
+    static PATTERNS: OnceLock<[Regex; 3]> = OnceLock::new();
+
+    let [address, position, line] = PATTERNS.get_or_init(|| {
+        [
+            Regex::new(r"0x[0-9a-fA-F]+").unwrap(),
+            Regex::new(r"\b\d+(:\d+)+\b").unwrap(),
+            Regex::new(r"\b(line|column|offset) \d+").unwrap(),
+        ]
+    });
+
+    let signature = address.replace_all(signature, "0x_");
+    let signature = position.replace_all(&signature, "_");
+    line.replace_all(&signature, "$1 _").into_owned()
+}
+
+/// Runs the reducer on a saved test case, writing the reduced shader to `<dir>/reduced`.
+fn reduce_test_case(options: &Options, dir: &Path, result: &ExecutionResult) -> eyre::Result<()> {
+    let log = std::fs::File::create(dir.join("reduce.log"))?;
+
+    let mut cmd = Command::new(std::env::current_exe()?);
+
+    cmd.arg("reduce");
+
+    match result {
+        ExecutionResult::Crash(output) => {
+            let config = options
+                .config
+                .as_ref()
+                .ok_or_else(|| eyre!("reducing crashes requires --config"))?;
+
+            let signature = crash_signature(output)
+                .ok_or_else(|| eyre!("crash output is empty, can't determine signature"))?;
+
+            std::fs::write(dir.join("signature.txt"), signature)?;
+
+            cmd.arg("crash")
+                .args(["--config", &config.to_string()])
+                .args(["--regex", &regex::escape(signature)]);
+        }
+        ExecutionResult::Mismatch => {
+            cmd.arg("mismatch");
+        }
+        _ => return Ok(()),
+    }
+
+    // Reconditioning would remove an injected bug, so the shader which already has it is reduced
+    // instead
+    if options.inject_bug.is_some() {
+        cmd.arg(dir.join("reconditioned.wgsl"))
+            .arg("--no-recondition");
+    } else {
+        cmd.arg(dir.join("shader.wgsl"));
+    }
+
+    cmd.arg(dir.join("inputs.json"))
+        .tap_mut(|cmd| {
+            if let Some(server) = &options.server {
+                cmd.args(["--server", server]);
+            }
+        })
+        .arg("--quiet")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    if !cmd.status()?.success() {
+        return Err(eyre!(
+            "reducer failed, see `{}`",
+            dir.join("reduce.log").display()
+        ));
+    }
+
     Ok(())
 }
 
@@ -170,6 +535,8 @@ pub fn run(config: Config, options: Options) -> eyre::Result<()> {
     unsafe { UTC_OFFSET = Some(UtcOffset::current_local_offset()?) };
 
     let disable_tui = options.disable_tui;
+    // The flags override each other, so at most one of them is set
+    let fail_fast = options.fail_fast && !options.collect_all;
     let harness = match options
         .server
         .as_deref()
@@ -186,20 +553,75 @@ pub fn run(config: Config, options: Options) -> eyre::Result<()> {
         ),
     };
 
-    let (worker_tx, worker_rx) = crossbeam_channel::bounded(1);
+    if let Some(seed) = options.replay_seed {
+        return replay(&options, &harness, seed);
+    }
 
-    std::thread::spawn(move || {
-        worker(config, options, harness, &mut |result| {
-            worker_tx.send(result).unwrap()
-        })
-        .unwrap()
-    });
+    if options.workers == 0 {
+        return Err(eyre!("number of workers must be greater than zero"));
+    }
+
+    let (worker_tx, worker_rx) = crossbeam_channel::bounded(options.workers);
+
+    let config = Arc::new(config);
+    let options = Arc::new(options);
+    let next_seed = Arc::new(AtomicU64::new(0));
+    let signatures = Arc::new(Mutex::new(HashSet::new()));
+
+    for _ in 0..options.workers {
+        // Each worker gets its own harness, and executions happen in separate harness processes,
+        // so no device handles are ever shared between threads.
+        let config = config.clone();
+        let options = options.clone();
+        let harness = harness.clone();
+        let next_seed = next_seed.clone();
+        let signatures = signatures.clone();
+        let worker_tx = worker_tx.clone();
+
+        std::thread::spawn(move || {
+            let state = WorkerState {
+                next_seed: &next_seed,
+                signatures: &signatures,
+            };
+
+            worker(&config, &options, harness, &state, &mut |result| {
+                worker_tx.send(result).unwrap()
+            })
+            .unwrap()
+        });
+    }
+
+    let start = Instant::now();
+    let stats = Arc::new(Mutex::new(Stats::default()));
+
+    // The first test case that was saved, if running with `--fail-fast`
+    let mut failure = None;
 
     if disable_tui {
-        while let Ok(msg) = worker_rx.recv() {
-            match msg {
-                WorkerMessage::Log(line) => println!("{line}"),
-                WorkerMessage::Result(result) => println!("saved: {}", result.saved),
+        let (sigint_tx, sigint_rx) = crossbeam_channel::bounded(1);
+        let mut signals = signal_hook::iterator::Signals::new([SIGINT])?;
+
+        thread::spawn(move || {
+            if signals.forever().next().is_some() {
+                sigint_tx.send(()).unwrap();
+            }
+        });
+
+        loop {
+            select! {
+                recv(sigint_rx) -> _ => break,
+                recv(worker_rx) -> msg => match msg? {
+                    WorkerMessage::Log(line) => println!("{line}"),
+                    WorkerMessage::Result(result) => {
+                        println!("saved: {}", result.finding.is_some());
+                        stats.lock().unwrap().record(&result);
+
+                        if fail_fast && result.finding.is_some() {
+                            failure = result.finding;
+                            break;
+                        }
+                    }
+                },
             }
         }
     } else {
@@ -207,7 +629,7 @@ pub fn run(config: Config, options: Options) -> eyre::Result<()> {
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen)?;
         let terminal = Terminal::new(CrosstermBackend::new(stdout))?;
-        let ui = Arc::new(Mutex::new(Ui::new(terminal, UiState::default())));
+        let ui = Arc::new(Mutex::new(Ui::new(terminal, stats.clone())));
 
         let (input_tx, input_rx) = crossbeam_channel::bounded(1);
 
@@ -215,36 +637,16 @@ pub fn run(config: Config, options: Options) -> eyre::Result<()> {
             input_tx.send(crossterm::event::read().unwrap()).unwrap();
         });
 
-        let on_result = |result: WorkerResult| {
-            let mut ui = ui.lock().unwrap();
-            ui.state.total += 1;
-            match result.kind {
-                WorkerResultKind::Success => ui.state.success += 1,
-                WorkerResultKind::Crash => {
-                    ui.state.crashes += 1;
-                    if result.saved {
-                        ui.state.saved_crashes += 1;
-                    }
-                }
-                WorkerResultKind::Mismatch => {
-                    ui.state.mismatches += 1;
-                    if result.saved {
-                        ui.state.saved_mismatches += 1;
-                    }
-                }
-                // WorkerResultKind::Timeout => ui.state.timeouts += 1,
-                WorkerResultKind::ReconditionFailure | WorkerResultKind::ExecutionFailure => {
-                    ui.state.failures += 1
-                }
-            }
-        };
-
         loop {
             ui.lock().unwrap().render()?;
             select! {
                 recv(input_rx) -> msg => {
                     if let crossterm::event::Event::Key(key) = msg? {
-                        if let KeyCode::Char('q') = key.code {
+                        // Ctrl-C doesn't raise SIGINT in raw mode, so it needs to be handled here
+                        let is_ctrl_c = key.code == KeyCode::Char('c')
+                            && key.modifiers.contains(KeyModifiers::CONTROL);
+
+                        if key.code == KeyCode::Char('q') || is_ctrl_c {
                             break;
                         }
                     }
@@ -254,7 +656,14 @@ pub fn run(config: Config, options: Options) -> eyre::Result<()> {
                 recv(worker_rx) -> msg => {
                     match msg? {
                         WorkerMessage::Log(_line) => {},
-                        WorkerMessage::Result(result) => on_result(result),
+                        WorkerMessage::Result(result) => {
+                            stats.lock().unwrap().record(&result);
+
+                            if fail_fast && result.finding.is_some() {
+                                failure = result.finding;
+                                break;
+                            }
+                        }
                     }
                 }
             }
@@ -268,6 +677,31 @@ pub fn run(config: Config, options: Options) -> eyre::Result<()> {
         }
     }
 
+    if options.stats {
+        let summary = stats.lock().unwrap().summary(start.elapsed());
+        if options.json {
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        } else {
+            print!("{summary}");
+        }
+    }
+
+    if let Some(finding) = failure {
+        match finding.seed {
+            Some(seed) => println!("seed: {seed}"),
+            None => println!("seed: unknown"),
+        }
+
+        println!("saved to: {}", finding.dir.display());
+        println!("result: {}", finding.result);
+
+        if let ExecutionResult::Crash(output) = &finding.result {
+            println!("{}", output.trim_end());
+        }
+
+        return Err(eyre!("stopped at the first saved test case (--fail-fast)"));
+    }
+
     Ok(())
 }
 
@@ -278,61 +712,170 @@ enum WorkerMessage {
 
 struct WorkerResult {
     kind: WorkerResultKind,
-    saved: bool,
+    /// The saved test case, if the shader was saved.
+    finding: Option<Finding>,
+    /// Whether the shader crashed with the same signature as an earlier crash, and so wasn't saved.
+    duplicate: bool,
+    /// Time spent generating and reconditioning the shader.
+    gen_time: Duration,
+    /// Time spent in the harness, if the shader was executed.
+    exec_time: Option<Duration>,
+}
+
+struct Finding {
+    seed: Option<u64>,
+    dir: PathBuf,
+    result: ExecutionResult,
+}
+
+/// State shared between all workers.
+struct WorkerState<'a> {
+    next_seed: &'a AtomicU64,
+    /// Normalised signatures of the crashes that have been saved so far.
+    signatures: &'a Mutex<HashSet<String>>,
 }
 
 enum WorkerResultKind {
     Success,
     Crash,
     Mismatch,
-    // Timeout,
+    Timeout,
+    ExceedsLimits,
     ReconditionFailure,
     ExecutionFailure,
+    Skipped,
 }
 
 fn worker(
-    config: Config,
-    options: Options,
+    config: &Config,
+    options: &Options,
     harness: Harness,
+    state: &WorkerState,
     on_message: &mut dyn FnMut(WorkerMessage),
 ) -> eyre::Result<()> {
     loop {
+        let seed = options
+            .shard
+            .map(|shard| shard.seed(state.next_seed.fetch_add(1, Ordering::Relaxed)));
         let mut logger = |line| on_message(WorkerMessage::Log(line));
-        let result = worker_iteration(&config, &options, &harness, &mut logger)?;
+        let result = worker_iteration(config, options, &harness, state, seed, &mut logger)?;
         on_message(WorkerMessage::Result(result))
     }
 }
 
+/// Regenerates and reconditions the shader for `seed` along the same path as a worker, then executes
+/// it, printing the harness output as it runs.
+fn replay(options: &Options, harness: &Harness, seed: u64) -> eyre::Result<()> {
+    let shader = gen_shader(options, Some(seed))?;
+    let (metadata, shader) = split_metadata(&shader)?;
+    let reconditioned = inject_bug(options, Some(seed), recondition_shader(shader)?)?;
+
+    let result = if options.reference {
+        harness_runner::exec_shader_with_reference(
+            harness,
+            options.config.clone(),
+            &reconditioned,
+            metadata,
+            &reference_output(&reconditioned, metadata)?,
+            |line| println!("{line}"),
+        )?
+    } else {
+        harness_runner::exec_shader(
+            harness,
+            options.config.clone(),
+            &reconditioned,
+            metadata,
+            |line| println!("{line}"),
+        )?
+    };
+
+    println!("result: {result}");
+
+    Ok(())
+}
+
 fn worker_iteration(
     config: &Config,
     options: &Options,
     harness: &Harness,
+    state: &WorkerState,
+    seed: Option<u64>,
     logger: &mut dyn FnMut(String),
 ) -> eyre::Result<WorkerResult> {
-    let shader = gen_shader(options)?;
-    let (metadata, shader) = shader
-        .split_once('\n')
-        .ok_or_else(|| eyre!("expected first line of shader to be a JSON metadata comment"))?;
+    let gen_start = Instant::now();
+    let shader = gen_shader(options, seed)?;
+    let (metadata, shader) = split_metadata(&shader)?;
+
+    if options.skip_constant {
+        let (constant, reason) = ast::constant::has_constant_output(&parser::parse(shader));
+        if constant {
+            logger(format!("skipping shader: {reason}"));
+            return Ok(WorkerResult {
+                kind: WorkerResultKind::Skipped,
+                finding: None,
+                duplicate: false,
+                gen_time: gen_start.elapsed(),
+                exec_time: None,
+            });
+        }
+    }
 
-    let metadata = metadata.trim_start_matches("//").trim();
     let reconditioned = match recondition_shader(shader) {
         Ok(reconditioned) => reconditioned,
         Err(_) => {
             eprintln!("reconditioner command failed, ignoring");
             return Ok(WorkerResult {
                 kind: WorkerResultKind::ReconditionFailure,
-                saved: false,
+                finding: None,
+                duplicate: false,
+                gen_time: gen_start.elapsed(),
+                exec_time: None,
             });
         }
     };
 
-    let exec_result = harness_runner::exec_shader(
-        harness,
-        options.config.clone(),
-        &reconditioned,
-        metadata,
-        logger,
-    );
+    let reconditioned = inject_bug(options, seed.or_else(|| read_seed(shader)), reconditioned)?;
+
+    let reference = if options.reference {
+        match reference_output(&reconditioned, metadata) {
+            Ok(reference) => Some(reference),
+            Err(e) => {
+                logger(format!("skipping shader: {e}"));
+                return Ok(WorkerResult {
+                    kind: WorkerResultKind::Skipped,
+                    finding: None,
+                    duplicate: false,
+                    gen_time: gen_start.elapsed(),
+                    exec_time: None,
+                });
+            }
+        }
+    } else {
+        None
+    };
+
+    let gen_time = gen_start.elapsed();
+    let exec_start = Instant::now();
+
+    let exec_result = match &reference {
+        Some(reference) => harness_runner::exec_shader_with_reference(
+            harness,
+            options.config.clone(),
+            &reconditioned,
+            metadata,
+            reference,
+            &mut *logger,
+        ),
+        None => harness_runner::exec_shader(
+            harness,
+            options.config.clone(),
+            &reconditioned,
+            metadata,
+            &mut *logger,
+        ),
+    };
+
+    let exec_time = exec_start.elapsed();
 
     let result = match exec_result {
         Ok(result) => result,
@@ -340,6 +883,7 @@ fn worker_iteration(
             if options.save_failures {
                 save_shader(
                     &options.output,
+                    seed,
                     shader,
                     &reconditioned,
                     metadata,
@@ -348,7 +892,10 @@ fn worker_iteration(
             }
             return Ok(WorkerResult {
                 kind: WorkerResultKind::ExecutionFailure,
-                saved: false,
+                finding: None,
+                duplicate: false,
+                gen_time,
+                exec_time: Some(exec_time),
             });
         }
     };
@@ -357,7 +904,10 @@ fn worker_iteration(
         ExecutionResult::Success => WorkerResultKind::Success,
         ExecutionResult::Crash(_) => WorkerResultKind::Crash,
         ExecutionResult::Mismatch => WorkerResultKind::Mismatch,
-        // ExecutionResult::Timeout => WorkerResultKind::Timeout,
+        ExecutionResult::Timeout => WorkerResultKind::Timeout,
+        ExecutionResult::ExceedsLimits => WorkerResultKind::ExceedsLimits,
+        // A capability mismatch between the shader and the device rather than a bug
+        ExecutionResult::UnsupportedFeature => WorkerResultKind::Skipped,
     };
 
     let mut output = None;
@@ -365,41 +915,211 @@ fn worker_iteration(
         output = Some(out.as_str());
     }
 
-    let should_save = result.should_save(
+    let mut should_save = result.should_save(
         &options.strategy,
         options.ignore.iter().chain(&config.fuzzer.ignore),
     );
 
+    let mut duplicate = false;
+
+    // Only keep the first crash for each signature, since the same bug is often hit by many
+    // different shaders
+    if should_save && !options.keep_duplicates {
+        if let Some(signature) = output.and_then(crash_signature) {
+            let signature = normalize_signature(signature);
+            if !state.signatures.lock().unwrap().insert(signature.clone()) {
+                logger(format!("skipping duplicate crash: {signature}"));
+                should_save = false;
+                duplicate = true;
+            }
+        }
+    }
+
+    let mut finding = None;
+
     if should_save {
-        save_shader(&options.output, shader, &reconditioned, metadata, output)?;
+        let dir = save_shader(
+            &options.output,
+            seed,
+            shader,
+            &reconditioned,
+            metadata,
+            output,
+        )?;
+
+        if let Some(reference) = &reference {
+            std::fs::write(dir.join("reference.bin"), reference)?;
+        }
+
+        if options.reduce_on_find {
+            logger(format!("reducing test case in `{}`", dir.display()));
+            match reduce_test_case(options, &dir, &result) {
+                Ok(()) => logger("reduction complete".to_owned()),
+                Err(e) => logger(format!("failed to reduce test case: {e}")),
+            }
+        }
+
+        // Without a shard, the generator picks a random seed and records it in the header
+        let seed = seed.or_else(|| read_seed(shader));
+
+        write_manifest(&dir, seed, &result, shader)?;
+
+        finding = Some(Finding { seed, dir, result });
     }
 
     Ok(WorkerResult {
         kind: result_kind,
-        saved: should_save,
+        finding,
+        duplicate,
+        gen_time,
+        exec_time: Some(exec_time),
     })
 }
 
-struct Ui<B: Backend> {
-    terminal: Terminal<B>,
-    state: UiState,
-}
-
+/// Counts of each outcome over a fuzzing session.
 #[derive(Default)]
-struct UiState {
+struct Stats {
     total: usize,
     success: usize,
     timeouts: usize,
+    exceeds_limits: usize,
+    crashes: usize,
+    saved_crashes: usize,
+    duplicate_crashes: usize,
+    mismatches: usize,
+    saved_mismatches: usize,
+    failures: usize,
+    skipped: usize,
+    gen_time: Duration,
+    exec_time: Duration,
+    executed: usize,
+}
+
+impl Stats {
+    fn record(&mut self, result: &WorkerResult) {
+        self.total += 1;
+        self.gen_time += result.gen_time;
+
+        if let Some(exec_time) = result.exec_time {
+            self.exec_time += exec_time;
+            self.executed += 1;
+        }
+
+        match result.kind {
+            WorkerResultKind::Success => self.success += 1,
+            WorkerResultKind::Crash => {
+                self.crashes += 1;
+                if result.finding.is_some() {
+                    self.saved_crashes += 1;
+                }
+                if result.duplicate {
+                    self.duplicate_crashes += 1;
+                }
+            }
+            WorkerResultKind::Mismatch => {
+                self.mismatches += 1;
+                if result.finding.is_some() {
+                    self.saved_mismatches += 1;
+                }
+            }
+            WorkerResultKind::Timeout => self.timeouts += 1,
+            WorkerResultKind::ExceedsLimits => self.exceeds_limits += 1,
+            WorkerResultKind::ReconditionFailure | WorkerResultKind::ExecutionFailure => {
+                self.failures += 1
+            }
+            WorkerResultKind::Skipped => self.skipped += 1,
+        }
+    }
+
+    fn summary(&self, elapsed: Duration) -> Summary {
+        fn avg(total: Duration, count: usize) -> f64 {
+            if count == 0 {
+                0.0
+            } else {
+                total.as_secs_f64() / count as f64
+            }
+        }
+
+        Summary {
+            generated: self.total,
+            executed: self.executed,
+            success: self.success,
+            crashes: self.crashes,
+            saved_crashes: self.saved_crashes,
+            duplicate_crashes: self.duplicate_crashes,
+            mismatches: self.mismatches,
+            saved_mismatches: self.saved_mismatches,
+            timeouts: self.timeouts,
+            exceeds_limits: self.exceeds_limits,
+            failures: self.failures,
+            skipped: self.skipped,
+            avg_gen_secs: avg(self.gen_time, self.total),
+            avg_exec_secs: avg(self.exec_time, self.executed),
+            elapsed_secs: elapsed.as_secs_f64(),
+            shaders_per_sec: self.total as f64 / elapsed.as_secs_f64(),
+        }
+    }
+}
+
+/// Summary of a fuzzing session, printed with `--stats`.
+///
+/// Execution times include compiling the shader for each configuration, since the harness doesn't
+/// report the two separately.
+#[derive(Serialize)]
+struct Summary {
+    generated: usize,
+    executed: usize,
+    success: usize,
     crashes: usize,
     saved_crashes: usize,
+    /// Crashes that weren't saved because an earlier crash had the same signature.
+    duplicate_crashes: usize,
     mismatches: usize,
     saved_mismatches: usize,
+    timeouts: usize,
+    exceeds_limits: usize,
     failures: usize,
+    skipped: usize,
+    avg_gen_secs: f64,
+    avg_exec_secs: f64,
+    elapsed_secs: f64,
+    shaders_per_sec: f64,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "generated:      {}", self.generated)?;
+        writeln!(f, "executed:       {}", self.executed)?;
+        writeln!(f, "ok:             {}", self.success)?;
+        writeln!(
+            f,
+            "crashes:        {} ({} saved, {} duplicates)",
+            self.crashes, self.saved_crashes, self.duplicate_crashes
+        )?;
+        writeln!(
+            f,
+            "mismatches:     {} ({} saved)",
+            self.mismatches, self.saved_mismatches
+        )?;
+        writeln!(f, "timeouts:       {}", self.timeouts)?;
+        writeln!(f, "exceeds limits: {}", self.exceeds_limits)?;
+        writeln!(f, "failures:       {}", self.failures)?;
+        writeln!(f, "skipped:        {}", self.skipped)?;
+        writeln!(f, "avg gen time:   {:.3}s", self.avg_gen_secs)?;
+        writeln!(f, "avg exec time:  {:.3}s", self.avg_exec_secs)?;
+        writeln!(f, "elapsed:        {:.1}s", self.elapsed_secs)?;
+        writeln!(f, "throughput:     {:.2} shaders/s", self.shaders_per_sec)
+    }
+}
+
+struct Ui<B: Backend> {
+    terminal: Terminal<B>,
+    stats: Arc<Mutex<Stats>>,
 }
 
 impl<B: Backend> Ui<B> {
-    fn new(terminal: Terminal<B>, state: UiState) -> Self {
-        Ui { terminal, state }
+    fn new(terminal: Terminal<B>, stats: Arc<Mutex<Stats>>) -> Self {
+        Ui { terminal, stats }
     }
 
     fn render(&mut self) -> eyre::Result<()> {
@@ -411,15 +1131,20 @@ impl<B: Backend> Ui<B> {
             }
         }
 
+        let stats = self.stats.lock().unwrap();
+
         self.terminal.draw(|f| {
-            let count = self.state.total;
-            let success = self.state.success;
-            let crashes = self.state.crashes;
-            let saved_crashes = self.state.saved_crashes;
-            let mismatches = self.state.mismatches;
-            let saved_mismatches = self.state.saved_mismatches;
-            let timeouts = self.state.timeouts;
-            let failures = self.state.failures;
+            let count = stats.total;
+            let success = stats.success;
+            let crashes = stats.crashes;
+            let saved_crashes = stats.saved_crashes;
+            let duplicate_crashes = stats.duplicate_crashes;
+            let mismatches = stats.mismatches;
+            let saved_mismatches = stats.saved_mismatches;
+            let timeouts = stats.timeouts;
+            let exceeds_limits = stats.exceeds_limits;
+            let failures = stats.failures;
+            let skipped = stats.skipped;
 
             #[rustfmt::skip]
             let lines = vec![
@@ -427,10 +1152,13 @@ impl<B: Backend> Ui<B> {
                 Spans::from(format!("ok:         {success} ({:.2}%)", pc(success, count))),
                 Spans::from(format!("crashes:    {crashes} ({:.2}%)", pc(crashes, count))),
                 Spans::from(format!("  saved:    {saved_crashes} ({:.2}%)", pc(saved_crashes, crashes))),
+                Spans::from(format!("  dupes:    {duplicate_crashes} ({:.2}%)", pc(duplicate_crashes, crashes))),
                 Spans::from(format!("mismatches: {mismatches} ({:.2}%)", pc(mismatches, count))),
                 Spans::from(format!("  saved:    {saved_mismatches} ({:.2}%)", pc(saved_mismatches, mismatches))),
                 Spans::from(format!("timeouts:   {timeouts} ({:.2}%)", pc(timeouts, count))),
+                Spans::from(format!("oversized:  {exceeds_limits} ({:.2}%)", pc(exceeds_limits, count))),
                 Spans::from(format!("failures:   {failures} ({:.2}%)", pc(failures, count))),
+                Spans::from(format!("skipped:    {skipped} ({:.2}%)", pc(skipped, count))),
             ];
 
             let line_count = lines.len();
@@ -2,6 +2,7 @@ use std::fmt::{Display, Write as _};
 use std::io::{self, BufRead, BufReader, BufWriter, Write as _};
 use std::path::PathBuf;
 use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
 
 use eyre::eyre;
@@ -13,9 +14,12 @@ pub enum ExecutionResult {
     Success,
     Crash(String),
     Mismatch,
-    // TODO: Detect timeouts from running harness
-    // Might not actually be necessary since it's probably fine to treat them as successful runs
-    // Timeout,
+    /// The harness gave up waiting for a remote server to respond.
+    Timeout,
+    /// The shader needs more resources than the device's limits allow.
+    ExceedsLimits,
+    /// The device rejected the shader for using a feature that it doesn't support.
+    UnsupportedFeature,
 }
 
 impl Display for ExecutionResult {
@@ -24,16 +28,35 @@ impl Display for ExecutionResult {
             ExecutionResult::Success => write!(f, "success"),
             ExecutionResult::Crash(_) => write!(f, "crash"),
             ExecutionResult::Mismatch => write!(f, "mismatch"),
-            // ExecutionResult::Timeout => write!(f, "timeout"),
+            ExecutionResult::Timeout => write!(f, "timeout"),
+            ExecutionResult::ExceedsLimits => write!(f, "exceeds limits"),
+            ExecutionResult::UnsupportedFeature => write!(f, "unsupported feature"),
         }
     }
 }
 
+#[derive(Clone)]
 pub enum Harness {
     Local(PathBuf),
     Remote(String),
 }
 
+/// Exit codes used by the harness to signal the result of an execution.
+pub struct ExitCodes {
+    pub mismatch: i32,
+    pub crash: i32,
+}
+
+impl Default for ExitCodes {
+    fn default() -> Self {
+        // The harness exits with 1 on a mismatch, and a crash is a Rust panic.
+        ExitCodes {
+            mismatch: 1,
+            crash: 101,
+        }
+    }
+}
+
 pub fn exec_shader(
     harness: &Harness,
     config: Option<ConfigId>,
@@ -41,7 +64,88 @@ pub fn exec_shader(
     metadata: &str,
     mut logger: impl FnMut(String),
 ) -> eyre::Result<ExecutionResult> {
-    exec_shader_impl(harness, config, shader, metadata, &mut logger)
+    exec_shader_impl(
+        harness,
+        config,
+        shader,
+        metadata,
+        1,
+        &ExitCodes::default(),
+        &mut logger,
+    )
+}
+
+/// Like [`exec_shader`], but dispatches `workgroups` workgroups and interprets the harness exit
+/// code using `exit_codes`.
+///
+/// This allows using a custom harness binary that signals results differently.
+pub fn exec_shader_with_exit_codes(
+    harness: &Harness,
+    config: Option<ConfigId>,
+    shader: &str,
+    metadata: &str,
+    workgroups: u32,
+    exit_codes: &ExitCodes,
+    mut logger: impl FnMut(String),
+) -> eyre::Result<ExecutionResult> {
+    exec_shader_impl(
+        harness,
+        config,
+        shader,
+        metadata,
+        workgroups,
+        exit_codes,
+        &mut logger,
+    )
+}
+
+/// Executes the shader `runs` times with each configuration, returning whether every
+/// configuration produced the same output on each run.
+///
+/// Differences between configurations are not considered here, so a shader that produces a
+/// mismatch is still deterministic as long as each configuration is self-consistent.
+///
+/// An execution failure makes the harness crash, which is returned as an error since it says
+/// nothing about whether the shader is deterministic.
+#[allow(clippy::too_many_arguments)]
+pub fn check_determinism(
+    harness: &Harness,
+    config: Option<ConfigId>,
+    shader: &str,
+    metadata: &str,
+    runs: u32,
+    workgroups: u32,
+    exit_codes: &ExitCodes,
+    mut logger: impl FnMut(String),
+) -> eyre::Result<bool> {
+    let mut cmd = harness_command(harness, "run", config, metadata)?;
+    cmd.args(["--repeat", &runs.to_string()]);
+
+    if workgroups != 1 {
+        cmd.args(["--workgroups", &workgroups.to_string()]);
+    }
+
+    let (status, output) = run_harness(cmd, shader, &mut logger)?;
+
+    match status.code() {
+        None => Err(eyre!("failed to get harness exit code")),
+        Some(harness_frontend::NONDETERMINISTIC_EXIT_CODE) => Ok(false),
+        Some(0) => Ok(true),
+        Some(code) if code == exit_codes.mismatch => Ok(true),
+        Some(code) if code == exit_codes.crash => Err(eyre!(
+            "harness crashed while checking determinism:\n{output}"
+        )),
+        Some(harness_frontend::TIMEOUT_EXIT_CODE) => {
+            Err(eyre!("timed out while checking determinism"))
+        }
+        Some(harness_frontend::EXCEEDS_LIMITS_EXIT_CODE) => Err(eyre!(
+            "shader exceeds device limits, so its determinism can't be checked"
+        )),
+        Some(harness_frontend::UNSUPPORTED_FEATURE_EXIT_CODE) => Err(eyre!(
+            "shader uses an unsupported feature, so its determinism can't be checked"
+        )),
+        Some(code) => Err(eyre!("harness exited with unrecognised code `{code}`")),
+    }
 }
 
 fn exec_shader_impl(
@@ -49,14 +153,91 @@ fn exec_shader_impl(
     config: Option<ConfigId>,
     shader: &str,
     metadata: &str,
+    workgroups: u32,
+    exit_codes: &ExitCodes,
     logger: &mut dyn FnMut(String),
 ) -> eyre::Result<ExecutionResult> {
+    let mut cmd = harness_command(harness, "run", config, metadata)?;
+
+    // Only passed when needed, since a custom harness binary might not support it
+    if workgroups != 1 {
+        cmd.args(["--workgroups", &workgroups.to_string()]);
+    }
+
+    let (status, output) = run_harness(cmd, shader, logger)?;
+    classify_exit_status(status, output, exit_codes)
+}
+
+/// Executes the shader and compares the output of each configuration against `expected`, which
+/// holds the contents of the shader's storage buffers concatenated in binding order.
+///
+/// A [`ExecutionResult::Mismatch`] means that at least one configuration disagreed with the
+/// expected output, even if all configurations agreed with each other.
+pub fn exec_shader_with_reference(
+    harness: &Harness,
+    config: Option<ConfigId>,
+    shader: &str,
+    metadata: &str,
+    expected: &[u8],
+    mut logger: impl FnMut(String),
+) -> eyre::Result<ExecutionResult> {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    // The harness reads the expected output from a file, so give each call its own file to allow
+    // concurrent executions from multiple workers
+    let expected_path = std::env::temp_dir().join(format!(
+        "wgslsmith-expected-{}-{}.bin",
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    std::fs::write(&expected_path, expected)?;
+
+    let mut cmd = harness_command(harness, "check-output", config, metadata)?;
+    cmd.arg("--expected").arg(&expected_path);
+
+    let result = run_harness(cmd, shader, &mut logger);
+    let _ = std::fs::remove_file(&expected_path);
+    let (status, output) = result?;
+
+    classify_exit_status(status, output, &ExitCodes::default())
+}
+
+fn classify_exit_status(
+    status: ExitStatus,
+    output: String,
+    exit_codes: &ExitCodes,
+) -> eyre::Result<ExecutionResult> {
+    // The configured codes are checked first, so that they take precedence if they happen to
+    // overlap with one of the harness's own codes
+    let result = match status.code() {
+        None => return Err(eyre!("failed to get harness exit code")),
+        Some(0) => ExecutionResult::Success,
+        Some(code) if code == exit_codes.mismatch => ExecutionResult::Mismatch,
+        Some(code) if code == exit_codes.crash => ExecutionResult::Crash(output),
+        Some(harness_frontend::TIMEOUT_EXIT_CODE) => ExecutionResult::Timeout,
+        Some(harness_frontend::EXCEEDS_LIMITS_EXIT_CODE) => ExecutionResult::ExceedsLimits,
+        Some(harness_frontend::UNSUPPORTED_FEATURE_EXIT_CODE) => {
+            ExecutionResult::UnsupportedFeature
+        }
+        Some(code) => return Err(eyre!("harness exited with unrecognised code `{code}`")),
+    };
+
+    Ok(result)
+}
+
+fn harness_command(
+    harness: &Harness,
+    subcommand: &str,
+    config: Option<ConfigId>,
+    metadata: &str,
+) -> eyre::Result<Command> {
     let mut cmd = match harness {
         Harness::Local(harness_path) => Command::new(harness_path).tap_mut(|cmd| {
-            cmd.args(["run", "-", metadata]);
+            cmd.args([subcommand, "-", metadata]);
         }),
         Harness::Remote(remote) => Command::new(std::env::current_exe()?).tap_mut(|cmd| {
-            cmd.args(["remote", remote, "run", "-", metadata]);
+            cmd.args(["remote", remote, subcommand, "-", metadata]);
         }),
     };
 
@@ -64,6 +245,15 @@ fn exec_shader_impl(
         cmd.args(["-c", &config.to_string()]);
     }
 
+    Ok(cmd)
+}
+
+/// Spawns the harness with the shader on stdin, returning its exit status and combined output.
+fn run_harness(
+    mut cmd: Command,
+    shader: &str,
+    logger: &mut dyn FnMut(String),
+) -> eyre::Result<(ExitStatus, String)> {
     let mut harness = cmd
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -84,15 +274,36 @@ fn exec_shader_impl(
         logger(line);
     })?;
 
-    let result = match status.code() {
-        None => return Err(eyre!("failed to get harness exit code")),
-        Some(0) => ExecutionResult::Success,
-        Some(1) => ExecutionResult::Mismatch,
-        Some(101) => ExecutionResult::Crash(output),
-        Some(code) => return Err(eyre!("harness exited with unrecognised code `{code}`")),
+    Ok((status, output))
+}
+
+/// Runs the harness to list the available configurations, returning its output.
+pub fn list_configs(harness: &Harness) -> eyre::Result<String> {
+    let mut cmd = match harness {
+        // The harness may either be a standalone harness binary, or wgslsmith itself in which case
+        // the harness commands are nested under the `harness` subcommand.
+        Harness::Local(harness_path) => {
+            let mut cmd = Command::new(harness_path);
+            if *harness_path == std::env::current_exe()? {
+                cmd.arg("harness");
+            }
+            cmd.arg("list");
+            cmd
+        }
+        Harness::Remote(remote) => Command::new(std::env::current_exe()?).tap_mut(|cmd| {
+            cmd.args(["remote", remote, "list"]);
+        }),
     };
 
-    Ok(result)
+    let output = cmd.env("NO_COLOR", "1").output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "failed to list harness configurations: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
 #[derive(PartialEq, Eq)]
@@ -1,16 +1,28 @@
+mod bundle;
 #[cfg(all(target_family = "unix", feature = "reducer"))]
 mod compiler;
 mod config;
+#[cfg(all(target_family = "unix", feature = "reducer"))]
+mod dedup;
+mod diff;
+mod diff_versions;
 mod fmt;
+#[cfg(all(target_family = "unix", feature = "reducer"))]
+mod fold;
 mod fuzzer;
 mod harness_runner;
 #[cfg(all(target_family = "unix", feature = "reducer"))]
+mod merge;
+#[cfg(all(target_family = "unix", feature = "reducer"))]
 mod reducer;
 mod remote;
 #[cfg(all(target_family = "unix", feature = "reducer"))]
 mod test;
 #[cfg(all(target_family = "unix", feature = "reducer"))]
+mod validate;
+#[cfg(all(target_family = "unix", feature = "reducer"))]
 mod validator;
+mod verify_recondition;
 
 use std::fs;
 use std::path::PathBuf;
@@ -37,6 +49,8 @@ enum Cmd {
     Config,
     /// Generate a random shader.
     Gen(generator::Options),
+    /// Measure how quickly shaders are generated, without executing them.
+    Bench(generator::bench::Options),
     /// Generate a random shader with data race
     DataRaceGen(data_race_generator::cli::Options),
     /// Run and compare a shader with data races to a safe one
@@ -59,9 +73,24 @@ enum Cmd {
     Reduce(reducer::Options),
     #[cfg(all(target_family = "unix", feature = "reducer"))]
     Test(test::Options),
+    /// Bundle a shader with everything needed to reproduce a bug into an archive.
+    Bundle(bundle::Options),
+    /// Deduplicate a directory of crashing test cases by crash signature.
+    #[cfg(all(target_family = "unix", feature = "reducer"))]
+    Dedup(dedup::Options),
+    /// Validate a batch of shaders with tint and naga.
+    #[cfg(all(target_family = "unix", feature = "reducer"))]
+    Validate(validate::Options),
+    /// Check that reconditioning a batch of shaders a second time doesn't change them.
+    VerifyRecondition(verify_recondition::Options),
+    /// Check that shaders generated by an older version are still generated the same way.
+    DiffVersions(diff_versions::Options),
     /// Execute a shader.
     #[cfg(feature = "harness")]
     Run(harness_frontend::cli::RunOptions),
+    /// Execute a shader and compare its output against a stored expected output.
+    #[cfg(feature = "harness")]
+    CheckOutput(harness_frontend::cli::CheckOutputOptions),
     #[cfg(feature = "harness")]
     Harness {
         #[clap(subcommand)]
@@ -73,6 +102,12 @@ enum Cmd {
         cmd: RemoteCmd,
         #[clap(action)]
         server: Option<String>,
+        /// Maximum time in seconds to wait for the server to finish a request.
+        ///
+        /// If not set, this defaults to `harness.request_timeout` from the config file, or no
+        /// timeout if that isn't set either.
+        #[clap(long, action)]
+        request_timeout: Option<u64>,
     },
 }
 
@@ -80,6 +115,36 @@ enum Cmd {
 enum RemoteCmd {
     List,
     Run(harness_frontend::cli::RunOptions),
+    CheckOutput(harness_frontend::cli::CheckOutputOptions),
+}
+
+struct RemoteExecutor<'a>(&'a str, Option<Duration>);
+
+impl harness_frontend::Executor for RemoteExecutor<'_> {
+    fn execute(
+        &self,
+        shader: &str,
+        workgroups: u32,
+        flow: bool,
+        zero_init: bool,
+        pipeline_desc: &PipelineDescription,
+        configs: &[ConfigId],
+        timeout: Option<Duration>,
+        on_event: &mut dyn FnMut(ExecutionEvent) -> Result<(), ExecutionError>,
+    ) -> Result<(), ExecutionError> {
+        remote::execute(
+            self.0,
+            shader.to_owned(),
+            workgroups,
+            flow,
+            zero_init,
+            pipeline_desc.clone(),
+            configs.to_owned(),
+            timeout,
+            self.1,
+            on_event,
+        )
+    }
 }
 
 fn main() -> eyre::Result<()> {
@@ -112,6 +177,7 @@ fn main() -> eyre::Result<()> {
             Ok(())
         }
         Cmd::Gen(options) => generator::run(options),
+        Cmd::Bench(options) => generator::bench::run(options),
         Cmd::DataRaceGen(options) => data_race_generator::cli::run(options),
         Cmd::DataRaceRunner(options) => data_race_runner::cli::run(options),
         Cmd::DataRaceCoordinator(options) => coordinator::cli::run(options),
@@ -125,11 +191,24 @@ fn main() -> eyre::Result<()> {
         Cmd::Reduce(options) => reducer::run(config, options),
         #[cfg(all(target_family = "unix", feature = "reducer"))]
         Cmd::Test(options) => test::run(&config, options),
+        Cmd::Bundle(options) => bundle::run(&config, options),
+        #[cfg(all(target_family = "unix", feature = "reducer"))]
+        Cmd::Dedup(options) => dedup::run(&config, options),
+        #[cfg(all(target_family = "unix", feature = "reducer"))]
+        Cmd::Validate(options) => validate::run(options),
+        Cmd::VerifyRecondition(options) => verify_recondition::run(options),
+        Cmd::DiffVersions(options) => diff_versions::run(options),
         #[cfg(feature = "harness")]
         Cmd::Run(options) => harness::cli::execute::<HarnessHost>(options),
         #[cfg(feature = "harness")]
+        Cmd::CheckOutput(options) => harness::cli::check_output::<HarnessHost>(options),
+        #[cfg(feature = "harness")]
         Cmd::Harness { cmd } => harness::cli::run::<HarnessHost>(cmd),
-        Cmd::Remote { cmd, server } => {
+        Cmd::Remote {
+            cmd,
+            server,
+            request_timeout,
+        } => {
             let address = server
                 .as_deref()
                 .map(|server| config.resolve_remote(server))
@@ -139,6 +218,10 @@ fn main() -> eyre::Result<()> {
                         .with_note(|| "specify a default remote using the `harness.remote` field in your config file")
                 })?;
 
+            let request_timeout = request_timeout
+                .or(config.harness.request_timeout)
+                .map(Duration::from_secs);
+
             match cmd {
                 RemoteCmd::List => {
                     let res = remote::list(address)?;
@@ -146,34 +229,12 @@ fn main() -> eyre::Result<()> {
                     Ok(())
                 }
                 RemoteCmd::Run(options) => {
-                    struct Executor<'a>(&'a str);
-
-                    impl harness_frontend::Executor for Executor<'_> {
-                        fn execute(
-                            &self,
-                            shader: &str,
-                            workgroups: u32,
-                            flow: bool,
-                            pipeline_desc: &PipelineDescription,
-                            configs: &[ConfigId],
-                            timeout: Option<Duration>,
-                            on_event: &mut dyn FnMut(ExecutionEvent) -> Result<(), ExecutionError>,
-                        ) -> Result<(), ExecutionError> {
-                            remote::execute(
-                                self.0,
-                                shader.to_owned(),
-                                workgroups,
-                                flow,
-                                pipeline_desc.clone(),
-                                configs.to_owned(),
-                                timeout,
-                                on_event,
-                            )
-                        }
-                    }
-
-                    harness_frontend::cli::run(options, &Executor(address))
+                    harness_frontend::cli::run(options, &RemoteExecutor(address, request_timeout))
                 }
+                RemoteCmd::CheckOutput(options) => harness_frontend::cli::check_output(
+                    options,
+                    &RemoteExecutor(address, request_timeout),
+                ),
             }
         }
     }
@@ -0,0 +1,410 @@
+use std::collections::HashSet;
+
+use ast::{
+    AssignmentLhs, Else, Expr, ExprNode, ForLoopInit, ForLoopUpdate, LhsExpr, LhsExprNode, Module,
+    Postfix, Statement,
+};
+
+/// Merges compound blocks (`{ ... }`) into the block that contains them, wherever this doesn't
+/// change which declaration a name refers to.
+///
+/// A compound block can't be merged if it declares a name that its parent block also declares
+/// (including function parameters and for loop variables), or that is used by a later statement in
+/// the parent block, since those uses would then refer to the merged declaration. This flattens
+/// redundant nesting like `{ { stmt } }` left behind by reduction.
+///
+/// Returns the number of blocks that were merged.
+pub fn merge_module(module: &mut Module) -> usize {
+    module
+        .functions
+        .iter_mut()
+        .map(|decl| {
+            let params = decl.inputs.iter().map(|it| it.name.clone()).collect();
+            merge_block(&mut decl.body, &params)
+        })
+        .sum()
+}
+
+/// Merges compound blocks in `block` and its nested blocks. `reserved` is the set of names which
+/// are declared in the same scope as `block` by its parent statement.
+fn merge_block(block: &mut Vec<Statement>, reserved: &HashSet<String>) -> usize {
+    // Merge nested blocks first, so that their contents are already flattened when deciding
+    // whether they can be merged into this one
+    let mut count: usize = block.iter_mut().map(merge_stmt).sum();
+
+    let mut i = 0;
+    while i < block.len() {
+        let inner = match &block[i] {
+            Statement::Compound(inner) => inner,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let mut conflicts = reserved.clone();
+        for (j, stmt) in block.iter().enumerate() {
+            if j != i {
+                collect_decl(stmt, &mut conflicts);
+            }
+        }
+
+        collect_idents(&block[i + 1..], &mut conflicts);
+
+        let mut declared = HashSet::new();
+        for stmt in inner {
+            collect_decl(stmt, &mut declared);
+        }
+
+        if !declared.is_disjoint(&conflicts) {
+            i += 1;
+            continue;
+        }
+
+        let inner = match block.remove(i) {
+            Statement::Compound(inner) => inner,
+            _ => unreachable!(),
+        };
+
+        // Don't advance, so that the merged statements are checked again with this block as
+        // their parent
+        block.splice(i..i, inner);
+        count += 1;
+    }
+
+    count
+}
+
+fn merge_stmt(stmt: &mut Statement) -> usize {
+    let none = HashSet::new();
+    match stmt {
+        Statement::Compound(block) => merge_block(block, &none),
+        Statement::If(stmt) => {
+            let mut count = merge_block(&mut stmt.body, &none);
+
+            let mut else_ = stmt.else_.as_deref_mut();
+            while let Some(e) = else_ {
+                match e {
+                    Else::If(stmt) => {
+                        count += merge_block(&mut stmt.body, &none);
+                        else_ = stmt.else_.as_deref_mut();
+                    }
+                    Else::Else(body) => {
+                        count += merge_block(body, &none);
+                        else_ = None;
+                    }
+                }
+            }
+
+            count
+        }
+        Statement::Loop(stmt) => merge_block(&mut stmt.body, &none),
+        Statement::Switch(stmt) => {
+            stmt.cases
+                .iter_mut()
+                .map(|case| merge_block(&mut case.body, &none))
+                .sum::<usize>()
+                + merge_block(&mut stmt.default, &none)
+        }
+        Statement::ForLoop(stmt) => {
+            let mut reserved = HashSet::new();
+            if let Some(ForLoopInit::VarDecl(decl)) = &stmt.header.init {
+                reserved.insert(decl.ident.clone());
+            }
+
+            merge_block(&mut stmt.body, &reserved)
+        }
+        Statement::While(stmt) => merge_block(&mut stmt.body, &none),
+        Statement::LetDecl(_)
+        | Statement::VarDecl(_)
+        | Statement::Assignment(_)
+        | Statement::Return(_)
+        | Statement::FnCall(_)
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Fallthrough
+        | Statement::Comment(_) => 0,
+    }
+}
+
+/// Adds the name declared by `stmt` in its own scope, if any.
+fn collect_decl(stmt: &Statement, names: &mut HashSet<String>) {
+    match stmt {
+        Statement::LetDecl(stmt) => {
+            names.insert(stmt.ident.clone());
+        }
+        Statement::VarDecl(stmt) => {
+            names.insert(stmt.ident.clone());
+        }
+        _ => {}
+    }
+}
+
+/// Adds every name that is declared or referred to in `block`, including in nested blocks.
+fn collect_idents(block: &[Statement], names: &mut HashSet<String>) {
+    for stmt in block {
+        collect_stmt_idents(stmt, names);
+    }
+}
+
+fn collect_stmt_idents(stmt: &Statement, names: &mut HashSet<String>) {
+    match stmt {
+        Statement::LetDecl(stmt) => {
+            names.insert(stmt.ident.clone());
+            collect_expr_idents(&stmt.initializer, names);
+        }
+        Statement::VarDecl(stmt) => {
+            names.insert(stmt.ident.clone());
+            if let Some(init) = &stmt.initializer {
+                collect_expr_idents(init, names);
+            }
+        }
+        Statement::Assignment(stmt) => {
+            if let AssignmentLhs::Expr(lhs) = &stmt.lhs {
+                collect_lhs_idents(lhs, names);
+            }
+
+            collect_expr_idents(&stmt.rhs, names);
+        }
+        Statement::Compound(block) => collect_idents(block, names),
+        Statement::If(stmt) => {
+            collect_expr_idents(&stmt.condition, names);
+            collect_idents(&stmt.body, names);
+
+            let mut else_ = stmt.else_.as_deref();
+            while let Some(e) = else_ {
+                match e {
+                    Else::If(stmt) => {
+                        collect_expr_idents(&stmt.condition, names);
+                        collect_idents(&stmt.body, names);
+                        else_ = stmt.else_.as_deref();
+                    }
+                    Else::Else(body) => {
+                        collect_idents(body, names);
+                        else_ = None;
+                    }
+                }
+            }
+        }
+        Statement::Return(stmt) => {
+            if let Some(value) = &stmt.value {
+                collect_expr_idents(value, names);
+            }
+        }
+        Statement::Loop(stmt) => collect_idents(&stmt.body, names),
+        Statement::Switch(stmt) => {
+            collect_expr_idents(&stmt.selector, names);
+
+            for case in &stmt.cases {
+                collect_expr_idents(&case.selector, names);
+                collect_idents(&case.body, names);
+            }
+
+            collect_idents(&stmt.default, names);
+        }
+        Statement::ForLoop(stmt) => {
+            if let Some(ForLoopInit::VarDecl(decl)) = &stmt.header.init {
+                names.insert(decl.ident.clone());
+                if let Some(init) = &decl.initializer {
+                    collect_expr_idents(init, names);
+                }
+            }
+
+            if let Some(condition) = &stmt.header.condition {
+                collect_expr_idents(condition, names);
+            }
+
+            if let Some(ForLoopUpdate::Assignment(stmt)) = &stmt.header.update {
+                if let AssignmentLhs::Expr(lhs) = &stmt.lhs {
+                    collect_lhs_idents(lhs, names);
+                }
+
+                collect_expr_idents(&stmt.rhs, names);
+            }
+
+            collect_idents(&stmt.body, names);
+        }
+        Statement::While(stmt) => {
+            collect_expr_idents(&stmt.condition, names);
+            collect_idents(&stmt.body, names);
+        }
+        Statement::FnCall(stmt) => {
+            for arg in &stmt.args {
+                collect_expr_idents(arg, names);
+            }
+        }
+        Statement::Break | Statement::Continue | Statement::Fallthrough => {}
+        Statement::Comment(_) => {}
+    }
+}
+
+fn collect_lhs_idents(node: &LhsExprNode, names: &mut HashSet<String>) {
+    match &node.expr {
+        LhsExpr::Ident(ident) => {
+            names.insert(ident.clone());
+        }
+        LhsExpr::Postfix(inner, postfix) => {
+            collect_lhs_idents(inner, names);
+            if let Postfix::Index(index) = postfix {
+                collect_expr_idents(index, names);
+            }
+        }
+        LhsExpr::Deref(inner) | LhsExpr::AddressOf(inner) => collect_lhs_idents(inner, names),
+    }
+}
+
+fn collect_expr_idents(node: &ExprNode, names: &mut HashSet<String>) {
+    match &node.expr {
+        Expr::Lit(_) => {}
+        Expr::Var(expr) => {
+            names.insert(expr.ident.clone());
+        }
+        Expr::TypeCons(expr) => {
+            for arg in &expr.args {
+                collect_expr_idents(arg, names);
+            }
+        }
+        Expr::Postfix(expr) => {
+            collect_expr_idents(&expr.inner, names);
+            if let Postfix::Index(index) = &expr.postfix {
+                collect_expr_idents(index, names);
+            }
+        }
+        Expr::UnOp(expr) => collect_expr_idents(&expr.inner, names),
+        Expr::BinOp(expr) => {
+            collect_expr_idents(&expr.left, names);
+            collect_expr_idents(&expr.right, names);
+        }
+        Expr::FnCall(expr) => {
+            for arg in &expr.args {
+                collect_expr_idents(arg, names);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_module;
+
+    fn write(module: &ast::Module) -> String {
+        let mut output = String::new();
+        ast::writer::Writer::default()
+            .write_module(&mut output, module)
+            .unwrap();
+        output
+    }
+
+    /// Checks that merging blocks in `before` merges `count` blocks and produces `after`.
+    fn check_merge(before: &str, count: usize, after: &str) {
+        let mut module = parser::parse(before);
+        assert_eq!(merge_module(&mut module), count);
+        assert_eq!(write(&module), write(&parser::parse(after)));
+    }
+
+    #[test]
+    fn flattens_nested_compound_blocks() {
+        check_merge(
+            r#"
+                fn f() {
+                    {
+                        {
+                            let a = 1;
+                        }
+                    }
+                    let b = 2;
+                }
+            "#,
+            2,
+            r#"
+                fn f() {
+                    let a = 1;
+                    let b = 2;
+                }
+            "#,
+        );
+    }
+
+    #[test]
+    fn merges_into_loop_bodies() {
+        check_merge(
+            r#"
+                fn f() {
+                    loop {
+                        {
+                            let a = 1;
+                            break;
+                        }
+                    }
+                }
+            "#,
+            1,
+            r#"
+                fn f() {
+                    loop {
+                        let a = 1;
+                        break;
+                    }
+                }
+            "#,
+        );
+    }
+
+    #[test]
+    fn shadowing_prevents_merge() {
+        let source = r#"
+            fn f(p: i32) -> i32 {
+                let a = 1;
+                {
+                    let a = 2;
+                }
+                var b = 3;
+                {
+                    let b = 4;
+                }
+                {
+                    let p = 5;
+                }
+                for (var i = 0; i < 2; i = i + 1) {
+                    {
+                        let i = 6;
+                    }
+                }
+                return a + b;
+            }
+        "#;
+
+        check_merge(source, 0, source);
+    }
+
+    #[test]
+    fn later_use_prevents_merge() {
+        check_merge(
+            r#"
+                var<private> g: i32;
+
+                fn f() -> i32 {
+                    {
+                        let g = 1;
+                    }
+                    {
+                        let h = 2;
+                    }
+                    return g;
+                }
+            "#,
+            1,
+            r#"
+                var<private> g: i32;
+
+                fn f() -> i32 {
+                    {
+                        let g = 1;
+                    }
+                    let h = 2;
+                    return g;
+                }
+            "#,
+        );
+    }
+}
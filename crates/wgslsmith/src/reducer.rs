@@ -1,10 +1,11 @@
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs::Permissions;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Instant;
 use std::{env, thread};
 
+use ast::Module;
 use clap::{Parser, ValueEnum};
 use eyre::{eyre, Context};
 use nix::sys::signal::Signal;
@@ -15,6 +16,7 @@ use tap::Tap;
 
 use crate::compiler::{Backend, Compiler};
 use crate::config::Config;
+use crate::harness_runner::{self, ExitCodes, Harness};
 
 #[derive(ValueEnum, Clone)]
 pub enum ReductionKind {
@@ -53,7 +55,14 @@ pub struct Options {
     config: Option<String>,
 
     /// Compiler to use for reducing a crash.
-    #[clap(long, action, action, requires("backend"))]
+    ///
+    /// A backend is required for all compilers except `dawn`, which is validated directly.
+    #[clap(
+        long,
+        action,
+        action,
+        requires_ifs(&[("tint", "backend"), ("naga", "backend")])
+    )]
     compiler: Option<Compiler>,
 
     /// Compiler backend to use for reducing a crash.
@@ -62,20 +71,88 @@ pub struct Options {
 
     /// Regex to match crash output against.
     ///
-    /// This is only valid if we're reducing a crash.
-    #[clap(long, action, required_if_eq("kind", "crash"))]
+    /// This is only valid if we're reducing a crash, and is required unless `--compiler-timeout`
+    /// is set.
+    #[clap(long, action)]
     regex: Option<Regex>,
 
     /// Don't recondition shader before executing.
-    ///
-    /// This is only valid if we're reducing a crash.
     #[clap(long, action)]
     no_recondition: bool,
 
+    /// Treat compiler warnings as failures, so that the regex is matched against them.
+    ///
+    /// This is only supported when reducing a crash with `--compiler dawn`.
+    #[clap(long, action, requires("compiler"))]
+    warnings_as_errors: bool,
+
+    /// Timeout in seconds for the compiler, after which it's considered to hang.
+    ///
+    /// A shader that makes the compiler hang is always interesting, so this reduces toward the
+    /// smallest shader that still hangs it. If `--regex` is also set, crashes that match it are
+    /// interesting as well.
+    #[clap(long, action, requires("compiler"))]
+    compiler_timeout: Option<u64>,
+
     /// Disable logging from harness.
     #[clap(short, long, action)]
     quiet: bool,
 
+    /// Don't try to replace constant expressions with their values after reducing.
+    #[clap(long, action)]
+    no_fold: bool,
+
+    /// Don't try to merge nested compound blocks (`{ ... }`) into their parents after reducing.
+    #[clap(long, action)]
+    no_merge_blocks: bool,
+
+    /// Harness exit code that signals a buffer mismatch.
+    ///
+    /// This is useful when reducing with a custom harness binary.
+    #[clap(long, action, default_value_t = ExitCodes::default().mismatch)]
+    interesting_exit_code: i32,
+
+    /// Harness exit code that signals a crash.
+    ///
+    /// Defaults to the exit code of a Rust panic.
+    #[clap(long, action, default_value_t = ExitCodes::default().crash)]
+    crash_exit_code: i32,
+
+    /// Execute the shader this many times with each configuration before reducing, and skip the
+    /// reduction if any configuration doesn't produce the same output on every run.
+    ///
+    /// This avoids reducing mismatches that are caused by a nondeterministic backend. This is only
+    /// valid if we're reducing a mismatch.
+    #[clap(long, action)]
+    check_determinism: Option<u32>,
+
+    /// Reject candidates with fewer than this many statement and expression nodes.
+    ///
+    /// This stops the reducer from shrinking a shader so far that it reproduces the bug for a
+    /// trivial reason, at the cost of a larger result. Candidates must be parseable for their size
+    /// to be measured.
+    #[clap(long, action)]
+    min_interesting_size: Option<usize>,
+
+    /// Number of workgroups to dispatch when executing the shader with the harness.
+    #[clap(
+        long,
+        action,
+        default_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    workgroups: u32,
+
+    /// Before reducing the shader, search for the smallest number of workgroups at which it's
+    /// still interesting, and reduce it with that many workgroups.
+    ///
+    /// The harness dispatches `(N, 1, 1)` workgroups, where `N` starts at `--workgroups`. The
+    /// search assumes that a bug which reproduces with `N` workgroups also reproduces with more,
+    /// and runs the interestingness test at each candidate size. The minimal size is printed
+    /// before reduction starts.
+    #[clap(long, action, conflicts_with = "compiler")]
+    shrink_workgroups: bool,
+
     #[clap(long, action, action)]
     reducer: Option<Reducer>,
 
@@ -202,6 +279,14 @@ fn thread_main(config: &Config, options: Options) -> eyre::Result<()> {
 
     let shader_path = shader_path.canonicalize()?;
 
+    let is_crash = matches!(options.kind, ReductionKind::Crash);
+    if is_crash && options.regex.is_none() && options.compiler_timeout.is_none() {
+        return Err(eyre!(
+            "--regex is required when reducing a crash, unless looking for a compiler hang with \
+             --compiler-timeout"
+        ));
+    }
+
     let input_path = if let Some(input_path) = options.input_data {
         input_path
     } else {
@@ -230,6 +315,65 @@ fn thread_main(config: &Config, options: Options) -> eyre::Result<()> {
 
     let metadata_path = input_path.canonicalize()?;
 
+    crate::test::read_inputs(&metadata_path)?;
+
+    let harness_server = options
+        .server
+        .as_deref()
+        .or_else(|| config.default_remote());
+
+    let exit_codes = ExitCodes {
+        mismatch: options.interesting_exit_code,
+        crash: options.crash_exit_code,
+    };
+
+    if let (ReductionKind::Mismatch, Some(runs)) = (&options.kind, options.check_determinism) {
+        let harness = match harness_server {
+            Some(server) => Harness::Remote(server.to_owned()),
+            None => Harness::Local(
+                config
+                    .harness
+                    .path
+                    .clone()
+                    .map(Ok)
+                    .unwrap_or_else(std::env::current_exe)?,
+            ),
+        };
+
+        let source = std::fs::read_to_string(&shader_path)?;
+        let metadata = crate::test::read_inputs(&metadata_path)?;
+
+        // Check the same shader that the interestingness test executes
+        let shader = if options.no_recondition {
+            source
+        } else {
+            crate::test::recondition(parser::parse(&source))
+        };
+
+        println!("> checking that shader is deterministic ({runs} runs)");
+
+        let is_deterministic = harness_runner::check_determinism(
+            &harness,
+            None,
+            &shader,
+            &metadata,
+            runs,
+            options.workgroups,
+            &exit_codes,
+            |line| {
+                if !options.quiet {
+                    println!("{line}");
+                }
+            },
+        )?;
+
+        if !is_deterministic {
+            return Err(eyre!(
+                "shader produced different outputs across runs, skipping reduction"
+            ));
+        }
+    }
+
     let out_dir = options.output.unwrap_or_else(|| {
         let out_dir = options.shader.parent().unwrap().join("reduced");
         if out_dir.exists() {
@@ -260,55 +404,93 @@ fn thread_main(config: &Config, options: Options) -> eyre::Result<()> {
 
     setup_out_dir(&out_dir, &options.shader, &reducer)?;
 
-    let harness_server = options
-        .server
-        .as_deref()
-        .or_else(|| config.default_remote());
-
     let parallelism = options
         .parallelism
         .or(config.reducer.parallelism)
         .unwrap_or(1);
 
-    let mut cmd = reducer
-        .cmd(config, parallelism, shader_name, "test.sh")?
-        .tap_mut(|cmd| {
-            cmd.current_dir(&out_dir)
-                .env("WGSLREDUCE_SHADER_NAME", shader_path.file_name().unwrap())
-                .env("WGSLREDUCE_METADATA_PATH", metadata_path);
+    // Environment variables used by the interestingness test script
+    let mut envs: Vec<(&str, OsString)> = vec![
+        (
+            "WGSLREDUCE_SHADER_NAME",
+            shader_path.file_name().unwrap().to_owned(),
+        ),
+        ("WGSLREDUCE_METADATA_PATH", metadata_path.into_os_string()),
+        (
+            "WGSLREDUCE_INTERESTING_EXIT_CODE",
+            exit_codes.mismatch.to_string().into(),
+        ),
+        (
+            "WGSLREDUCE_CRASH_EXIT_CODE",
+            exit_codes.crash.to_string().into(),
+        ),
+    ];
+
+    if let Some(server) = harness_server {
+        envs.push(("WGSLREDUCE_SERVER", server.into()));
+    }
 
-            if let Some(server) = harness_server {
-                cmd.env("WGSLREDUCE_SERVER", server);
-            }
+    if let Some(min_size) = options.min_interesting_size {
+        envs.push(("WGSLREDUCE_MIN_SIZE", min_size.to_string().into()));
+    }
 
-            if let Some(tmpdir) = &config.reducer.tmpdir {
-                cmd.env("TMPDIR", tmpdir);
-            }
-        });
+    if let Some(tmpdir) = &config.reducer.tmpdir {
+        envs.push(("TMPDIR", tmpdir.into()));
+    }
 
     match options.kind {
         ReductionKind::Crash => {
-            cmd.env("WGSLREDUCE_KIND", "crash")
-                .env("WGSLREDUCE_REGEX", options.regex.unwrap().as_str());
+            envs.push(("WGSLREDUCE_KIND", "crash".into()));
+
+            if let Some(regex) = options.regex {
+                envs.push(("WGSLREDUCE_REGEX", regex.as_str().into()));
+            }
+
+            if let Some(timeout) = options.compiler_timeout {
+                envs.push(("WGSLREDUCE_COMPILER_TIMEOUT", timeout.to_string().into()));
+            }
 
             if let Some(config) = options.config {
-                cmd.env("WGSLREDUCE_CONFIG", config);
+                envs.push(("WGSLREDUCE_CONFIG", config.into()));
             } else {
                 let compiler = options.compiler.unwrap();
-                let backend = options.backend.unwrap();
-                cmd.env("WGSLREDUCE_COMPILER", compiler.to_string())
-                    .env("WGSLREDUCE_BACKEND", backend.to_string());
+                envs.push(("WGSLREDUCE_COMPILER", compiler.to_string().into()));
+
+                if let Some(backend) = options.backend {
+                    envs.push(("WGSLREDUCE_BACKEND", backend.to_string().into()));
+                }
             }
 
-            if !options.no_recondition {
-                cmd.env("WGSLREDUCE_RECONDITION", "1");
+            if options.warnings_as_errors {
+                envs.push(("WGSLREDUCE_WARNINGS_AS_ERRORS", "1".into()));
             }
         }
         ReductionKind::Mismatch => {
-            cmd.env("WGSLREDUCE_KIND", "mismatch");
+            envs.push(("WGSLREDUCE_KIND", "mismatch".into()));
         }
     }
 
+    if !options.no_recondition {
+        envs.push(("WGSLREDUCE_RECONDITION", "1".into()));
+    }
+
+    let workgroups = if options.shrink_workgroups {
+        let workgroups =
+            shrink_workgroups(options.workgroups, &out_dir, shader_name, &reducer, &envs)?;
+        println!("> minimal dispatch size: ({workgroups}, 1, 1)");
+        workgroups
+    } else {
+        options.workgroups
+    };
+
+    envs.push(("WGSLREDUCE_WORKGROUPS", workgroups.to_string().into()));
+
+    let mut cmd = reducer
+        .cmd(config, parallelism, shader_name, "test.sh")?
+        .tap_mut(|cmd| {
+            cmd.current_dir(&out_dir).envs(envs.iter().cloned());
+        });
+
     let start_time = Instant::now();
 
     if !cmd.status()?.success() {
@@ -330,8 +512,17 @@ fn thread_main(config: &Config, options: Options) -> eyre::Result<()> {
     crate::fmt::run(crate::fmt::Options {
         input: result_path.clone(),
         output: result_path,
+        input_format: crate::fmt::InputFormat::Wgsl,
     })?;
 
+    if !options.no_fold {
+        apply_cleanup(Cleanup::FoldConsts, &out_dir, shader_name, &reducer, &envs)?;
+    }
+
+    if !options.no_merge_blocks {
+        apply_cleanup(Cleanup::MergeBlocks, &out_dir, shader_name, &reducer, &envs)?;
+    }
+
     // crate::reconditioner::run(crate::reconditioner::Options {
     //     input: result_path,
     //     output: reconditioned_path,
@@ -340,6 +531,137 @@ fn thread_main(config: &Config, options: Options) -> eyre::Result<()> {
     Ok(())
 }
 
+/// A semantics-preserving transform which is applied to the reduced shader, to make it easier to
+/// read.
+#[derive(Clone, Copy)]
+enum Cleanup {
+    /// Replaces constant expressions with their values.
+    FoldConsts,
+    /// Merges compound blocks into their parents where this doesn't change name resolution.
+    MergeBlocks,
+}
+
+impl Cleanup {
+    fn apply(self, module: &mut Module) -> usize {
+        match self {
+            Cleanup::FoldConsts => crate::fold::fold_module(module),
+            Cleanup::MergeBlocks => crate::merge::merge_module(module),
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Cleanup::FoldConsts => "constant expressions",
+            Cleanup::MergeBlocks => "compound blocks",
+        }
+    }
+
+    fn verb(self) -> (&'static str, &'static str) {
+        match self {
+            Cleanup::FoldConsts => ("folded", "folding"),
+            Cleanup::MergeBlocks => ("merged", "merging"),
+        }
+    }
+}
+
+/// Applies `cleanup` to the reduced shader, keeping the result only if the shader is still
+/// interesting.
+fn apply_cleanup(
+    cleanup: Cleanup,
+    out_dir: &Path,
+    shader_name: &OsStr,
+    reducer: &Reducer,
+    envs: &[(&str, OsString)],
+) -> eyre::Result<()> {
+    let shader_path = out_dir.join(shader_name);
+    let source = std::fs::read_to_string(&shader_path)?;
+
+    let mut module = parser::parse(&source);
+    let count = cleanup.apply(&mut module);
+    if count == 0 {
+        return Ok(());
+    }
+
+    let mut cleaned = String::new();
+    ast::writer::Writer::default()
+        .write_module(&mut cleaned, &module)
+        .unwrap();
+
+    std::fs::write(&shader_path, cleaned)?;
+
+    let (past, present) = cleanup.verb();
+    let description = cleanup.description();
+
+    if run_test(out_dir, shader_name, reducer, envs)? {
+        println!("> {past} {count} {description}");
+    } else {
+        println!("> shader is not interesting after {present} {description}, reverting");
+        std::fs::write(&shader_path, source)?;
+    }
+
+    Ok(())
+}
+
+/// Finds the smallest number of workgroups, up to `max`, with which the shader in `out_dir` is still
+/// interesting.
+fn shrink_workgroups(
+    max: u32,
+    out_dir: &Path,
+    shader_name: &OsStr,
+    reducer: &Reducer,
+    envs: &[(&str, OsString)],
+) -> eyre::Result<u32> {
+    let is_interesting = |workgroups: u32| {
+        println!("> trying dispatch size ({workgroups}, 1, 1)");
+
+        let mut envs = envs.to_vec();
+        envs.push(("WGSLREDUCE_WORKGROUPS", workgroups.to_string().into()));
+
+        run_test(out_dir, shader_name, reducer, &envs)
+    };
+
+    // The search relies on the starting size reproducing, so check it rather than assuming it
+    if !is_interesting(max)? {
+        return Err(eyre!(
+            "shader is not interesting with dispatch size ({max}, 1, 1)"
+        ));
+    }
+
+    // Binary search between a size that is assumed not to reproduce and one that is known to
+    let mut lo = 0;
+    let mut hi = max;
+
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+
+        if is_interesting(mid)? {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+
+    Ok(hi)
+}
+
+/// Runs the interestingness test on the shader in `out_dir`, returning whether it's interesting.
+fn run_test(
+    out_dir: &Path,
+    shader_name: &OsStr,
+    reducer: &Reducer,
+    envs: &[(&str, OsString)],
+) -> eyre::Result<bool> {
+    let mut test = Command::new(out_dir.join("test.sh"));
+
+    test.current_dir(out_dir).envs(envs.iter().cloned());
+
+    if let Reducer::Picire = reducer {
+        test.arg(shader_name);
+    }
+
+    Ok(test.status()?.success())
+}
+
 fn setup_out_dir(out_dir: &Path, shader: &Path, reducer: &Reducer) -> eyre::Result<()> {
     // Create output dir
     if !out_dir.exists() {
@@ -1,6 +1,6 @@
 use std::net::{SocketAddr, TcpStream};
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use bincode::Decode;
 use eyre::{eyre, Context};
@@ -18,17 +18,21 @@ pub fn execute(
     shader: String,
     workgroups: u32,
     flow: bool,
+    zero_init: bool,
     pipeline_desc: PipelineDescription,
     configs: Vec<ConfigId>,
     timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
     on_event: &mut dyn FnMut(ExecutionEvent) -> Result<(), ExecutionError>,
 ) -> Result<(), ExecutionError> {
+    let deadline = request_timeout.map(|timeout| Instant::now() + timeout);
     let mut stream = req(
         server,
         Request::Run(RunRequest {
             shader,
             workgroups,
             flow,
+            zero_init,
             pipeline_desc,
             configs,
             timeout,
@@ -36,7 +40,25 @@ pub fn execute(
     )?;
 
     loop {
-        match decode_from_stream(&mut stream)? {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(ExecutionError::Timeout);
+            }
+
+            stream.set_read_timeout(Some(remaining))?;
+        }
+
+        let message = match decode_from_stream(&mut stream) {
+            Ok(message) => message,
+            // If the read failed because the deadline passed, report it as a timeout
+            Err(_) if matches!(deadline, Some(deadline) if Instant::now() >= deadline) => {
+                return Err(ExecutionError::Timeout)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        match message {
             RunMessage::UsingDefaultConfigs(configs) => {
                 on_event(ExecutionEvent::UsingDefaultConfigs(configs))?
             }
@@ -46,6 +68,12 @@ pub fn execute(
             }
             RunMessage::ExecFailure(stderr) => on_event(ExecutionEvent::Failure(stderr))?,
             RunMessage::ExecTimeout => on_event(ExecutionEvent::Timeout)?,
+            RunMessage::ExecExceedsLimits(message) => {
+                on_event(ExecutionEvent::ExceedsLimits(message))?
+            }
+            RunMessage::ExecUnsupportedFeature(message) => {
+                on_event(ExecutionEvent::UnsupportedFeature(message))?
+            }
             RunMessage::End(result) => {
                 return result.map_err(|e| match e {
                     RunError::NoDefaultConfigs => ExecutionError::NoDefaultConfigs,
@@ -69,3 +97,85 @@ fn req(server: &str, req: Request) -> eyre::Result<TcpStream> {
 fn decode_from_stream<T: Decode>(stream: &mut TcpStream) -> Result<T, bincode::error::DecodeError> {
     bincode::decode_from_std_read(stream, bincode::config::standard())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use harness_frontend::ExecutionError;
+    use harness_server_types::RunMessage;
+    use reflection_types::{PipelineDescription, PipelineStage};
+
+    /// Starts a server which accepts a single request and passes the connection to `respond`,
+    /// returning the server's address.
+    fn serve(respond: impl FnOnce(TcpStream) + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            respond(stream);
+        });
+
+        address
+    }
+
+    fn execute(server: &str, request_timeout: Duration) -> Result<(), ExecutionError> {
+        let pipeline_desc = PipelineDescription {
+            stage: PipelineStage::Compute,
+            resources: vec![],
+        };
+
+        super::execute(
+            server,
+            String::new(),
+            1,
+            false,
+            true,
+            pipeline_desc,
+            vec![],
+            None,
+            Some(request_timeout),
+            &mut |_| Ok(()),
+        )
+    }
+
+    #[test]
+    fn request_times_out_without_response() {
+        let server = serve(|stream| {
+            // Hold the connection open without ever responding
+            thread::sleep(Duration::from_secs(5));
+            drop(stream);
+        });
+
+        let start = Instant::now();
+        let result = execute(&server, Duration::from_millis(200));
+
+        assert!(matches!(result, Err(ExecutionError::Timeout)), "{result:?}");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn deadline_covers_whole_request() {
+        // Messages keep arriving well within the timeout, but the request never finishes
+        let server = serve(|mut stream| {
+            for _ in 0..100 {
+                let message = RunMessage::ExecFailure(vec![]);
+                let config = bincode::config::standard();
+                if bincode::encode_into_std_write(message, &mut stream, config).is_err() {
+                    break;
+                }
+
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let start = Instant::now();
+        let result = execute(&server, Duration::from_millis(300));
+
+        assert!(matches!(result, Err(ExecutionError::Timeout)), "{result:?}");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
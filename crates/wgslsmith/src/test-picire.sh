@@ -6,26 +6,47 @@ args=(
     "$WGSLREDUCE_KIND"
     "$1"
     "$WGSLREDUCE_METADATA_PATH"
+    "--interesting-exit-code" "$WGSLREDUCE_INTERESTING_EXIT_CODE"
+    "--crash-exit-code" "$WGSLREDUCE_CRASH_EXIT_CODE"
 )
 
 if [[ -n "${WGSLREDUCE_SERVER-}" ]]; then
     args+=("--server" "$WGSLREDUCE_SERVER")
 fi
 
+if [[ -n "${WGSLREDUCE_MIN_SIZE-}" ]]; then
+    args+=("--min-size" "$WGSLREDUCE_MIN_SIZE")
+fi
+
+if [[ -n "${WGSLREDUCE_WORKGROUPS-}" ]]; then
+    args+=("--workgroups" "$WGSLREDUCE_WORKGROUPS")
+fi
+
+if [[ ! -n "${WGSLREDUCE_RECONDITION}" ]]; then
+    args+=("--no-recondition")
+fi
+
 if [[ "$WGSLREDUCE_KIND" == "crash" ]]; then
-    args+=("--regex" "$WGSLREDUCE_REGEX")
+    if [[ -n "${WGSLREDUCE_REGEX-}" ]]; then
+        args+=("--regex" "$WGSLREDUCE_REGEX")
+    fi
+
+    if [[ -n "${WGSLREDUCE_COMPILER_TIMEOUT-}" ]]; then
+        args+=("--compiler-timeout" "$WGSLREDUCE_COMPILER_TIMEOUT")
+    fi
 
     if [[ -n "${WGSLREDUCE_CONFIG-}" ]]; then
         args+=("--config" "$WGSLREDUCE_CONFIG")
     else
-        args+=(
-            "--compiler" "$WGSLREDUCE_COMPILER"
-            "--backend" "$WGSLREDUCE_BACKEND"
-        )
+        args+=("--compiler" "$WGSLREDUCE_COMPILER")
+
+        if [[ -n "${WGSLREDUCE_BACKEND-}" ]]; then
+            args+=("--backend" "$WGSLREDUCE_BACKEND")
+        fi
     fi
 
-    if [[ ! -n "${WGSLREDUCE_RECONDITION}" ]]; then
-        args+=("--no-recondition")
+    if [[ -n "${WGSLREDUCE_WARNINGS_AS_ERRORS-}" ]]; then
+        args+=("--warnings-as-errors")
     fi
 fi
 
@@ -1,14 +1,15 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use ast::Module;
 use clap::Parser;
-use eyre::eyre;
-use harness_types::ConfigId;
+use eyre::{eyre, Context};
+use harness_types::{ConfigId, HarnessError};
 use regex::Regex;
 
-use crate::compiler::{Backend, Compiler};
+use crate::compiler::{self, Backend, Compiler, CompilerTimeout};
 use crate::config::Config;
-use crate::harness_runner::{ExecutionResult, Harness};
+use crate::harness_runner::{ExecutionResult, ExitCodes, Harness};
 use crate::reducer::ReductionKind;
 use crate::{harness_runner, validator};
 
@@ -31,6 +32,27 @@ pub struct Options {
 
     #[clap(short, long, action)]
     quiet: bool,
+
+    /// Number of workgroups to dispatch when executing the shader with the harness.
+    #[clap(
+        long,
+        action,
+        default_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    workgroups: u32,
+
+    /// Harness exit code that signals a buffer mismatch.
+    #[clap(long, action, default_value_t = ExitCodes::default().mismatch)]
+    interesting_exit_code: i32,
+
+    /// Harness exit code that signals a crash.
+    #[clap(long, action, default_value_t = ExitCodes::default().crash)]
+    crash_exit_code: i32,
+
+    /// Treat shaders with fewer than this many statement and expression nodes as uninteresting.
+    #[clap(long, action)]
+    min_size: Option<usize>,
 }
 
 #[derive(Parser)]
@@ -38,22 +60,48 @@ pub struct CrashOptions {
     #[clap(long, action, conflicts_with("compiler"))]
     config: Option<ConfigId>,
 
-    #[clap(long, value_enum, action, requires("backend"))]
+    #[clap(
+        long,
+        value_enum,
+        action,
+        requires_ifs(&[("tint", "backend"), ("naga", "backend")])
+    )]
     compiler: Option<Compiler>,
 
     #[clap(long, value_enum, action)]
     backend: Option<Backend>,
 
-    #[clap(long, action, required_if_eq("kind", "crash"))]
+    #[clap(long, action)]
     regex: Option<Regex>,
 
     #[clap(long, action)]
     no_recondition: bool,
+
+    /// Treat warnings as failures, so that the regex is matched against them.
+    ///
+    /// This is only supported with `--compiler dawn`.
+    #[clap(long, action)]
+    warnings_as_errors: bool,
+
+    /// Timeout in seconds for the compiler, after which it's considered to hang.
+    ///
+    /// A hang is always interesting, so `--regex` can be left out to only look for hangs.
+    #[clap(long, action, requires("compiler"))]
+    compiler_timeout: Option<u64>,
 }
 
 pub fn run(config: &Config, options: Options) -> eyre::Result<()> {
     let source = std::fs::read_to_string(&options.shader)?;
 
+    if let Some(min_size) = options.min_size {
+        let size = crate::dedup::count_nodes(&parser::parse(&source));
+        if size < min_size {
+            return Err(eyre!(
+                "shader is too small to be interesting ({size} < {min_size} nodes)"
+            ));
+        }
+    }
+
     let input_path = if let Some(input_path) = options.input_data {
         input_path
     } else {
@@ -87,7 +135,7 @@ pub fn run(config: &Config, options: Options) -> eyre::Result<()> {
         try_path
     };
 
-    let metadata = std::fs::read_to_string(&input_path)?;
+    let metadata = read_inputs(&input_path)?;
 
     let harness = if let Some(server) = options.server {
         Harness::Remote(server)
@@ -102,6 +150,11 @@ pub fn run(config: &Config, options: Options) -> eyre::Result<()> {
         )
     };
 
+    let exit_codes = ExitCodes {
+        mismatch: options.interesting_exit_code,
+        crash: options.crash_exit_code,
+    };
+
     match options.kind {
         ReductionKind::Crash => reduce_crash(
             config,
@@ -109,9 +162,19 @@ pub fn run(config: &Config, options: Options) -> eyre::Result<()> {
             source,
             metadata,
             &harness,
+            options.workgroups,
+            &exit_codes,
+            options.quiet,
+        )?,
+        ReductionKind::Mismatch => reduce_mismatch(
+            source,
+            metadata,
+            !options.crash_options.no_recondition,
+            &harness,
+            options.workgroups,
+            &exit_codes,
             options.quiet,
         )?,
-        ReductionKind::Mismatch => reduce_mismatch(source, metadata, &harness, options.quiet)?,
     }
 
     println!("interesting :)");
@@ -119,15 +182,29 @@ pub fn run(config: &Config, options: Options) -> eyre::Result<()> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn reduce_crash(
     config: &Config,
     options: CrashOptions,
     source: String,
     metadata: String,
     harness: &Harness,
+    workgroups: u32,
+    exit_codes: &ExitCodes,
     quiet: bool,
 ) -> eyre::Result<()> {
-    let regex = options.regex.unwrap();
+    let regex = options.regex.as_ref();
+    if regex.is_none() && options.compiler_timeout.is_none() {
+        return Err(eyre!(
+            "--regex is required when reducing a crash, unless looking for a compiler hang with \
+             --compiler-timeout"
+        ));
+    }
+
+    if let Some(timeout) = options.compiler_timeout {
+        compiler::set_timeout(Duration::from_secs(timeout));
+    }
+
     let should_recondition = !options.no_recondition;
 
     let source = if should_recondition {
@@ -137,29 +214,35 @@ fn reduce_crash(
     };
 
     let interesting = if let Some(config) = options.config {
-        let result =
-            harness_runner::exec_shader(harness, Some(config), &source, &metadata, |line| {
+        let result = harness_runner::exec_shader_with_exit_codes(
+            harness,
+            Some(config),
+            &source,
+            &metadata,
+            workgroups,
+            exit_codes,
+            |line| {
                 if !quiet {
                     println!("{line}");
                 }
-            })?;
+            },
+        )?;
 
         eprintln!("{result:?}");
 
-        matches!(result, ExecutionResult::Crash(output) if regex.is_match(&output))
+        match result {
+            ExecutionResult::Crash(output) => regex.is_some_and(|it| it.is_match(&output)),
+            _ => false,
+        }
     } else {
-        let compiler = options.compiler.unwrap();
-        let backend = options.backend.unwrap();
-        let compiled = compiler.compile(&source, backend)?;
-
-        match backend {
-            Backend::Hlsl => {
-                remote_validate(config, &compiled, validator::Backend::Hlsl, &regex, quiet)?
-            }
-            Backend::Msl => {
-                remote_validate(config, &compiled, validator::Backend::Msl, &regex, quiet)?
+        match check_compiler(config, &options, &source, regex, quiet) {
+            // A hang is a different kind of bug from whatever the regex matches, so it's
+            // interesting on its own
+            Err(e) if e.downcast_ref::<CompilerTimeout>().is_some() => {
+                eprintln!("Hang: {e}");
+                true
             }
-            Backend::Spirv => todo!(),
+            result => result?,
         }
     };
 
@@ -170,23 +253,67 @@ fn reduce_crash(
     Ok(())
 }
 
+/// Compiles or validates `source` with the compiler from `options`, returning whether it fails
+/// with an error that matches `regex`.
+fn check_compiler(
+    config: &Config,
+    options: &CrashOptions,
+    source: &str,
+    regex: Option<&Regex>,
+    quiet: bool,
+) -> eyre::Result<bool> {
+    if let Some(Compiler::Dawn) = options.compiler {
+        return dawn_validate(source, regex, options.warnings_as_errors, quiet);
+    }
+
+    if options.warnings_as_errors {
+        return Err(eyre!(
+            "--warnings-as-errors is only supported with --compiler dawn"
+        ));
+    }
+
+    let compiler = options.compiler.as_ref().unwrap();
+    let backend = options.backend.unwrap();
+    let compiled = compiler.compile(source, backend)?;
+
+    match backend {
+        Backend::Hlsl => remote_validate(config, &compiled, validator::Backend::Hlsl, regex, quiet),
+        Backend::Msl => remote_validate(config, &compiled, validator::Backend::Msl, regex, quiet),
+        Backend::Spirv => todo!(),
+    }
+}
+
 fn reduce_mismatch(
     source: String,
     metadata: String,
+    should_recondition: bool,
     harness: &Harness,
+    workgroups: u32,
+    exit_codes: &ExitCodes,
     quiet: bool,
 ) -> eyre::Result<()> {
-    let module = parser::parse(&source);
-    let reconditioned = recondition(module);
+    let reconditioned = if should_recondition {
+        recondition(parser::parse(&source))
+    } else {
+        source
+    };
 
     Compiler::Naga.validate(&reconditioned)?;
     Compiler::Tint.validate(&reconditioned)?;
 
-    let result = harness_runner::exec_shader(harness, None, &reconditioned, &metadata, |line| {
-        if !quiet {
-            println!("{line}");
-        }
-    })?;
+    let result = harness_runner::exec_shader_with_exit_codes(
+        harness,
+        None,
+        &reconditioned,
+        &metadata,
+        workgroups,
+        exit_codes,
+        |line| {
+            if !quiet {
+                println!("{line}");
+            }
+        },
+    )?;
 
     if result != ExecutionResult::Mismatch {
         return Err(eyre!("shader is not interesting"));
@@ -195,7 +322,18 @@ fn reduce_mismatch(
     Ok(())
 }
 
-fn recondition(module: Module) -> String {
+/// Reads an inputs file, checking that it's valid so that a malformed file is reported up front
+/// rather than as a failure of every harness run.
+pub fn read_inputs(path: &Path) -> eyre::Result<String> {
+    let metadata = std::fs::read_to_string(path)?;
+
+    reflection_types::parse_input_data(&metadata)
+        .wrap_err_with(|| eyre!("failed to parse inputs file at {path:?}"))?;
+
+    Ok(metadata)
+}
+
+pub fn recondition(module: Module) -> String {
     let reconditioned = reconditioner::recondition(module);
     let mut formatted = String::new();
 
@@ -210,7 +348,7 @@ fn remote_validate(
     config: &Config,
     source: &str,
     backend: validator::Backend,
-    regex: &Regex,
+    regex: Option<&Regex>,
     quiet: bool,
 ) -> eyre::Result<bool> {
     if !quiet {
@@ -228,9 +366,43 @@ fn remote_validate(
                 println!("-----");
                 println!("{err}");
             }
-            regex.is_match(&err)
+            regex.is_some_and(|it| it.is_match(&err))
         }
     };
 
     Ok(is_interesting)
 }
+
+/// Validates `source` with Dawn's front-end and matches `regex` against the messages it reports,
+/// including warnings if `warnings_as_errors` is set.
+///
+/// Unlike the other compilers, this runs locally since Dawn doesn't produce any output for the
+/// validation server to check.
+fn dawn_validate(
+    source: &str,
+    regex: Option<&Regex>,
+    warnings_as_errors: bool,
+    quiet: bool,
+) -> eyre::Result<bool> {
+    if !quiet {
+        println!("[SOURCE]");
+        println!("{source}");
+    }
+
+    let err = match Compiler::Dawn.validate_with(source, warnings_as_errors) {
+        Ok(()) => return Ok(false),
+        Err(err) => err,
+    };
+
+    // Anything other than a shader error (e.g. no Dawn adapter) is a problem with the test setup
+    match err.downcast_ref::<HarnessError>() {
+        Some(HarnessError::Compilation(message) | HarnessError::Validation(message)) => {
+            if !quiet {
+                println!("-----");
+                println!("{message}");
+            }
+            Ok(regex.is_some_and(|it| it.is_match(message)))
+        }
+        _ => Err(err),
+    }
+}
@@ -0,0 +1,168 @@
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use clap::Parser;
+use eyre::{eyre, Context};
+use rand::SeedableRng;
+
+use crate::compiler::Compiler;
+
+#[derive(Parser)]
+pub struct Options {
+    /// Paths to shaders to validate.
+    ///
+    /// Directories are searched recursively for `.wgsl` files.
+    #[clap(action)]
+    paths: Vec<PathBuf>,
+
+    /// Also generate and validate shaders from the seeds `0..N`.
+    ///
+    /// Generated shaders are reconditioned before being validated.
+    #[clap(long, action)]
+    seeds: Option<u64>,
+
+    /// Compilers to validate with (defaults to `tint` and `naga`).
+    #[clap(long = "compiler", value_enum, action)]
+    compilers: Vec<Compiler>,
+
+    /// Recondition shaders loaded from disk before validating them.
+    #[clap(long, action)]
+    recondition: bool,
+
+    /// Validate every shader instead of stopping at the first failure.
+    ///
+    /// A report of all failures is printed at the end, and the command exits with an error if
+    /// there were any.
+    #[clap(long, action)]
+    keep_going: bool,
+
+    /// Treat warnings from tint or dawn as validation failures.
+    ///
+    /// This is useful for finding valid shaders that a compiler emits spurious warnings for.
+    #[clap(long, action)]
+    warnings_as_errors: bool,
+}
+
+enum TestCase {
+    Path(PathBuf),
+    Seed(u64),
+}
+
+impl Display for TestCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestCase::Path(path) => write!(f, "{}", path.display()),
+            TestCase::Seed(seed) => write!(f, "seed {seed}"),
+        }
+    }
+}
+
+struct Failure {
+    test_case: TestCase,
+    error: eyre::Report,
+}
+
+pub fn run(options: Options) -> eyre::Result<()> {
+    let compilers = if options.compilers.is_empty() {
+        vec![Compiler::Tint, Compiler::Naga]
+    } else {
+        options.compilers.clone()
+    };
+
+    let mut test_cases = vec![];
+
+    for path in &options.paths {
+        collect_shaders(path, &mut test_cases)
+            .wrap_err_with(|| eyre!("failed to read `{}`", path.display()))?;
+    }
+
+    if let Some(seeds) = options.seeds {
+        test_cases.extend((0..seeds).map(TestCase::Seed));
+    }
+
+    let total = test_cases.len();
+    let mut failures = vec![];
+
+    for test_case in test_cases {
+        println!("> validating {test_case}");
+
+        if let Err(error) = validate(&options, &compilers, &test_case) {
+            if !options.keep_going {
+                return Err(error.wrap_err(format!("validation failed for {test_case}")));
+            }
+
+            println!("> failed: {error:#}");
+            failures.push(Failure { test_case, error });
+        }
+    }
+
+    if failures.is_empty() {
+        println!("> validated {total} shaders");
+        return Ok(());
+    }
+
+    println!();
+    println!("{} of {total} shaders failed validation:", failures.len());
+    println!();
+
+    for Failure { test_case, error } in &failures {
+        println!("  {test_case}: {error:#}");
+    }
+
+    Err(eyre!(
+        "{} of {total} shaders failed validation",
+        failures.len()
+    ))
+}
+
+fn collect_shaders(path: &Path, test_cases: &mut Vec<TestCase>) -> eyre::Result<()> {
+    if !path.is_dir() {
+        test_cases.push(TestCase::Path(path.to_owned()));
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_shaders(&entry, test_cases)?;
+        } else if entry.extension() == Some(OsStr::new("wgsl")) {
+            test_cases.push(TestCase::Path(entry));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate(options: &Options, compilers: &[Compiler], test_case: &TestCase) -> eyre::Result<()> {
+    let source = match test_case {
+        TestCase::Path(path) => {
+            let source = std::fs::read_to_string(path)?;
+            if options.recondition {
+                crate::test::recondition(parser::parse(&source))
+            } else {
+                source
+            }
+        }
+        TestCase::Seed(seed) => gen_shader(*seed)?,
+    };
+
+    for compiler in compilers {
+        compiler.validate_with(&source, options.warnings_as_errors)?;
+    }
+
+    Ok(())
+}
+
+fn gen_shader(seed: u64) -> eyre::Result<String> {
+    let config = Rc::new(generator::GenerationConfig::default());
+    let mut rng = generator::GenRng::seed_from_u64(seed);
+    let module = generator::Generator::new(&mut rng, config).gen_module();
+    Ok(crate::test::recondition(module))
+}
@@ -0,0 +1,125 @@
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use ast::Module;
+use clap::Parser;
+use eyre::{eyre, Context};
+use rand::SeedableRng;
+
+use crate::diff::unified_diff;
+
+#[derive(Parser)]
+pub struct Options {
+    /// Paths to shaders to check.
+    ///
+    /// Directories are searched recursively for `.wgsl` files.
+    #[clap(action)]
+    paths: Vec<PathBuf>,
+
+    /// Also generate and check shaders from the seeds `0..N`.
+    #[clap(long, action)]
+    seeds: Option<u64>,
+}
+
+enum TestCase {
+    Path(PathBuf),
+    Seed(u64),
+}
+
+impl Display for TestCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestCase::Path(path) => write!(f, "{}", path.display()),
+            TestCase::Seed(seed) => write!(f, "seed {seed}"),
+        }
+    }
+}
+
+/// Checks that reconditioning each shader a second time doesn't change it, stopping at the first
+/// shader that does.
+pub fn run(options: Options) -> eyre::Result<()> {
+    let mut test_cases = vec![];
+
+    for path in &options.paths {
+        let mut paths = vec![];
+        collect_shaders(path, &mut paths)
+            .wrap_err_with(|| eyre!("failed to read `{}`", path.display()))?;
+        test_cases.extend(paths.into_iter().map(TestCase::Path));
+    }
+
+    if let Some(seeds) = options.seeds {
+        test_cases.extend((0..seeds).map(TestCase::Seed));
+    }
+
+    let total = test_cases.len();
+
+    for test_case in test_cases {
+        println!("> checking {test_case}");
+
+        let module = match &test_case {
+            TestCase::Path(path) => parser::parse(&std::fs::read_to_string(path)?),
+            TestCase::Seed(seed) => {
+                let config = Rc::new(generator::GenerationConfig::default());
+                let mut rng = generator::GenRng::seed_from_u64(*seed);
+                generator::Generator::new(&mut rng, config).gen_module()
+            }
+        };
+
+        let once =
+            recondition(module).wrap_err_with(|| eyre!("failed to recondition {test_case}"))?;
+
+        let twice = recondition(parser::parse(&once))
+            .wrap_err_with(|| eyre!("failed to recondition {test_case} a second time"))?;
+
+        if once != twice {
+            print!("{}", unified_diff(&once, &twice));
+            return Err(eyre!(
+                "reconditioning {test_case} is not idempotent (see diff above)"
+            ));
+        }
+    }
+
+    println!("> checked {total} shaders");
+
+    Ok(())
+}
+
+/// Adds `path` to `paths` if it's a file, or all `.wgsl` files under it if it's a directory.
+pub fn collect_shaders(path: &Path, paths: &mut Vec<PathBuf>) -> eyre::Result<()> {
+    if !path.is_dir() {
+        paths.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_shaders(&entry, paths)?;
+        } else if entry.extension() == Some(OsStr::new("wgsl")) {
+            paths.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reconditions `module` and formats the result, turning a panic in the reconditioner into an
+/// error so that it's reported against the shader that caused it.
+fn recondition(module: Module) -> eyre::Result<String> {
+    let reconditioned =
+        panic::catch_unwind(AssertUnwindSafe(|| reconditioner::recondition(module)))
+            .map_err(|_| eyre!("reconditioner panicked"))?;
+
+    let mut formatted = String::new();
+    ast::writer::Writer::default().write_module(&mut formatted, &reconditioned)?;
+
+    Ok(formatted)
+}